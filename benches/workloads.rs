@@ -0,0 +1,166 @@
+extern crate lc3;
+
+use std::time::{Duration, Instant};
+
+use lc3::assembler;
+use lc3::{Machine, NullConsole, Reg, StopReason};
+
+// instructions per second on a few representative programs, with and
+// without the decode cache, and through the threaded-code backend when
+// built with the jit feature. Run with `cargo bench`, or `cargo bench --
+// <name>` for the workloads whose names contain <name>.
+
+// runs per measurement, the fastest of which is reported
+const RUNS: usize = 5;
+
+struct Workload {
+  name: &'static str,
+  src: &'static str,
+  os: bool,
+  // address checked after the run and the value it should hold
+  check: Option<(u16, u16)>,
+}
+
+const WORKLOADS: &[Workload] = &[
+  Workload { name: "busy loop", src: BUSY_LOOP, os: false, check: None },
+  Workload { name: "fibonacci", src: FIBONACCI, os: false, check: Some((0x3019, 6765)) },
+  Workload { name: "strings", src: STRINGS, os: true, check: None },
+];
+
+// a million passes through a five instruction inner loop
+const BUSY_LOOP: &str = "\
+.ORIG x3000
+      LD R1, N
+OUTER LD R2, N
+INNER ADD R0, R0, R2
+      AND R3, R0, #15
+      NOT R4, R3
+      ADD R2, R2, #-1
+      BRp INNER
+      ADD R1, R1, #-1
+      BRp OUTER
+      HALT
+N     .FILL #1000
+.END";
+
+// fib(20) by naive recursion, exercising JSR/RET and the stack
+const FIBONACCI: &str = "\
+.ORIG x3000
+       LD R6, STACK
+       LD R0, N
+       JSR FIB
+       ST R0, RESULT
+       HALT
+FIB    ADD R6, R6, #-3
+       STR R7, R6, #0
+       STR R1, R6, #1
+       STR R2, R6, #2
+       ADD R1, R0, #-2
+       BRn BASE
+       ADD R1, R0, #0
+       ADD R0, R1, #-1
+       JSR FIB
+       ADD R2, R0, #0
+       ADD R0, R1, #-2
+       JSR FIB
+       ADD R0, R0, R2
+BASE   LDR R7, R6, #0
+       LDR R1, R6, #1
+       LDR R2, R6, #2
+       ADD R6, R6, #3
+       RET
+N      .FILL #20
+STACK  .FILL x6000
+RESULT .BLKW 1
+.END";
+
+// prints a line with PUTS and again upper-cased with OUT, through the OS
+// routines, to a null console
+const STRINGS: &str = "\
+.ORIG x3000
+      LD R3, COUNT
+AGAIN LEA R0, TEXT
+      PUTS
+      LEA R1, TEXT
+NEXT  LDR R0, R1, #0
+      BRz DONE
+      LD R4, NEGA
+      ADD R2, R0, R4
+      BRn PRINT
+      ADD R0, R0, #-16
+      ADD R0, R0, #-16
+PRINT OUT
+      ADD R1, R1, #1
+      BRnzp NEXT
+DONE  ADD R3, R3, #-1
+      BRp AGAIN
+      HALT
+COUNT .FILL #500
+NEGA  .FILL #-97
+TEXT  .STRINGZ \"the quick brown fox jumps over the lazy dog\\n\"
+.END";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Backend {
+  Interpreter,
+  DecodeCache,
+  #[cfg(feature = "jit")]
+  Jit,
+}
+
+fn measure(w: &Workload, obj: &[u8], backend: Backend) -> f64 {
+  let mut best: Duration = Duration::MAX;
+  let mut steps: u64 = 0;
+  for _ in 0..RUNS {
+    let mut m = Machine::builder().io(Box::new(NullConsole)).os(w.os).build();
+    m.load_image_bytes(obj).unwrap();
+    m.write_reg(Reg::PC, 0x3000);
+    match backend {
+      Backend::Interpreter => {},
+      Backend::DecodeCache => m.set_decode_cache(true),
+      #[cfg(feature = "jit")]
+      Backend::Jit => m.set_jit(true),
+    }
+
+    let start: Instant = Instant::now();
+    let run = m.run();
+    best = best.min(start.elapsed());
+    steps = run.steps;
+
+    assert_eq!(run.reason, StopReason::Halt, "{} didn't halt", w.name);
+    if let Some((addr, val)) = w.check {
+      assert_eq!(m.read_mem(addr), val, "{} computed the wrong result", w.name);
+    }
+  }
+  steps as f64 / best.as_secs_f64()
+}
+
+fn main() {
+  // cargo bench passes --bench, and anything after -- on the command line
+  let filter: Option<String> = std::env::args().skip(1).find(|a| !a.starts_with("--"));
+
+  let backends: Vec<(Backend, &str)> = vec![
+    (Backend::DecodeCache, "with cache"),
+    #[cfg(feature = "jit")]
+    (Backend::Jit, "jit"),
+  ];
+
+  print!("{:<12} {:>16}", "workload", "instructions/s");
+  for (_, name) in &backends {
+    print!(" {:>16}      ", name);
+  }
+  println!();
+  for w in WORKLOADS {
+    if filter.as_ref().is_some_and(|f| !w.name.contains(f.as_str())) {
+      continue;
+    }
+    let obj: Vec<u8> = assembler::assemble(w.src).unwrap().to_obj();
+    let plain: f64 = measure(w, &obj, Backend::Interpreter);
+    print!("{:<12} {:>16.0}", w.name, plain);
+    for &(backend, _) in &backends {
+      let rate: f64 = measure(w, &obj, backend);
+      print!(" {:>16.0} {:.2}x", rate, rate / plain);
+    }
+    println!();
+  }
+}
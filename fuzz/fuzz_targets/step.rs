@@ -0,0 +1,8 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate lc3;
+
+fuzz_target!(|data: &[u8]| {
+  lc3::fuzz::fuzz_step(data);
+});
@@ -0,0 +1,241 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use disasm::disassemble;
+use instr::{decode, Instruction};
+use loader::LoadedImage;
+use machine::{Machine, Reg, Run, StopReason};
+use symbols::SymbolTable;
+
+// where control can go after the instruction at addr: the address it may
+// branch or call to, and whether it may go on to the next. TRAPs return,
+// jumps through registers go nowhere that's known.
+pub fn flow(addr: u16, op: Instruction) -> (Option<u16>, bool) {
+  let target = |offset: i16| Some(addr.wrapping_add(1).wrapping_add(offset as u16));
+  match op {
+    Instruction::Br { n: false, z: false, p: false, .. } => (None, true),
+    Instruction::Br { n: true, z: true, p: true, offset } => (target(offset), false),
+    Instruction::Br { offset, .. } | Instruction::Jsr { offset } => (target(offset), true),
+    Instruction::Jmp { .. } | Instruction::Rti | Instruction::Res { .. } | Instruction::Trap { vector: 0x25 } => (None, false),
+    _ => (None, true),
+  }
+}
+
+// instructions only entered at the first and left after the last
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+  pub start: u16,
+  pub words: Vec<u16>,
+}
+
+impl Block {
+  // the address of the last instruction
+  pub fn last(&self) -> u16 {
+    self.start.wrapping_add(self.words.len() as u16 - 1)
+  }
+
+  pub fn instructions(&self) -> impl Iterator<Item = (u16, Instruction)> + '_ {
+    self.words.iter().enumerate().map(move |(i, &w)| (self.start.wrapping_add(i as u16), decode(w)))
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EdgeKind {
+  // on to the next instruction, including after a call or TRAP
+  Fall,
+  // a conditional BR taken
+  Branch,
+  // a BR that always branches
+  Jump,
+  Call,
+  // a JMP or JSRR seen going there in a run, see Cfg::refine()
+  Indirect,
+}
+
+// between the blocks starting at from and to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Edge {
+  pub from: u16,
+  pub to: u16,
+  pub kind: EdgeKind,
+}
+
+// the control-flow graph of the code in an image reachable from an entry
+// point, see cfg()
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cfg {
+  pub entry: u16,
+  // in address order
+  pub blocks: Vec<Block>,
+  // in order of from, then to
+  pub edges: Vec<Edge>,
+  image: LoadedImage,
+  // JMP and JSRR addresses and where they went
+  indirect: BTreeSet<(u16, u16)>,
+}
+
+// the blocks and edges of the code image runs from entry, found by
+// decoding it: following branches and calls, and the instructions after
+// those that can go on. Only code in the image is decoded, so calls into
+// the OS aren't followed. Cfg::refine() adds where jumps through
+// registers go.
+pub fn cfg(image: &LoadedImage, entry: u16) -> Cfg {
+  let mut g: Cfg = Cfg { entry, blocks: Vec::new(), edges: Vec::new(), image: image.clone(), indirect: BTreeSet::new() };
+  g.build();
+  g
+}
+
+impl Cfg {
+  fn word(&self, addr: u16) -> Option<u16> {
+    self.image.segments.iter()
+      .find(|s| (addr.wrapping_sub(s.origin) as usize) < s.words.len())
+      .map(|s| s.words[addr.wrapping_sub(s.origin) as usize])
+  }
+
+  fn build(&mut self) {
+    let jumps = |from: u16| self.indirect.range((from, 0)..=(from, u16::MAX)).map(|&(_, to)| to);
+
+    // every instruction reachable, and the ones that start blocks
+    let mut code: BTreeMap<u16, Instruction> = BTreeMap::new();
+    let mut leaders: BTreeSet<u16> = BTreeSet::new();
+    let mut todo: Vec<u16> = vec![self.entry];
+    leaders.insert(self.entry);
+    while let Some(addr) = todo.pop() {
+      if code.contains_key(&addr) {
+        continue;
+      }
+      let op: Instruction = match self.word(addr) {
+        Some(w) => decode(w),
+        None => continue,
+      };
+      code.insert(addr, op);
+      let (target, falls): (Option<u16>, bool) = flow(addr, op);
+      let targets: Vec<u16> = target.into_iter().chain(jumps(addr)).collect();
+      if !targets.is_empty() || !falls {
+        leaders.insert(addr.wrapping_add(1));
+      }
+      leaders.extend(&targets);
+      todo.extend(&targets);
+      if falls {
+        todo.push(addr.wrapping_add(1));
+      }
+    }
+
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut edges: Vec<Edge> = Vec::new();
+    let mut addrs = code.keys().copied().peekable();
+    while let Some(start) = addrs.next() {
+      let mut block: Block = Block { start, words: vec![self.word(start).unwrap()] };
+      let mut last: u16 = start;
+      while let Some(next) = addrs.next_if(|&a| a == last.wrapping_add(1) && !leaders.contains(&a)) {
+        block.words.push(self.word(next).unwrap());
+        last = next;
+      }
+
+      let op: Instruction = code[&last];
+      let (target, falls): (Option<u16>, bool) = flow(last, op);
+      let mut edge = |to: u16, kind: EdgeKind| if code.contains_key(&to) {
+        edges.push(Edge { from: start, to, kind });
+      };
+      if let Some(to) = target {
+        edge(to, match op {
+          Instruction::Jsr { .. } => EdgeKind::Call,
+          _ if falls => EdgeKind::Branch,
+          _ => EdgeKind::Jump,
+        });
+      }
+      if falls {
+        edge(last.wrapping_add(1), EdgeKind::Fall);
+      }
+      for to in jumps(last) {
+        edge(to, EdgeKind::Indirect);
+      }
+      blocks.push(block);
+    }
+    edges.sort();
+    edges.dedup();
+    self.blocks = blocks;
+    self.edges = edges;
+  }
+
+  // the block with an instruction at addr
+  pub fn block(&self, addr: u16) -> Option<&Block> {
+    self.blocks.iter().find(|b| addr.wrapping_sub(b.start) < b.words.len() as u16)
+  }
+
+  // the edges out of the block starting at start
+  pub fn successors(&self, start: u16) -> impl Iterator<Item = &Edge> {
+    self.edges.iter().filter(move |e| e.from == start)
+  }
+
+  pub fn predecessors(&self, start: u16) -> impl Iterator<Item = &Edge> {
+    self.edges.iter().filter(move |e| e.to == start)
+  }
+
+  // runs m, which should have the image loaded, for at most max_steps
+  // instructions, then adds the jumps through registers (JMP other than
+  // RET, and JSRR) it saw that land in the image, and the code they lead
+  // to
+  pub fn refine(&mut self, m: &mut Machine, max_steps: u64) -> Run {
+    if max_steps == 0 {
+      return Run { steps: 0, reason: StopReason::StepLimit };
+    }
+    let mut seen: BTreeSet<(u16, u16)> = BTreeSet::new();
+    let mut pc: u16 = m.read_reg(Reg::PC);
+    let mut left: u64 = max_steps;
+    let mut run: Run = m.run_until(|m| {
+      let next: u16 = m.read_reg(Reg::PC);
+      if let Instruction::Jmp { base: 0..=6 } | Instruction::Jsrr { .. } = decode(m.read_mem(pc)) {
+        seen.insert((pc, next));
+      }
+      pc = next;
+      left -= 1;
+      left == 0
+    });
+    if run.reason == StopReason::Condition {
+      run.reason = StopReason::StepLimit;
+    }
+
+    let before: usize = self.indirect.len();
+    let known: Vec<(u16, u16)> = seen.into_iter().filter(|&(from, to)| self.word(from).is_some() && self.word(to).is_some()).collect();
+    self.indirect.extend(known);
+    if self.indirect.len() != before {
+      self.build();
+    }
+    run
+  }
+
+  // Graphviz source with a node per block listing its instructions, e.g.
+  // for `dot -Tsvg`. Edges are labelled by kind, calls dashed and
+  // indirect jumps dotted.
+  pub fn to_dot(&self, symbols: &SymbolTable) -> String {
+    let mut out: String = String::from("digraph cfg {\n  node [shape=box, fontname=\"monospace\"];\n");
+    out.push_str("  entry [shape=point];\n");
+    for b in &self.blocks {
+      let mut label: String = match symbols.label(b.start) {
+        Some(name) => format!("{} (x{:04X})\\l", name, b.start),
+        None => format!("x{:04X}\\l", b.start),
+      };
+      for (addr, w) in b.words.iter().enumerate().map(|(i, &w)| (b.start.wrapping_add(i as u16), w)) {
+        label.push_str(&format!("  {}\\l", disassemble(w, addr, symbols)).replace('"', "\\\""));
+      }
+      out.push_str(&format!("  b{:04X} [label=\"{}\"];\n", b.start, label));
+    }
+    if self.block(self.entry).is_some() {
+      out.push_str(&format!("  entry -> b{:04X};\n", self.entry));
+    }
+    for e in &self.edges {
+      let attrs: &str = match e.kind {
+        EdgeKind::Fall => "",
+        EdgeKind::Branch => " [label=\"branch\"]",
+        EdgeKind::Jump => " [label=\"jump\"]",
+        EdgeKind::Call => " [label=\"call\", style=dashed]",
+        EdgeKind::Indirect => " [label=\"indirect\", style=dotted]",
+      };
+      out.push_str(&format!("  b{:04X} -> b{:04X}{};\n", e.from, e.to, attrs));
+    }
+    out.push_str("}\n");
+    out
+  }
+}
@@ -0,0 +1,819 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::{error, fmt, iter, mem};
+use core::ops::Range;
+#[cfg(feature = "std")]
+use std::fs;
+#[cfg(feature = "std")]
+use std::path::Path;
+
+use instr::{encode, Instruction};
+use symbols::SymbolTable;
+
+use self::diagnostic::suggest;
+pub use self::diagnostic::{Diagnostic, Span, Spans};
+pub use self::expr::ConstExpr;
+pub use self::format::{format_source, Case, Style};
+pub use self::lint::{lint, Lint, LintKind, LINTS};
+use self::expr::{EvalError, OPERATORS};
+use self::preprocess::{preprocess, Source};
+
+mod diagnostic;
+mod expr;
+mod format;
+mod lint;
+mod preprocess;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+  Reg(u16),
+  Imm(i32),
+  Label(String),
+  Str(String),
+  // naming a label, constant ones are Imm
+  Expr(ConstExpr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+  pub num: usize,
+  pub label: Option<String>,
+  pub op: Option<String>,
+  pub operands: Vec<Operand>,
+  pub spans: Spans,
+}
+
+// the first of diagnostics, every error found before assembly stopped
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsmError {
+  pub line: usize,
+  pub msg: String,
+  pub diagnostics: Vec<Diagnostic>,
+}
+
+impl From<Vec<Diagnostic>> for AsmError {
+  fn from(diagnostics: Vec<Diagnostic>) -> AsmError {
+    AsmError { line: diagnostics[0].line, msg: diagnostics[0].located(), diagnostics }
+  }
+}
+
+impl fmt::Display for AsmError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "line {}: {}", self.line, self.msg)
+  }
+}
+
+impl error::Error for AsmError {}
+
+// where the words of one source line ended up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineEntry {
+  pub line: usize,
+  pub addr: u16,
+  pub len: u16,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+  pub origin: u16,
+  pub words: Vec<u16>,
+  pub symbols: SymbolTable,
+  // source lines that emit words, in address order
+  pub lines: Vec<LineEntry>,
+}
+
+impl Program {
+  // standard object file layout: big-endian words, origin first
+  pub fn to_obj(&self) -> Vec<u8> {
+    let mut out: Vec<u8> = Vec::with_capacity(2 * (self.words.len() + 1));
+    out.extend_from_slice(&self.origin.to_be_bytes());
+    for w in &self.words {
+      out.extend_from_slice(&w.to_be_bytes());
+    }
+    out
+  }
+
+  // lc3as style listing of `src`, the source this program was assembled
+  // from: address, word in hex and binary, line number and source text,
+  // with one extra row per word of multi-word directives
+  pub fn listing(&self, src: &str) -> String {
+    let mut out: String = String::new();
+    let mut entries = self.lines.iter().peekable();
+
+    for (i, text) in src.lines().enumerate() {
+      let num: usize = i + 1;
+      let entry: Option<&LineEntry> = entries.next_if(|e| e.line == num);
+      let words: &[u16] = match entry {
+        Some(e) => {
+          let start: usize = e.addr.wrapping_sub(self.origin) as usize;
+          &self.words[start..start + e.len as usize]
+        },
+        None => &[],
+      };
+
+      match words.first() {
+        Some(&w) => out.push_str(&format!("  ({:04X}) {:04X}  {:016b} ", entry.unwrap().addr, w, w)),
+        None => out.push_str(&format!("{:32}", "")),
+      }
+      out.push_str(&format!("({:4}) {}\n", num, text));
+
+      for (j, &w) in words.iter().enumerate().skip(1) {
+        let addr: u16 = entry.unwrap().addr.wrapping_add(j as u16);
+        out.push_str(&format!("  ({:04X}) {:04X}  {:016b}\n", addr, w, w));
+      }
+    }
+    out
+  }
+}
+
+fn err<T>(line: usize, cols: Option<Span>, msg: impl Into<String>) -> Result<T, Diagnostic> {
+  Err(Diagnostic::new(line, cols, msg))
+}
+
+const OPCODES: &[&str] = &[
+  "ADD", "AND", "JMP", "JSR", "JSRR", "LD", "LDI", "LDR", "LEA", "NOT",
+  "RET", "RTI", "ST", "STI", "STR", "TRAP",
+  "GETC", "OUT", "PUTS", "IN", "PUTSP", "HALT",
+  ".ORIG", ".FILL", ".BLKW", ".STRINGZ", ".END",
+];
+
+// what an unknown opcode might have meant, besides OPCODES
+const SPELLINGS: &[&str] = &[
+  "BR", "BRN", "BRZ", "BRP", "BRNZ", "BRNP", "BRZP", "BRNZP", ".MACRO", ".ENDM", ".INCLUDE",
+];
+
+// reads the file an .INCLUDE names: given the key of the including file,
+// None for a top-level source without one, and the name as written, gives
+// the included file's key, the same for every name of the file, and its
+// source
+pub type Include<'a> = dyn FnMut(Option<&str>, &str) -> Result<(String, String), String> + 'a;
+
+fn no_include(_: Option<&str>, _: &str) -> Result<(String, String), String> {
+  Err("there are no files to include from".to_string())
+}
+
+fn is_opcode(tok: &str) -> bool {
+  let up: String = tok.to_uppercase();
+  if OPCODES.contains(&up.as_str()) {
+    return true;
+  }
+  match up.strip_prefix("BR") {
+    Some(flags) => flags.chars().all(|c| "NZP".contains(c)),
+    None => false,
+  }
+}
+
+fn unknown_opcode(num: usize, tok: &str, cols: Span) -> Diagnostic {
+  Diagnostic::new(num, Some(cols), format!("unknown opcode `{}`", tok))
+    .with_help(suggest(tok, OPCODES.iter().chain(SPELLINGS).cloned()))
+}
+
+// the tokens of text and their columns, strings prefixed with " and
+// their escapes processed
+fn split_tokens(num: usize, text: &str) -> Result<Vec<(String, Span)>, Diagnostic> {
+  // and whether a comma came before each
+  let mut toks: Vec<(String, Span, bool)> = Vec::new();
+  let mut chars = text.chars().enumerate().peekable();
+  let mut comma: bool = false;
+
+  while let Some(&(start, c)) = chars.peek() {
+    if c == ';' {
+      break;
+    } else if c.is_whitespace() || c == ',' {
+      comma |= c == ',';
+      chars.next();
+    } else if c == '"' {
+      chars.next();
+      let mut s: String = String::from("\"");
+      let unterminated = || err(num, Some(Span { start: start as u32, end: text.chars().count() as u32 }), "unterminated string");
+      let end: u32 = loop {
+        match chars.next() {
+          Some((i, '"')) => break i as u32 + 1,
+          Some((_, '\\')) => match chars.next() {
+            Some((_, 'n')) => s.push('\n'),
+            Some((_, 't')) => s.push('\t'),
+            Some((_, 'r')) => s.push('\r'),
+            Some((_, '0')) => s.push('\0'),
+            Some((_, e)) => s.push(e),
+            None => return unterminated(),
+          },
+          Some((_, ch)) => s.push(ch),
+          None => return unterminated(),
+        }
+      };
+      toks.push((s, Span { start: start as u32, end }, mem::take(&mut comma)));
+    } else {
+      let mut s: String = String::new();
+      let mut end: usize = start;
+      while let Some(&(i, ch)) = chars.peek() {
+        if ch.is_whitespace() || ch == ',' || ch == ';' {
+          break;
+        }
+        s.push(ch);
+        end = i + 1;
+        chars.next();
+        // a character literal, which may be ' ' or ';'
+        if ch == '\'' {
+          let escape: bool = chars.peek().is_some_and(|&(_, c)| c == '\\');
+          for _ in 0..(if escape { 3 } else { 2 }) {
+            if let Some((i, c)) = chars.next() {
+              s.push(c);
+              end = i + 1;
+            }
+          }
+        }
+      }
+      toks.push((s, Span { start: start as u32, end: end as u32 }, mem::take(&mut comma)));
+    }
+  }
+
+  // operands spaced around operators, e.g. `END - START`, are one
+  let mut joined: Vec<(String, Span)> = Vec::new();
+  for (tok, cols, comma) in toks {
+    if let Some((prev, prev_cols)) = joined.last_mut() {
+      let operand = |t: &str| !t.starts_with('"') && !is_opcode(t);
+      let open: bool = prev.ends_with(|c: char| OPERATORS.contains(c) && c != ')');
+      let binary: bool = tok == "+" || tok == "-" || tok.starts_with(|c: char| "|^&<>*/%)".contains(c));
+      if !comma && operand(prev) && operand(&tok) && (open || binary) {
+        prev.push(' ');
+        prev.push_str(&tok);
+        prev_cols.end = cols.end;
+        continue;
+      }
+    }
+    joined.push((tok, cols));
+  }
+  Ok(joined)
+}
+
+fn parse_number(tok: &str) -> Option<i32> {
+  let (neg, body) = match tok.strip_prefix('-') {
+    Some(rest) => (true, rest),
+    None => (false, tok),
+  };
+
+  let val: i64 = if let Some(d) = body.strip_prefix('#') {
+    let (neg2, d) = match d.strip_prefix('-') {
+      Some(rest) => (true, rest),
+      None => (false, d),
+    };
+    let v: i64 = d.parse().ok()?;
+    if neg2 { -v } else { v }
+  } else if let Some(h) = body.strip_prefix("0x").or_else(|| body.strip_prefix("0X")) {
+    i64::from_str_radix(h, 16).ok()?
+  } else if let Some(h) = body.strip_prefix('x').or_else(|| body.strip_prefix('X')) {
+    let (neg2, h) = match h.strip_prefix('-') {
+      Some(rest) => (true, rest),
+      None => (false, h),
+    };
+    let v: i64 = i64::from_str_radix(h, 16).ok()?;
+    if neg2 { -v } else { v }
+  } else if let Some(b) = body.strip_prefix('b').or_else(|| body.strip_prefix('B')) {
+    i64::from_str_radix(b, 2).ok()?
+  } else if body.chars().next().is_some_and(|c| c.is_ascii_digit()) {
+    body.parse().ok()?
+  } else {
+    return None;
+  };
+
+  let val: i64 = if neg { -val } else { val };
+  if (-0x8000..=0xFFFF).contains(&val) { Some(val as i32) } else { None }
+}
+
+fn is_name(tok: &str) -> bool {
+  tok.chars().all(|c| c.is_alphanumeric() || c == '_')
+    && tok.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+}
+
+fn parse_operand(num: usize, tok: &str, cols: Span) -> Result<Operand, Diagnostic> {
+  if let Some(s) = tok.strip_prefix('"') {
+    return Ok(Operand::Str(s.to_string()));
+  }
+
+  let up: String = tok.to_uppercase();
+  if up.len() == 2 && up.starts_with('R') {
+    if let Some(r) = up[1..].parse::<u16>().ok().filter(|&r| r < 8) {
+      return Ok(Operand::Reg(r));
+    }
+  }
+
+  if let Some(n) = parse_number(tok) {
+    return Ok(Operand::Imm(n));
+  }
+
+  if is_name(tok) {
+    return Ok(Operand::Label(tok.to_string()));
+  }
+
+  match ConstExpr::parse(tok) {
+    Some(e) if e.names_label() => Ok(Operand::Expr(e)),
+    Some(e) => match e.eval(&|_| None) {
+      Ok(n) if (-0x8000..=0xFFFF).contains(&n) => Ok(Operand::Imm(n as i32)),
+      Ok(n) => err(num, Some(cols), format!("value {} does not fit in 16 bits", n)),
+      Err(e) => err(num, Some(cols), e.message()),
+    },
+    None => err(num, Some(cols), format!("invalid operand `{}`", tok)),
+  }
+}
+
+// parses src, expanding its macros; it cannot .INCLUDE
+pub fn parse(src: &str) -> Result<Vec<Line>, AsmError> {
+  let srcs: Vec<Source> = preprocess(src, None, &mut no_include).map_err(|d| AsmError::from(vec![d]))?;
+  Ok(parse_sources(&srcs)?.into_iter().map(|(line, _)| line).collect())
+}
+
+// each line of src as written, with macros and includes left unexpanded,
+// for tools that want what they can get from a file with errors
+pub fn parse_lines(src: &str) -> Vec<Result<Line, Diagnostic>> {
+  let mut lines: Vec<Result<Line, Diagnostic>> = Vec::new();
+  for (i, text) in src.lines().enumerate() {
+    match parse_line(i + 1, text) {
+      Ok(Some(line)) => lines.push(Ok(line)),
+      Ok(None) => {},
+      Err(d) => lines.push(Err(d.on(text, None))),
+    }
+  }
+  lines
+}
+
+// the lines of srcs, each with the source it came from, or every line's
+// errors
+fn parse_sources(srcs: &[Source]) -> Result<Vec<(Line, &Source)>, Vec<Diagnostic>> {
+  let mut lines: Vec<(Line, &Source)> = Vec::new();
+  let mut errors: Vec<Diagnostic> = Vec::new();
+
+  for src in srcs {
+    match parse_line(src.num, &src.text) {
+      Ok(Some(line)) => lines.push((line, src)),
+      Ok(None) => {},
+      Err(d) => errors.push(d.on(&src.text, src.at.as_deref())),
+    }
+  }
+
+  if !errors.is_empty() {
+    return Err(errors);
+  }
+  Ok(lines)
+}
+
+fn parse_line(num: usize, text: &str) -> Result<Option<Line>, Diagnostic> {
+  let toks: Vec<(String, Span)> = split_tokens(num, text)?;
+  let mut toks = toks.into_iter().peekable();
+  let mut spans: Spans = Spans::default();
+
+  let mut label: Option<String> = None;
+  if let Some((first, cols)) = toks.next_if(|(t, _)| !is_opcode(t)) {
+    // a label followed by an operand rather than an opcode is more likely a
+    // misspelled opcode, e.g. `ADDD R1, R1, #1`
+    if let Some((next, next_cols)) = toks.peek() {
+      let operand: bool = !first.ends_with(':')
+        && matches!(parse_operand(num, next, *next_cols), Ok(Operand::Reg(_) | Operand::Imm(_) | Operand::Str(_)));
+      if operand {
+        return Err(unknown_opcode(num, &first, cols));
+      }
+    }
+    let name: String = first.trim_end_matches(':').to_string();
+    match parse_operand(num, &name, cols) {
+      Ok(Operand::Label(_)) => label = Some(name),
+      _ => return err(num, Some(cols), format!("invalid label `{}`", first)),
+    }
+    spans.label = Some(cols);
+  }
+
+  let op: Option<String> = match toks.next() {
+    Some((t, cols)) if is_opcode(&t) => {
+      spans.op = Some(cols);
+      Some(t.to_uppercase())
+    },
+    Some((t, cols)) => return Err(unknown_opcode(num, &t, cols)),
+    None => None,
+  };
+
+  let mut operands: Vec<Operand> = Vec::new();
+  for (t, cols) in toks {
+    operands.push(parse_operand(num, &t, cols)?);
+    spans.operands.push(cols);
+  }
+
+  if label.is_none() && op.is_none() {
+    return Ok(None);
+  }
+  Ok(Some(Line { num, label, op, operands, spans }))
+}
+
+// where operand i of line is, or its opcode for a missing operand
+fn operand_cols(line: &Line, i: Option<usize>) -> Option<Span> {
+  i.and_then(|i| line.spans.operands.get(i).cloned()).or(line.spans.op)
+}
+
+// number of words a statement occupies in the image
+fn size(line: &Line) -> Result<u16, Diagnostic> {
+  match line.op.as_deref() {
+    None | Some(".ORIG") | Some(".END") => Ok(0),
+    Some(".BLKW") => match line.operands.first() {
+      Some(&Operand::Imm(n)) if (0..=0xFFFF).contains(&n) => Ok(n as u16),
+      _ => err(line.num, operand_cols(line, Some(0)), ".BLKW expects a word count"),
+    },
+    Some(".STRINGZ") => match line.operands.first() {
+      // a word per character, which has to be ASCII, and the terminator
+      Some(Operand::Str(s)) => match s.chars().find(|c| !c.is_ascii()) {
+        Some(c) => err(line.num, operand_cols(line, Some(0)), format!("non-ASCII character `{}` in string", c)),
+        None if s.len() >= 0xFFFF => err(line.num, operand_cols(line, Some(0)), "string does not fit in memory"),
+        None => Ok(s.len() as u16 + 1),
+      },
+      _ => err(line.num, operand_cols(line, Some(0)), ".STRINGZ expects a string"),
+    },
+    Some(_) => Ok(1),
+  }
+}
+
+struct Encoder<'a> {
+  line: &'a Line,
+  addr: u16,
+  symbols: &'a SymbolTable,
+}
+
+impl<'a> Encoder<'a> {
+  // an error at operand i, or at the opcode for None
+  fn fail<T>(&self, i: Option<usize>, msg: impl Into<String>) -> Result<T, Diagnostic> {
+    err(self.line.num, operand_cols(self.line, i), msg)
+  }
+
+  fn operand(&self, i: usize) -> Result<&'a Operand, Diagnostic> {
+    match self.line.operands.get(i) {
+      Some(op) => Ok(op),
+      None => self.fail(None, format!("missing operand {}", i + 1)),
+    }
+  }
+
+  fn expect(&self, n: usize) -> Result<(), Diagnostic> {
+    let found: usize = self.line.operands.len();
+    if found != n {
+      // at the first extra operand, or the opcode when some are missing
+      return self.fail(Some(n).filter(|_| found > n), format!("expected {} operands, found {}", n, found));
+    }
+    Ok(())
+  }
+
+  fn reg(&self, i: usize) -> Result<u16, Diagnostic> {
+    match *self.operand(i)? {
+      Operand::Reg(r) => Ok(r),
+      _ => self.fail(Some(i), format!("operand {} must be a register", i + 1)),
+    }
+  }
+
+  fn imm(&self, i: usize, bits: u32) -> Result<i16, Diagnostic> {
+    let n: i64 = match self.operand(i)? {
+      &Operand::Imm(n) => n as i64,
+      Operand::Expr(e) => self.eval(i, e)?,
+      _ => return self.fail(Some(i), format!("operand {} must be an immediate", i + 1)),
+    };
+    let min: i64 = -(1 << (bits - 1));
+    let max: i64 = (1 << (bits - 1)) - 1;
+    if n < min || n > max {
+      return self.fail(Some(i), format!("immediate {} does not fit in {} bits", n, bits));
+    }
+    Ok(n as i16)
+  }
+
+  // the value of e, operand i
+  fn eval(&self, i: usize, e: &ConstExpr) -> Result<i64, Diagnostic> {
+    match e.eval(&|name| self.symbols.lookup(name)) {
+      Ok(n) => Ok(n),
+      Err(EvalError::Undefined(name)) => self.label(i, &name).map(|addr| addr as i64),
+      Err(e) => self.fail(Some(i), e.message()),
+    }
+  }
+
+  // operand i as a word of data, what its error says it must be
+  fn word(&self, i: usize, what: &str) -> Result<u16, Diagnostic> {
+    match self.operand(i)? {
+      &Operand::Imm(n) => Ok(n as u16),
+      Operand::Label(name) => self.label(i, name),
+      Operand::Expr(e) => match self.eval(i, e)? {
+        n if (-0x8000..=0xFFFF).contains(&n) => Ok(n as u16),
+        n => self.fail(Some(i), format!("value {} does not fit in 16 bits", n)),
+      },
+      _ => self.fail(Some(i), what),
+    }
+  }
+
+  // the address of the label that is operand i
+  fn label(&self, i: usize, name: &str) -> Result<u16, Diagnostic> {
+    match self.symbols.lookup(name) {
+      Some(addr) => Ok(addr),
+      None => {
+        let register: bool = name.strip_prefix(['R', 'r']).is_some_and(|d| !d.is_empty() && d.chars().all(|c| c.is_ascii_digit()));
+        let help: Option<String> = if register {
+          Some("the registers are R0 to R7".to_string())
+        } else {
+          suggest(name, self.symbols.iter().map(|(l, _)| l))
+        };
+        Err(Diagnostic::new(self.line.num, operand_cols(self.line, Some(i)), format!("undefined label `{}`", name)).with_help(help))
+      },
+    }
+  }
+
+  // PC-relative offset to a label, an expression naming one, or literal
+  // offset
+  fn offset(&self, i: usize, bits: u32) -> Result<i16, Diagnostic> {
+    let off: i64 = match self.operand(i)? {
+      Operand::Label(name) => self.label(i, name)? as i64 - (self.addr as i64 + 1),
+      Operand::Expr(e) => self.eval(i, e)? - (self.addr as i64 + 1),
+      &Operand::Imm(n) => n as i64,
+      _ => return self.fail(Some(i), format!("operand {} must be a label or offset", i + 1)),
+    };
+
+    let min: i64 = -(1 << (bits - 1));
+    let max: i64 = (1 << (bits - 1)) - 1;
+    if off < min || off > max {
+      return self.fail(Some(i), format!("offset {} does not fit in {} bits", off, bits));
+    }
+    Ok(off as i16)
+  }
+
+  fn encode(&self, out: &mut Vec<u16>) -> Result<(), Diagnostic> {
+    let op: &str = match self.line.op.as_deref() {
+      Some(op) => op,
+      None => return Ok(()),
+    };
+
+    let instr: Instruction = match op {
+      ".ORIG" | ".END" => return Ok(()),
+
+      ".FILL" => {
+        self.expect(1)?;
+        out.push(self.word(0, ".FILL expects a value or label")?);
+        return Ok(());
+      },
+
+      ".BLKW" => {
+        let n: u16 = size(self.line)?;
+        let fill: u16 = match self.line.operands.get(1) {
+          Some(_) => self.word(1, ".BLKW fill value must be a value or label")?,
+          None => 0,
+        };
+        out.extend(iter::repeat_n(fill, n as usize));
+        return Ok(());
+      },
+
+      ".STRINGZ" => {
+        self.expect(1)?;
+        if let Operand::Str(s) = self.operand(0)? {
+          out.extend(s.bytes().map(u16::from));
+          out.push(0);
+        }
+        return Ok(());
+      },
+
+      "ADD" | "AND" => {
+        self.expect(3)?;
+        let dr: u16 = self.reg(0)?;
+        let sr1: u16 = self.reg(1)?;
+        match (op, self.operand(2)?) {
+          ("ADD", &Operand::Reg(sr2)) => Instruction::Add { dr, sr1, sr2 },
+          ("ADD", _) => Instruction::AddI { dr, sr1, imm: self.imm(2, 5)? },
+          (_, &Operand::Reg(sr2)) => Instruction::And { dr, sr1, sr2 },
+          _ => Instruction::AndI { dr, sr1, imm: self.imm(2, 5)? },
+        }
+      },
+
+      "NOT" => {
+        self.expect(2)?;
+        Instruction::Not { dr: self.reg(0)?, sr: self.reg(1)? }
+      },
+
+      "JMP" => {
+        self.expect(1)?;
+        Instruction::Jmp { base: self.reg(0)? }
+      },
+
+      "RET" => {
+        self.expect(0)?;
+        Instruction::Jmp { base: 7 }
+      },
+
+      "JSR" => {
+        self.expect(1)?;
+        Instruction::Jsr { offset: self.offset(0, 11)? }
+      },
+
+      "JSRR" => {
+        self.expect(1)?;
+        Instruction::Jsrr { base: self.reg(0)? }
+      },
+
+      "LD" | "LDI" | "LEA" | "ST" | "STI" => {
+        self.expect(2)?;
+        let r: u16 = self.reg(0)?;
+        let offset: i16 = self.offset(1, 9)?;
+        match op {
+          "LD" => Instruction::Ld { dr: r, offset },
+          "LDI" => Instruction::Ldi { dr: r, offset },
+          "LEA" => Instruction::Lea { dr: r, offset },
+          "ST" => Instruction::St { sr: r, offset },
+          _ => Instruction::Sti { sr: r, offset },
+        }
+      },
+
+      "LDR" | "STR" => {
+        self.expect(3)?;
+        let r: u16 = self.reg(0)?;
+        let base: u16 = self.reg(1)?;
+        let offset: i16 = self.imm(2, 6)?;
+        if op == "LDR" {
+          Instruction::Ldr { dr: r, base, offset }
+        } else {
+          Instruction::Str { sr: r, base, offset }
+        }
+      },
+
+      "RTI" => {
+        self.expect(0)?;
+        Instruction::Rti
+      },
+
+      "TRAP" => {
+        self.expect(1)?;
+        match *self.operand(0)? {
+          Operand::Imm(n) if (0..=0xFF).contains(&n) => Instruction::Trap { vector: n as u8 },
+          _ => return self.fail(Some(0), "TRAP expects an 8-bit vector"),
+        }
+      },
+
+      "GETC" => Instruction::Trap { vector: 0x20 },
+      "OUT" => Instruction::Trap { vector: 0x21 },
+      "PUTS" => Instruction::Trap { vector: 0x22 },
+      "IN" => Instruction::Trap { vector: 0x23 },
+      "PUTSP" => Instruction::Trap { vector: 0x24 },
+      "HALT" => Instruction::Trap { vector: 0x25 },
+
+      br => {
+        self.expect(1)?;
+        let flags: &str = &br[2..];
+        let all: bool = flags.is_empty();
+        Instruction::Br {
+          n: all || flags.contains('N'),
+          z: all || flags.contains('Z'),
+          p: all || flags.contains('P'),
+          offset: self.offset(0, 9)?,
+        }
+      },
+    };
+
+    out.push(encode(instr));
+    Ok(())
+  }
+}
+
+pub fn assemble(src: &str) -> Result<Program, AsmError> {
+  assemble_with(src, None, &mut no_include)
+}
+
+// assembles src, the file with key name, reading the files it includes
+// with include
+pub fn assemble_with(src: &str, name: Option<&str>, include: &mut Include) -> Result<Program, AsmError> {
+  assemble_linked(src, name, include, &SymbolTable::new())
+}
+
+// assembles src as one module of a program linked from several, with
+// externs the labels the other modules define
+pub fn assemble_linked(src: &str, name: Option<&str>, include: &mut Include, externs: &SymbolTable) -> Result<Program, AsmError> {
+  let srcs: Vec<Source> = preprocess(src, name, include).map_err(|d| AsmError::from(vec![d]))?;
+  let lines: Vec<(Line, &Source)> = parse_sources(&srcs)?;
+  let (origin, body): (u16, Range<usize>) = program_body(&lines)?;
+  assemble_lines(&lines[body], origin, externs.clone())
+}
+
+// the labels src defines, without encoding it, so modules being linked
+// can refer to one another
+pub fn labels(src: &str, name: Option<&str>, include: &mut Include) -> Result<SymbolTable, AsmError> {
+  let srcs: Vec<Source> = preprocess(src, name, include).map_err(|d| AsmError::from(vec![d]))?;
+  let lines: Vec<(Line, &Source)> = parse_sources(&srcs)?;
+  let (origin, body): (u16, Range<usize>) = program_body(&lines)?;
+  let mut symbols: SymbolTable = SymbolTable::new();
+  layout(&lines[body], origin, &mut symbols)?;
+  Ok(symbols)
+}
+
+// the origin of a program and where its lines between .ORIG and .END are
+fn program_body(lines: &[(Line, &Source)]) -> Result<(u16, Range<usize>), AsmError> {
+  let start: usize = match lines.iter().position(|(l, _)| l.op.is_some()) {
+    Some(i) => i,
+    None => return Err(AsmError::from(vec![Diagnostic::new(1, None, "empty program")])),
+  };
+
+  let (orig, src): &(Line, &Source) = &lines[start];
+  let origin: u16 = match (orig.op.as_deref(), orig.operands.first()) {
+    (Some(".ORIG"), Some(&Operand::Imm(n))) => n as u16,
+    (Some(".ORIG"), _) => {
+      let d: Diagnostic = Diagnostic::new(orig.num, operand_cols(orig, Some(0)), ".ORIG expects an address");
+      return Err(AsmError::from(vec![d.on(&src.text, src.at.as_deref())]));
+    },
+    _ => {
+      let d: Diagnostic = Diagnostic::new(orig.num, orig.spans.op, "program must start with .ORIG");
+      return Err(AsmError::from(vec![d.on(&src.text, src.at.as_deref())]));
+    },
+  };
+
+  let end: usize = lines.iter()
+    .position(|(l, _)| l.op.as_deref() == Some(".END"))
+    .unwrap_or(lines.len());
+  Ok((origin, start + 1..end))
+}
+
+// the key of the file at path for assemble_with(), which include_file()
+// resolves includes against
+#[cfg(feature = "std")]
+pub fn file_key(path: &Path) -> String {
+  fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()).to_string_lossy().into_owned()
+}
+
+// reads the file name includes, relative to the file with key from
+#[cfg(feature = "std")]
+pub fn include_file(from: Option<&str>, name: &str) -> Result<(String, String), String> {
+  let dir: &Path = Path::new(from.unwrap_or("")).parent().unwrap_or(Path::new(""));
+  let file = dir.join(name);
+  let src: String = fs::read_to_string(&file).map_err(|e| e.to_string())?;
+  Ok((file_key(&file), src))
+}
+
+// assembles src read from path, including files relative to the file
+// that includes them
+#[cfg(feature = "std")]
+pub fn assemble_file(src: &str, path: &Path) -> Result<Program, AsmError> {
+  assemble_with(src, Some(&file_key(path)), &mut include_file)
+}
+
+// assembles src as if it followed an .ORIG origin, with symbols already
+// defined, e.g. one line at a time at the repl
+pub fn assemble_at(src: &str, origin: u16, symbols: &SymbolTable) -> Result<Program, AsmError> {
+  let srcs: Vec<Source> = preprocess(src, None, &mut no_include).map_err(|d| AsmError::from(vec![d]))?;
+  let lines: Vec<(Line, &Source)> = parse_sources(&srcs)?;
+  assemble_lines(&lines, origin, symbols.clone())
+}
+
+// the first pass: assigns addresses to labels, going on past lines with
+// errors to find the rest
+fn layout(body: &[(Line, &Source)], origin: u16, symbols: &mut SymbolTable) -> Result<(), AsmError> {
+  let mut errors: Vec<Diagnostic> = Vec::new();
+  let mut addr: u32 = origin as u32;
+  for (line, src) in body {
+    let mut fail = |d: Diagnostic| errors.push(d.on(&src.text, src.at.as_deref()));
+    if line.op.as_deref() == Some(".ORIG") {
+      fail(Diagnostic::new(line.num, line.spans.op, "multiple .ORIG blocks are not supported"));
+    }
+    if let Some(label) = &line.label {
+      if symbols.lookup(label).is_some() {
+        fail(Diagnostic::new(line.num, line.spans.label, format!("duplicate label `{}`", label)));
+      } else {
+        symbols.insert(label, addr as u16);
+      }
+    }
+    addr += size(line).unwrap_or_else(|d| {
+      fail(d);
+      0
+    }) as u32;
+    if addr > 0x10000 {
+      fail(Diagnostic::new(line.num, None, "program does not fit in memory"));
+      break;
+    }
+  }
+  if !errors.is_empty() {
+    return Err(AsmError::from(errors));
+  }
+  Ok(())
+}
+
+// each pass goes on past lines with errors to find the rest, stopping
+// before the next
+fn assemble_lines(body: &[(Line, &Source)], origin: u16, mut symbols: SymbolTable) -> Result<Program, AsmError> {
+  layout(body, origin, &mut symbols)?;
+
+  // second pass: encode. Lines expanded from a macro or an include share
+  // the line number they stand for, and one entry.
+  let mut errors: Vec<Diagnostic> = Vec::new();
+  let mut words: Vec<u16> = Vec::new();
+  let mut entries: Vec<LineEntry> = Vec::new();
+  for (line, src) in body {
+    let addr: u16 = origin.wrapping_add(words.len() as u16);
+    let enc = Encoder { line, addr, symbols: &symbols };
+    let before: usize = words.len();
+    if let Err(d) = enc.encode(&mut words) {
+      errors.push(d.on(&src.text, src.at.as_deref()));
+      // keeping the addresses of the lines after it
+      words.resize(before + size(line).unwrap_or(0) as usize, 0);
+    }
+    let len: u16 = (words.len() - before) as u16;
+    match entries.last_mut() {
+      _ if len == 0 => {},
+      Some(e) if e.line == line.num && e.addr.wrapping_add(e.len) == addr => e.len += len,
+      _ => entries.push(LineEntry { line: line.num, addr, len }),
+    }
+  }
+  if !errors.is_empty() {
+    return Err(AsmError::from(errors));
+  }
+
+  Ok(Program { origin, words, symbols, lines: entries })
+}
@@ -0,0 +1,148 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use json::Json;
+
+// columns start..end of a line, counted in characters from 0
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+  pub start: u32,
+  pub end: u32,
+}
+
+// where the parts of a Line are in its text
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Spans {
+  pub label: Option<Span>,
+  pub op: Option<Span>,
+  pub operands: Vec<Span>,
+}
+
+// a problem with one line of a program. line is in the top-level source
+// and cols, None if it's the whole line, are of text, the line as
+// assembled: the source line itself unless it came from a macro or from
+// an included file, which at says as "file:line".
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+  pub line: usize,
+  pub cols: Option<Span>,
+  pub text: String,
+  pub at: Option<String>,
+  pub msg: String,
+  // e.g. "did you mean `.FILL`?"
+  pub help: Option<String>,
+}
+
+impl Diagnostic {
+  pub(super) fn new(line: usize, cols: Option<Span>, msg: impl Into<String>) -> Diagnostic {
+    Diagnostic { line, cols, text: String::new(), at: None, msg: msg.into(), help: None }
+  }
+
+  pub(super) fn with_help(mut self, help: Option<String>) -> Diagnostic {
+    self.help = help;
+    self
+  }
+
+  // fills in the line the diagnostic is on, unless a more specific one
+  // already has
+  pub(super) fn on(mut self, text: &str, at: Option<&str>) -> Diagnostic {
+    if self.text.is_empty() {
+      self.text = text.to_string();
+      self.at = at.map(String::from);
+    }
+    self
+  }
+
+  // msg, after where in an included file the line is
+  pub fn located(&self) -> String {
+    match &self.at {
+      Some(at) => format!("{}: {}", at, self.msg),
+      None => self.msg.clone(),
+    }
+  }
+
+  // the diagnostic as a compiler would print it, for the source file name:
+  //
+  //   prog.asm:3:3: unknown opcode `.FIL`
+  //       3 | X .FIL 5
+  //         |   ^^^^ did you mean `.FILL`?
+  pub fn render(&self, name: &str) -> String {
+    self.render_as(name, "")
+  }
+
+  // render() with kind, e.g. "warning: ", before the message
+  pub(super) fn render_as(&self, name: &str, kind: &str) -> String {
+    let mut out: String = format!("{}:{}", name, self.line);
+    match (&self.at, self.cols) {
+      (Some(at), Some(c)) => out.push_str(&format!(": {}:{}", at, c.start + 1)),
+      (Some(at), None) => out.push_str(&format!(": {}", at)),
+      (None, Some(c)) => out.push_str(&format!(":{}", c.start + 1)),
+      (None, None) => {},
+    }
+    out.push_str(&format!(": {}{}\n", kind, self.msg));
+    if self.text.is_empty() {
+      return out;
+    }
+
+    let gutter: String = if self.at.is_none() { self.line.to_string() } else { String::new() };
+    out.push_str(&format!("{:>5} | {}\n", gutter, self.text));
+    let help: String = self.help.as_ref().map_or(String::new(), |h| format!(" {}", h));
+    match self.cols {
+      Some(c) => {
+        let carets: String = "^".repeat((c.end - c.start).max(1) as usize);
+        out.push_str(&format!("{:>5} | {:pad$}{}{}\n", "", "", carets, help, pad = c.start as usize));
+      },
+      None if !help.is_empty() => out.push_str(&format!("{:>5} |{}\n", "", help)),
+      None => {},
+    }
+    out
+  }
+
+  // for editors: 1-based line and columns, the end exclusive
+  pub fn to_json(&self) -> Json {
+    let opt = |v: Option<u32>| v.map_or(Json::Null, |v| Json::from(v as u64));
+    Json::object(vec![
+      ("line", Json::from(self.line as u64)),
+      ("column", opt(self.cols.map(|c| c.start + 1))),
+      ("endColumn", opt(self.cols.map(|c| c.end + 1))),
+      ("file", self.at.as_deref().map_or(Json::Null, Json::from)),
+      ("message", Json::from(self.msg.as_str())),
+      ("help", self.help.as_deref().map_or(Json::Null, Json::from)),
+    ])
+  }
+}
+
+impl fmt::Display for Diagnostic {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "line {}: {}", self.line, self.located())
+  }
+}
+
+// edit distance between a and b, ignoring case
+fn distance(a: &str, b: &str) -> usize {
+  let a: Vec<char> = a.to_uppercase().chars().collect();
+  let b: Vec<char> = b.to_uppercase().chars().collect();
+  let mut row: Vec<usize> = (0..=b.len()).collect();
+  for i in 1..=a.len() {
+    let mut diag: usize = row[0];
+    row[0] = i;
+    for j in 1..=b.len() {
+      let next: usize = (diag + (a[i - 1] != b[j - 1]) as usize).min(row[j] + 1).min(row[j - 1] + 1);
+      diag = row[j];
+      row[j] = next;
+    }
+  }
+  row[b.len()]
+}
+
+// "did you mean ...?" for the candidate closest to word, if any is close:
+// one edit away for short words, two for longer ones
+pub(super) fn suggest<'a>(word: &str, candidates: impl Iterator<Item = &'a str>) -> Option<String> {
+  let max: usize = if word.chars().count() <= 4 { 1 } else { 2 };
+  candidates
+    .map(|c| (distance(word, c), c))
+    .filter(|&(d, _)| d <= max)
+    .min_by_key(|&(d, _)| d)
+    .map(|(_, c)| format!("did you mean `{}`?", c))
+}
@@ -0,0 +1,237 @@
+use super::*;
+
+use alloc::boxed::Box;
+use core::convert::TryFrom;
+
+// a constant expression as an operand, e.g.
+//
+//   LD R0, TABLE+2
+//   ADD R1, R1, #10*3
+//   .FILL 'A' | x80
+//   .FILL END - START
+//
+// evaluated as the program is assembled. Atoms are numbers as elsewhere,
+// characters such as 'A' or '\n' and labels. Operators, loosest first: |,
+// ^, &, << and >>, + and -, * / and %, then unary - and ~. Values are
+// integers, narrowed where they're used. Whitespace splits operands, so
+// operators between spaced atoms need a space on both sides.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConstExpr(Node);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+  Num(i64),
+  Label(String),
+  Neg(Box<Node>),
+  Not(Box<Node>),
+  Binary(Op, Box<Node>, Box<Node>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+  Or, Xor, And, Shl, Shr, Add, Sub, Mul, Div, Rem,
+}
+
+// operators by precedence level, loosest first
+const LEVELS: &[&[(&str, Op)]] = &[
+  &[("|", Op::Or)],
+  &[("^", Op::Xor)],
+  &[("&", Op::And)],
+  &[("<<", Op::Shl), (">>", Op::Shr)],
+  &[("+", Op::Add), ("-", Op::Sub)],
+  &[("*", Op::Mul), ("/", Op::Div), ("%", Op::Rem)],
+];
+
+const PUNCT: &[&str] = &["<<", ">>", "|", "^", "&", "+", "-", "*", "/", "%", "~", "(", ")"];
+
+// the characters an operator can start with, for joining spaced operands
+pub(super) const OPERATORS: &str = "|^&<>+-*/%~()";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+  Num(i64),
+  Word(String),
+  Punct(&'static str),
+}
+
+pub(super) enum EvalError {
+  Undefined(String),
+  DivByZero,
+  Overflow,
+  // a shift by a negative count or one of 64 or more
+  Shift(i64),
+}
+
+impl EvalError {
+  pub(super) fn message(&self) -> String {
+    match self {
+      EvalError::Undefined(name) => format!("undefined label `{}`", name),
+      EvalError::DivByZero => "division by zero".to_string(),
+      EvalError::Overflow => "value does not fit in 64 bits".to_string(),
+      EvalError::Shift(n) => format!("shift by {} is not in 0 to 63", n),
+    }
+  }
+}
+
+// the character of a literal such as 'A' or '\n' at the start of src, and
+// the rest of src
+fn char_literal(src: &str) -> Option<(i64, &str)> {
+  let mut chars = src.strip_prefix('\'')?.chars();
+  let c: char = match chars.next()? {
+    '\\' => match chars.next()? {
+      'n' => '\n',
+      't' => '\t',
+      'r' => '\r',
+      '0' => '\0',
+      e => e,
+    },
+    c => c,
+  };
+  let rest: &str = chars.as_str().strip_prefix('\'')?;
+  Some((c as i64, rest))
+}
+
+fn tokenize(src: &str) -> Option<Vec<Token>> {
+  let word = |c: char| c.is_alphanumeric() || c == '_' || c == '#';
+  let mut tokens: Vec<Token> = Vec::new();
+  let mut rest: &str = src.trim_start();
+  while let Some(c) = rest.chars().next() {
+    if c == '\'' {
+      let (n, after) = char_literal(rest)?;
+      tokens.push(Token::Num(n));
+      rest = after;
+    } else if word(c) {
+      // #-5 is one number, not # then -5
+      let start: usize = if rest.starts_with("#-") { 2 } else { 0 };
+      let end: usize = rest[start..].find(|c: char| !word(c)).map_or(rest.len(), |e| start + e);
+      tokens.push(Token::Word(rest[..end].to_string()));
+      rest = &rest[end..];
+    } else {
+      let p: &'static str = PUNCT.iter().find(|p| rest.starts_with(**p))?;
+      tokens.push(Token::Punct(p));
+      rest = &rest[p.len()..];
+    }
+    rest = rest.trim_start();
+  }
+  Some(tokens)
+}
+
+struct Parser {
+  tokens: Vec<Token>,
+  pos: usize,
+}
+
+impl Parser {
+  fn eat(&mut self, p: &str) -> bool {
+    if matches!(self.tokens.get(self.pos), Some(Token::Punct(q)) if *q == p) {
+      self.pos += 1;
+      return true;
+    }
+    false
+  }
+
+  fn binary(&mut self, level: usize) -> Option<Node> {
+    if level == LEVELS.len() {
+      return self.unary();
+    }
+    let mut lhs: Node = self.binary(level + 1)?;
+    'outer: loop {
+      for &(p, op) in LEVELS[level] {
+        if self.eat(p) {
+          let rhs: Node = self.binary(level + 1)?;
+          lhs = Node::Binary(op, Box::new(lhs), Box::new(rhs));
+          continue 'outer;
+        }
+      }
+      return Some(lhs);
+    }
+  }
+
+  fn unary(&mut self) -> Option<Node> {
+    if self.eat("-") {
+      return Some(Node::Neg(Box::new(self.unary()?)));
+    }
+    if self.eat("~") {
+      return Some(Node::Not(Box::new(self.unary()?)));
+    }
+    if self.eat("(") {
+      let inner: Node = self.binary(0)?;
+      return if self.eat(")") { Some(inner) } else { None };
+    }
+    let token: Token = self.tokens.get(self.pos)?.clone();
+    self.pos += 1;
+    match token {
+      Token::Num(n) => Some(Node::Num(n)),
+      Token::Word(w) => match parse_number(&w) {
+        Some(n) => Some(Node::Num(n as i64)),
+        None if is_name(&w) => Some(Node::Label(w)),
+        None => None,
+      },
+      Token::Punct(_) => None,
+    }
+  }
+}
+
+fn eval(node: &Node, lookup: &dyn Fn(&str) -> Option<u16>) -> Result<i64, EvalError> {
+  Ok(match node {
+    Node::Num(n) => *n,
+    Node::Label(name) => match lookup(name) {
+      Some(addr) => addr as i64,
+      None => return Err(EvalError::Undefined(name.clone())),
+    },
+    Node::Neg(v) => eval(v, lookup)?.checked_neg().ok_or(EvalError::Overflow)?,
+    Node::Not(v) => !eval(v, lookup)?,
+    Node::Binary(op, a, b) => {
+      let (a, b): (i64, i64) = (eval(a, lookup)?, eval(b, lookup)?);
+      let shift: Result<u32, EvalError> = u32::try_from(b).ok().filter(|&s| s < 64).ok_or(EvalError::Shift(b));
+      let n: Option<i64> = match op {
+        Op::Or => Some(a | b),
+        Op::Xor => Some(a ^ b),
+        Op::And => Some(a & b),
+        // bits shifted out of the top, or into the sign, are an overflow
+        Op::Shl => {
+          let s: u32 = shift?;
+          Some(a << s).filter(|n| n >> s == a)
+        },
+        Op::Shr => Some(a >> shift?),
+        Op::Add => a.checked_add(b),
+        Op::Sub => a.checked_sub(b),
+        Op::Mul => a.checked_mul(b),
+        Op::Div | Op::Rem if b == 0 => return Err(EvalError::DivByZero),
+        Op::Div => a.checked_div(b),
+        Op::Rem => a.checked_rem(b),
+      };
+      n.ok_or(EvalError::Overflow)?
+    },
+  })
+}
+
+fn names_label(node: &Node) -> bool {
+  match node {
+    Node::Num(_) => false,
+    Node::Label(_) => true,
+    Node::Neg(v) | Node::Not(v) => names_label(v),
+    Node::Binary(_, a, b) => names_label(a) || names_label(b),
+  }
+}
+
+impl ConstExpr {
+  pub fn parse(src: &str) -> Option<ConstExpr> {
+    let mut p: Parser = Parser { tokens: tokenize(src)?, pos: 0 };
+    let node: Node = p.binary(0)?;
+    if p.pos != p.tokens.len() {
+      return None;
+    }
+    Some(ConstExpr(node))
+  }
+
+  // whether the value depends on a label, and so is an address in
+  // PC-relative operands
+  pub fn names_label(&self) -> bool {
+    names_label(&self.0)
+  }
+
+  pub(super) fn eval(&self, lookup: &dyn Fn(&str) -> Option<u16>) -> Result<i64, EvalError> {
+    eval(&self.0, lookup)
+  }
+}
@@ -0,0 +1,145 @@
+use super::*;
+
+// how format_source() lays out a program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+  // where opcodes start, None for one past the longest label. A longer
+  // label is followed by a single space.
+  pub op_column: Option<usize>,
+  // where comments after code start, None for one past the longest line
+  // of code
+  pub comment_column: Option<usize>,
+  // of opcodes, directives and registers; labels keep theirs
+  pub case: Case,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Case {
+  Upper,
+  Lower,
+  Keep,
+}
+
+impl Default for Style {
+  fn default() -> Style {
+    Style { op_column: None, comment_column: None, case: Case::Upper }
+  }
+}
+
+// the narrowest opcode column when it follows the labels
+const MIN_OP_COLUMN: usize = 4;
+
+// a line taken apart for laying out again
+enum Parts {
+  Code { label: Option<String>, op: Option<String>, operands: Vec<String>, comment: Option<String> },
+  // a line with only a comment, and whether it was indented
+  Comment { text: String, indented: bool },
+  // blank lines, and lines that don't parse, e.g. in macro bodies, which
+  // are left as they are
+  Verbatim(String),
+}
+
+fn cut(text: &str, cols: Span) -> String {
+  text.chars().skip(cols.start as usize).take((cols.end - cols.start) as usize).collect()
+}
+
+fn recase(s: &str, case: Case) -> String {
+  match case {
+    Case::Upper => s.to_uppercase(),
+    Case::Lower => s.to_lowercase(),
+    Case::Keep => s.to_string(),
+  }
+}
+
+fn parts(num: usize, text: &str, case: Case) -> Parts {
+  let line: Line = match parse_line(num, text) {
+    Ok(Some(line)) => line,
+    Ok(None) if text.trim_start().starts_with(';') => {
+      return Parts::Comment { text: text.trim().to_string(), indented: text.starts_with(char::is_whitespace) };
+    },
+    _ => return Parts::Verbatim(text.trim_end().to_string()),
+  };
+
+  let operands: Vec<String> = line.operands.iter().zip(&line.spans.operands)
+    .map(|(op, &cols)| match op {
+      Operand::Reg(_) => recase(&cut(text, cols), case),
+      _ => cut(text, cols),
+    })
+    .collect();
+  // everything after the last token that is a comment
+  let end: u32 = line.spans.operands.last().copied().or(line.spans.op).or(line.spans.label).map_or(0, |c| c.end);
+  let rest: String = text.chars().skip(end as usize).collect();
+  let comment: Option<String> = rest.find(';').map(|i| rest[i..].trim_end().to_string());
+  Parts::Code {
+    label: line.spans.label.map(|c| cut(text, c)),
+    op: line.spans.op.map(|c| recase(&cut(text, c), case)),
+    operands,
+    comment,
+  }
+}
+
+// src with labels, opcodes, operands and comments lined up in columns,
+// operands separated by ", "
+pub fn format_source(src: &str, style: &Style) -> String {
+  let lines: Vec<Parts> = src.lines().enumerate().map(|(i, text)| parts(i + 1, text, style.case)).collect();
+
+  let longest_label: usize = lines.iter()
+    .filter_map(|p| match p {
+      Parts::Code { label: Some(l), op: Some(_), .. } => Some(l.chars().count()),
+      _ => None,
+    })
+    .max()
+    .unwrap_or(0);
+  let op_column: usize = style.op_column.unwrap_or((longest_label + 1).max(MIN_OP_COLUMN));
+
+  // label, opcode and operands of each line
+  let code: Vec<Option<String>> = lines.iter().map(|p| match p {
+    Parts::Code { label, op, operands, .. } => {
+      let mut out: String = label.clone().unwrap_or_default();
+      if let Some(op) = op {
+        let pad: usize = op_column.saturating_sub(out.chars().count()).max(if out.is_empty() { 0 } else { 1 });
+        out.push_str(&" ".repeat(pad));
+        out.push_str(op);
+        if !operands.is_empty() {
+          out.push(' ');
+          out.push_str(&operands.join(", "));
+        }
+      }
+      Some(out)
+    },
+    _ => None,
+  }).collect();
+
+  let longest_code: usize = lines.iter().zip(&code)
+    .filter_map(|(p, c)| match p {
+      Parts::Code { comment: Some(_), .. } => c.as_ref().map(|c| c.chars().count()),
+      _ => None,
+    })
+    .max()
+    .unwrap_or(0);
+  let comment_column: usize = style.comment_column.unwrap_or(longest_code + 1);
+
+  let mut out: String = String::with_capacity(src.len());
+  for (p, c) in lines.iter().zip(code) {
+    match p {
+      Parts::Code { comment, .. } => {
+        let mut line: String = c.unwrap();
+        if let Some(comment) = comment {
+          let pad: usize = comment_column.saturating_sub(line.chars().count()).max(if line.is_empty() { 0 } else { 1 });
+          line.push_str(&" ".repeat(pad));
+          line.push_str(comment);
+        }
+        out.push_str(&line);
+      },
+      Parts::Comment { text, indented } => {
+        if *indented {
+          out.push_str(&" ".repeat(op_column));
+        }
+        out.push_str(text);
+      },
+      Parts::Verbatim(text) => out.push_str(text),
+    }
+    out.push('\n');
+  }
+  out
+}
@@ -0,0 +1,316 @@
+use super::*;
+
+use alloc::collections::{BTreeMap, BTreeSet};
+
+use analysis::flow;
+use instr::decode;
+use json::Json;
+
+// what lint() looks for in a program that assembles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LintKind {
+  // an instruction after an unconditional branch that nothing branches to
+  Unreachable,
+  // a BR or JSR to a .FILL, .BLKW or .STRINGZ
+  BranchToData,
+  // an instruction that goes on into data
+  FallIntoData,
+  // no HALT is reachable from the start of the program
+  MissingHalt,
+  // a JSR to a subroutine that overwrites a register the caller set
+  // before the call and reads after it
+  ClobberedRegister,
+}
+
+pub const LINTS: &[LintKind] = &[
+  LintKind::Unreachable,
+  LintKind::BranchToData,
+  LintKind::FallIntoData,
+  LintKind::MissingHalt,
+  LintKind::ClobberedRegister,
+];
+
+impl LintKind {
+  pub fn name(self) -> &'static str {
+    match self {
+      LintKind::Unreachable => "unreachable",
+      LintKind::BranchToData => "branch-to-data",
+      LintKind::FallIntoData => "fall-into-data",
+      LintKind::MissingHalt => "missing-halt",
+      LintKind::ClobberedRegister => "clobbered-register",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<LintKind> {
+    LINTS.iter().copied().find(|l| l.name() == name)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lint {
+  pub kind: LintKind,
+  pub diagnostic: Diagnostic,
+}
+
+impl Lint {
+  // as Diagnostic::render(), marked as a warning:
+  //
+  //   prog.asm:5:3: warning[unreachable]: unreachable instruction
+  pub fn render(&self, name: &str) -> String {
+    self.diagnostic.render_as(name, &format!("warning[{}]: ", self.kind.name()))
+  }
+
+  // Diagnostic::to_json() with the lint's name
+  pub fn to_json(&self) -> Json {
+    match self.diagnostic.to_json() {
+      Json::Object(mut fields) => {
+        fields.push(("lint".to_string(), Json::from(self.kind.name())));
+        Json::Object(fields)
+      },
+      json => json,
+    }
+  }
+}
+
+// one word of the program, op None for data
+struct Word<'a> {
+  line: &'a Line,
+  src: &'a Source,
+  op: Option<Instruction>,
+}
+
+struct Linter<'a> {
+  origin: u16,
+  words: Vec<Word<'a>>,
+  symbols: &'a SymbolTable,
+  // every address something branches or calls to
+  targets: BTreeSet<u16>,
+  lints: Vec<Lint>,
+}
+
+impl<'a> Linter<'a> {
+  fn word(&self, addr: u16) -> Option<&Word<'a>> {
+    self.words.get(addr.wrapping_sub(self.origin) as usize)
+  }
+
+  fn code(&self, addr: u16) -> Option<Instruction> {
+    self.word(addr).and_then(|w| w.op)
+  }
+
+  fn is_data(&self, addr: u16) -> bool {
+    self.word(addr).is_some_and(|w| w.op.is_none())
+  }
+
+  // `LABEL` for addr, or its address
+  fn name(&self, addr: u16) -> String {
+    match self.symbols.label(addr) {
+      Some(label) => format!("`{}`", label),
+      None => format!("x{:04X}", addr),
+    }
+  }
+
+  fn warn(&mut self, kind: LintKind, addr: u16, operand: Option<usize>, msg: String, help: Option<String>) {
+    let (line, src): (&Line, &Source) = match self.word(addr) {
+      Some(w) => (w.line, w.src),
+      None => return,
+    };
+    let cols: Option<Span> = match operand {
+      Some(i) => operand_cols(line, Some(i)),
+      None => line.spans.op,
+    };
+    let d: Diagnostic = Diagnostic::new(line.num, cols, msg).with_help(help).on(&src.text, src.at.as_deref());
+    self.lints.push(Lint { kind, diagnostic: d });
+  }
+
+  fn addrs(&self) -> impl Iterator<Item = (u16, Instruction)> + '_ {
+    self.words.iter().enumerate().filter_map(move |(i, w)| w.op.map(|op| (self.origin.wrapping_add(i as u16), op)))
+  }
+
+  fn unreachable(&mut self) {
+    let found: Vec<u16> = self.addrs()
+      .filter(|&(addr, op)| !flow(addr, op).1)
+      .map(|(addr, _)| addr.wrapping_add(1))
+      .filter(|&next| self.code(next).is_some() && self.symbols.label(next).is_none() && !self.targets.contains(&next))
+      .collect();
+    for addr in found {
+      self.warn(LintKind::Unreachable, addr, None, "unreachable instruction".to_string(),
+        Some("nothing branches here and the instruction before never goes on".to_string()));
+    }
+  }
+
+  fn branch_to_data(&mut self) {
+    let found: Vec<(u16, u16)> = self.addrs()
+      .filter(|(_, op)| matches!(op, Instruction::Br { .. } | Instruction::Jsr { .. }))
+      .filter_map(|(addr, op)| flow(addr, op).0.map(|t| (addr, t)))
+      .filter(|&(_, t)| self.is_data(t))
+      .collect();
+    for (addr, target) in found {
+      let msg: String = format!("branch to {}, which is data", self.name(target));
+      self.warn(LintKind::BranchToData, addr, Some(0), msg, None);
+    }
+  }
+
+  fn fall_into_data(&mut self) {
+    let found: Vec<u16> = self.addrs()
+      .filter(|&(addr, op)| flow(addr, op).1)
+      .map(|(addr, _)| addr.wrapping_add(1))
+      .filter(|&next| self.is_data(next))
+      .collect();
+    for addr in found {
+      self.warn(LintKind::FallIntoData, addr, None, "execution goes on into data".to_string(),
+        Some("is a HALT or BR missing before it?".to_string()));
+    }
+  }
+
+  // whether a HALT can be reached from the origin
+  fn halts(&self) -> bool {
+    let mut seen: BTreeSet<u16> = BTreeSet::new();
+    let mut todo: Vec<u16> = vec![self.origin];
+    while let Some(addr) = todo.pop() {
+      let op: Instruction = match self.code(addr) {
+        Some(op) if seen.insert(addr) => op,
+        _ => continue,
+      };
+      if op == (Instruction::Trap { vector: 0x25 }) {
+        return true;
+      }
+      let (target, falls): (Option<u16>, bool) = flow(addr, op);
+      todo.extend(target);
+      if falls {
+        todo.push(addr.wrapping_add(1));
+      }
+    }
+    false
+  }
+
+  // the registers the subroutine at entry overwrites for its caller: the
+  // ones it writes without storing them anywhere, which would save them,
+  // or reading them first, which makes them arguments. R7 aside, as every
+  // JSR writes it.
+  fn clobbers(&self, entry: u16, memo: &mut BTreeMap<u16, u8>) -> u8 {
+    if let Some(&c) = memo.get(&entry) {
+      return c;
+    }
+    // recursion clobbers nothing more
+    memo.insert(entry, 0);
+
+    let (mut writes, mut stores, mut args): (u8, u8, u8) = (0, 0, 0);
+    // arguments are read before they're written on the way in
+    let mut written: u8 = 0;
+    let mut straight: bool = true;
+    let mut seen: BTreeSet<u16> = BTreeSet::new();
+    let mut todo: Vec<u16> = vec![entry];
+    while let Some(addr) = todo.pop() {
+      let op: Instruction = match self.code(addr) {
+        Some(op) if seen.insert(addr) => op,
+        _ => continue,
+      };
+      if straight {
+        args |= op.reads() & !written;
+        written |= op.writes();
+      }
+      writes |= op.writes();
+      if let Instruction::St { sr, .. } | Instruction::Sti { sr, .. } | Instruction::Str { sr, .. } = op {
+        stores |= 1 << sr;
+      }
+      let (target, falls): (Option<u16>, bool) = flow(addr, op);
+      match (op, target) {
+        (Instruction::Jsr { .. }, Some(t)) => writes |= self.clobbers(t, memo),
+        (_, Some(t)) => {
+          straight = false;
+          todo.push(t);
+        },
+        _ => {},
+      }
+      if falls {
+        todo.push(addr.wrapping_add(1));
+      } else {
+        straight = false;
+      }
+    }
+
+    let c: u8 = writes & !stores & !args & !(1 << 7);
+    memo.insert(entry, c);
+    c
+  }
+
+  fn clobbered(&mut self) {
+    let mut memo: BTreeMap<u16, u8> = BTreeMap::new();
+    let calls: Vec<(u16, u16)> = self.addrs()
+      .filter_map(|(addr, op)| match (op, flow(addr, op).0) {
+        (Instruction::Jsr { .. }, Some(t)) if self.code(t).is_some() => Some((addr, t)),
+        _ => None,
+      })
+      .collect();
+
+    for (call, sub) in calls {
+      // set on the way straight into the call
+      let mut set: u8 = 0;
+      let mut addr: u16 = call;
+      while !self.targets.contains(&addr) && self.symbols.label(addr).is_none() {
+        addr = addr.wrapping_sub(1);
+        match self.code(addr) {
+          Some(op) if flow(addr, op) == (None, true) => set |= op.writes(),
+          _ => break,
+        }
+      }
+      // read after it returns before they're written again
+      let (mut read, mut written): (u8, u8) = (0, 0);
+      addr = call;
+      loop {
+        addr = addr.wrapping_add(1);
+        let op: Instruction = match self.code(addr) {
+          Some(op) => op,
+          None => break,
+        };
+        read |= op.reads() & !written;
+        written |= op.writes();
+        if flow(addr, op) != (None, true) {
+          break;
+        }
+      }
+
+      let lost: u8 = set & read & self.clobbers(sub, &mut memo);
+      for r in (0..8).filter(|r| lost & (1 << r) != 0) {
+        let msg: String = format!("{} overwrites R{}, which is set before this call and read after it", self.name(sub), r);
+        let help: String = format!("save and restore R{} in {}", r, self.name(sub));
+        self.warn(LintKind::ClobberedRegister, call, Some(0), msg, Some(help));
+      }
+    }
+  }
+}
+
+// the lints of src, the file with key name, reading the files it
+// includes with include; Err if it doesn't assemble
+pub fn lint(src: &str, name: Option<&str>, include: &mut Include) -> Result<Vec<Lint>, AsmError> {
+  let srcs: Vec<Source> = preprocess(src, name, include).map_err(|d| AsmError::from(vec![d]))?;
+  let lines: Vec<(Line, &Source)> = parse_sources(&srcs)?;
+  let (origin, body): (u16, Range<usize>) = program_body(&lines)?;
+  let orig: usize = body.start - 1;
+  let prog: Program = assemble_lines(&lines[body.clone()], origin, SymbolTable::new())?;
+
+  let mut words: Vec<Word> = Vec::new();
+  for (line, src) in &lines[body] {
+    let data: bool = matches!(line.op.as_deref(), Some(".FILL") | Some(".BLKW") | Some(".STRINGZ"));
+    for _ in 0..size(line).unwrap_or(0) {
+      let op: Option<Instruction> = if data { None } else { Some(decode(prog.words[words.len()])) };
+      words.push(Word { line, src, op });
+    }
+  }
+
+  let mut l: Linter = Linter { origin, words, symbols: &prog.symbols, targets: BTreeSet::new(), lints: Vec::new() };
+  l.targets = l.addrs().filter_map(|(addr, op)| flow(addr, op).0).collect();
+  l.unreachable();
+  l.branch_to_data();
+  l.fall_into_data();
+  if !l.halts() {
+    let (line, src): &(Line, &Source) = &lines[orig];
+    let d: Diagnostic = Diagnostic::new(line.num, line.spans.op, "no HALT is reachable from the start of the program");
+    l.lints.push(Lint { kind: LintKind::MissingHalt, diagnostic: d.on(&src.text, src.at.as_deref()) });
+  }
+  l.clobbered();
+
+  let mut lints: Vec<Lint> = l.lints;
+  lints.sort_by_key(|l| l.diagnostic.line);
+  Ok(lints)
+}
@@ -0,0 +1,296 @@
+use super::*;
+
+use alloc::collections::BTreeMap;
+
+// macros and includes, expanded before parsing:
+//
+//   .MACRO PUSH reg        ; a macro named PUSH, with one parameter
+//     ADD R6, R6, #-1
+//     STR \reg, R6, #0
+//   .ENDM
+//   PUSH R1                ; the body with \reg replaced by R1
+//   .INCLUDE "print.asm"   ; the lines of print.asm
+//
+// \@ in a body is a number unique to each expansion, for labels such as
+// LOOP\@. Macros are defined before use and may use one another; included
+// files may not have .ORIG or .END.
+
+// how deep macros may expand inside one another, to stop one that
+// invokes itself
+const MAX_DEPTH: usize = 64;
+
+// a line left once macros and includes are expanded: the line of the
+// top-level source it stands for and, for lines of included files, where
+// it came from as "file:line", after the lines including that file
+pub(super) struct Source {
+  pub num: usize,
+  pub text: String,
+  pub at: Option<String>,
+}
+
+struct Macro {
+  params: Vec<String>,
+  body: Vec<String>,
+}
+
+// a macro between its .MACRO and .ENDM
+struct Pending {
+  name: String,
+  mac: Macro,
+  num: usize,
+  at: Option<String>,
+}
+
+// a file being read: the key its includer gave it, its name as written
+// and the line being read
+struct File {
+  key: Option<String>,
+  name: String,
+  line: usize,
+}
+
+struct Expander<'a, 'b> {
+  include: &'a mut Include<'b>,
+  macros: BTreeMap<String, Macro>,
+  pending: Option<Pending>,
+  files: Vec<File>,
+  expansions: usize,
+  out: Vec<Source>,
+}
+
+fn fail<T>(num: usize, at: &Option<String>, msg: impl Into<String>) -> Result<T, Diagnostic> {
+  let mut d: Diagnostic = Diagnostic::new(num, None, msg);
+  d.at = at.clone();
+  Err(d)
+}
+
+// splits text at whitespace and commas up to its comment, as
+// split_tokens() does, but leaving strings and characters quoted and
+// escaped as written
+fn raw_tokens(text: &str) -> Vec<&str> {
+  let mut toks: Vec<&str> = Vec::new();
+  let mut start: Option<usize> = None;
+  let (mut quote, mut escaped): (Option<char>, bool) = (None, false);
+  let mut end: usize = text.len();
+
+  for (i, c) in text.char_indices() {
+    if let Some(q) = quote {
+      if escaped {
+        escaped = false;
+      } else if c == '\\' {
+        escaped = true;
+      } else if c == q {
+        quote = None;
+      }
+    } else if c == '"' || c == '\'' {
+      quote = Some(c);
+      start.get_or_insert(i);
+    } else if c == ';' {
+      end = i;
+      break;
+    } else if c.is_whitespace() || c == ',' {
+      if let Some(s) = start.take() {
+        toks.push(&text[s..i]);
+      }
+    } else if start.is_none() {
+      start = Some(i);
+    }
+  }
+  if let Some(s) = start {
+    toks.push(&text[s..end]);
+  }
+  toks
+}
+
+// a line of a macro body with \param replaced by its argument and \@ by
+// n, a number unique to the expansion for making labels unique. Strings,
+// characters and comments are left alone.
+fn substitute(text: &str, params: &[String], args: &[&str], n: usize) -> Result<String, String> {
+  let mut out: String = String::with_capacity(text.len());
+  let mut chars = text.char_indices().peekable();
+  let (mut quote, mut escaped): (Option<char>, bool) = (None, false);
+
+  while let Some((i, c)) = chars.next() {
+    if let Some(q) = quote {
+      out.push(c);
+      if escaped {
+        escaped = false;
+      } else if c == '\\' {
+        escaped = true;
+      } else if c == q {
+        quote = None;
+      }
+    } else if c == ';' {
+      out.push_str(&text[i..]);
+      break;
+    } else if c == '\\' {
+      if chars.next_if(|&(_, c)| c == '@').is_some() {
+        out.push_str(&n.to_string());
+        continue;
+      }
+      let mut name: String = String::new();
+      while let Some((_, c)) = chars.next_if(|&(_, c)| c.is_alphanumeric() || c == '_') {
+        name.push(c);
+      }
+      match params.iter().position(|p| *p == name) {
+        Some(j) => out.push_str(args[j]),
+        None => return Err(format!("unknown macro parameter `\\{}`", name)),
+      }
+    } else {
+      if c == '"' || c == '\'' {
+        quote = Some(c);
+      }
+      out.push(c);
+    }
+  }
+  Ok(out)
+}
+
+impl<'a, 'b> Expander<'a, 'b> {
+  fn is_macro(&self, tok: &str) -> bool {
+    self.macros.contains_key(&tok.to_uppercase())
+  }
+
+  // where the line being read is, None at the top level
+  fn at(&self) -> Option<String> {
+    let chain: Vec<String> = self.files[1..].iter().map(|f| format!("{}:{}", f.name, f.line)).collect();
+    if chain.is_empty() { None } else { Some(chain.join(": ")) }
+  }
+
+  fn file(&mut self, src: &str, top: Option<usize>) -> Result<(), Diagnostic> {
+    for (i, text) in src.lines().enumerate() {
+      self.files.last_mut().unwrap().line = i + 1;
+      let at: Option<String> = self.at();
+      self.line(top.unwrap_or(i + 1), &at, text, 0).map_err(|d| d.on(text, at.as_deref()))?;
+    }
+    match self.pending.take() {
+      Some(p) => fail(p.num, &p.at, format!("macro `{}` has no .ENDM", p.name)),
+      None => Ok(()),
+    }
+  }
+
+  fn line(&mut self, num: usize, at: &Option<String>, text: &str, depth: usize) -> Result<(), Diagnostic> {
+    let toks: Vec<&str> = raw_tokens(text);
+    let first: Option<String> = toks.first().map(|t| t.to_uppercase());
+
+    if let Some(p) = &mut self.pending {
+      match first.as_deref() {
+        Some(".ENDM") => {
+          let p: Pending = self.pending.take().unwrap();
+          self.macros.insert(p.name, p.mac);
+        },
+        Some(".MACRO") => return fail(num, at, "macros cannot be defined inside macros"),
+        _ => p.mac.body.push(text.to_string()),
+      }
+      return Ok(());
+    }
+
+    match first.as_deref() {
+      Some(".MACRO") => return self.define(num, at, &toks[1..]),
+      Some(".ENDM") => return fail(num, at, ".ENDM without .MACRO"),
+      Some(".INCLUDE") => return self.include(num, at, &toks[1..]),
+      _ => {},
+    }
+
+    // a macro invocation, possibly after a label that is left on a line of
+    // its own
+    let call: Option<usize> = if toks.first().is_some_and(|t| self.is_macro(t)) {
+      Some(0)
+    } else if toks.len() > 1 && !is_opcode(toks[0]) && self.is_macro(toks[1]) {
+      Some(1)
+    } else {
+      None
+    };
+    match call {
+      Some(i) => {
+        if i == 1 {
+          self.out.push(Source { num, text: toks[0].to_string(), at: at.clone() });
+        }
+        self.expand(num, at, toks[i], &toks[i + 1..], depth)
+      },
+      None => {
+        if self.files.len() > 1 && toks.iter().take(2).any(|t| t.eq_ignore_ascii_case(".ORIG") || t.eq_ignore_ascii_case(".END")) {
+          return fail(num, at, "included files cannot have .ORIG or .END");
+        }
+        self.out.push(Source { num, text: text.to_string(), at: at.clone() });
+        Ok(())
+      },
+    }
+  }
+
+  fn define(&mut self, num: usize, at: &Option<String>, toks: &[&str]) -> Result<(), Diagnostic> {
+    let name: String = match toks.first() {
+      Some(t) if is_name(t) && !is_opcode(t) => t.to_uppercase(),
+      Some(t) => return fail(num, at, format!("invalid macro name `{}`", t)),
+      None => return fail(num, at, ".MACRO expects a name"),
+    };
+    if self.macros.contains_key(&name) {
+      return fail(num, at, format!("macro `{}` is already defined", toks[0]));
+    }
+    let mut params: Vec<String> = Vec::new();
+    for t in &toks[1..] {
+      if !is_name(t) {
+        return fail(num, at, format!("invalid macro parameter `{}`", t));
+      }
+      if params.iter().any(|p| p == t) {
+        return fail(num, at, format!("duplicate macro parameter `{}`", t));
+      }
+      params.push(t.to_string());
+    }
+    self.pending = Some(Pending { name, mac: Macro { params, body: Vec::new() }, num, at: at.clone() });
+    Ok(())
+  }
+
+  fn expand(&mut self, num: usize, at: &Option<String>, name: &str, args: &[&str], depth: usize) -> Result<(), Diagnostic> {
+    if depth == MAX_DEPTH {
+      return fail(num, at, format!("macro `{}` expands too deeply", name));
+    }
+    self.expansions += 1;
+    let n: usize = self.expansions;
+    let mac: &Macro = &self.macros[&name.to_uppercase()];
+    if args.len() != mac.params.len() {
+      return fail(num, at, format!("macro `{}` expects {} arguments, found {}", name, mac.params.len(), args.len()));
+    }
+    let body: Vec<String> = match mac.body.iter().map(|l| substitute(l, &mac.params, args, n)).collect() {
+      Ok(body) => body,
+      Err(msg) => return fail(num, at, msg),
+    };
+    for text in &body {
+      self.line(num, at, text, depth + 1)?;
+    }
+    Ok(())
+  }
+
+  fn include(&mut self, num: usize, at: &Option<String>, toks: &[&str]) -> Result<(), Diagnostic> {
+    let name: &str = match toks {
+      [t] if t.len() >= 2 && t.starts_with('"') && t.ends_with('"') => &t[1..t.len() - 1],
+      _ => return fail(num, at, ".INCLUDE expects a file name in quotes"),
+    };
+    let from: Option<&str> = self.files.last().and_then(|f| f.key.as_deref());
+    let (key, src): (String, String) = match (self.include)(from, name) {
+      Ok(file) => file,
+      Err(e) => return fail(num, at, format!("cannot include `{}`: {}", name, e)),
+    };
+    if self.files.iter().any(|f| f.key.as_deref() == Some(key.as_str())) {
+      return fail(num, at, format!("`{}` includes itself", name));
+    }
+    self.files.push(File { key: Some(key), name: name.to_string(), line: 0 });
+    self.file(&src, Some(num))?;
+    self.files.pop();
+    Ok(())
+  }
+}
+
+// expands the macros and includes of src, named name for the includer
+pub(super) fn preprocess(src: &str, name: Option<&str>, include: &mut Include) -> Result<Vec<Source>, Diagnostic> {
+  let mut ex: Expander = Expander {
+    include,
+    macros: BTreeMap::new(),
+    pending: None,
+    files: vec![File { key: name.map(String::from), name: name.unwrap_or_default().to_string(), line: 0 }],
+    expansions: 0,
+    out: Vec::new(),
+  };
+  ex.file(src, None)?;
+  Ok(ex.out)
+}
@@ -0,0 +1,538 @@
+extern crate lc3;
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use lc3::assembler::{self, Diagnostic, LineEntry};
+use lc3::instr::{self, Instruction};
+use lc3::json::Json;
+use lc3::{Console, Expr, FormatError, Goal, Machine, Reg, Run, StopReason, NEG, POS, ZRO};
+
+// Debug Adapter Protocol server on stdin/stdout. Launch arguments are
+// `program` (.obj or .asm), `stopOnEntry` and `os`. Source breakpoints and
+// line numbers need an .asm program, whose line table maps lines to
+// addresses. Keyboard input is typed into the debug console, each
+// evaluate request queues its text and a newline.
+
+// steps executed between checks for new requests while running
+const SLICE: u64 = 10_000;
+
+const THREAD: i64 = 1;
+const REGISTERS: i64 = 1;
+
+const GPRS: [Reg; 8] = [Reg::R0, Reg::R1, Reg::R2, Reg::R3, Reg::R4, Reg::R5, Reg::R6, Reg::R7];
+
+#[derive(Default)]
+struct Io {
+  output: Vec<u8>,
+  keys: VecDeque<u8>,
+}
+
+struct DapConsole(Rc<RefCell<Io>>);
+
+impl Console for DapConsole {
+  // the adapter doesn't run into GETC without a key queued, see waiting()
+  fn read_char(&mut self) -> Option<u8> {
+    self.0.borrow_mut().keys.pop_front()
+  }
+
+  fn write_char(&mut self, c: u8) {
+    self.0.borrow_mut().output.push(c);
+  }
+
+  fn poll_key(&mut self) -> Option<u8> {
+    self.0.borrow_mut().keys.pop_front()
+  }
+}
+
+// one base-protocol message per item, None once stdin closes
+fn messages() -> Receiver<Json> {
+  let (tx, rx) = mpsc::channel();
+  thread::spawn(move || {
+    let mut input = BufReader::new(io::stdin());
+    loop {
+      let mut len: Option<usize> = None;
+      loop {
+        let mut line: String = String::new();
+        match input.read_line(&mut line) {
+          Ok(0) | Err(_) => return,
+          Ok(_) => {},
+        }
+        let line: &str = line.trim();
+        if line.is_empty() {
+          break;
+        }
+        if let Some(n) = line.strip_prefix("Content-Length:") {
+          len = n.trim().parse().ok();
+        }
+      }
+
+      let mut body: Vec<u8> = vec![0; len.unwrap_or(0)];
+      if input.read_exact(&mut body).is_err() {
+        return;
+      }
+      match Json::parse(&String::from_utf8_lossy(&body)) {
+        Ok(msg) => if tx.send(msg).is_err() { return },
+        Err(e) => eprintln!("lc3-dap: {}", e),
+      }
+    }
+  });
+  rx
+}
+
+// how far a step request runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+  In,
+  Over,
+  Out,
+}
+
+struct Adapter {
+  seq: i64,
+  m: Option<Machine>,
+  io: Rc<RefCell<Io>>,
+  source: Option<String>,
+  lines: Vec<LineEntry>,
+  stop_on_entry: bool,
+  running: bool,
+}
+
+fn next_op(m: &Machine) -> Instruction {
+  instr::decode(m.read_mem(m.read_reg(Reg::PC)))
+}
+
+impl Adapter {
+  fn send(&mut self, kind: &str, mut fields: Vec<(&str, Json)>) {
+    self.seq += 1;
+    let mut msg: Vec<(&str, Json)> = vec![("seq", Json::from(self.seq)), ("type", Json::from(kind))];
+    msg.append(&mut fields);
+    let body: String = Json::object(msg).to_string();
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let _ = write!(out, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+    let _ = out.flush();
+  }
+
+  fn event(&mut self, event: &str, body: Json) {
+    self.send("event", vec![("event", Json::from(event)), ("body", body)]);
+  }
+
+  fn respond(&mut self, req: &Json, body: Json) {
+    let (seq, command) = request_header(req);
+    self.send("response", vec![
+      ("request_seq", Json::from(seq)),
+      ("success", Json::from(true)),
+      ("command", Json::from(command)),
+      ("body", body),
+    ]);
+  }
+
+  fn fail(&mut self, req: &Json, message: &str) {
+    let (seq, command) = request_header(req);
+    self.send("response", vec![
+      ("request_seq", Json::from(seq)),
+      ("success", Json::from(false)),
+      ("command", Json::from(command)),
+      ("message", Json::from(message)),
+    ]);
+  }
+
+  fn stopped(&mut self, reason: &str, text: Option<String>) {
+    self.running = false;
+    let mut body: Vec<(&str, Json)> = vec![
+      ("reason", Json::from(reason)),
+      ("threadId", Json::from(THREAD)),
+      ("allThreadsStopped", Json::from(true)),
+    ];
+    if let Some(text) = text {
+      body.push(("text", Json::from(text)));
+    }
+    self.event("stopped", Json::object(body));
+  }
+
+  fn flush_output(&mut self) {
+    let output: Vec<u8> = std::mem::take(&mut self.io.borrow_mut().output);
+    if !output.is_empty() {
+      self.event("output", Json::object(vec![
+        ("category", Json::from("stdout")),
+        ("output", Json::from(String::from_utf8_lossy(&output).into_owned())),
+      ]));
+    }
+  }
+
+  // assembler errors as output the client can show against the source
+  fn diagnostics(&mut self, program: &str, diagnostics: &[Diagnostic]) {
+    for d in diagnostics {
+      let mut body: Vec<(&str, Json)> = vec![
+        ("category", Json::from("stderr")),
+        ("output", Json::from(d.render(program))),
+        ("source", Json::object(vec![("path", Json::from(program))])),
+        ("line", Json::from(d.line as u64)),
+      ];
+      if let (None, Some(cols)) = (&d.at, d.cols) {
+        body.push(("column", Json::from(cols.start as u64 + 1)));
+      }
+      self.event("output", Json::object(body));
+    }
+  }
+
+  // reports why a run or step stopped
+  fn finish(&mut self, reason: StopReason) {
+    self.flush_output();
+    match reason {
+      StopReason::Breakpoint(_) => self.stopped("breakpoint", None),
+      StopReason::Watchpoint(..) => self.stopped("data breakpoint", None),
+      StopReason::Trap(vector) => self.stopped("exception", Some(format!("trap x{:02X}", vector))),
+      StopReason::StepLimit | StopReason::GoalReached => self.stopped("step", None),
+      StopReason::Condition => self.stopped("pause", Some("waiting for input".to_string())),
+      StopReason::LimitExceeded => self.stopped("pause", Some("limit exceeded".to_string())),
+      StopReason::NoProgress => self.stopped("pause", Some("stuck in a loop".to_string())),
+      StopReason::Fault(e) => self.stopped("exception", Some(e.to_string())),
+      StopReason::Halt => {
+        self.running = false;
+        self.event("exited", Json::object(vec![("exitCode", Json::from(0i64))]));
+        self.event("terminated", Json::object(vec![]));
+      },
+    }
+  }
+
+  // true when the next instruction blocks on keyboard input that hasn't
+  // been typed yet
+  fn waiting(m: &Machine, io: &RefCell<Io>) -> bool {
+    let reads_key: bool = matches!(next_op(m),
+      Instruction::Trap { vector: 0x20 } | Instruction::Trap { vector: 0x23 });
+    reads_key && io.borrow().keys.is_empty()
+  }
+
+  fn launch(&mut self, req: &Json) {
+    let args: &Json = req.get("arguments").unwrap_or(&Json::Null);
+    let program: String = match args.get("program").and_then(Json::as_str) {
+      Some(p) => p.to_string(),
+      None => return self.fail(req, "launch needs a `program`"),
+    };
+    self.stop_on_entry = args.get("stopOnEntry").and_then(Json::as_bool).unwrap_or(false);
+    let os: bool = args.get("os").and_then(Json::as_bool).unwrap_or(false);
+
+    let mut m = Machine::builder().io(Box::new(DapConsole(self.io.clone()))).os(os).build();
+    let path: &Path = Path::new(&program);
+    let loaded: Result<(), String> = if path.extension().is_some_and(|e| e == "asm") {
+      fs::read_to_string(path).map_err(|e| e.to_string())
+        .and_then(|src| assembler::assemble_file(&src, path).map_err(|e| {
+          self.diagnostics(&program, &e.diagnostics);
+          e.to_string()
+        }))
+        .and_then(|prog| {
+          m.load_image_bytes(&prog.to_obj()).map_err(|e| e.to_string())?;
+          m.symbols_mut().extend(&prog.symbols);
+          m.write_reg(Reg::PC, prog.origin);
+          self.lines = prog.lines;
+          self.source = Some(program.clone());
+          Ok(())
+        })
+    } else {
+      m.load_program(path).map_err(|e| e.to_string())
+    };
+
+    match loaded {
+      Ok(()) => {
+        self.m = Some(m);
+        self.respond(req, Json::Null);
+      },
+      Err(e) => self.fail(req, &format!("failed to load {}: {}", program, e)),
+    }
+  }
+
+  fn set_breakpoints(&mut self, req: &Json) {
+    let args: &Json = req.get("arguments").unwrap_or(&Json::Null);
+    let path: Option<&str> = args.get("source").and_then(|s| s.get("path")).and_then(Json::as_str);
+    let ours: bool = path.is_some() && path == self.source.as_deref();
+    // lines, each with an optional condition expression, see lc3::Expr
+    let wanted: Vec<(i64, Option<String>)> = args.get("breakpoints").and_then(Json::as_array).unwrap_or(&[])
+      .iter()
+      .filter_map(|b| {
+        let cond: Option<String> = b.get("condition").and_then(Json::as_str).map(|c| c.to_string());
+        b.get("line").and_then(Json::as_i64).map(|l| (l, cond))
+      })
+      .collect();
+
+    let m: &mut Machine = match self.m.as_mut() {
+      Some(m) => m,
+      None => return self.fail(req, "no program launched"),
+    };
+    if ours {
+      for bp in m.breakpoints().to_vec() {
+        m.remove_breakpoint(bp);
+      }
+    }
+
+    // a breakpoint on a line without code moves to the next line with some
+    let mut result: Vec<Json> = Vec::new();
+    for (line, cond) in wanted {
+      let entry: Option<&LineEntry> = if ours {
+        self.lines.iter().find(|e| e.line as i64 >= line)
+      } else {
+        None
+      };
+      let cond: Option<Result<Expr, FormatError>> = cond.filter(|c| !c.trim().is_empty()).map(|c| Expr::parse(&c, m.symbols()));
+      result.push(match (entry, cond) {
+        (Some(e), Some(Err(err))) => Json::object(vec![
+          ("verified", Json::from(false)),
+          ("line", Json::from(e.line as i64)),
+          ("message", Json::from(format!("invalid condition: {}", err.0))),
+        ]),
+        (Some(e), cond) => {
+          match cond {
+            Some(Ok(cond)) => m.add_conditional_breakpoint(e.addr, cond),
+            _ => m.add_breakpoint(e.addr),
+          }
+          Json::object(vec![("verified", Json::from(true)), ("line", Json::from(e.line as i64))])
+        },
+        (None, _) => Json::object(vec![
+          ("verified", Json::from(false)),
+          ("line", Json::from(line)),
+          ("message", Json::from("no code at this line")),
+        ]),
+      });
+    }
+    self.respond(req, Json::object(vec![("breakpoints", Json::from(result))]));
+  }
+
+  fn line_of(&self, addr: u16) -> Option<usize> {
+    self.lines.iter()
+      .find(|e| addr.wrapping_sub(e.addr) < e.len)
+      .map(|e| e.line)
+  }
+
+  fn stack_trace(&mut self, req: &Json) {
+    let m: &Machine = match self.m.as_ref() {
+      Some(m) => m,
+      None => return self.fail(req, "no program launched"),
+    };
+
+    let mut frames: Vec<Json> = Vec::new();
+    for (i, f) in m.backtrace().into_iter().enumerate() {
+      let pc: u16 = f.pc;
+      let name: String = f.name.unwrap_or_else(|| format!("x{:04X}", pc));
+      let mut frame: Vec<(&str, Json)> = vec![
+        ("id", Json::from(i as i64 + 1)),
+        ("name", Json::from(name)),
+        ("column", Json::from(1i64)),
+        ("instructionPointerReference", Json::from(format!("0x{:04X}", pc))),
+      ];
+      match (self.line_of(pc), &self.source) {
+        (Some(line), Some(path)) => {
+          frame.push(("line", Json::from(line as i64)));
+          frame.push(("source", Json::object(vec![("path", Json::from(path.as_str()))])));
+        },
+        _ => frame.push(("line", Json::from(0i64))),
+      }
+      frames.push(Json::object(frame));
+    }
+
+    let total: i64 = frames.len() as i64;
+    let body: Json = Json::object(vec![
+      ("stackFrames", Json::from(frames)),
+      ("totalFrames", Json::from(total)),
+    ]);
+    self.respond(req, body);
+  }
+
+  fn variables(&mut self, req: &Json) {
+    let m: &Machine = match self.m.as_ref() {
+      Some(m) => m,
+      None => return self.fail(req, "no program launched"),
+    };
+    let var = |name: String, val: u16| Json::object(vec![
+      ("name", Json::from(name)),
+      ("value", Json::from(format!("x{:04X} ({})", val, val as i16))),
+      ("variablesReference", Json::from(0i64)),
+    ]);
+
+    let mut vars: Vec<Json> = (0..8).map(|i| var(format!("R{}", i), m.read_reg(GPRS[i]))).collect();
+    vars.push(var("PC".to_string(), m.read_reg(Reg::PC)));
+    vars.push(var("PSR".to_string(), m.psr()));
+    let cc: &str = match m.read_reg(Reg::COND) {
+      NEG => "N",
+      ZRO => "Z",
+      POS => "P",
+      _ => "-",
+    };
+    vars.push(Json::object(vec![
+      ("name", Json::from("CC")),
+      ("value", Json::from(cc)),
+      ("variablesReference", Json::from(0i64)),
+    ]));
+    self.respond(req, Json::object(vec![("variables", Json::from(vars))]));
+  }
+
+  fn step(&mut self, req: &Json, how: Step) {
+    let m: &mut Machine = match self.m.as_mut() {
+      Some(m) => m,
+      None => return self.fail(req, "no program launched"),
+    };
+    if m.halt {
+      return self.fail(req, "machine halted");
+    }
+    if Adapter::waiting(m, &self.io) {
+      return self.fail(req, "waiting for input, type it in the debug console");
+    }
+
+    let io: Rc<RefCell<Io>> = self.io.clone();
+    let waiting = |m: &Machine| Adapter::waiting(m, &io);
+    let run: Run = match how {
+      Step::In => m.run_for(1),
+      Step::Over => m.run_to_goal(Goal::Over, waiting),
+      Step::Out => m.run_to_goal(Goal::Out, waiting),
+    };
+    self.respond(req, Json::Null);
+    self.finish(run.reason);
+  }
+
+  fn run_slice(&mut self) {
+    let m: &mut Machine = match self.m.as_mut() {
+      Some(m) => m,
+      None => return,
+    };
+    if Adapter::waiting(m, &self.io) {
+      return;
+    }
+
+    let io: Rc<RefCell<Io>> = self.io.clone();
+    let mut steps: u64 = 0;
+    let run = m.run_until(|m| {
+      steps += 1;
+      steps >= SLICE || Adapter::waiting(m, &io)
+    });
+    if run.reason == StopReason::Condition {
+      self.flush_output();
+    } else {
+      self.finish(run.reason);
+    }
+  }
+
+  // handles one request, false once the client disconnects
+  fn handle(&mut self, req: &Json) -> bool {
+    let command: &str = req.get("command").and_then(Json::as_str).unwrap_or("");
+    match command {
+      "initialize" => {
+        self.respond(req, Json::object(vec![
+          ("supportsConfigurationDoneRequest", Json::from(true)),
+          ("supportsConditionalBreakpoints", Json::from(true)),
+          ("supportsTerminateRequest", Json::from(true)),
+        ]));
+        self.event("initialized", Json::Null);
+      },
+      "launch" => self.launch(req),
+      "setBreakpoints" => self.set_breakpoints(req),
+      "setExceptionBreakpoints" => self.respond(req, Json::object(vec![("breakpoints", Json::from(vec![]))])),
+      "configurationDone" => {
+        self.respond(req, Json::Null);
+        if self.stop_on_entry {
+          self.stopped("entry", None);
+        } else {
+          self.running = self.m.is_some();
+        }
+      },
+      "threads" => {
+        let thread: Json = Json::object(vec![("id", Json::from(THREAD)), ("name", Json::from("LC-3"))]);
+        self.respond(req, Json::object(vec![("threads", Json::from(vec![thread]))]));
+      },
+      "stackTrace" => self.stack_trace(req),
+      "scopes" => {
+        let scope: Json = Json::object(vec![
+          ("name", Json::from("Registers")),
+          ("variablesReference", Json::from(REGISTERS)),
+          ("expensive", Json::from(false)),
+        ]);
+        self.respond(req, Json::object(vec![("scopes", Json::from(vec![scope]))]));
+      },
+      "variables" => self.variables(req),
+      "continue" => {
+        self.running = self.m.as_ref().is_some_and(|m| !m.halt);
+        self.respond(req, Json::object(vec![("allThreadsContinued", Json::from(true))]));
+      },
+      "next" => self.step(req, Step::Over),
+      "stepIn" => self.step(req, Step::In),
+      "stepOut" => self.step(req, Step::Out),
+      "pause" => {
+        self.respond(req, Json::Null);
+        self.stopped("pause", None);
+      },
+      "evaluate" => {
+        let text: &str = req.get("arguments").and_then(|a| a.get("expression")).and_then(Json::as_str).unwrap_or("");
+        let mut io = self.io.borrow_mut();
+        io.keys.extend(text.bytes());
+        io.keys.push_back(b'\n');
+        drop(io);
+        self.respond(req, Json::object(vec![
+          ("result", Json::from("")),
+          ("variablesReference", Json::from(0i64)),
+        ]));
+      },
+      "terminate" => {
+        self.running = false;
+        self.respond(req, Json::Null);
+        self.event("terminated", Json::object(vec![]));
+      },
+      "disconnect" => {
+        self.respond(req, Json::Null);
+        return false;
+      },
+      _ => self.fail(req, &format!("unsupported request `{}`", command)),
+    }
+    true
+  }
+}
+
+fn request_header(req: &Json) -> (i64, String) {
+  let seq: i64 = req.get("seq").and_then(Json::as_i64).unwrap_or(0);
+  let command: &str = req.get("command").and_then(Json::as_str).unwrap_or("");
+  (seq, command.to_string())
+}
+
+fn main() {
+  let requests: Receiver<Json> = messages();
+  let mut dap = Adapter {
+    seq: 0,
+    m: None,
+    io: Rc::new(RefCell::new(Io::default())),
+    source: None,
+    lines: Vec::new(),
+    stop_on_entry: false,
+    running: false,
+  };
+
+  loop {
+    let idle: bool = dap.m.as_ref().is_some_and(|m| Adapter::waiting(m, &dap.io));
+    let req: Json = if dap.running {
+      // nothing to run until a key is typed, so wait for the request that
+      // brings it
+      let wait: Duration = Duration::from_millis(if idle { 50 } else { 0 });
+      match requests.recv_timeout(wait) {
+        Ok(req) => req,
+        Err(RecvTimeoutError::Timeout) => {
+          dap.run_slice();
+          continue;
+        },
+        Err(RecvTimeoutError::Disconnected) => break,
+      }
+    } else {
+      match requests.recv() {
+        Ok(req) => req,
+        Err(_) => break,
+      }
+    };
+    if !dap.handle(&req) {
+      break;
+    }
+  }
+}
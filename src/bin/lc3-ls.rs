@@ -0,0 +1,353 @@
+extern crate lc3;
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use lc3::assembler::{self, AsmError, Diagnostic, Line, Operand, Program, Span};
+use lc3::json::Json;
+
+// Language Server Protocol server for LC-3 assembly on stdin/stdout.
+// Documents are assembled on every change, and give:
+//
+//   diagnostics       the assembler's errors
+//   definition        where a label under the cursor is defined
+//   hover             the address and words a line assembles to, or the
+//                     address of a label
+//   document symbols  the labels of the document
+//
+// Includes of file: documents are read from disk. Columns are counted in
+// characters, which is what clients mean for ASCII sources.
+
+// SymbolKind values
+const FUNCTION: i64 = 12;
+const VARIABLE: i64 = 13;
+
+struct Document {
+  text: String,
+  // None if the text doesn't assemble
+  program: Option<Program>,
+}
+
+struct Server {
+  docs: HashMap<String, Document>,
+  shutdown: bool,
+}
+
+// one base-protocol message, None once stdin closes
+fn read_message(input: &mut impl BufRead) -> Option<Json> {
+  loop {
+    let mut len: Option<usize> = None;
+    loop {
+      let mut line: String = String::new();
+      match input.read_line(&mut line) {
+        Ok(0) | Err(_) => return None,
+        Ok(_) => {},
+      }
+      let line: &str = line.trim();
+      if line.is_empty() {
+        break;
+      }
+      if let Some(n) = line.strip_prefix("Content-Length:") {
+        len = n.trim().parse().ok();
+      }
+    }
+
+    let mut body: Vec<u8> = vec![0; len.unwrap_or(0)];
+    if input.read_exact(&mut body).is_err() {
+      return None;
+    }
+    match Json::parse(&String::from_utf8_lossy(&body)) {
+      Ok(msg) => return Some(msg),
+      Err(e) => eprintln!("lc3-ls: {}", e),
+    }
+  }
+}
+
+fn send(msg: Json) {
+  let body: String = msg.to_string();
+  let stdout = io::stdout();
+  let mut out = stdout.lock();
+  let _ = write!(out, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+  let _ = out.flush();
+}
+
+fn notify(method: &str, params: Json) {
+  send(Json::object(vec![
+    ("jsonrpc", Json::from("2.0")),
+    ("method", Json::from(method)),
+    ("params", params),
+  ]));
+}
+
+// the path of a file: URI, percent escapes decoded
+fn uri_path(uri: &str) -> Option<PathBuf> {
+  let rest: &str = uri.strip_prefix("file://")?;
+  let bytes: &[u8] = rest.as_bytes();
+  let mut path: Vec<u8> = Vec::new();
+  let mut i: usize = 0;
+  while i < bytes.len() {
+    let hex = || std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok().and_then(|h| u8::from_str_radix(h, 16).ok());
+    match (bytes[i], hex()) {
+      (b'%', Some(b)) => {
+        path.push(b);
+        i += 3;
+      },
+      (b, _) => {
+        path.push(b);
+        i += 1;
+      },
+    }
+  }
+  String::from_utf8(path).ok().map(PathBuf::from)
+}
+
+fn assemble(uri: &str, text: &str) -> Result<Program, AsmError> {
+  match uri_path(uri) {
+    Some(path) => assembler::assemble_with(text, Some(&assembler::file_key(&path)), &mut assembler::include_file),
+    None => assembler::assemble(text),
+  }
+}
+
+fn position(line: usize, character: u32) -> Json {
+  Json::object(vec![("line", Json::from(line as u64)), ("character", Json::from(character as u64))])
+}
+
+// cols of 1-based line num
+fn range(num: usize, cols: Span) -> Json {
+  Json::object(vec![("start", position(num - 1, cols.start)), ("end", position(num - 1, cols.end))])
+}
+
+fn line_range(text: &str, num: usize) -> Json {
+  let len: usize = text.lines().nth(num - 1).map_or(0, |l| l.chars().count());
+  range(num, Span { start: 0, end: len as u32 })
+}
+
+fn diagnostic(text: &str, d: &Diagnostic) -> Json {
+  // columns are of the line as assembled, which is only the document's
+  // own line outside of macros and included files
+  let own: bool = d.at.is_none() && text.lines().nth(d.line - 1) == Some(d.text.as_str());
+  let range: Json = match d.cols {
+    Some(cols) if own => range(d.line, cols),
+    _ => line_range(text, d.line),
+  };
+  let message: String = match &d.help {
+    Some(help) => format!("{}\n{}", d.located(), help),
+    None => d.located(),
+  };
+  Json::object(vec![
+    ("range", range),
+    ("severity", Json::from(1i64)),
+    ("source", Json::from("lc3")),
+    ("message", Json::from(message)),
+  ])
+}
+
+// the labels text defines where it says so itself, with their lines and
+// columns
+fn labels(text: &str) -> Vec<(Line, Span)> {
+  let lines: Vec<&str> = text.lines().collect();
+  assembler::parse_lines(text).into_iter()
+    .filter_map(|l| l.ok())
+    .filter_map(|l| {
+      let cols: Span = l.spans.label?;
+      let written: String = lines[l.num - 1].chars().skip(cols.start as usize).take((cols.end - cols.start) as usize).collect();
+      if Some(written.as_str()) == l.label.as_deref() { Some((l, cols)) } else { None }
+    })
+    .collect()
+}
+
+// the name under the cursor, for labels
+fn word_at(text: &str, line: usize, character: usize) -> Option<String> {
+  let chars: Vec<char> = text.lines().nth(line)?.chars().collect();
+  let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+  let at: usize = character.min(chars.len());
+  let start: usize = at - chars[..at].iter().rev().take_while(|c| is_word(c)).count();
+  let end: usize = at + chars[at..].iter().take_while(|c| is_word(c)).count();
+  if start == end {
+    return None;
+  }
+  Some(chars[start..end].iter().collect())
+}
+
+fn is_data(line: &Line) -> bool {
+  matches!(line.op.as_deref(), Some(".FILL") | Some(".BLKW") | Some(".STRINGZ"))
+}
+
+impl Server {
+  // the document and cursor of a textDocument/ request
+  fn cursor<'a>(&'a self, params: &'a Json) -> Option<(&'a str, &'a Document, usize, usize)> {
+    let uri: &str = params.get("textDocument")?.get("uri")?.as_str()?;
+    let pos: &Json = params.get("position")?;
+    let line: i64 = pos.get("line")?.as_i64()?;
+    let character: i64 = pos.get("character")?.as_i64()?;
+    Some((uri, self.docs.get(uri)?, line as usize, character as usize))
+  }
+
+  fn update(&mut self, uri: &str, text: String) {
+    let program: Result<Program, AsmError> = assemble(uri, &text);
+    let diagnostics: Vec<Json> = match &program {
+      Ok(_) => Vec::new(),
+      Err(e) => e.diagnostics.iter().map(|d| diagnostic(&text, d)).collect(),
+    };
+    notify("textDocument/publishDiagnostics", Json::object(vec![
+      ("uri", Json::from(uri)),
+      ("diagnostics", Json::from(diagnostics)),
+    ]));
+    self.docs.insert(uri.to_string(), Document { text, program: program.ok() });
+  }
+
+  fn definition(&self, params: &Json) -> Json {
+    let (uri, doc, line, character) = match self.cursor(params) {
+      Some(c) => c,
+      None => return Json::Null,
+    };
+    let word: String = match word_at(&doc.text, line, character) {
+      Some(w) => w,
+      None => return Json::Null,
+    };
+    match labels(&doc.text).into_iter().find(|(l, _)| l.label.as_deref() == Some(word.as_str())) {
+      Some((l, cols)) => Json::object(vec![("uri", Json::from(uri)), ("range", range(l.num, cols))]),
+      None => Json::Null,
+    }
+  }
+
+  fn hover(&self, params: &Json) -> Json {
+    let (_, doc, line, character) = match self.cursor(params) {
+      Some(c) => c,
+      None => return Json::Null,
+    };
+    let prog: &Program = match &doc.program {
+      Some(p) => p,
+      None => return Json::Null,
+    };
+
+    let mut rows: Vec<String> = Vec::new();
+    if let Some(word) = word_at(&doc.text, line, character) {
+      if let Some(addr) = prog.symbols.lookup(&word) {
+        rows.push(format!("{} = x{:04X}", word, addr));
+      }
+    }
+    if let Some(e) = prog.lines.iter().find(|e| e.line == line + 1) {
+      let start: usize = e.addr.wrapping_sub(prog.origin) as usize;
+      for (i, w) in prog.words[start..start + e.len as usize].iter().enumerate() {
+        if i == 8 {
+          rows.push(format!("... {} more", e.len as usize - i));
+          break;
+        }
+        rows.push(format!("x{:04X}: x{:04X}  {:016b}", e.addr.wrapping_add(i as u16), w, w));
+      }
+    }
+    if rows.is_empty() {
+      return Json::Null;
+    }
+    Json::object(vec![("contents", Json::object(vec![
+      ("kind", Json::from("markdown")),
+      ("value", Json::from(format!("```\n{}\n```", rows.join("\n")))),
+    ]))])
+  }
+
+  fn symbols(&self, params: &Json) -> Json {
+    let doc: Option<&Document> = params.get("textDocument")
+      .and_then(|d| d.get("uri"))
+      .and_then(Json::as_str)
+      .and_then(|uri| self.docs.get(uri));
+    let doc: &Document = match doc {
+      Some(d) => d,
+      None => return Json::Null,
+    };
+    let symbols: Vec<Json> = labels(&doc.text).into_iter().map(|(l, cols)| {
+      let mut fields: Vec<(&str, Json)> = vec![
+        ("name", Json::from(l.label.clone().unwrap())),
+        ("kind", Json::from(if is_data(&l) { VARIABLE } else { FUNCTION })),
+        ("range", line_range(&doc.text, l.num)),
+        ("selectionRange", range(l.num, cols)),
+      ];
+      if let Some(addr) = doc.program.as_ref().and_then(|p| p.symbols.lookup(l.label.as_deref().unwrap())) {
+        fields.push(("detail", Json::from(format!("x{:04X}", addr))));
+      } else if let (Some(op), [Operand::Imm(n)]) = (l.op.as_deref(), &l.operands[..]) {
+        fields.push(("detail", Json::from(format!("{} #{}", op, n))));
+      }
+      Json::object(fields)
+    }).collect();
+    Json::from(symbols)
+  }
+
+  // the result of a request, Err with a message for one it doesn't know
+  fn request(&mut self, method: &str, params: &Json) -> Result<Json, String> {
+    Ok(match method {
+      "initialize" => Json::object(vec![
+        ("capabilities", Json::object(vec![
+          // whole documents on every change
+          ("textDocumentSync", Json::from(1i64)),
+          ("definitionProvider", Json::from(true)),
+          ("hoverProvider", Json::from(true)),
+          ("documentSymbolProvider", Json::from(true)),
+        ])),
+        ("serverInfo", Json::object(vec![("name", Json::from("lc3-ls"))])),
+      ]),
+      "shutdown" => {
+        self.shutdown = true;
+        Json::Null
+      },
+      "textDocument/definition" => self.definition(params),
+      "textDocument/hover" => self.hover(params),
+      "textDocument/documentSymbol" => self.symbols(params),
+      _ => return Err(format!("unsupported request `{}`", method)),
+    })
+  }
+
+  fn notification(&mut self, method: &str, params: &Json) {
+    let doc: Option<&Json> = params.get("textDocument");
+    let uri: Option<&str> = doc.and_then(|d| d.get("uri")).and_then(Json::as_str);
+    match (method, uri) {
+      ("textDocument/didOpen", Some(uri)) => {
+        if let Some(text) = doc.and_then(|d| d.get("text")).and_then(Json::as_str) {
+          self.update(uri, text.to_string());
+        }
+      },
+      ("textDocument/didChange", Some(uri)) => {
+        let changes: &[Json] = params.get("contentChanges").and_then(Json::as_array).unwrap_or(&[]);
+        if let Some(text) = changes.last().and_then(|c| c.get("text")).and_then(Json::as_str) {
+          self.update(uri, text.to_string());
+        }
+      },
+      ("textDocument/didClose", Some(uri)) => {
+        self.docs.remove(uri);
+        notify("textDocument/publishDiagnostics", Json::object(vec![
+          ("uri", Json::from(uri)),
+          ("diagnostics", Json::from(vec![])),
+        ]));
+      },
+      _ => {},
+    }
+  }
+}
+
+fn main() {
+  let mut input = BufReader::new(io::stdin());
+  let mut server = Server { docs: HashMap::new(), shutdown: false };
+  while let Some(msg) = read_message(&mut input) {
+    let method: &str = msg.get("method").and_then(Json::as_str).unwrap_or("");
+    let params: &Json = msg.get("params").unwrap_or(&Json::Null);
+    if method == "exit" {
+      std::process::exit(if server.shutdown { 0 } else { 1 });
+    }
+    let id: Json = match msg.get("id") {
+      Some(id) => id.clone(),
+      None => {
+        server.notification(method, params);
+        continue;
+      },
+    };
+    let reply: (&str, Json) = match server.request(method, params) {
+      Ok(result) => ("result", result),
+      // MethodNotFound
+      Err(message) => ("error", Json::object(vec![
+        ("code", Json::from(-32601i64)),
+        ("message", Json::from(message)),
+      ])),
+    };
+    send(Json::object(vec![("jsonrpc", Json::from("2.0")), ("id", id), reply]));
+  }
+}
@@ -0,0 +1,648 @@
+extern crate lc3;
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::future::Future;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::pin::Pin;
+use std::process;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread;
+use std::time::Duration;
+
+use lc3::assembler::{self, Diagnostic};
+use lc3::json::Json;
+use lc3::{Console, KeySource, Machine, Reg, StopReason, NEG, POS, ZRO};
+
+// HTTP and WebSocket server for running programs from a web page. Each
+// session is one machine on its own thread:
+//
+//   POST   /sessions             assembly source, or an object file sent as
+//                                application/octet-stream. ?os loads the OS.
+//                                Answers {"id": n}.
+//   GET    /sessions/n           registers and run state
+//   GET    /sessions/n/output    everything the program has printed
+//   POST   /sessions/n/keys      queues the body as keyboard input
+//   GET    /sessions/n/events    WebSocket streaming {"output": text} and
+//                                {"state": ...} messages; text or binary
+//                                messages sent to it are queued as keys
+//   DELETE /sessions/n           ends the session
+//
+// Programs waiting for a key are parked rather than spinning, and any
+// program is stopped after --max-steps instructions. A session not running
+// is dropped after --idle-timeout seconds without a request, and new ones
+// get 503 while --max-sessions are live.
+
+const USAGE: &str = "usage: lc3-serve [--addr <host:port>] [--max-steps <n>] [--max-sessions <n>] [--idle-timeout <secs>]";
+
+const MAX_BODY: usize = 1 << 20;
+const MAX_FRAME: u64 = 1 << 16;
+const WS_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC11B85";
+
+const GPRS: [Reg; 8] = [Reg::R0, Reg::R1, Reg::R2, Reg::R3, Reg::R4, Reg::R5, Reg::R6, Reg::R7];
+
+enum Command {
+  Keys(Vec<u8>),
+  Registers(Sender<Json>),
+  Output(Sender<Vec<u8>>),
+  Subscribe(TcpStream),
+  Stop,
+}
+
+type Sessions = Arc<Mutex<HashMap<u64, Sender<Command>>>>;
+
+struct Server {
+  sessions: Sessions,
+  next_id: Mutex<u64>,
+  max_steps: u64,
+  max_sessions: usize,
+  idle_timeout: Duration,
+}
+
+// a session's entry in Server::sessions, removed once its thread ends
+struct Registration {
+  sessions: Sessions,
+  id: u64,
+}
+
+impl Drop for Registration {
+  fn drop(&mut self) {
+    self.sessions.lock().unwrap().remove(&self.id);
+  }
+}
+
+// console output collected for the session thread to hand out
+struct SessionConsole(Rc<RefCell<Vec<u8>>>);
+
+impl Console for SessionConsole {
+  // keys come in through run_async(), see Keys
+  fn read_char(&mut self) -> Option<u8> {
+    None
+  }
+
+  fn write_char(&mut self, c: u8) {
+    self.0.borrow_mut().push(c);
+  }
+
+  fn poll_key(&mut self) -> Option<u8> {
+    None
+  }
+}
+
+// the session's queued keys, pending while it is empty
+struct Keys<'a>(&'a mut VecDeque<u8>);
+
+impl<'a> KeySource for Keys<'a> {
+  fn poll_key(&mut self, _cx: &mut Context<'_>) -> Poll<Option<u8>> {
+    match self.0.pop_front() {
+      Some(c) => Poll::Ready(Some(c)),
+      None => Poll::Pending,
+    }
+  }
+}
+
+// set when run_async() yields to be polled again rather than for a key
+struct Yielded(AtomicBool);
+
+impl Wake for Yielded {
+  fn wake(self: Arc<Self>) {
+    self.0.store(true, Ordering::SeqCst);
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+  Running,
+  Waiting,
+  Done(StopReason),
+}
+
+fn state_json(state: State) -> Vec<(&'static str, Json)> {
+  match state {
+    State::Running => vec![("state", Json::from("running"))],
+    State::Waiting => vec![("state", Json::from("waiting"))],
+    State::Done(StopReason::Halt) => vec![("state", Json::from("halted"))],
+    State::Done(StopReason::StepLimit) => vec![("state", Json::from("step limit"))],
+    State::Done(StopReason::Fault(e)) => vec![("state", Json::from("fault")), ("error", Json::from(e.to_string()))],
+    State::Done(_) => vec![("state", Json::from("stopped"))],
+  }
+}
+
+fn registers(m: &Machine, state: State) -> Json {
+  let mut fields: Vec<(&str, Json)> = state_json(state);
+  let names: [&str; 8] = ["r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7"];
+  for (name, &r) in names.iter().zip(GPRS.iter()) {
+    fields.push((name, Json::from(m.read_reg(r))));
+  }
+  let cc: &str = match m.read_reg(Reg::COND) {
+    NEG => "n",
+    ZRO => "z",
+    POS => "p",
+    _ => "",
+  };
+  fields.push(("pc", Json::from(m.read_reg(Reg::PC))));
+  fields.push(("psr", Json::from(m.psr())));
+  fields.push(("cc", Json::from(cc)));
+  fields.push(("steps", Json::from(m.instructions())));
+  Json::object(fields)
+}
+
+// runs one session until it is stopped, every handle to it is gone or,
+// while not running, no command has come for idle_timeout
+fn session(mut m: Machine, output: Rc<RefCell<Vec<u8>>>, commands: Receiver<Command>, max_steps: u64, idle_timeout: Duration) {
+  let yielded: Arc<Yielded> = Arc::new(Yielded(AtomicBool::new(false)));
+  let waker: Waker = Waker::from(yielded.clone());
+  let mut cx = Context::from_waker(&waker);
+
+  let mut keys: VecDeque<u8> = VecDeque::new();
+  let mut subscribers: Vec<TcpStream> = Vec::new();
+  let mut sent: usize = 0;
+  let mut state: State = State::Running;
+  let mut reported: Option<State> = None;
+
+  loop {
+    let cmd: Option<Command> = if state == State::Running {
+      match commands.try_recv() {
+        Ok(cmd) => Some(cmd),
+        Err(TryRecvError::Empty) => None,
+        Err(TryRecvError::Disconnected) => return,
+      }
+    } else {
+      match commands.recv_timeout(idle_timeout) {
+        Ok(cmd) => Some(cmd),
+        Err(_) => return,
+      }
+    };
+
+    match cmd {
+      Some(Command::Keys(bytes)) => {
+        keys.extend(bytes);
+        if state == State::Waiting {
+          state = State::Running;
+        }
+      },
+      Some(Command::Registers(reply)) => {
+        let _ = reply.send(registers(&m, state));
+      },
+      Some(Command::Output(reply)) => {
+        let _ = reply.send(output.borrow().clone());
+      },
+      Some(Command::Subscribe(mut ws)) => {
+        let text: String = String::from_utf8_lossy(&output.borrow()[..sent]).into_owned();
+        let ok: bool = (text.is_empty() || send_frame(&mut ws, &Json::object(vec![("output", Json::from(text))])).is_ok())
+          && send_frame(&mut ws, &Json::object(state_json(state))).is_ok();
+        if ok {
+          subscribers.push(ws);
+        }
+      },
+      Some(Command::Stop) => return,
+      None => {
+        yielded.0.store(false, Ordering::SeqCst);
+        let poll: Poll<StopReason> = {
+          let mut run = m.run_async(Keys(&mut keys));
+          Pin::new(&mut run).poll(&mut cx).map(|run| run.reason)
+        };
+        state = match poll {
+          Poll::Ready(reason) => State::Done(reason),
+          Poll::Pending if m.instructions() >= max_steps => State::Done(StopReason::StepLimit),
+          Poll::Pending if yielded.0.load(Ordering::SeqCst) => State::Running,
+          Poll::Pending => State::Waiting,
+        };
+      },
+    }
+
+    // pass new output and state changes on to the WebSockets
+    let len: usize = output.borrow().len();
+    let mut messages: Vec<Json> = Vec::new();
+    if len > sent {
+      let text: String = String::from_utf8_lossy(&output.borrow()[sent..]).into_owned();
+      messages.push(Json::object(vec![("output", Json::from(text))]));
+      sent = len;
+    }
+    if reported != Some(state) {
+      messages.push(Json::object(state_json(state)));
+      reported = Some(state);
+    }
+    if !messages.is_empty() {
+      subscribers.retain_mut(|ws| messages.iter().all(|msg| send_frame(ws, msg).is_ok()));
+    }
+  }
+}
+
+struct Request {
+  method: String,
+  path: String,
+  query: String,
+  headers: Vec<(String, String)>,
+  body: Vec<u8>,
+}
+
+impl Request {
+  fn header(&self, name: &str) -> Option<&str> {
+    self.headers.iter().find(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+  }
+
+  fn flag(&self, name: &str) -> bool {
+    self.query.split('&').any(|p| {
+      let mut kv = p.splitn(2, '=');
+      kv.next() == Some(name) && !matches!(kv.next(), Some("0") | Some("false"))
+    })
+  }
+}
+
+fn read_request(input: &mut BufReader<TcpStream>) -> io::Result<Request> {
+  let bad = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+  let mut line: String = String::new();
+  input.read_line(&mut line)?;
+  let mut parts = line.split_whitespace();
+  let method: String = parts.next().ok_or_else(|| bad("empty request"))?.to_string();
+  let target: &str = parts.next().ok_or_else(|| bad("no request target"))?;
+  let (path, query): (&str, &str) = match target.find('?') {
+    Some(i) => (&target[..i], &target[i + 1..]),
+    None => (target, ""),
+  };
+  let (path, query): (String, String) = (path.to_string(), query.to_string());
+
+  let mut headers: Vec<(String, String)> = Vec::new();
+  loop {
+    let mut line: String = String::new();
+    if input.read_line(&mut line)? == 0 {
+      return Err(bad("unexpected end of headers"));
+    }
+    let line: &str = line.trim_end();
+    if line.is_empty() {
+      break;
+    }
+    if let Some(i) = line.find(':') {
+      headers.push((line[..i].trim().to_string(), line[i + 1..].trim().to_string()));
+    }
+  }
+
+  let mut req = Request { method, path, query, headers, body: Vec::new() };
+  let len: usize = req.header("Content-Length").and_then(|n| n.parse().ok()).unwrap_or(0);
+  if len > MAX_BODY {
+    return Err(bad("request body too large"));
+  }
+  req.body = vec![0; len];
+  input.read_exact(&mut req.body)?;
+  Ok(req)
+}
+
+fn respond(out: &mut TcpStream, status: &str, kind: &str, body: &[u8]) -> io::Result<()> {
+  write!(out, "HTTP/1.1 {}\r\n", status)?;
+  write!(out, "Content-Type: {}\r\nContent-Length: {}\r\n", kind, body.len())?;
+  write!(out, "Access-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n")?;
+  out.write_all(body)?;
+  out.flush()
+}
+
+fn respond_json(out: &mut TcpStream, status: &str, body: &Json) -> io::Result<()> {
+  respond(out, status, "application/json", body.to_string().as_bytes())
+}
+
+fn failure(msg: &str) -> Json {
+  Json::object(vec![("error", Json::from(msg))])
+}
+
+fn error(out: &mut TcpStream, status: &str, msg: &str) -> io::Result<()> {
+  respond_json(out, status, &failure(msg))
+}
+
+impl Server {
+  fn session(&self, id: &str) -> Option<Sender<Command>> {
+    let id: u64 = id.parse().ok()?;
+    self.sessions.lock().unwrap().get(&id).cloned()
+  }
+
+  // an error is the status and body to respond with, for a source that
+  // doesn't assemble with its diagnostics
+  fn create(&self, req: &Request) -> Result<u64, (&'static str, Json)> {
+    let binary: bool = req.header("Content-Type").is_some_and(|t| t.contains("octet-stream"));
+    let obj: Vec<u8> = if binary {
+      req.body.clone()
+    } else {
+      let src: String = String::from_utf8_lossy(&req.body).into_owned();
+      assembler::assemble(&src).map_err(|e| ("400 Bad Request", Json::object(vec![
+        ("error", Json::from(e.to_string())),
+        ("diagnostics", Json::from(e.diagnostics.iter().map(Diagnostic::to_json).collect::<Vec<Json>>())),
+      ])))?.to_obj()
+    };
+    let os: bool = req.flag("os");
+    let (max_steps, idle_timeout): (u64, Duration) = (self.max_steps, self.idle_timeout);
+
+    // the session's place is taken before its thread starts, so the limit
+    // holds however many requests come at once
+    let (tx, commands) = mpsc::channel();
+    let id: u64 = {
+      let mut sessions = self.sessions.lock().unwrap();
+      if sessions.len() >= self.max_sessions {
+        return Err(("503 Service Unavailable", failure("too many sessions")));
+      }
+      let mut next_id = self.next_id.lock().unwrap();
+      *next_id += 1;
+      sessions.insert(*next_id, tx);
+      *next_id
+    };
+    let registration: Registration = Registration { sessions: self.sessions.clone(), id };
+    let (loaded_tx, loaded) = mpsc::channel();
+    thread::spawn(move || {
+      let _registration: Registration = registration;
+      let output: Rc<RefCell<Vec<u8>>> = Rc::new(RefCell::new(Vec::new()));
+      let mut m = Machine::builder().io(Box::new(SessionConsole(output.clone()))).os(os).build();
+      match m.load_image_bytes(&obj) {
+        Ok(()) => {
+          // the program starts at its origin, the object file's first word
+          if obj.len() >= 2 {
+            m.write_reg(Reg::PC, u16::from_be_bytes([obj[0], obj[1]]));
+          }
+          let _ = loaded_tx.send(Ok(()));
+          session(m, output, commands, max_steps, idle_timeout);
+        },
+        Err(e) => {
+          let _ = loaded_tx.send(Err(e.to_string()));
+        },
+      }
+    });
+    let bad = |e: String| ("400 Bad Request", failure(&e));
+    loaded.recv().map_err(|e| bad(e.to_string()))?.map_err(bad)?;
+    Ok(id)
+  }
+
+  fn handle(&self, mut out: TcpStream) -> io::Result<()> {
+    let mut input = BufReader::new(out.try_clone()?);
+    let req: Request = match read_request(&mut input) {
+      Ok(req) => req,
+      Err(e) => return error(&mut out, "400 Bad Request", &e.to_string()),
+    };
+    let path: Vec<&str> = req.path.split('/').filter(|p| !p.is_empty()).collect();
+
+    match (req.method.as_str(), path.as_slice()) {
+      ("OPTIONS", _) => {
+        write!(out, "HTTP/1.1 204 No Content\r\nAccess-Control-Allow-Origin: *\r\n")?;
+        write!(out, "Access-Control-Allow-Methods: GET, POST, DELETE\r\n")?;
+        write!(out, "Access-Control-Allow-Headers: Content-Type\r\nConnection: close\r\n\r\n")
+      },
+      ("POST", ["sessions"]) => match self.create(&req) {
+        Ok(id) => respond_json(&mut out, "201 Created", &Json::object(vec![("id", Json::from(id))])),
+        Err((status, e)) => respond_json(&mut out, status, &e),
+      },
+      (method, ["sessions", id, rest @ ..]) => {
+        let session: Sender<Command> = match self.session(id) {
+          Some(s) => s,
+          None => return error(&mut out, "404 Not Found", "no such session"),
+        };
+        match (method, rest) {
+          ("GET", []) => {
+            let (tx, rx) = mpsc::channel();
+            let _ = session.send(Command::Registers(tx));
+            match rx.recv_timeout(Duration::from_secs(5)) {
+              Ok(regs) => respond_json(&mut out, "200 OK", &regs),
+              Err(_) => error(&mut out, "500 Internal Server Error", "session not responding"),
+            }
+          },
+          ("GET", ["output"]) => {
+            let (tx, rx) = mpsc::channel();
+            let _ = session.send(Command::Output(tx));
+            match rx.recv_timeout(Duration::from_secs(5)) {
+              Ok(text) => respond(&mut out, "200 OK", "text/plain; charset=utf-8", &text),
+              Err(_) => error(&mut out, "500 Internal Server Error", "session not responding"),
+            }
+          },
+          ("POST", ["keys"]) => {
+            let _ = session.send(Command::Keys(req.body.clone()));
+            respond(&mut out, "204 No Content", "text/plain", b"")
+          },
+          ("GET", ["events"]) => self.upgrade(&req, out, input, session),
+          ("DELETE", []) => {
+            if let Ok(id) = id.parse() {
+              self.sessions.lock().unwrap().remove(&id);
+            }
+            let _ = session.send(Command::Stop);
+            respond(&mut out, "204 No Content", "text/plain", b"")
+          },
+          _ => error(&mut out, "404 Not Found", "no such endpoint"),
+        }
+      },
+      _ => error(&mut out, "404 Not Found", "no such endpoint"),
+    }
+  }
+
+  // completes the WebSocket handshake, gives the session the stream to send
+  // on and reads keys from it here
+  fn upgrade(&self, req: &Request, mut out: TcpStream, mut input: BufReader<TcpStream>, session: Sender<Command>) -> io::Result<()> {
+    let key: &str = match req.header("Sec-WebSocket-Key") {
+      Some(key) if req.header("Upgrade").is_some_and(|u| u.eq_ignore_ascii_case("websocket")) => key,
+      _ => return error(&mut out, "400 Bad Request", "expected a WebSocket upgrade"),
+    };
+    let accept: String = base64(&sha1(format!("{}{}", key, WS_GUID).as_bytes()));
+    write!(out, "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n")?;
+    write!(out, "Sec-WebSocket-Accept: {}\r\n\r\n", accept)?;
+    out.flush()?;
+    let _ = session.send(Command::Subscribe(out.try_clone()?));
+
+    while let Some((opcode, payload)) = read_frame(&mut input)? {
+      // close, then continuation, text and binary
+      if opcode == 8 || opcode <= 2 && session.send(Command::Keys(payload)).is_err() {
+        break;
+      }
+    }
+    let _ = out.write_all(&[0x88, 0]);
+    Ok(())
+  }
+}
+
+// one frame from the client as its opcode and unmasked payload, None once
+// the connection closes
+fn read_frame<R: Read>(input: &mut R) -> io::Result<Option<(u8, Vec<u8>)>> {
+  let mut head: [u8; 2] = [0; 2];
+  if input.read_exact(&mut head).is_err() {
+    return Ok(None);
+  }
+  let opcode: u8 = head[0] & 0x0F;
+  let masked: bool = head[1] & 0x80 != 0;
+  let len: u64 = match head[1] & 0x7F {
+    126 => {
+      let mut n: [u8; 2] = [0; 2];
+      input.read_exact(&mut n)?;
+      u16::from_be_bytes(n) as u64
+    },
+    127 => {
+      let mut n: [u8; 8] = [0; 8];
+      input.read_exact(&mut n)?;
+      u64::from_be_bytes(n)
+    },
+    n => n as u64,
+  };
+  if len > MAX_FRAME {
+    return Err(io::Error::new(io::ErrorKind::InvalidData, "frame too large"));
+  }
+  let mut mask: [u8; 4] = [0; 4];
+  if masked {
+    input.read_exact(&mut mask)?;
+  }
+  let mut payload: Vec<u8> = vec![0; len as usize];
+  input.read_exact(&mut payload)?;
+  for (i, b) in payload.iter_mut().enumerate() {
+    *b ^= mask[i % 4];
+  }
+  Ok(Some((opcode, payload)))
+}
+
+// sends msg as a single unmasked text frame
+fn send_frame(out: &mut TcpStream, msg: &Json) -> io::Result<()> {
+  let text: String = msg.to_string();
+  let len: usize = text.len();
+  let mut frame: Vec<u8> = vec![0x81];
+  if len < 126 {
+    frame.push(len as u8);
+  } else if len <= 0xFFFF {
+    frame.push(126);
+    frame.extend_from_slice(&(len as u16).to_be_bytes());
+  } else {
+    frame.push(127);
+    frame.extend_from_slice(&(len as u64).to_be_bytes());
+  }
+  frame.extend_from_slice(text.as_bytes());
+  out.write_all(&frame)
+}
+
+// only needed for the handshake's Sec-WebSocket-Accept
+fn sha1(data: &[u8]) -> [u8; 20] {
+  let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+  let mut msg: Vec<u8> = data.to_vec();
+  msg.push(0x80);
+  while msg.len() % 64 != 56 {
+    msg.push(0);
+  }
+  msg.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+  for chunk in msg.chunks(64) {
+    let mut w: [u32; 80] = [0; 80];
+    for (w, b) in w.iter_mut().zip(chunk.chunks(4)) {
+      *w = u32::from_be_bytes([b[0], b[1], b[2], b[3]]);
+    }
+    for i in 16..80 {
+      w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+    }
+    let [mut a, mut b, mut c, mut d, mut e] = h;
+    for (i, &wi) in w.iter().enumerate() {
+      let (f, k): (u32, u32) = match i {
+        0..=19 => ((b & c) | (!b & d), 0x5A827999),
+        20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+        40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+        _ => (b ^ c ^ d, 0xCA62C1D6),
+      };
+      let t: u32 = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(wi);
+      e = d;
+      d = c;
+      c = b.rotate_left(30);
+      b = a;
+      a = t;
+    }
+    for (h, v) in h.iter_mut().zip([a, b, c, d, e].iter()) {
+      *h = h.wrapping_add(*v);
+    }
+  }
+
+  let mut out: [u8; 20] = [0; 20];
+  for (o, h) in out.chunks_mut(4).zip(h.iter()) {
+    o.copy_from_slice(&h.to_be_bytes());
+  }
+  out
+}
+
+fn base64(data: &[u8]) -> String {
+  const DIGITS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+  let mut s: String = String::new();
+  for chunk in data.chunks(3) {
+    let n: u32 = chunk.iter().enumerate().fold(0, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+    for i in 0..4 {
+      if i <= chunk.len() {
+        s.push(DIGITS[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+      } else {
+        s.push('=');
+      }
+    }
+  }
+  s
+}
+
+fn main() {
+  let args: Vec<String> = env::args().skip(1).collect();
+  let mut addr: String = "127.0.0.1:8080".to_string();
+  let mut max_steps: u64 = 100_000_000;
+  let mut max_sessions: usize = 64;
+  let mut idle_timeout: u64 = 600;
+  let mut i: usize = 0;
+  while i < args.len() {
+    let value: Option<&String> = args.get(i + 1);
+    match (args[i].as_str(), value) {
+      ("--addr", Some(v)) => addr = v.clone(),
+      ("--max-steps", Some(v)) => match v.parse() {
+        Ok(n) => max_steps = n,
+        Err(_) => {
+          eprintln!("invalid step count {}", v);
+          process::exit(2);
+        },
+      },
+      ("--max-sessions", Some(v)) => match v.parse() {
+        Ok(n) => max_sessions = n,
+        Err(_) => {
+          eprintln!("invalid session count {}", v);
+          process::exit(2);
+        },
+      },
+      ("--idle-timeout", Some(v)) => match v.parse() {
+        Ok(n) => idle_timeout = n,
+        Err(_) => {
+          eprintln!("invalid timeout {}", v);
+          process::exit(2);
+        },
+      },
+      _ => {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+      },
+    }
+    i += 2;
+  }
+
+  let listener: TcpListener = match TcpListener::bind(&addr) {
+    Ok(l) => l,
+    Err(e) => {
+      eprintln!("failed to listen on {}: {}", addr, e);
+      process::exit(1);
+    },
+  };
+  eprintln!("listening on {}", addr);
+
+  let server: Arc<Server> = Arc::new(Server {
+    sessions: Arc::new(Mutex::new(HashMap::new())),
+    next_id: Mutex::new(0),
+    max_steps,
+    max_sessions,
+    idle_timeout: Duration::from_secs(idle_timeout),
+  });
+  for stream in listener.incoming() {
+    let stream: TcpStream = match stream {
+      Ok(s) => s,
+      Err(e) => {
+        eprintln!("lc3-serve: {}", e);
+        continue;
+      },
+    };
+    let server: Arc<Server> = server.clone();
+    thread::spawn(move || {
+      if let Err(e) = server.handle(stream) {
+        eprintln!("lc3-serve: {}", e);
+      }
+    });
+  }
+}
@@ -0,0 +1,372 @@
+extern crate lc3;
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::env;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process;
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use lc3::disasm;
+use lc3::instr::{self, Instruction};
+use lc3::term::RawMode;
+use lc3::{Console, Machine, Reg, Region, StopReason, NEG, POS, ZRO};
+
+const USAGE: &str = "usage: lc3-tui <program> [--region <start>..[<end>]:<kind>[:<name>]]... [--watch <expr>]...";
+
+const HELP: &str = "s step  c continue  esc pause  b breakpoint  j/k memory  g memory at PC  v view  q quit";
+
+// steps executed between redraws while running
+const SLICE: u64 = 10_000;
+
+const LEFT: usize = 34;
+const RIGHT: usize = 45;
+const CONSOLE_ROWS: usize = 10;
+
+const ESC: u8 = 0x1B;
+
+const GPRS: [Reg; 8] = [Reg::R0, Reg::R1, Reg::R2, Reg::R3, Reg::R4, Reg::R5, Reg::R6, Reg::R7];
+
+// captures program output for the console pane and feeds it keys typed
+// while the machine is running
+#[derive(Default)]
+struct Screen {
+  output: Vec<u8>,
+  keys: VecDeque<u8>,
+}
+
+struct TuiConsole(Rc<RefCell<Screen>>);
+
+impl Console for TuiConsole {
+  // the frontend doesn't run the machine into GETC without a key queued,
+  // see waiting()
+  fn read_char(&mut self) -> Option<u8> {
+    self.0.borrow_mut().keys.pop_front()
+  }
+
+  fn write_char(&mut self, c: u8) {
+    self.0.borrow_mut().output.push(c);
+  }
+
+  fn poll_key(&mut self) -> Option<u8> {
+    self.0.borrow_mut().keys.pop_front()
+  }
+}
+
+struct Tui {
+  m: Machine,
+  screen: Rc<RefCell<Screen>>,
+  name: String,
+  running: bool,
+  status: String,
+  mem_addr: u16,
+  // the memory pane as dump_text() renders it rather than in hex
+  view: bool,
+}
+
+fn stdin_keys() -> Receiver<u8> {
+  let (tx, rx) = mpsc::channel();
+  thread::spawn(move || {
+    for b in io::stdin().lock().bytes() {
+      match b {
+        Ok(c) => if tx.send(c).is_err() { break },
+        Err(_) => break,
+      }
+    }
+  });
+  rx
+}
+
+impl Tui {
+  // true when the next instruction blocks on keyboard input that hasn't
+  // been typed yet
+  fn waiting(m: &Machine, screen: &RefCell<Screen>) -> bool {
+    let pc: u16 = m.read_reg(Reg::PC);
+    let reads_key: bool = matches!(instr::decode(m.read_mem(pc)),
+      Instruction::Trap { vector: 0x20 } | Instruction::Trap { vector: 0x23 });
+    reads_key && screen.borrow().keys.is_empty()
+  }
+
+  fn key(&mut self, c: u8) -> bool {
+    if self.running {
+      match c {
+        ESC => {
+          self.running = false;
+          self.status = "paused".to_string();
+        },
+        c => self.screen.borrow_mut().keys.push_back(c),
+      }
+      return true;
+    }
+
+    match c {
+      b's' => self.step(),
+      b'c' => {
+        self.running = !self.m.halt;
+        self.status = if self.running { "running" } else { "machine halted" }.to_string();
+      },
+      b'b' => {
+        let pc: u16 = self.m.read_reg(Reg::PC);
+        if self.m.breakpoints().contains(&pc) {
+          self.m.remove_breakpoint(pc);
+          self.status = format!("breakpoint at x{:04X} removed", pc);
+        } else {
+          self.m.add_breakpoint(pc);
+          self.status = format!("breakpoint at x{:04X}", pc);
+        }
+      },
+      b'j' => self.mem_addr = self.mem_addr.wrapping_add(0x40),
+      b'k' => self.mem_addr = self.mem_addr.wrapping_sub(0x40),
+      b'g' => self.mem_addr = self.m.read_reg(Reg::PC) & !0x7,
+      b'v' => self.view = !self.view,
+      b'q' => return false,
+      _ => {},
+    }
+    true
+  }
+
+  fn step(&mut self) {
+    if self.m.halt {
+      self.status = "machine halted".to_string();
+    } else if Tui::waiting(&self.m, &self.screen) {
+      self.status = "waiting for input, continue and type a key".to_string();
+    } else {
+      let reason: StopReason = self.m.run_for(1).reason;
+      self.stopped(reason);
+    }
+  }
+
+  fn run_slice(&mut self) {
+    if Tui::waiting(&self.m, &self.screen) {
+      self.status = "waiting for input".to_string();
+      return;
+    }
+    self.status = "running".to_string();
+
+    let screen: Rc<RefCell<Screen>> = self.screen.clone();
+    let mut steps: u64 = 0;
+    let run = self.m.run_until(|m| {
+      steps += 1;
+      steps >= SLICE || Tui::waiting(m, &screen)
+    });
+    if run.reason != StopReason::Condition {
+      self.running = false;
+      self.stopped(run.reason);
+    }
+  }
+
+  fn stopped(&mut self, reason: StopReason) {
+    self.status = match reason {
+      StopReason::Breakpoint(addr) => format!("breakpoint at x{:04X}", addr),
+      StopReason::Watchpoint(addr, kind) => format!("watchpoint ({:?}) at x{:04X}", kind, addr),
+      StopReason::Halt => "machine halted".to_string(),
+      StopReason::Trap(vector) => format!("trap x{:02X}", vector),
+      StopReason::StepLimit | StopReason::Condition | StopReason::GoalReached => "paused".to_string(),
+      StopReason::LimitExceeded => "limit exceeded".to_string(),
+      StopReason::NoProgress => "stuck in a loop".to_string(),
+      StopReason::Fault(e) => e.to_string(),
+    };
+  }
+
+  fn registers(&self) -> Vec<String> {
+    let r = |i: usize| self.m.read_reg(GPRS[i]);
+    let cc: char = match self.m.read_reg(Reg::COND) {
+      NEG => 'N',
+      ZRO => 'Z',
+      POS => 'P',
+      _ => '-',
+    };
+    vec![
+      " Registers".to_string(),
+      format!(" R0 x{:04X}  R1 x{:04X}  R2 x{:04X}", r(0), r(1), r(2)),
+      format!(" R3 x{:04X}  R4 x{:04X}  R5 x{:04X}", r(3), r(4), r(5)),
+      format!(" R6 x{:04X}  R7 x{:04X}", r(6), r(7)),
+      format!(" PC x{:04X}  PSR x{:04X}  CC {}", self.m.read_reg(Reg::PC), self.m.psr(), cc),
+      String::new(),
+    ]
+  }
+
+  // kept up to date by the machine after every instruction
+  fn watches(&self) -> Vec<String> {
+    if self.m.watches().is_empty() {
+      return Vec::new();
+    }
+    let mut lines: Vec<String> = vec![" Watches".to_string()];
+    lines.extend(self.m.watches().iter().map(|w| format!(" {}", w)));
+    lines.push(String::new());
+    lines
+  }
+
+  fn disassembly(&self, rows: usize) -> Vec<String> {
+    let pc: u16 = self.m.read_reg(Reg::PC);
+    let mut lines: Vec<String> = vec![" Disassembly".to_string()];
+    for i in 0..rows - 1 {
+      let a: u16 = pc.wrapping_sub(3).wrapping_add(i as u16);
+      let marker: &str = if a == pc { "=>" } else if self.m.breakpoints().contains(&a) { " *" } else { "  " };
+      let label: &str = self.m.symbols().label(a).unwrap_or("");
+      lines.push(format!("{}x{:04X} {:<7} {}", marker, a, label, disasm::disassemble(self.m.read_mem(a), a, self.m.symbols())));
+    }
+    lines
+  }
+
+  fn memory(&self) -> Vec<String> {
+    let title: String = match self.m.region_at(self.mem_addr) {
+      Some(region) => format!(" Memory  {}", region),
+      None => " Memory".to_string(),
+    };
+    let mut lines: Vec<String> = vec![title];
+    if self.view {
+      let text: String = self.m.dump_text(self.mem_addr..self.mem_addr.saturating_add(0x40));
+      lines.extend(text.lines().take(8).map(|l| l.to_string()));
+      lines.resize(9, String::new());
+      lines.push(String::new());
+      return lines;
+    }
+    for row in 0..8 {
+      let base: u16 = self.mem_addr.wrapping_add(row * 8);
+      let mut line: String = format!("x{:04X}", base);
+      for i in 0..8 {
+        line.push_str(&format!(" {:04X}", self.m.read_mem(base.wrapping_add(i))));
+      }
+      lines.push(line);
+    }
+    lines.push(String::new());
+    lines
+  }
+
+  fn console(&self) -> Vec<String> {
+    let text: String = String::from_utf8_lossy(&self.screen.borrow().output).replace('\r', "");
+    let all: Vec<&str> = text.split('\n').collect();
+    let start: usize = all.len().saturating_sub(CONSOLE_ROWS);
+
+    let mut lines: Vec<String> = vec![" Console".to_string()];
+    lines.extend(all[start..].iter().map(|l| format!(" {}", l)));
+    lines
+  }
+
+  fn draw(&self, out: &mut impl Write) -> io::Result<()> {
+    let watches: Vec<String> = self.watches();
+    let rows: usize = (CONSOLE_ROWS + 5).saturating_sub(watches.len()).max(4);
+    let left: Vec<String> = [self.registers(), watches, self.disassembly(rows)].concat();
+    let right: Vec<String> = [self.memory(), self.console()].concat();
+    let state: &str = if self.running { "running" } else { "paused" };
+
+    // redrawn in place from the top left, clearing the rest of each line;
+    // raw mode leaves output processing alone so \n still returns the
+    // carriage
+    write!(out, "\x1b[H")?;
+    writeln!(out, "\x1b[7m{:<width$}\x1b[0m", fit(&format!(" lc3 - {} [{}]", self.name, state), LEFT + RIGHT + 1), width = LEFT + RIGHT + 1)?;
+    for i in 0..left.len().max(right.len()) {
+      let l: &str = left.get(i).map_or("", |s| s.as_str());
+      let r: &str = right.get(i).map_or("", |s| s.as_str());
+      writeln!(out, "{:<lw$}|{}\x1b[K", fit(l, LEFT), fit(r, RIGHT), lw = LEFT)?;
+    }
+    writeln!(out, " {}\x1b[K", fit(&self.status, LEFT + RIGHT))?;
+    write!(out, "\x1b[2m {}\x1b[0m\x1b[K\x1b[J", HELP)?;
+    out.flush()
+  }
+}
+
+fn fit(s: &str, width: usize) -> String {
+  s.chars().take(width).collect()
+}
+
+fn main() {
+  let args: Vec<String> = env::args().skip(1).collect();
+  let (program, rest): (&String, &[String]) = match args.split_first() {
+    Some((program, rest)) if rest.len() % 2 == 0 => (program, rest),
+    _ => {
+      eprintln!("{}", USAGE);
+      process::exit(2);
+    },
+  };
+  let mut regions: Vec<Region> = Vec::new();
+  let mut watches: Vec<&String> = Vec::new();
+  for pair in rest.chunks(2) {
+    match (pair[0].as_str(), Region::parse(&pair[1])) {
+      ("--region", Ok(region)) => regions.push(region),
+      ("--watch", _) => watches.push(&pair[1]),
+      _ => {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+      },
+    }
+  }
+
+  let screen: Rc<RefCell<Screen>> = Rc::new(RefCell::new(Screen::default()));
+  let mut m = Machine::with_io(Box::new(TuiConsole(screen.clone())));
+  m.init();
+  if let Err(e) = m.load_program(Path::new(program)) {
+    eprintln!("failed to load {}: {}", program, e);
+    process::exit(1);
+  }
+  for region in regions {
+    m.add_region(region);
+  }
+  for src in watches {
+    if let Err(e) = m.add_watch(src) {
+      eprintln!("invalid watch `{}`: {}", src, e);
+      process::exit(2);
+    }
+  }
+
+  let mut tui = Tui {
+    mem_addr: m.read_reg(Reg::PC) & !0x7,
+    m,
+    screen,
+    name: program.clone(),
+    running: false,
+    status: "paused".to_string(),
+    view: false,
+  };
+
+  let keys: Receiver<u8> = stdin_keys();
+  let raw: RawMode = RawMode::enter();
+  let mut out = io::stdout();
+  // alternate screen, hidden cursor
+  print!("\x1b[?1049h\x1b[?25l");
+
+  loop {
+    if tui.draw(&mut out).is_err() {
+      break;
+    }
+
+    let key: Option<u8> = if tui.running {
+      match keys.recv_timeout(Duration::from_millis(0)) {
+        Ok(c) => Some(c),
+        Err(RecvTimeoutError::Timeout) => None,
+        Err(RecvTimeoutError::Disconnected) => break,
+      }
+    } else {
+      match keys.recv() {
+        Ok(c) => Some(c),
+        Err(_) => break,
+      }
+    };
+    if let Some(c) = key {
+      if !tui.key(c) {
+        break;
+      }
+    }
+
+    if tui.running {
+      tui.run_slice();
+      if tui.running && Tui::waiting(&tui.m, &tui.screen) {
+        // nothing to do until a key arrives
+        match keys.recv_timeout(Duration::from_millis(50)) {
+          Ok(c) => { tui.key(c); },
+          Err(RecvTimeoutError::Timeout) => {},
+          Err(RecvTimeoutError::Disconnected) => break,
+        }
+      }
+    }
+  }
+
+  print!("\x1b[?25h\x1b[?1049l");
+  let _ = out.flush();
+  drop(raw);
+}
@@ -0,0 +1,104 @@
+use alloc::vec::Vec;
+
+use machine::{Machine, StopReason};
+
+// several machines run side by side, e.g. connected by Mailbox devices:
+//
+//   let mut c = Cluster::new(Schedule::Lockstep);
+//   for mbx in Mailbox::network(2) {
+//     c.add(Machine::builder().device(Box::new(mbx)).load(&words).build());
+//   }
+//   c.run();
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schedule {
+  // every machine executes one instruction per round
+  Lockstep,
+  // each machine in turn executes up to quantum instructions
+  RoundRobin { quantum: u64 },
+}
+
+// outcome of Cluster::run_for(), like Run for a single machine. stop is
+// the first machine to stop for anything but HALT and why; the others have
+// finished the round.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClusterRun {
+  pub rounds: u64,
+  pub stop: Option<(usize, StopReason)>,
+}
+
+pub struct Cluster {
+  machines: Vec<Machine>,
+  schedule: Schedule,
+}
+
+impl Cluster {
+  pub fn new(schedule: Schedule) -> Cluster {
+    Cluster { machines: Vec::new(), schedule }
+  }
+
+  // adds a machine, returning its index
+  pub fn add(&mut self, m: Machine) -> usize {
+    self.machines.push(m);
+    self.machines.len() - 1
+  }
+
+  pub fn len(&self) -> usize {
+    self.machines.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.machines.is_empty()
+  }
+
+  pub fn machine(&self, i: usize) -> &Machine {
+    &self.machines[i]
+  }
+
+  pub fn machine_mut(&mut self, i: usize) -> &mut Machine {
+    &mut self.machines[i]
+  }
+
+  pub fn machines(&self) -> &[Machine] {
+    &self.machines
+  }
+
+  pub fn halted(&self) -> bool {
+    self.machines.iter().all(|m| m.halt)
+  }
+
+  // one round of the schedule over the machines still running
+  pub fn round(&mut self) -> Option<(usize, StopReason)> {
+    let steps: u64 = match self.schedule {
+      Schedule::Lockstep => 1,
+      Schedule::RoundRobin { quantum } => quantum.max(1),
+    };
+    let mut stop: Option<(usize, StopReason)> = None;
+    for (i, m) in self.machines.iter_mut().enumerate().filter(|(_, m)| !m.halt) {
+      match m.run_for(steps).reason {
+        StopReason::StepLimit | StopReason::Halt => {},
+        reason => {
+          stop = stop.or(Some((i, reason)));
+        },
+      }
+    }
+    stop
+  }
+
+  // runs rounds until every machine has halted, one stops for another
+  // reason, or the limit is reached
+  pub fn run_for(&mut self, rounds: u64) -> ClusterRun {
+    let mut done: u64 = 0;
+    while done < rounds && !self.halted() {
+      let stop: Option<(usize, StopReason)> = self.round();
+      done += 1;
+      if stop.is_some() {
+        return ClusterRun { rounds: done, stop };
+      }
+    }
+    ClusterRun { rounds: done, stop: None }
+  }
+
+  pub fn run(&mut self) -> ClusterRun {
+    self.run_for(u64::MAX)
+  }
+}
@@ -0,0 +1,99 @@
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
+#[cfg(feature = "std")]
+use std::sync::mpsc::{self, Receiver};
+#[cfg(feature = "std")]
+use std::thread;
+
+// host side of the keyboard and display, used by the TRAP routines and the
+// memory-mapped device registers
+pub trait Console {
+  // blocks until a character is available, None once input is exhausted
+  fn read_char(&mut self) -> Option<u8>;
+
+  fn write_char(&mut self, c: u8);
+
+  // returns a pending key without blocking
+  fn poll_key(&mut self) -> Option<u8>;
+
+  fn flush(&mut self) {}
+}
+
+// stdin/stdout console; stdin is read on a background thread so the
+// keyboard can be polled
+#[cfg(feature = "std")]
+#[derive(Default)]
+pub struct StdConsole {
+  stdin: Option<Receiver<u8>>,
+  // display on stderr instead, leaving stdout to the host
+  stderr: bool,
+}
+
+#[cfg(feature = "std")]
+impl StdConsole {
+  pub fn new() -> StdConsole {
+    StdConsole { stdin: None, stderr: false }
+  }
+
+  pub fn stderr() -> StdConsole {
+    StdConsole { stdin: None, stderr: true }
+  }
+
+  fn stdin(&mut self) -> &Receiver<u8> {
+    self.stdin.get_or_insert_with(|| {
+      let (tx, rx) = mpsc::channel();
+      thread::spawn(move || {
+        for b in io::stdin().lock().bytes() {
+          match b {
+            Ok(c) => if tx.send(c).is_err() { break },
+            Err(_) => break,
+          }
+        }
+      });
+      rx
+    })
+  }
+}
+
+#[cfg(feature = "std")]
+impl Console for StdConsole {
+  fn read_char(&mut self) -> Option<u8> {
+    self.stdin().recv().ok()
+  }
+
+  fn write_char(&mut self, c: u8) {
+    if self.stderr {
+      io::stderr().write_all(&[c]).unwrap();
+    } else {
+      io::stdout().write_all(&[c]).unwrap();
+    }
+  }
+
+  fn poll_key(&mut self) -> Option<u8> {
+    self.stdin().try_recv().ok()
+  }
+
+  fn flush(&mut self) {
+    if self.stderr {
+      io::stderr().flush().unwrap();
+    } else {
+      io::stdout().flush().unwrap();
+    }
+  }
+}
+
+// no input and discarded output, for machines that don't do I/O
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NullConsole;
+
+impl Console for NullConsole {
+  fn read_char(&mut self) -> Option<u8> {
+    None
+  }
+
+  fn write_char(&mut self, _c: u8) {}
+
+  fn poll_key(&mut self) -> Option<u8> {
+    None
+  }
+}
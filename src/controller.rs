@@ -0,0 +1,192 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+use machine::{Machine, Snapshot, StopReason};
+
+// instructions between checks for a pause or stop request
+const SLICE: u64 = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunState {
+  Running,
+  Paused,
+  // stopped by the machine itself, e.g. at HALT or a breakpoint.
+  // resume() continues from there.
+  Stopped(StopReason),
+  // the worker is gone, after Controller::stop()
+  Finished,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Request {
+  None,
+  Pause,
+  Resume,
+  Stop,
+}
+
+struct Status {
+  state: RunState,
+  request: Request,
+  snapshot: Option<Snapshot>,
+}
+
+struct Shared {
+  status: Mutex<Status>,
+  changed: Condvar,
+  // set with a pause or stop request, so the running worker only takes the
+  // lock when there is something to do
+  interrupt: AtomicBool,
+  steps: AtomicU64,
+}
+
+impl Shared {
+  fn lock(&self) -> MutexGuard<'_, Status> {
+    self.status.lock().unwrap_or_else(|e| e.into_inner())
+  }
+}
+
+// runs a machine on a worker thread and controls it from others. The
+// machine's console and devices needn't be Send, so the worker builds it:
+//
+//   let ctl = Controller::spawn(move || Machine::builder().load(&words).build());
+//   ctl.pause();
+//   let pc: u16 = ctl.snapshot().unwrap().reg[PC as usize];
+//
+// The machine's state is published as a Snapshot whenever the worker
+// pauses or stops.
+pub struct Controller {
+  shared: Arc<Shared>,
+  worker: Option<JoinHandle<()>>,
+}
+
+impl Controller {
+  pub fn spawn<F: FnOnce() -> Machine + Send + 'static>(build: F) -> Controller {
+    let shared: Arc<Shared> = Arc::new(Shared {
+      status: Mutex::new(Status { state: RunState::Running, request: Request::None, snapshot: None }),
+      changed: Condvar::new(),
+      interrupt: AtomicBool::new(false),
+      steps: AtomicU64::new(0),
+    });
+    let worker_shared: Arc<Shared> = shared.clone();
+    let worker: JoinHandle<()> = thread::spawn(move || work(build(), &worker_shared));
+    Controller { shared, worker: Some(worker) }
+  }
+
+  // blocks until the worker has paused, or has stopped on its own
+  pub fn pause(&self) {
+    let mut status: MutexGuard<Status> = self.shared.lock();
+    if status.state != RunState::Running {
+      return;
+    }
+    status.request = Request::Pause;
+    self.shared.interrupt.store(true, Ordering::SeqCst);
+    while status.state == RunState::Running {
+      status = self.shared.changed.wait(status).unwrap_or_else(|e| e.into_inner());
+    }
+  }
+
+  pub fn resume(&self) {
+    let mut status: MutexGuard<Status> = self.shared.lock();
+    if let RunState::Paused | RunState::Stopped(_) = status.state {
+      status.request = Request::Resume;
+      status.state = RunState::Running;
+      self.shared.changed.notify_all();
+    }
+  }
+
+  // ends the worker, returning the machine's final state
+  pub fn stop(&mut self) -> Option<Snapshot> {
+    {
+      let mut status: MutexGuard<Status> = self.shared.lock();
+      status.request = Request::Stop;
+      self.shared.interrupt.store(true, Ordering::SeqCst);
+      self.shared.changed.notify_all();
+    }
+    if let Some(worker) = self.worker.take() {
+      let _ = worker.join();
+    }
+    self.snapshot()
+  }
+
+  // blocks until the machine stops on its own, or the controller is
+  // paused or stopped elsewhere
+  pub fn wait(&self) -> RunState {
+    let mut status: MutexGuard<Status> = self.shared.lock();
+    while status.state == RunState::Running {
+      status = self.shared.changed.wait(status).unwrap_or_else(|e| e.into_inner());
+    }
+    status.state
+  }
+
+  pub fn state(&self) -> RunState {
+    self.shared.lock().state
+  }
+
+  // instructions executed so far, updated every few thousand while running
+  pub fn steps(&self) -> u64 {
+    self.shared.steps.load(Ordering::Relaxed)
+  }
+
+  // the machine as of the last pause or stop
+  pub fn snapshot(&self) -> Option<Snapshot> {
+    self.shared.lock().snapshot.clone()
+  }
+}
+
+impl Drop for Controller {
+  fn drop(&mut self) {
+    if self.worker.is_some() {
+      self.stop();
+    }
+  }
+}
+
+fn work(mut m: Machine, shared: &Shared) {
+  let mut total: u64 = 0;
+  'run: loop {
+    let mut n: u64 = 0;
+    let run = m.run_until(|_| {
+      n += 1;
+      if !n.is_multiple_of(SLICE) {
+        return false;
+      }
+      shared.steps.store(total + n, Ordering::Relaxed);
+      shared.interrupt.load(Ordering::SeqCst)
+    });
+    total += run.steps;
+    shared.steps.store(total, Ordering::Relaxed);
+
+    let mut status: MutexGuard<Status> = shared.lock();
+    shared.interrupt.store(false, Ordering::SeqCst);
+    status.snapshot = Some(m.snapshot());
+    status.state = match run.reason {
+      StopReason::Condition => RunState::Paused,
+      reason => RunState::Stopped(reason),
+    };
+    shared.changed.notify_all();
+
+    loop {
+      match status.request {
+        Request::Stop => break 'run,
+        Request::Resume => break,
+        // a pause that came in after a resume the worker hadn't seen yet
+        Request::Pause => {
+          shared.interrupt.store(false, Ordering::SeqCst);
+          if status.state == RunState::Running {
+            status.state = RunState::Paused;
+          }
+          status.request = Request::None;
+          shared.changed.notify_all();
+        },
+        Request::None => status = shared.changed.wait(status).unwrap_or_else(|e| e.into_inner()),
+      }
+    }
+    status.request = Request::None;
+  }
+
+  let mut status: MutexGuard<Status> = shared.lock();
+  status.state = RunState::Finished;
+  shared.changed.notify_all();
+}
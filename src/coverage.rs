@@ -0,0 +1,129 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use assembler::{self, Line, LineEntry, Program};
+use machine::MEM_SIZE;
+
+const WORDS: usize = MEM_SIZE / 64;
+
+fn set(bits: &mut [u64], addr: u16) {
+  bits[addr as usize / 64] |= 1 << (addr % 64);
+}
+
+fn get(bits: &[u64], addr: u16) -> bool {
+  bits[addr as usize / 64] & (1 << (addr % 64)) != 0
+}
+
+// addresses executed, read and written while Machine::start_coverage() is
+// in effect. Reads and writes are the data accesses MemObserver sees.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+  executed: Vec<u64>,
+  read: Vec<u64>,
+  written: Vec<u64>,
+}
+
+impl Default for Coverage {
+  fn default() -> Coverage {
+    Coverage { executed: vec![0; WORDS], read: vec![0; WORDS], written: vec![0; WORDS] }
+  }
+}
+
+impl Coverage {
+  pub fn new() -> Coverage {
+    Coverage::default()
+  }
+
+  pub fn mark_executed(&mut self, addr: u16) {
+    set(&mut self.executed, addr);
+  }
+
+  pub fn mark_read(&mut self, addr: u16) {
+    set(&mut self.read, addr);
+  }
+
+  pub fn mark_written(&mut self, addr: u16) {
+    set(&mut self.written, addr);
+  }
+
+  pub fn executed(&self, addr: u16) -> bool {
+    get(&self.executed, addr)
+  }
+
+  pub fn read(&self, addr: u16) -> bool {
+    get(&self.read, addr)
+  }
+
+  pub fn written(&self, addr: u16) -> bool {
+    get(&self.written, addr)
+  }
+
+  // distinct addresses executed
+  pub fn executed_count(&self) -> usize {
+    self.executed.iter().map(|w| w.count_ones() as usize).sum()
+  }
+
+  // adds what other saw, e.g. to total up the runs of a test suite
+  pub fn merge(&mut self, other: &Coverage) {
+    for (bits, more) in [(&mut self.executed, &other.executed), (&mut self.read, &other.read), (&mut self.written, &other.written)] {
+      for (w, m) in bits.iter_mut().zip(more.iter()) {
+        *w |= m;
+      }
+    }
+  }
+
+  // the instructions of prog executed and their total
+  pub fn instructions(&self, prog: &Program, src: &str) -> (usize, usize) {
+    let code: Vec<&LineEntry> = code_lines(prog, src);
+    (code.iter().filter(|e| self.executed(e.addr)).count(), code.len())
+  }
+
+  // src annotated with the coverage of prog, the program assembled from it.
+  // Each line that emits words is prefixed with x, r and w for whether any
+  // of them was executed, read or written, and instructions never executed
+  // are marked #####:
+  //
+  //         x--  x3001 | LOOP  ADD R1, R1, #-1
+  //   ##### ---  x3004 |       ST R1, N
+  //         -r-  x3007 | N     .FILL #3
+  //
+  // A count of the instructions executed follows.
+  pub fn listing(&self, prog: &Program, src: &str) -> String {
+    let code: Vec<&LineEntry> = code_lines(prog, src);
+    let mut out: String = String::new();
+    let mut entries = prog.lines.iter().peekable();
+
+    for (i, text) in src.lines().enumerate() {
+      let entry: &LineEntry = match entries.next_if(|e| e.line == i + 1) {
+        Some(e) => e,
+        None => {
+          out.push_str(&format!("{:17}| {}\n", "", text));
+          continue;
+        },
+      };
+      let addrs = || (0..entry.len).map(|j| entry.addr.wrapping_add(j));
+      let flag = |c: char, hit: &dyn Fn(u16) -> bool| if addrs().any(hit) { c } else { '-' };
+      let flags: String = [
+        flag('x', &|a| self.executed(a)),
+        flag('r', &|a| self.read(a)),
+        flag('w', &|a| self.written(a)),
+      ].iter().collect();
+      let missed: bool = code.iter().any(|e| e.line == entry.line) && !self.executed(entry.addr);
+      out.push_str(&format!("{:5} {}  x{:04X} | {}\n", if missed { "#####" } else { "" }, flags, entry.addr, text));
+    }
+
+    let (hit, total): (usize, usize) = self.instructions(prog, src);
+    out.push_str(&format!("\n{} of {} instructions executed\n", hit, total));
+    out
+  }
+}
+
+// the lines of prog that are instructions rather than directives
+fn code_lines<'a>(prog: &'a Program, src: &str) -> Vec<&'a LineEntry> {
+  let lines: Vec<Line> = assembler::parse(src).unwrap_or_default();
+  let code: Vec<usize> = lines.iter()
+    .filter(|l| l.op.as_deref().is_some_and(|op| !op.starts_with('.')))
+    .map(|l| l.num)
+    .collect();
+  prog.lines.iter().filter(|e| code.binary_search(&e.line).is_ok()).collect()
+}
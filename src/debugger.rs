@@ -0,0 +1,283 @@
+use std::io::{self, Write};
+
+use num_traits::FromPrimitive;
+
+use disasm;
+use machine::*;
+use term::RawMode;
+
+const HELP: &str = "\
+commands:
+  s, step [n]           execute n instructions (default 1)
+  n, next               execute an instruction, a call as one
+  finish                run until the current subroutine returns
+  u, until <loc>        run until PC reaches an address, a label or a
+                        source line
+  back [n]              undo the last n instructions (default 1)
+  c, continue           run until a breakpoint or halt
+  b, break <loc>        set a breakpoint at an address, a label or a
+                        source line, file.asm:42
+  b, break <loc> if <e> stop there only when e holds, e.g.
+                        R0 == 5 && MEM[x4000] != 0
+  w, watch <addr> [rw]  set a watchpoint (r, w or rw, default w)
+  w, watch <expr>       show an expression whenever execution stops, e.g.
+                        R3, MEM[SP] or MEM[x4000..x4010] for a range
+  unwatch <n>           remove watch expression n
+  watches               show the watch expressions
+  delete <loc>          remove a breakpoint or watchpoint
+  r, regs               show registers
+  bt, backtrace         show the subroutine calls that led to PC
+  m, mem <addr> [len]   dump memory
+  v, view <addr> [len]  show memory as its regions say: code disassembled,
+                        data as numbers and strings, the stack with R6
+  str <addr>            show the .STRINGZ string at an address
+  region <spec>         annotate memory, <start>..[<end>]:<kind>[:<name>]
+                        with kind code, data, stack or device
+  regions               list the regions
+  d, disasm [addr] [n]  disassemble n instructions (default at PC)
+  q, quit               exit the debugger";
+
+// instructions the debugger can step back over
+const HISTORY: usize = 100_000;
+
+pub use utils::parse_addr;
+
+fn parse_kind(s: &str) -> Option<WatchKind> {
+  match s {
+    "r" => Some(WatchKind::Read),
+    "w" => Some(WatchKind::Write),
+    "rw" => Some(WatchKind::Access),
+    _ => None,
+  }
+}
+
+#[derive(Default)]
+pub struct Debugger;
+
+impl Debugger {
+  pub fn new() -> Debugger {
+    Debugger
+  }
+
+  pub fn run(&mut self, m: &mut Machine) {
+    if m.history_len() == 0 {
+      m.enable_history(HISTORY);
+    }
+
+    println!("lc3 debugger, type `help` for commands");
+    self.show_pc(m);
+
+    loop {
+      print!("(lc3) ");
+      io::stdout().flush().unwrap();
+
+      let line: String = match m.read_line() {
+        Some(line) => line,
+        None => break,
+      };
+      let args: Vec<&str> = line.split_whitespace().collect();
+
+      match args.as_slice() {
+        [] => {},
+        ["s"] | ["step"] => self.step(m, 1),
+        ["s", n] | ["step", n] => match n.parse() {
+          Ok(n) => self.step(m, n),
+          Err(_) => println!("invalid count `{}`", n),
+        },
+        ["n"] | ["next"] => self.go(m, Goal::Over),
+        ["finish"] => self.go(m, Goal::Out),
+        ["u", a] | ["until", a] => match m.resolve(a) {
+          Some(addr) => self.go(m, Goal::To(addr)),
+          None => println!("invalid location `{}`", a),
+        },
+        ["back"] => self.back(m, 1),
+        ["back", n] => match n.parse() {
+          Ok(n) => self.back(m, n),
+          Err(_) => println!("invalid count `{}`", n),
+        },
+        ["c"] | ["continue"] => self.cont(m),
+        ["b", a] | ["break", a] => match m.break_at(a) {
+          Some(addr) => println!("breakpoint at x{:04X}", addr),
+          None => println!("invalid location `{}`", a),
+        },
+        ["b", a, "if", cond @ ..] | ["break", a, "if", cond @ ..] => match m.break_at_if(a, &cond.join(" ")) {
+          Ok(addr) => println!("conditional breakpoint at x{:04X}", addr),
+          Err(e) => println!("{}", e.0),
+        },
+        ["w", a] | ["watch", a] if parse_addr(a).is_some() => self.watch(m, a, "w"),
+        ["w", a, k] | ["watch", a, k] if parse_addr(a).is_some() && parse_kind(k).is_some() => self.watch(m, a, k),
+        ["w", e @ ..] | ["watch", e @ ..] if !e.is_empty() => match m.add_watch(&e.join(" ")) {
+          Ok(id) => println!("watch {}: {}", id, m.watches().last().unwrap()),
+          Err(e) => println!("{}", e.0),
+        },
+        ["unwatch", n] => match n.parse() {
+          Ok(id) if m.remove_watch(id) => {},
+          _ => println!("no watch `{}`", n),
+        },
+        ["watches"] => watches(m),
+        ["delete", a] => match m.resolve(a) {
+          Some(addr) => {
+            m.remove_breakpoint(addr);
+            m.remove_watchpoint(addr);
+          },
+          None => println!("invalid location `{}`", a),
+        },
+        ["r"] | ["regs"] => regs(m),
+        ["bt"] | ["backtrace"] => backtrace(m),
+        ["m", a] | ["mem", a] => self.with_range(a, "16", |addr, len| mem(m, addr, len)),
+        ["m", a, n] | ["mem", a, n] => self.with_range(a, n, |addr, len| mem(m, addr, len)),
+        ["v", a] | ["view", a] => self.with_range(a, "16", |addr, len| print!("{}", m.dump_text(addr..addr.saturating_add(len)))),
+        ["v", a, n] | ["view", a, n] => self.with_range(a, n, |addr, len| print!("{}", m.dump_text(addr..addr.saturating_add(len)))),
+        ["str", a] => match m.resolve(a) {
+          Some(addr) => println!("{:?}", m.read_string(addr)),
+          None => println!("invalid location `{}`", a),
+        },
+        ["region", spec] => match Region::parse(spec) {
+          Ok(region) => {
+            println!("{}", region);
+            m.add_region(region);
+          },
+          Err(e) => println!("{}", e),
+        },
+        ["regions"] => for region in m.regions() {
+          println!("{}", region);
+        },
+        ["d"] | ["disasm"] => disassemble(m, m.read_reg(Reg::PC), 8),
+        ["d", a] | ["disasm", a] => self.with_range(a, "8", |addr, len| disassemble(m, addr, len)),
+        ["d", a, n] | ["disasm", a, n] => self.with_range(a, n, |addr, len| disassemble(m, addr, len)),
+        ["h"] | ["help"] => println!("{}", HELP),
+        ["q"] | ["quit"] => break,
+        _ => println!("unknown command, type `help` for commands"),
+      }
+    }
+  }
+
+  fn with_range<F: FnOnce(u16, u16)>(&self, addr: &str, len: &str, f: F) {
+    match (parse_addr(addr), len.parse()) {
+      (Some(addr), Ok(len)) => f(addr, len),
+      _ => println!("invalid range `{} {}`", addr, len),
+    }
+  }
+
+  fn watch(&mut self, m: &mut Machine, addr: &str, kind: &str) {
+    match (parse_addr(addr), parse_kind(kind)) {
+      (Some(addr), Some(kind)) => {
+        m.add_watchpoint(addr, kind);
+        println!("watchpoint at x{:04X}", addr);
+      },
+      _ => println!("invalid watchpoint `{} {}`", addr, kind),
+    }
+  }
+
+  fn step(&mut self, m: &mut Machine, n: u64) {
+    if n > 0 {
+      let raw: RawMode = RawMode::enter();
+      let run: Run = m.run_for(n);
+      drop(raw);
+      report(run.reason);
+    }
+    self.show_pc(m);
+  }
+
+  fn go(&mut self, m: &mut Machine, goal: Goal) {
+    let raw: RawMode = RawMode::enter();
+    let run: Run = m.run_to_goal(goal, |_| false);
+    drop(raw);
+    report(run.reason);
+    self.show_pc(m);
+  }
+
+  fn back(&mut self, m: &mut Machine, n: u64) {
+    for _ in 0..n {
+      if !m.step_back() {
+        println!("no more history");
+        break;
+      }
+    }
+    self.show_pc(m);
+  }
+
+  fn cont(&mut self, m: &mut Machine) {
+    let raw: RawMode = RawMode::enter();
+    let run: Run = m.run();
+    drop(raw);
+    report(run.reason);
+    self.show_pc(m);
+  }
+
+  fn show_pc(&self, m: &Machine) {
+    watches(m);
+    disassemble(m, m.read_reg(Reg::PC), 1);
+  }
+}
+
+fn report(reason: StopReason) {
+  match reason {
+    StopReason::Breakpoint(addr) => println!("breakpoint at x{:04X}", addr),
+    StopReason::Watchpoint(addr, kind) => println!("watchpoint ({:?}) at x{:04X}", kind, addr),
+    StopReason::Halt => println!("machine halted"),
+    StopReason::Trap(vector) => println!("trap x{:02X}", vector),
+    StopReason::StepLimit | StopReason::Condition | StopReason::GoalReached => {},
+    StopReason::LimitExceeded => println!("limit exceeded"),
+    StopReason::NoProgress => println!("stuck in a loop that makes no progress"),
+    StopReason::Fault(e) => println!("{}", e),
+  }
+}
+
+fn regs(m: &Machine) {
+  for r in 0..8 {
+    print!("R{} x{:04X}  ", r, m.read_reg(Reg::from_u16(r).unwrap()));
+    if r == 3 {
+      println!();
+    }
+  }
+  println!();
+
+  let cc: char = match m.read_reg(Reg::COND) {
+    NEG => 'N',
+    ZRO => 'Z',
+    POS => 'P',
+    _ => '-',
+  };
+  println!("PC x{:04X}  PSR x{:04X}  CC {}", m.read_reg(Reg::PC), m.psr(), cc);
+}
+
+fn watches(m: &Machine) {
+  for w in m.watches() {
+    println!("{}: {}", w.id, w);
+  }
+}
+
+fn backtrace(m: &Machine) {
+  for (i, frame) in m.backtrace().iter().enumerate() {
+    let name: &str = frame.name.as_deref().unwrap_or("??");
+    match frame.entry {
+      Some(entry) => println!("#{:<3} x{:04X} in {} (x{:04X})", i, frame.pc, name, entry),
+      None => println!("#{:<3} x{:04X} in {}", i, frame.pc, name),
+    }
+  }
+}
+
+fn mem(m: &Machine, addr: u16, len: u16) {
+  for i in 0..len {
+    let a: u16 = addr.wrapping_add(i);
+    if i % 8 == 0 {
+      if i > 0 {
+        println!();
+      }
+      print!("x{:04X}:", a);
+    }
+    print!(" x{:04X}", m.read_mem(a));
+  }
+  println!();
+}
+
+fn disassemble(m: &Machine, addr: u16, len: u16) {
+  for i in 0..len {
+    let a: u16 = addr.wrapping_add(i);
+    let word: u16 = m.read_mem(a);
+    let marker: &str = if a == m.read_reg(Reg::PC) { "=>" } else { "  " };
+    let label: &str = m.symbols().label(a).unwrap_or("");
+    println!("{} x{:04X}: x{:04X}  {:<10} {}", marker, a, word, label, disasm::disassemble(word, a, m.symbols()));
+  }
+}
@@ -0,0 +1,35 @@
+use alloc::string::{String, ToString};
+
+use symbols::SymbolTable;
+
+pub use instr::{decode, encode, Instruction};
+
+// renders the instruction at addr with PC-relative operands resolved to
+// labels, or absolute addresses when no label is known
+pub fn disassemble(instr: u16, addr: u16, symbols: &SymbolTable) -> String {
+  let target = |offset: i16| -> String {
+    let t: u16 = addr.wrapping_add(1).wrapping_add(offset as u16);
+    match symbols.label(t) {
+      Some(label) => label.to_string(),
+      None => format!("x{:04X}", t),
+    }
+  };
+
+  match decode(instr) {
+    Instruction::Br { n: false, z: false, p: false, .. } => "NOP".to_string(),
+    Instruction::Br { n, z, p, offset } => {
+      let mut s: String = String::from("BR");
+      if n { s.push('n'); }
+      if z { s.push('z'); }
+      if p { s.push('p'); }
+      format!("{} {}", s, target(offset))
+    },
+    Instruction::Jsr { offset }     => format!("JSR {}", target(offset)),
+    Instruction::Ld { dr, offset }  => format!("LD R{}, {}", dr, target(offset)),
+    Instruction::Ldi { dr, offset } => format!("LDI R{}, {}", dr, target(offset)),
+    Instruction::Lea { dr, offset } => format!("LEA R{}, {}", dr, target(offset)),
+    Instruction::St { sr, offset }  => format!("ST R{}, {}", sr, target(offset)),
+    Instruction::Sti { sr, offset } => format!("STI R{}, {}", sr, target(offset)),
+    op => op.to_string(),
+  }
+}
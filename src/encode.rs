@@ -0,0 +1,188 @@
+use alloc::vec::Vec;
+
+use assembler::Program;
+use instr::{self, Instruction};
+use symbols::SymbolTable;
+
+// one function per instruction form, returning the encoded word. Registers
+// are register numbers, offsets and immediates are truncated to their
+// field width like the assembler's range-checked operands would be.
+
+pub fn add_reg(dr: u16, sr1: u16, sr2: u16) -> u16 {
+  instr::encode(Instruction::Add { dr, sr1, sr2 })
+}
+
+pub fn add_imm(dr: u16, sr1: u16, imm: i16) -> u16 {
+  instr::encode(Instruction::AddI { dr, sr1, imm })
+}
+
+pub fn and_reg(dr: u16, sr1: u16, sr2: u16) -> u16 {
+  instr::encode(Instruction::And { dr, sr1, sr2 })
+}
+
+pub fn and_imm(dr: u16, sr1: u16, imm: i16) -> u16 {
+  instr::encode(Instruction::AndI { dr, sr1, imm })
+}
+
+pub fn br(n: bool, z: bool, p: bool, offset: i16) -> u16 {
+  instr::encode(Instruction::Br { n, z, p, offset })
+}
+
+pub fn nop() -> u16 {
+  br(false, false, false, 0)
+}
+
+pub fn jmp(base: u16) -> u16 {
+  instr::encode(Instruction::Jmp { base })
+}
+
+pub fn ret() -> u16 {
+  jmp(7)
+}
+
+pub fn jsr(offset: i16) -> u16 {
+  instr::encode(Instruction::Jsr { offset })
+}
+
+pub fn jsrr(base: u16) -> u16 {
+  instr::encode(Instruction::Jsrr { base })
+}
+
+pub fn ld(dr: u16, offset: i16) -> u16 {
+  instr::encode(Instruction::Ld { dr, offset })
+}
+
+pub fn ldi(dr: u16, offset: i16) -> u16 {
+  instr::encode(Instruction::Ldi { dr, offset })
+}
+
+pub fn ldr(dr: u16, base: u16, offset: i16) -> u16 {
+  instr::encode(Instruction::Ldr { dr, base, offset })
+}
+
+pub fn lea(dr: u16, offset: i16) -> u16 {
+  instr::encode(Instruction::Lea { dr, offset })
+}
+
+pub fn not(dr: u16, sr: u16) -> u16 {
+  instr::encode(Instruction::Not { dr, sr })
+}
+
+pub fn rti() -> u16 {
+  instr::encode(Instruction::Rti)
+}
+
+pub fn st(sr: u16, offset: i16) -> u16 {
+  instr::encode(Instruction::St { sr, offset })
+}
+
+pub fn sti(sr: u16, offset: i16) -> u16 {
+  instr::encode(Instruction::Sti { sr, offset })
+}
+
+pub fn str(sr: u16, base: u16, offset: i16) -> u16 {
+  instr::encode(Instruction::Str { sr, base, offset })
+}
+
+pub fn trap(vector: u8) -> u16 {
+  instr::encode(Instruction::Trap { vector })
+}
+
+pub fn getc() -> u16 {
+  trap(0x20)
+}
+
+pub fn out() -> u16 {
+  trap(0x21)
+}
+
+pub fn puts() -> u16 {
+  trap(0x22)
+}
+
+pub fn halt() -> u16 {
+  trap(0x25)
+}
+
+// the opcode xD extensions, see IsaExtensions
+pub fn lshf(dr: u16, sr: u16, amount: u16) -> u16 {
+  0xD << 12 | dr << 9 | sr << 6 | (amount & 0xF)
+}
+
+pub fn rshfl(dr: u16, sr: u16, amount: u16) -> u16 {
+  0xD << 12 | dr << 9 | sr << 6 | 0b01 << 4 | (amount & 0xF)
+}
+
+pub fn rshfa(dr: u16, sr: u16, amount: u16) -> u16 {
+  0xD << 12 | dr << 9 | sr << 6 | 0b11 << 4 | (amount & 0xF)
+}
+
+pub fn mul_reg(dr: u16, sr1: u16, sr2: u16) -> u16 {
+  0xD << 12 | dr << 9 | sr1 << 6 | sr2
+}
+
+pub fn mul_imm(dr: u16, sr1: u16, imm: i16) -> u16 {
+  0xD << 12 | dr << 9 | sr1 << 6 | 1 << 5 | (imm as u16 & 0x1F)
+}
+
+// a program laid out word by word from an origin, for building test
+// images in Rust instead of hex:
+//
+//   Image::new(0x3000).word(lea(0, 2)).word(puts()).word(halt()).stringz("hi").build()
+#[derive(Debug, Clone)]
+pub struct Image {
+  origin: u16,
+  words: Vec<u16>,
+  symbols: SymbolTable,
+}
+
+impl Image {
+  pub fn new(origin: u16) -> Image {
+    Image { origin, words: Vec::new(), symbols: SymbolTable::new() }
+  }
+
+  // address the next word goes to
+  pub fn here(&self) -> u16 {
+    self.origin.wrapping_add(self.words.len() as u16)
+  }
+
+  pub fn word(mut self, word: u16) -> Image {
+    self.words.push(word);
+    self
+  }
+
+  pub fn words(mut self, words: &[u16]) -> Image {
+    self.words.extend_from_slice(words);
+    self
+  }
+
+  // like .BLKW
+  pub fn fill(mut self, n: u16, word: u16) -> Image {
+    self.words.extend((0..n).map(|_| word));
+    self
+  }
+
+  // like .STRINGZ, one character per word and a null terminator
+  pub fn stringz(mut self, s: &str) -> Image {
+    self.words.extend(s.bytes().map(|c| c as u16));
+    self.words.push(0);
+    self
+  }
+
+  // names the next word's address in the program's symbol table
+  pub fn label(mut self, name: &str) -> Image {
+    let addr: u16 = self.here();
+    self.symbols.insert(name, addr);
+    self
+  }
+
+  // offset from the word about to be emitted to `target`, for the
+  // PC-relative operand of that word
+  pub fn offset_to(&self, target: u16) -> i16 {
+    target.wrapping_sub(self.here().wrapping_add(1)) as i16
+  }
+
+  pub fn build(self) -> Program {
+    Program { origin: self.origin, words: self.words, symbols: self.symbols, lines: Vec::new() }
+  }
+}
@@ -0,0 +1,245 @@
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use machine::{Machine, Reg};
+use symbols::SymbolTable;
+use utils::FormatError;
+
+// an expression over machine state, e.g. the condition of a breakpoint:
+//
+//   R0 == 5 && MEM[x4000] != 0
+//   MEM[R6 + 1] == RESULT || PC == LOOP
+//
+// Values are 16-bit words and arithmetic wraps. Operands are numbers (5,
+// #5, x1F, 0x1F), registers (R0-R7, SP for R6, PC, CC, PSR), MEM[addr] and labels,
+// resolved when the expression is parsed. Operators, loosest first: ||,
+// &&, the comparisons, |, &, + and -, then unary ! and -. < and friends
+// compare signed, like the condition codes; logical operators give 1 or 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expr(Node);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+  Num(u16),
+  Reg(Reg),
+  Psr,
+  Mem(Box<Node>),
+  Not(Box<Node>),
+  Neg(Box<Node>),
+  Binary(Op, Box<Node>, Box<Node>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+  Or, And, Eq, Ne, Lt, Le, Gt, Ge, BitOr, BitAnd, Add, Sub,
+}
+
+// operators by precedence level, loosest first
+const LEVELS: &[&[(&str, Op)]] = &[
+  &[("||", Op::Or)],
+  &[("&&", Op::And)],
+  &[("==", Op::Eq), ("!=", Op::Ne), ("<=", Op::Le), (">=", Op::Ge), ("<", Op::Lt), (">", Op::Gt)],
+  &[("|", Op::BitOr)],
+  &[("&", Op::BitAnd)],
+  &[("+", Op::Add), ("-", Op::Sub)],
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+  Word(String),
+  Punct(&'static str),
+}
+
+const PUNCT: &[&str] = &["||", "&&", "==", "!=", "<=", ">=", "<", ">", "|", "&", "+", "-", "!", "(", ")", "[", "]"];
+
+fn err<T>(msg: impl Into<String>) -> Result<T, FormatError> {
+  Err(FormatError(msg.into()))
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, FormatError> {
+  let mut tokens: Vec<Token> = Vec::new();
+  let mut rest: &str = src.trim_start();
+  while let Some(c) = rest.chars().next() {
+    if c.is_ascii_alphanumeric() || c == '_' || c == '#' {
+      // #-5 is one number, not # then -5
+      let start: usize = if rest.starts_with("#-") { 2 } else { 0 };
+      let end: usize = rest[start..].find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '#')).map_or(rest.len(), |e| start + e);
+      tokens.push(Token::Word(rest[..end].to_string()));
+      rest = &rest[end..];
+    } else if let Some(&p) = PUNCT.iter().find(|p| rest.starts_with(**p)) {
+      tokens.push(Token::Punct(p));
+      rest = &rest[p.len()..];
+    } else {
+      return err(format!("unexpected `{}`", c));
+    }
+    rest = rest.trim_start();
+  }
+  Ok(tokens)
+}
+
+fn register(word: &str) -> Option<Node> {
+  let reg: Reg = match word.to_ascii_uppercase().as_str() {
+    "R0" => Reg::R0,
+    "R1" => Reg::R1,
+    "R2" => Reg::R2,
+    "R3" => Reg::R3,
+    "R4" => Reg::R4,
+    "R5" => Reg::R5,
+    "R6" | "SP" => Reg::R6,
+    "R7" => Reg::R7,
+    "PC" => Reg::PC,
+    "CC" => Reg::COND,
+    "PSR" => return Some(Node::Psr),
+    _ => return None,
+  };
+  Some(Node::Reg(reg))
+}
+
+fn number(word: &str) -> Option<u16> {
+  if let Some(d) = word.strip_prefix("#-") {
+    return d.parse::<u32>().ok().filter(|&n| n <= 0x8000).map(|n| (n as u16).wrapping_neg());
+  }
+  let (digits, radix): (&str, u32) = if let Some(d) = word.strip_prefix('#') {
+    (d, 10)
+  } else if let Some(h) = word.strip_prefix("0x").or_else(|| word.strip_prefix('x')).or_else(|| word.strip_prefix('X')) {
+    (h, 16)
+  } else {
+    (word, 10)
+  };
+  // wide enough for 65535 and -32768 after negation
+  u32::from_str_radix(digits, radix).ok().filter(|&n| n <= 0xFFFF).map(|n| n as u16)
+}
+
+struct Parser<'a> {
+  tokens: Vec<Token>,
+  pos: usize,
+  symbols: &'a SymbolTable,
+}
+
+impl<'a> Parser<'a> {
+  fn peek(&self) -> Option<&Token> {
+    self.tokens.get(self.pos)
+  }
+
+  fn eat(&mut self, p: &str) -> bool {
+    if self.peek() == Some(&Token::Punct(PUNCT.iter().find(|q| **q == p).unwrap())) {
+      self.pos += 1;
+      return true;
+    }
+    false
+  }
+
+  fn expect(&mut self, p: &str) -> Result<(), FormatError> {
+    if !self.eat(p) {
+      return err(format!("expected `{}`", p));
+    }
+    Ok(())
+  }
+
+  fn binary(&mut self, level: usize) -> Result<Node, FormatError> {
+    if level == LEVELS.len() {
+      return self.unary();
+    }
+    let mut lhs: Node = self.binary(level + 1)?;
+    'outer: loop {
+      for &(p, op) in LEVELS[level] {
+        if self.eat(p) {
+          let rhs: Node = self.binary(level + 1)?;
+          lhs = Node::Binary(op, Box::new(lhs), Box::new(rhs));
+          continue 'outer;
+        }
+      }
+      return Ok(lhs);
+    }
+  }
+
+  fn unary(&mut self) -> Result<Node, FormatError> {
+    if self.eat("!") {
+      return Ok(Node::Not(Box::new(self.unary()?)));
+    }
+    if self.eat("-") {
+      return Ok(Node::Neg(Box::new(self.unary()?)));
+    }
+    if self.eat("(") {
+      let inner: Node = self.binary(0)?;
+      self.expect(")")?;
+      return Ok(inner);
+    }
+
+    let word: String = match self.peek() {
+      Some(Token::Word(w)) => w.clone(),
+      Some(Token::Punct(p)) => return err(format!("unexpected `{}`", p)),
+      None => return err("unexpected end of expression"),
+    };
+    self.pos += 1;
+    if word.eq_ignore_ascii_case("MEM") {
+      self.expect("[")?;
+      let addr: Node = self.binary(0)?;
+      self.expect("]")?;
+      return Ok(Node::Mem(Box::new(addr)));
+    }
+    if let Some(reg) = register(&word) {
+      return Ok(reg);
+    }
+    // labels win over numbers they spell, as for Machine::resolve()
+    if let Some(addr) = self.symbols.lookup(&word) {
+      return Ok(Node::Num(addr));
+    }
+    match number(&word) {
+      Some(n) => Ok(Node::Num(n)),
+      None => err(format!("unknown name `{}`", word)),
+    }
+  }
+}
+
+fn eval(node: &Node, m: &Machine) -> u16 {
+  match node {
+    Node::Num(n) => *n,
+    Node::Reg(r) => m.read_reg(*r),
+    Node::Psr => m.psr(),
+    Node::Mem(addr) => m.read_mem(eval(addr, m)),
+    Node::Not(v) => (eval(v, m) == 0) as u16,
+    Node::Neg(v) => eval(v, m).wrapping_neg(),
+    // short-circuits, MEM[] has no side effects but there's no need
+    Node::Binary(Op::Or, a, b) => (eval(a, m) != 0 || eval(b, m) != 0) as u16,
+    Node::Binary(Op::And, a, b) => (eval(a, m) != 0 && eval(b, m) != 0) as u16,
+    Node::Binary(op, a, b) => {
+      let (a, b): (u16, u16) = (eval(a, m), eval(b, m));
+      match op {
+        Op::Eq => (a == b) as u16,
+        Op::Ne => (a != b) as u16,
+        Op::Lt => ((a as i16) < (b as i16)) as u16,
+        Op::Le => ((a as i16) <= (b as i16)) as u16,
+        Op::Gt => ((a as i16) > (b as i16)) as u16,
+        Op::Ge => ((a as i16) >= (b as i16)) as u16,
+        Op::BitOr => a | b,
+        Op::BitAnd => a & b,
+        Op::Add => a.wrapping_add(b),
+        Op::Sub => a.wrapping_sub(b),
+        Op::Or | Op::And => unreachable!(),
+      }
+    },
+  }
+}
+
+impl Expr {
+  // labels are looked up in symbols, e.g. m.symbols()
+  pub fn parse(src: &str, symbols: &SymbolTable) -> Result<Expr, FormatError> {
+    let mut p: Parser = Parser { tokens: tokenize(src)?, pos: 0, symbols };
+    let node: Node = p.binary(0)?;
+    match p.peek() {
+      None => Ok(Expr(node)),
+      Some(Token::Word(w)) => err(format!("unexpected `{}`", w)),
+      Some(Token::Punct(t)) => err(format!("unexpected `{}`", t)),
+    }
+  }
+
+  pub fn eval(&self, m: &Machine) -> u16 {
+    eval(&self.0, m)
+  }
+
+  pub fn holds(&self, m: &Machine) -> bool {
+    self.eval(m) != 0
+  }
+}
@@ -0,0 +1,90 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use machine::Machine;
+
+// the video memory of the 128x124 LC-3 variants: one word per pixel, row
+// by row from xC000 up to xFDFF, right below the I/O page
+pub const VIDEO_BASE   : u16 = 0xC000;
+pub const VIDEO_WIDTH  : u16 = 128;
+pub const VIDEO_HEIGHT : u16 = 124;
+
+// a region of memory read as a display. It's only a view, programs draw by
+// storing to it like any other memory; a pixel is lit when its word is
+// nonzero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Framebuffer {
+  pub base: u16,
+  pub width: u16,
+  pub height: u16,
+}
+
+impl Default for Framebuffer {
+  fn default() -> Framebuffer {
+    Framebuffer { base: VIDEO_BASE, width: VIDEO_WIDTH, height: VIDEO_HEIGHT }
+  }
+}
+
+impl Framebuffer {
+  pub fn new(base: u16, width: u16, height: u16) -> Framebuffer {
+    Framebuffer { base, width, height }
+  }
+
+  pub fn addr(&self, x: u16, y: u16) -> u16 {
+    self.base.wrapping_add(y.wrapping_mul(self.width)).wrapping_add(x)
+  }
+
+  // the current contents of the region
+  pub fn frame(&self, m: &Machine) -> Frame {
+    let mut pixels: Vec<u16> = Vec::with_capacity(self.width as usize * self.height as usize);
+    for y in 0..self.height {
+      for x in 0..self.width {
+        pixels.push(m.read_mem(self.addr(x, y)));
+      }
+    }
+    Frame { width: self.width, height: self.height, pixels }
+  }
+}
+
+// a copy of a framebuffer's pixels, row by row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+  pub width: u16,
+  pub height: u16,
+  pub pixels: Vec<u16>,
+}
+
+impl Frame {
+  pub fn get(&self, x: u16, y: u16) -> u16 {
+    self.pixels[y as usize * self.width as usize + x as usize]
+  }
+
+  pub fn lit(&self, x: u16, y: u16) -> bool {
+    self.get(x, y) != 0
+  }
+
+  // plain PBM, viewable with most image tools
+  pub fn to_pbm(&self) -> String {
+    let mut out: String = format!("P1\n{} {}\n", self.width, self.height);
+    for y in 0..self.height {
+      let row: Vec<&str> = (0..self.width).map(|x| if self.lit(x, y) { "1" } else { "0" }).collect();
+      out.push_str(&row.join(" "));
+      out.push('\n');
+    }
+    out
+  }
+}
+
+// one character per pixel, `#` lit and `.` dark
+impl fmt::Display for Frame {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    for y in 0..self.height {
+      for x in 0..self.width {
+        f.write_str(if self.lit(x, y) { "#" } else { "." })?;
+      }
+      writeln!(f)?;
+    }
+    Ok(())
+  }
+}
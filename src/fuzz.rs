@@ -0,0 +1,72 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+
+use assembler;
+use console::NullConsole;
+use disasm;
+use instr::{decode, encode};
+use machine::*;
+use symbols::SymbolTable;
+
+// entry points for the cargo-fuzz targets in fuzz/, usable from any other
+// fuzzer too. Each takes arbitrary bytes and panics when an invariant
+// breaks.
+
+// instructions executed per fuzz_step() input
+const FUZZ_STEPS: usize = 256;
+
+fn words(data: &[u8]) -> impl Iterator<Item = u16> + '_ {
+  data.chunks(2).map(|w| u16::from_be_bytes([w[0], *w.get(1).unwrap_or(&0)]))
+}
+
+// every word decodes to an instruction that encodes back to a word with
+// the same meaning. Disassembly doesn't panic.
+pub fn fuzz_decode(data: &[u8]) {
+  let symbols: SymbolTable = SymbolTable::new();
+  for (i, w) in words(data).enumerate() {
+    let op = decode(w);
+    assert_eq!(decode(encode(op)), op, "word {:#06x}", w);
+    let _ = disasm::disassemble(w, i as u16, &symbols);
+  }
+}
+
+// the assembler rejects or accepts any source without panicking, and what
+// it accepts fits in memory
+pub fn fuzz_assemble(data: &[u8]) {
+  let src: String = String::from_utf8_lossy(data).into_owned();
+  if let Ok(prog) = assembler::assemble(&src) {
+    assert!(prog.origin as usize + prog.words.len() <= MEM_SIZE);
+  }
+}
+
+// runs a machine built from the input: R0-R7, PC and PSR as the first ten
+// big-endian words, the rest loaded at the PC. After every instruction,
+// faulting or not, the condition codes hold exactly one of N, Z and P and
+// the PSR has no bits outside the privilege, priority and CC fields.
+pub fn fuzz_step(data: &[u8]) {
+  let mut w = words(data);
+  let mut m = Machine::builder().io(Box::new(NullConsole)).build();
+
+  for r in [Reg::R0, Reg::R1, Reg::R2, Reg::R3, Reg::R4, Reg::R5, Reg::R6, Reg::R7].iter() {
+    m.write_reg(*r, w.next().unwrap_or(0));
+  }
+  let pc: u16 = w.next().unwrap_or(0x3000);
+  let psr: u16 = w.next().unwrap_or(0x8002);
+  // a well-formed starting PSR, the CC picked by the low bits
+  m.set_psr((psr & (PSR_USER | PSR_PRIO)) | [NEG, ZRO, POS][(psr % 3) as usize]);
+  m.write_reg(Reg::PC, pc);
+  for (i, word) in w.enumerate() {
+    m.write_mem(pc.wrapping_add(i as u16), word);
+  }
+
+  for _ in 0..FUZZ_STEPS {
+    let result = m.step();
+    let cc: u16 = m.read_reg(Reg::COND);
+    assert!(cc == NEG || cc == ZRO || cc == POS, "CC {:#05b} after {:?}", cc, result);
+    assert_eq!(m.psr() & !(PSR_USER | PSR_PRIO | NEG | ZRO | POS), 0, "PSR {:#06x}", m.psr());
+    match result {
+      Ok(None) => {},
+      _ => break,
+    }
+  }
+}
@@ -0,0 +1,327 @@
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt;
+use core::time::Duration;
+
+use assembler::Program;
+use console::Console;
+use coverage::Coverage;
+use json::Json;
+use machine::*;
+
+mod output;
+mod regex;
+mod spec;
+
+pub use self::output::{diff, fits, normalize_whitespace, ExpectedOutput};
+pub use self::regex::Regex;
+pub use self::spec::parse_spec;
+
+// grading harness: a TestCase sets up registers, memory and keyboard input,
+// runs a program with a step limit and checks the final state, giving a
+// Report.
+//
+//   let report = TestCase::new("adds")
+//     .reg(Reg::R1, 2).reg(Reg::R2, 3)
+//     .expect_reg(Reg::R0, 5)
+//     .run(&program);
+
+// instructions a case may run unless it sets max_steps()
+const DEFAULT_STEPS: u64 = 1_000_000;
+
+// scripted keyboard, captured display
+struct ScriptConsole {
+  input: VecDeque<u8>,
+  output: Rc<RefCell<Vec<u8>>>,
+}
+
+impl Console for ScriptConsole {
+  fn read_char(&mut self) -> Option<u8> {
+    self.input.pop_front()
+  }
+
+  fn write_char(&mut self, c: u8) {
+    self.output.borrow_mut().push(c);
+  }
+
+  fn poll_key(&mut self) -> Option<u8> {
+    self.input.pop_front()
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestCase {
+  pub name: String,
+  pub regs: Vec<(Reg, u16)>,
+  pub mem: Vec<(u16, u16)>,
+  pub input: Vec<u8>,
+  pub max_steps: u64,
+  pub time_limit: Option<Duration>,
+  pub detect_loops: bool,
+  pub os: bool,
+  pub expect_regs: Vec<(Reg, u16)>,
+  pub expect_mem: Vec<(u16, u16)>,
+  pub expect_output: Option<ExpectedOutput>,
+  // compare output with normalize_whitespace() applied
+  pub ignore_whitespace: bool,
+  pub expect_halt: bool,
+}
+
+// a postcondition that didn't hold
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Failure {
+  Register { reg: Reg, expected: u16, found: u16 },
+  Memory { addr: u16, expected: u16, found: u16 },
+  Output { expected: String, found: String },
+  OutputRegex { pattern: String, found: String },
+  OutputTemplate { template: String, found: String },
+  InvalidRegex { pattern: String, error: String },
+  // the program stopped for another reason than HALT, or not at all
+  NoHalt(StopReason),
+  Load(String),
+}
+
+impl fmt::Display for Failure {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Failure::Register { reg, expected, found } =>
+        write!(f, "{:?} is x{:04X}, expected x{:04X}", reg, found, expected),
+      Failure::Memory { addr, expected, found } =>
+        write!(f, "x{:04X} is x{:04X}, expected x{:04X}", addr, found, expected),
+      Failure::Output { expected, found } => write!(f, "output {:?}, expected {:?}", found, expected),
+      Failure::OutputRegex { pattern, found } => write!(f, "output {:?} doesn't match /{}/", found, pattern),
+      Failure::OutputTemplate { template, found } => write!(f, "output {:?} doesn't fit {:?}", found, template),
+      Failure::InvalidRegex { pattern, error } => write!(f, "invalid regex /{}/: {}", pattern, error),
+      Failure::NoHalt(StopReason::StepLimit) => write!(f, "did not halt within the step limit"),
+      Failure::NoHalt(StopReason::LimitExceeded) => write!(f, "did not halt within the time limit"),
+      Failure::NoHalt(StopReason::NoProgress) => write!(f, "stuck in a loop that makes no progress"),
+      Failure::NoHalt(StopReason::Fault(e)) => write!(f, "{}", e),
+      Failure::NoHalt(reason) => write!(f, "stopped: {:?}", reason),
+      Failure::Load(e) => write!(f, "failed to load the program: {}", e),
+    }
+  }
+}
+
+impl Failure {
+  // for output that isn't what was expected, a line diff of the two
+  pub fn diff(&self) -> Option<String> {
+    match self {
+      Failure::Output { expected, found } => Some(diff(expected, found, |e, f| e == f)),
+      Failure::OutputTemplate { template, found } => Some(diff(template, found, fits)),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+  pub name: String,
+  pub steps: u64,
+  pub output: String,
+  pub failures: Vec<Failure>,
+  // what the program executed, read and written
+  pub coverage: Coverage,
+}
+
+impl Report {
+  pub fn passed(&self) -> bool {
+    self.failures.is_empty()
+  }
+
+  pub fn to_json(&self) -> Json {
+    Json::object(vec![
+      ("name", Json::from(self.name.as_str())),
+      ("passed", Json::from(self.passed())),
+      ("steps", Json::from(self.steps)),
+      ("output", Json::from(self.output.as_str())),
+      ("failures", Json::from(self.failures.iter().map(|f| Json::from(f.to_string())).collect::<Vec<Json>>())),
+      ("diff", self.failures.iter().find_map(Failure::diff).map_or(Json::Null, Json::from)),
+    ])
+  }
+}
+
+// "PASS name" or "FAIL name" followed by one indented line per failure,
+// and under output failures the diff, indented further
+impl fmt::Display for Report {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(f, "{} {}", if self.passed() { "PASS" } else { "FAIL" }, self.name)?;
+    for failure in &self.failures {
+      writeln!(f, "  {}", failure)?;
+      for line in failure.diff().iter().flat_map(|d| d.lines()) {
+        writeln!(f, "    {}", line)?;
+      }
+    }
+    Ok(())
+  }
+}
+
+impl TestCase {
+  pub fn new(name: &str) -> TestCase {
+    TestCase {
+      name: name.to_string(),
+      regs: Vec::new(),
+      mem: Vec::new(),
+      input: Vec::new(),
+      max_steps: DEFAULT_STEPS,
+      time_limit: None,
+      detect_loops: false,
+      os: false,
+      expect_regs: Vec::new(),
+      expect_mem: Vec::new(),
+      expect_output: None,
+      ignore_whitespace: false,
+      expect_halt: true,
+    }
+  }
+
+  pub fn reg(mut self, reg: Reg, val: u16) -> TestCase {
+    self.regs.push((reg, val));
+    self
+  }
+
+  pub fn mem(mut self, addr: u16, val: u16) -> TestCase {
+    self.mem.push((addr, val));
+    self
+  }
+
+  // keyboard input, read by GETC and IN in order
+  pub fn input(mut self, keys: &str) -> TestCase {
+    self.input.extend_from_slice(keys.as_bytes());
+    self
+  }
+
+  pub fn max_steps(mut self, n: u64) -> TestCase {
+    self.max_steps = n;
+    self
+  }
+
+  // wall time the case may run for, see Machine::set_limits()
+  pub fn time_limit(mut self, limit: Duration) -> TestCase {
+    self.time_limit = Some(limit);
+    self
+  }
+
+  // fails the case as soon as the program is stuck in a loop, with
+  // Failure::NoHalt(StopReason::NoProgress), instead of at the step or time
+  // limit; see Machine::set_progress_check()
+  pub fn detect_loops(mut self, enable: bool) -> TestCase {
+    self.detect_loops = enable;
+    self
+  }
+
+  // runs TRAPs through the LC-3 OS instead of the built-in routines
+  pub fn os(mut self, enable: bool) -> TestCase {
+    self.os = enable;
+    self
+  }
+
+  pub fn expect_reg(mut self, reg: Reg, val: u16) -> TestCase {
+    self.expect_regs.push((reg, val));
+    self
+  }
+
+  pub fn expect_mem(mut self, addr: u16, val: u16) -> TestCase {
+    self.expect_mem.push((addr, val));
+    self
+  }
+
+  // the whole display output, including what the HALT routine prints
+  pub fn expect_output(mut self, output: &str) -> TestCase {
+    self.expect_output = Some(ExpectedOutput::Exact(output.to_string()));
+    self
+  }
+
+  // output the whole of which matches pattern, see Regex
+  pub fn expect_output_regex(mut self, pattern: &str) -> TestCase {
+    self.expect_output = Some(ExpectedOutput::Regex(pattern.to_string()));
+    self
+  }
+
+  // output that fits template, see ExpectedOutput::Template
+  pub fn expect_output_template(mut self, template: &str) -> TestCase {
+    self.expect_output = Some(ExpectedOutput::Template(template.to_string()));
+    self
+  }
+
+  // compares the output as normalize_whitespace() leaves it, the expected
+  // text or template likewise, so spacing and blank lines don't matter
+  pub fn ignore_whitespace(mut self, enable: bool) -> TestCase {
+    self.ignore_whitespace = enable;
+    self
+  }
+
+  // whether the program has to reach HALT, the default, or may run out of
+  // steps
+  pub fn expect_halt(mut self, halt: bool) -> TestCase {
+    self.expect_halt = halt;
+    self
+  }
+
+  pub fn run(&self, program: &Program) -> Report {
+    self.run_obj(&program.to_obj())
+  }
+
+  // runs an .obj image, starting at its origin
+  pub fn run_obj(&self, obj: &[u8]) -> Report {
+    let output: Rc<RefCell<Vec<u8>>> = Rc::new(RefCell::new(Vec::new()));
+    let io = ScriptConsole { input: self.input.iter().cloned().collect(), output: output.clone() };
+    let mut m = Machine::builder().io(Box::new(io)).os(self.os).build();
+    let mut report = Report { name: self.name.clone(), steps: 0, output: String::new(), failures: Vec::new(), coverage: Coverage::new() };
+
+    if let Err(e) = m.load_image_bytes(obj) {
+      report.failures.push(Failure::Load(e.to_string()));
+      return report;
+    }
+    if obj.len() >= 2 {
+      m.write_reg(Reg::PC, u16::from_be_bytes([obj[0], obj[1]]));
+    }
+    for &(reg, val) in &self.regs {
+      m.write_reg(reg, val);
+    }
+    for &(addr, val) in &self.mem {
+      m.write_mem(addr, val);
+    }
+
+    m.start_coverage();
+    m.set_limits(None, self.time_limit);
+    m.set_progress_check(self.detect_loops && self.expect_halt);
+    let run: Run = m.run_for(self.max_steps);
+    report.steps = run.steps;
+    report.coverage = m.stop_coverage().unwrap_or_default();
+    report.output = String::from_utf8_lossy(&output.borrow()).into_owned();
+
+    if self.expect_halt && run.reason != StopReason::Halt {
+      report.failures.push(Failure::NoHalt(run.reason));
+    }
+    for &(reg, expected) in &self.expect_regs {
+      let found: u16 = m.read_reg(reg);
+      if found != expected {
+        report.failures.push(Failure::Register { reg, expected, found });
+      }
+    }
+    for &(addr, expected) in &self.expect_mem {
+      let found: u16 = m.read_mem(addr);
+      if found != expected {
+        report.failures.push(Failure::Memory { addr, expected, found });
+      }
+    }
+    if let Some(expected) = &self.expect_output {
+      let (expected, found): (ExpectedOutput, String) = match self.ignore_whitespace {
+        true => (expected.map(normalize_whitespace), normalize_whitespace(&report.output)),
+        false => (expected.clone(), report.output.clone()),
+      };
+      match (expected.matches(&found), expected) {
+        (Ok(true), _) => {},
+        (Ok(false), ExpectedOutput::Exact(expected)) => report.failures.push(Failure::Output { expected, found }),
+        (Ok(false), ExpectedOutput::Regex(pattern)) => report.failures.push(Failure::OutputRegex { pattern, found }),
+        (Ok(false), ExpectedOutput::Template(template)) => report.failures.push(Failure::OutputTemplate { template, found }),
+        (Err(error), expected) => report.failures.push(Failure::InvalidRegex { pattern: expected.text().to_string(), error }),
+      }
+    }
+    report
+  }
+}
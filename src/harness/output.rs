@@ -0,0 +1,151 @@
+use super::*;
+
+// what a case's output must be
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExpectedOutput {
+  Exact(String),
+  // a Regex the whole output matches
+  Regex(String),
+  // text a line at a time, with placeholders for numbers: `{}` any
+  // number, `{42}` 42 and `{42+-0.5}` a number within 0.5 of 42. Literal
+  // braces are `{{` and `}}`.
+  Template(String),
+}
+
+// the lines of s trimmed, with runs of spaces and tabs as one and blank
+// lines left out
+pub fn normalize_whitespace(s: &str) -> String {
+  let lines: Vec<String> = s.lines()
+    .map(|l| l.split_whitespace().collect::<Vec<&str>>().join(" "))
+    .filter(|l| !l.is_empty())
+    .collect();
+  lines.join("\n")
+}
+
+// the number at the start of s, optionally signed and with a fraction,
+// and its length
+fn number(s: &str) -> Option<(f64, usize)> {
+  let b: &[u8] = s.as_bytes();
+  let mut end: usize = usize::from(b.first() == Some(&b'-'));
+  let digits = |from: usize| from + b[from..].iter().take_while(|c| c.is_ascii_digit()).count();
+  let int: usize = digits(end);
+  if int == end {
+    return None;
+  }
+  end = int;
+  if b.get(end) == Some(&b'.') && b.get(end + 1).is_some_and(u8::is_ascii_digit) {
+    end = digits(end + 1);
+  }
+  s[..end].parse().ok().map(|n| (n, end))
+}
+
+// whether line fits the template line
+pub fn fits(template: &str, line: &str) -> bool {
+  let mut t: &str = template;
+  let mut l: &str = line;
+  loop {
+    if let Some(rest) = t.strip_prefix("{{") {
+      match l.strip_prefix('{') {
+        Some(more) => (t, l) = (rest, more),
+        None => return false,
+      }
+    } else if let Some(rest) = t.strip_prefix("}}") {
+      match l.strip_prefix('}') {
+        Some(more) => (t, l) = (rest, more),
+        None => return false,
+      }
+    } else if let Some(rest) = t.strip_prefix('{') {
+      let close: usize = match rest.find('}') {
+        Some(i) => i,
+        None => return t == l,
+      };
+      let (n, len): (f64, usize) = match number(l) {
+        Some(found) => found,
+        None => return false,
+      };
+      let spec: &str = rest[..close].trim();
+      let ok: bool = if spec.is_empty() {
+        true
+      } else {
+        let (want, tolerance): (&str, &str) = spec.split_once("+-").or_else(|| spec.split_once('±')).unwrap_or((spec, "0"));
+        match (want.trim().parse::<f64>(), tolerance.trim().parse::<f64>()) {
+          (Ok(want), Ok(tolerance)) => (n - want).abs() <= tolerance,
+          _ => false,
+        }
+      };
+      if !ok {
+        return false;
+      }
+      (t, l) = (&rest[close + 1..], &l[len..]);
+    } else {
+      let mut chars = t.chars();
+      match chars.next() {
+        None => return l.is_empty(),
+        Some(c) => match l.strip_prefix(c) {
+          Some(more) => (t, l) = (chars.as_str(), more),
+          None => return false,
+        },
+      }
+    }
+  }
+}
+
+impl ExpectedOutput {
+  pub fn matches(&self, output: &str) -> Result<bool, String> {
+    Ok(match self {
+      ExpectedOutput::Exact(text) => text == output,
+      ExpectedOutput::Regex(pattern) => Regex::parse(pattern)?.matches(output),
+      ExpectedOutput::Template(template) => {
+        let (t, o): (Vec<&str>, Vec<&str>) = (template.split('\n').collect(), output.split('\n').collect());
+        t.len() == o.len() && t.iter().zip(&o).all(|(t, o)| fits(t, o))
+      },
+    })
+  }
+
+  pub fn text(&self) -> &str {
+    match self {
+      ExpectedOutput::Exact(s) | ExpectedOutput::Regex(s) | ExpectedOutput::Template(s) => s,
+    }
+  }
+
+  // the same with its text through f
+  pub fn map<F: FnOnce(&str) -> String>(&self, f: F) -> ExpectedOutput {
+    match self {
+      ExpectedOutput::Exact(s) => ExpectedOutput::Exact(f(s)),
+      ExpectedOutput::Regex(s) => ExpectedOutput::Regex(s.clone()),
+      ExpectedOutput::Template(s) => ExpectedOutput::Template(f(s)),
+    }
+  }
+}
+
+// the lines of expected and found, each marked `  ` when same
+// in both (alike says when a line of expected stands for one found), `- `
+// when only expected or `+ ` when only found
+pub fn diff<F: Fn(&str, &str) -> bool>(expected: &str, found: &str, alike: F) -> String {
+  let e: Vec<&str> = expected.split('\n').collect();
+  let f: Vec<&str> = found.split('\n').collect();
+  // lcs[i][j], the longest common run of e[i..] and f[j..]
+  let mut lcs: Vec<Vec<usize>> = vec![vec![0; f.len() + 1]; e.len() + 1];
+  for i in (0..e.len()).rev() {
+    for j in (0..f.len()).rev() {
+      lcs[i][j] = if alike(e[i], f[j]) { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+    }
+  }
+
+  let mut out: String = String::new();
+  let (mut i, mut j): (usize, usize) = (0, 0);
+  while i < e.len() || j < f.len() {
+    if i < e.len() && j < f.len() && alike(e[i], f[j]) {
+      out.push_str(&format!("  {}\n", f[j]));
+      i += 1;
+      j += 1;
+    } else if i < e.len() && (j == f.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+      out.push_str(&format!("- {}\n", e[i]));
+      i += 1;
+    } else {
+      out.push_str(&format!("+ {}\n", f[j]));
+      j += 1;
+    }
+  }
+  out
+}
@@ -0,0 +1,318 @@
+use super::*;
+
+use core::iter::Peekable;
+use core::str::Chars;
+
+// the regular expressions of expect_output_regex(), compiled to an NFA
+// run over the text a char at a time, so matching takes time linear in
+// its length: literals, `.` for any char but a newline, classes like
+// `[a-z_]` and `[^0-9]`, `\d`, `\w`, `\s` and their negations `\D`, `\W`,
+// `\S`, groups with `|`, the repeats `*`, `+`, `?`, `{n}`, `{n,}` and
+// `{n,m}`, and `^` and `$` at the start and end of a line. `\n`, `\t` and
+// `\r` are the control characters, other escaped chars stand for
+// themselves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Regex {
+  prog: Vec<Inst>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+  Char(char),
+  Any,
+  // inclusive ranges, and whether it matches chars outside them instead
+  Class(Vec<(char, char)>, bool),
+  LineStart,
+  LineEnd,
+  Group(Vec<Vec<Node>>),
+  Repeat(Box<Node>, u32, Option<u32>),
+}
+
+// an NFA state, the next one at the following index unless it says
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Inst {
+  Char(char),
+  Any,
+  Class(Vec<(char, char)>, bool),
+  LineStart,
+  LineEnd,
+  Split(usize, usize),
+  Jump(usize),
+  Match,
+}
+
+// most states a pattern compiles to, repeats with large counts copying
+// what they repeat
+const MAX_STATES: usize = 100_000;
+
+const DIGIT: &[(char, char)] = &[('0', '9')];
+const WORD: &[(char, char)] = &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+const SPACE: &[(char, char)] = &[('\t', '\r'), (' ', ' ')];
+
+struct Parser<'a> {
+  chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> Parser<'a> {
+  fn alts(&mut self) -> Result<Vec<Vec<Node>>, String> {
+    let mut alts: Vec<Vec<Node>> = vec![Vec::new()];
+    while let Some(&c) = self.chars.peek() {
+      match c {
+        ')' => break,
+        '|' => {
+          self.chars.next();
+          alts.push(Vec::new());
+        },
+        _ => {
+          let node: Node = self.atom()?;
+          let node: Node = self.repeat(node)?;
+          alts.last_mut().unwrap().push(node);
+        },
+      }
+    }
+    Ok(alts)
+  }
+
+  fn atom(&mut self) -> Result<Node, String> {
+    Ok(match self.chars.next().unwrap() {
+      '.' => Node::Any,
+      '^' => Node::LineStart,
+      '$' => Node::LineEnd,
+      '(' => {
+        let alts: Vec<Vec<Node>> = self.alts()?;
+        if self.chars.next() != Some(')') {
+          return Err("unclosed `(`".to_string());
+        }
+        Node::Group(alts)
+      },
+      '[' => self.class()?,
+      '\\' => self.escape()?,
+      c @ ('*' | '+' | '?' | '{') => return Err(format!("nothing to repeat before `{}`", c)),
+      c => Node::Char(c),
+    })
+  }
+
+  fn escape(&mut self) -> Result<Node, String> {
+    Ok(match self.chars.next() {
+      Some('d') => Node::Class(DIGIT.to_vec(), false),
+      Some('D') => Node::Class(DIGIT.to_vec(), true),
+      Some('w') => Node::Class(WORD.to_vec(), false),
+      Some('W') => Node::Class(WORD.to_vec(), true),
+      Some('s') => Node::Class(SPACE.to_vec(), false),
+      Some('S') => Node::Class(SPACE.to_vec(), true),
+      Some('n') => Node::Char('\n'),
+      Some('t') => Node::Char('\t'),
+      Some('r') => Node::Char('\r'),
+      Some(c) => Node::Char(c),
+      None => return Err("trailing `\\`".to_string()),
+    })
+  }
+
+  fn class(&mut self) -> Result<Node, String> {
+    let negated: bool = self.chars.next_if_eq(&'^').is_some();
+    let mut ranges: Vec<(char, char)> = Vec::new();
+    let mut first: bool = true;
+    loop {
+      let c: char = match self.chars.next() {
+        Some(']') if !first => return Ok(Node::Class(ranges, negated)),
+        Some('\\') => match self.escape()? {
+          Node::Char(c) => c,
+          Node::Class(more, false) => {
+            ranges.extend(more);
+            first = false;
+            continue;
+          },
+          _ => return Err("negated classes can't go in `[]`".to_string()),
+        },
+        Some(c) => c,
+        None => return Err("unclosed `[`".to_string()),
+      };
+      first = false;
+      let mut ahead = self.chars.clone();
+      match (ahead.next(), ahead.next()) {
+        (Some('-'), Some(end)) if end != ']' => {
+          self.chars.next();
+          self.chars.next();
+          if end < c {
+            return Err(format!("invalid range `{}-{}`", c, end));
+          }
+          ranges.push((c, end));
+        },
+        _ => ranges.push((c, c)),
+      }
+    }
+  }
+
+  fn number(&mut self) -> Option<u32> {
+    let mut digits: String = String::new();
+    while let Some(c) = self.chars.next_if(char::is_ascii_digit) {
+      digits.push(c);
+    }
+    digits.parse().ok()
+  }
+
+  fn repeat(&mut self, node: Node) -> Result<Node, String> {
+    let (min, max): (u32, Option<u32>) = match self.chars.peek() {
+      Some('*') => (0, None),
+      Some('+') => (1, None),
+      Some('?') => (0, Some(1)),
+      Some('{') => {
+        self.chars.next();
+        let min: u32 = self.number().ok_or("expected a count after `{`")?;
+        let max: Option<u32> = match self.chars.next() {
+          Some('}') => return self.repeat(Node::Repeat(Box::new(node), min, Some(min))),
+          Some(',') if self.chars.peek() == Some(&'}') => None,
+          Some(',') => Some(self.number().ok_or("expected a count after `,`")?),
+          _ => return Err("expected `,` or `}`".to_string()),
+        };
+        if self.chars.next() != Some('}') {
+          return Err("unclosed `{`".to_string());
+        }
+        if max.is_some_and(|m| m < min) {
+          return Err(format!("invalid repeat `{{{},{}}}`", min, max.unwrap()));
+        }
+        return self.repeat(Node::Repeat(Box::new(node), min, max));
+      },
+      _ => return Ok(node),
+    };
+    self.chars.next();
+    self.repeat(Node::Repeat(Box::new(node), min, max))
+  }
+}
+
+struct Compiler {
+  prog: Vec<Inst>,
+}
+
+impl Compiler {
+  fn push(&mut self, inst: Inst) -> Result<usize, String> {
+    if self.prog.len() == MAX_STATES {
+      return Err("pattern too large".to_string());
+    }
+    self.prog.push(inst);
+    Ok(self.prog.len() - 1)
+  }
+
+  fn alts(&mut self, alts: &[Vec<Node>]) -> Result<(), String> {
+    let mut jumps: Vec<usize> = Vec::new();
+    for (i, seq) in alts.iter().enumerate() {
+      let split: Option<usize> = if i + 1 < alts.len() { Some(self.push(Inst::Split(0, 0))?) } else { None };
+      for node in seq {
+        self.node(node)?;
+      }
+      if let Some(split) = split {
+        jumps.push(self.push(Inst::Jump(0))?);
+        self.prog[split] = Inst::Split(split + 1, self.prog.len());
+      }
+    }
+    let end: usize = self.prog.len();
+    for j in jumps {
+      self.prog[j] = Inst::Jump(end);
+    }
+    Ok(())
+  }
+
+  fn node(&mut self, node: &Node) -> Result<(), String> {
+    match node {
+      Node::Char(c) => self.push(Inst::Char(*c)).map(drop),
+      Node::Any => self.push(Inst::Any).map(drop),
+      Node::Class(ranges, negated) => self.push(Inst::Class(ranges.clone(), *negated)).map(drop),
+      Node::LineStart => self.push(Inst::LineStart).map(drop),
+      Node::LineEnd => self.push(Inst::LineEnd).map(drop),
+      Node::Group(alts) => self.alts(alts),
+      Node::Repeat(node, min, max) => {
+        for _ in 0..*min {
+          self.node(node)?;
+        }
+        match *max {
+          None => {
+            let split: usize = self.push(Inst::Split(0, 0))?;
+            self.node(node)?;
+            self.push(Inst::Jump(split))?;
+            self.prog[split] = Inst::Split(split + 1, self.prog.len());
+          },
+          Some(max) => {
+            let mut splits: Vec<usize> = Vec::new();
+            for _ in *min..max {
+              splits.push(self.push(Inst::Split(0, 0))?);
+              self.node(node)?;
+            }
+            let end: usize = self.prog.len();
+            for split in splits {
+              self.prog[split] = Inst::Split(split + 1, end);
+            }
+          },
+        }
+        Ok(())
+      },
+    }
+  }
+}
+
+impl Regex {
+  pub fn parse(pattern: &str) -> Result<Regex, String> {
+    let mut p: Parser = Parser { chars: pattern.chars().peekable() };
+    let alts: Vec<Vec<Node>> = p.alts()?;
+    if p.chars.next().is_some() {
+      return Err("unmatched `)`".to_string());
+    }
+    let mut c: Compiler = Compiler { prog: Vec::new() };
+    c.alts(&alts)?;
+    c.push(Inst::Match)?;
+    Ok(Regex { prog: c.prog })
+  }
+
+  // whether the whole of text matches, following every path through the
+  // NFA at once
+  pub fn matches(&self, text: &str) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    // the position + 1 each state was last added at, so it's added once
+    let mut added: Vec<usize> = vec![0; self.prog.len()];
+    let mut current: Vec<usize> = Vec::new();
+    let mut next: Vec<usize> = Vec::new();
+    self.add(&mut current, &mut added, &text, 0, 0);
+    for at in 0..=text.len() {
+      for &pc in &current {
+        let c: Option<char> = text.get(at).copied();
+        let step: bool = match &self.prog[pc] {
+          Inst::Match => return true,
+          Inst::Char(want) => c == Some(*want),
+          Inst::Any => c.is_some_and(|c| c != '\n'),
+          Inst::Class(ranges, negated) => c.is_some_and(|c| ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != *negated),
+          _ => false,
+        };
+        if step {
+          self.add(&mut next, &mut added, &text, pc + 1, at + 1);
+        }
+      }
+      if next.is_empty() {
+        return false;
+      }
+      core::mem::swap(&mut current, &mut next);
+      next.clear();
+    }
+    false
+  }
+
+  // pc and the states reachable from it at position at without reading
+  // a char, those that read one (or Match, only added at the end) going
+  // on list
+  fn add(&self, list: &mut Vec<usize>, added: &mut [usize], text: &[char], pc: usize, at: usize) {
+    let mut stack: Vec<usize> = vec![pc];
+    while let Some(pc) = stack.pop() {
+      if added[pc] == at + 1 {
+        continue;
+      }
+      added[pc] = at + 1;
+      match self.prog[pc] {
+        Inst::Jump(to) => stack.push(to),
+        Inst::Split(first, second) => stack.extend([second, first]),
+        Inst::LineStart if at == 0 || text[at - 1] == '\n' => stack.push(pc + 1),
+        Inst::LineEnd if at == text.len() || text[at] == '\n' => stack.push(pc + 1),
+        Inst::LineStart | Inst::LineEnd => {},
+        Inst::Match if at < text.len() => {},
+        _ => list.push(pc),
+      }
+    }
+  }
+}
@@ -0,0 +1,284 @@
+use super::*;
+
+use core::iter::Peekable;
+use core::str::Chars;
+use core::time::Duration;
+
+use num_traits::FromPrimitive;
+use utils::FormatError;
+
+// test specs for `lc3 grade`, written in a subset of TOML: one [[test]]
+// table per case, keys before the first table are defaults for every
+// case.
+//
+//   max_steps = 10000
+//   time_limit_ms = 2000
+//   detect_loops = true
+//
+//   [[test]]
+//   name = "adds"
+//   regs = { R1 = 2, R2 = 3 }
+//   mem = { x3100 = 0x00FF }
+//   input = "a"
+//   expect_regs = { R0 = 5 }
+//   expect_mem = { x3101 = -1 }
+//   expect_output = "A\nHALT\n"
+//
+//   [[test]]
+//   name = "average"
+//   ignore_whitespace = true
+//   expect_output_template = "mean: {2.5+-0.01}\nHALT"
+//
+// expect_output_regex is the other way to give the output, see Regex.
+// Values are basic or literal strings, integers (decimal, 0x hex, 0b
+// binary), booleans and inline tables of integers. Table keys are register
+// names or addresses in x, 0x or # notation.
+
+#[derive(Debug)]
+enum Value {
+  Str(String),
+  Int(i64),
+  Bool(bool),
+  Table(Vec<(String, i64)>),
+}
+
+struct Line<'a> {
+  chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> Line<'a> {
+  fn ws(&mut self) {
+    while self.chars.next_if(|&c| c == ' ' || c == '\t').is_some() {}
+  }
+
+  // nothing but whitespace and a comment left
+  fn end(&mut self) -> Result<(), String> {
+    self.ws();
+    match self.chars.peek() {
+      None | Some('#') => Ok(()),
+      Some(c) => Err(format!("unexpected `{}`", c)),
+    }
+  }
+
+  fn key(&mut self) -> Result<String, String> {
+    self.ws();
+    match self.chars.peek() {
+      Some('"') => self.basic(),
+      Some('\'') => self.literal(),
+      _ => {
+        let mut key: String = String::new();
+        while let Some(c) = self.chars.next_if(|&c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '#') {
+          key.push(c);
+        }
+        if key.is_empty() {
+          return Err("expected a key".to_string());
+        }
+        Ok(key)
+      },
+    }
+  }
+
+  fn eq(&mut self) -> Result<(), String> {
+    self.ws();
+    match self.chars.next() {
+      Some('=') => Ok(()),
+      _ => Err("expected `=`".to_string()),
+    }
+  }
+
+  fn value(&mut self) -> Result<Value, String> {
+    self.ws();
+    match self.chars.peek() {
+      Some('"') => self.basic().map(Value::Str),
+      Some('\'') => self.literal().map(Value::Str),
+      Some('{') => {
+        self.chars.next();
+        let mut fields: Vec<(String, i64)> = Vec::new();
+        self.ws();
+        if self.chars.next_if_eq(&'}').is_some() {
+          return Ok(Value::Table(fields));
+        }
+        loop {
+          let key: String = self.key()?;
+          self.eq()?;
+          match self.value()? {
+            Value::Int(n) => fields.push((key, n)),
+            _ => return Err(format!("`{}` must be an integer", key)),
+          }
+          self.ws();
+          match self.chars.next() {
+            Some(',') => {},
+            Some('}') => return Ok(Value::Table(fields)),
+            _ => return Err("expected `,` or `}`".to_string()),
+          }
+        }
+      },
+      _ => {
+        let mut word: String = String::new();
+        while let Some(c) = self.chars.next_if(|&c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '+') {
+          word.push(c);
+        }
+        match word.as_str() {
+          "true" => Ok(Value::Bool(true)),
+          "false" => Ok(Value::Bool(false)),
+          _ => int(&word).map(Value::Int).ok_or_else(|| format!("invalid value `{}`", word)),
+        }
+      },
+    }
+  }
+
+  fn basic(&mut self) -> Result<String, String> {
+    self.chars.next();
+    let mut s: String = String::new();
+    loop {
+      match self.chars.next() {
+        Some('"') => return Ok(s),
+        Some('\\') => match self.chars.next() {
+          Some('n') => s.push('\n'),
+          Some('t') => s.push('\t'),
+          Some('r') => s.push('\r'),
+          Some('0') => s.push('\0'),
+          Some('"') => s.push('"'),
+          Some('\\') => s.push('\\'),
+          Some('u') => {
+            let hex: String = (0..4).filter_map(|_| self.chars.next()).collect();
+            match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+              Some(c) => s.push(c),
+              None => return Err(format!("invalid escape `\\u{}`", hex)),
+            }
+          },
+          Some(c) => return Err(format!("invalid escape `\\{}`", c)),
+          None => return Err("unterminated string".to_string()),
+        },
+        Some(c) => s.push(c),
+        None => return Err("unterminated string".to_string()),
+      }
+    }
+  }
+
+  fn literal(&mut self) -> Result<String, String> {
+    self.chars.next();
+    let mut s: String = String::new();
+    loop {
+      match self.chars.next() {
+        Some('\'') => return Ok(s),
+        Some(c) => s.push(c),
+        None => return Err("unterminated string".to_string()),
+      }
+    }
+  }
+}
+
+fn int(word: &str) -> Option<i64> {
+  let digits: String = word.chars().filter(|&c| c != '_').collect();
+  let (neg, digits): (bool, &str) = match digits.strip_prefix('-') {
+    Some(d) => (true, d),
+    None => (false, digits.strip_prefix('+').unwrap_or(&digits)),
+  };
+  let n: i64 = if let Some(h) = digits.strip_prefix("0x") {
+    i64::from_str_radix(h, 16).ok()?
+  } else if let Some(b) = digits.strip_prefix("0b") {
+    i64::from_str_radix(b, 2).ok()?
+  } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+    digits.parse().ok()?
+  } else {
+    return None;
+  };
+  Some(if neg { -n } else { n })
+}
+
+// a word, as unsigned or two's complement
+fn word(key: &str, n: i64) -> Result<u16, String> {
+  if (-0x8000..=0xFFFF).contains(&n) {
+    Ok(n as u16)
+  } else {
+    Err(format!("`{}` doesn't fit in 16 bits", key))
+  }
+}
+
+fn reg(name: &str) -> Result<Reg, String> {
+  let r: Option<Reg> = match name.to_ascii_uppercase().as_str() {
+    "PC" => Some(Reg::PC),
+    "CC" | "COND" => Some(Reg::COND),
+    n => n.strip_prefix('R').and_then(|i| i.parse::<u16>().ok()).filter(|&i| i < 8).and_then(Reg::from_u16),
+  };
+  r.ok_or_else(|| format!("unknown register `{}`", name))
+}
+
+fn addr(key: &str) -> Result<u16, String> {
+  let a: Option<u16> = if let Some(d) = key.strip_prefix('#') {
+    d.parse().ok()
+  } else {
+    key.strip_prefix("0x").or_else(|| key.strip_prefix('x')).and_then(|h| u16::from_str_radix(h, 16).ok())
+  };
+  a.ok_or_else(|| format!("invalid address `{}`", key))
+}
+
+fn set(case: &mut TestCase, key: &str, val: Value) -> Result<(), String> {
+  let regs = |fields: &[(String, i64)]| -> Result<Vec<(Reg, u16)>, String> {
+    fields.iter().map(|(k, n)| Ok((reg(k)?, word(k, *n)?))).collect()
+  };
+  let mem = |fields: &[(String, i64)]| -> Result<Vec<(u16, u16)>, String> {
+    fields.iter().map(|(k, n)| Ok((addr(k)?, word(k, *n)?))).collect()
+  };
+
+  match (key, val) {
+    ("name", Value::Str(s)) => case.name = s,
+    ("input", Value::Str(s)) => case.input = s.into_bytes(),
+    ("max_steps", Value::Int(n)) if n >= 0 => case.max_steps = n as u64,
+    ("time_limit_ms", Value::Int(n)) if n >= 0 => case.time_limit = Some(Duration::from_millis(n as u64)),
+    ("detect_loops", Value::Bool(b)) => case.detect_loops = b,
+    ("os", Value::Bool(b)) => case.os = b,
+    ("regs", Value::Table(t)) => case.regs.extend(regs(&t)?),
+    ("mem", Value::Table(t)) => case.mem.extend(mem(&t)?),
+    ("expect_regs", Value::Table(t)) => case.expect_regs.extend(regs(&t)?),
+    ("expect_mem", Value::Table(t)) => case.expect_mem.extend(mem(&t)?),
+    ("expect_output", Value::Str(s)) => case.expect_output = Some(ExpectedOutput::Exact(s)),
+    ("expect_output_regex", Value::Str(s)) => {
+      Regex::parse(&s).map_err(|e| format!("invalid regex: {}", e))?;
+      case.expect_output = Some(ExpectedOutput::Regex(s));
+    },
+    ("expect_output_template", Value::Str(s)) => case.expect_output = Some(ExpectedOutput::Template(s)),
+    ("ignore_whitespace", Value::Bool(b)) => case.ignore_whitespace = b,
+    ("expect_halt", Value::Bool(b)) => case.expect_halt = b,
+    ("name", _) | ("input", _) | ("max_steps", _) | ("time_limit_ms", _) | ("detect_loops", _) | ("os", _) | ("regs", _) | ("mem", _) | ("expect_regs", _)
+      | ("expect_mem", _) | ("expect_output", _) | ("expect_output_regex", _) | ("expect_output_template", _) | ("ignore_whitespace", _)
+      | ("expect_halt", _) => return Err(format!("invalid value for `{}`", key)),
+    _ => return Err(format!("unknown key `{}`", key)),
+  }
+  Ok(())
+}
+
+pub fn parse_spec(src: &str) -> Result<Vec<TestCase>, FormatError> {
+  let mut defaults: TestCase = TestCase::new("");
+  let mut cases: Vec<TestCase> = Vec::new();
+
+  for (i, text) in src.lines().enumerate() {
+    let fail = |msg: String| FormatError(format!("line {}: {}", i + 1, msg));
+    let trimmed: &str = text.trim();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+      continue;
+    }
+
+    let mut line = Line { chars: trimmed.chars().peekable() };
+    if let Some(rest) = trimmed.strip_prefix("[[") {
+      line = Line { chars: rest.chars().peekable() };
+      if line.key().map_err(fail)? != "test" || line.chars.next() != Some(']') || line.chars.next() != Some(']') {
+        return Err(fail("expected `[[test]]`".to_string()));
+      }
+      line.end().map_err(fail)?;
+      let mut case: TestCase = defaults.clone();
+      case.name = format!("test {}", cases.len() + 1);
+      cases.push(case);
+      continue;
+    }
+
+    let key: String = line.key().map_err(fail)?;
+    line.eq().map_err(fail)?;
+    let val: Value = line.value().map_err(fail)?;
+    line.end().map_err(fail)?;
+    set(cases.last_mut().unwrap_or(&mut defaults), &key, val).map_err(fail)?;
+  }
+
+  Ok(cases)
+}
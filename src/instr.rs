@@ -0,0 +1,168 @@
+use core::fmt;
+
+use utils::sign_extend;
+
+// a decoded instruction word, shared by the interpreter, the disassembler
+// and the assembler. Register fields hold register numbers, offsets and
+// immediates are sign-extended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+  Br   { n: bool, z: bool, p: bool, offset: i16 },
+  Add  { dr: u16, sr1: u16, sr2: u16 },
+  AddI { dr: u16, sr1: u16, imm: i16 },
+  And  { dr: u16, sr1: u16, sr2: u16 },
+  AndI { dr: u16, sr1: u16, imm: i16 },
+  Jmp  { base: u16 },
+  Jsr  { offset: i16 },
+  Jsrr { base: u16 },
+  Ld   { dr: u16, offset: i16 },
+  Ldi  { dr: u16, offset: i16 },
+  Ldr  { dr: u16, base: u16, offset: i16 },
+  Lea  { dr: u16, offset: i16 },
+  Not  { dr: u16, sr: u16 },
+  Rti,
+  St   { sr: u16, offset: i16 },
+  Sti  { sr: u16, offset: i16 },
+  Str  { sr: u16, base: u16, offset: i16 },
+  Trap { vector: u8 },
+  Res  { word: u16 },
+}
+
+fn sext(instr: u16, bits: usize) -> i16 {
+  sign_extend(instr & ((1 << bits) - 1), bits) as i16
+}
+
+pub fn decode(instr: u16) -> Instruction {
+  let r9: u16 = (instr >> 9) & 0x7;
+  let r6: u16 = (instr >> 6) & 0x7;
+  let imm_mode: bool = (instr >> 5) & 0x1 == 1;
+
+  match instr >> 12 {
+    0x0 => Instruction::Br {
+      n: (instr >> 11) & 0x1 == 1,
+      z: (instr >> 10) & 0x1 == 1,
+      p: (instr >>  9) & 0x1 == 1,
+      offset: sext(instr, 9),
+    },
+    0x1 if imm_mode => Instruction::AddI { dr: r9, sr1: r6, imm: sext(instr, 5) },
+    0x1 => Instruction::Add { dr: r9, sr1: r6, sr2: instr & 0x7 },
+    0x2 => Instruction::Ld { dr: r9, offset: sext(instr, 9) },
+    0x3 => Instruction::St { sr: r9, offset: sext(instr, 9) },
+    0x4 if (instr >> 11) & 0x1 == 1 => Instruction::Jsr { offset: sext(instr, 11) },
+    0x4 => Instruction::Jsrr { base: r6 },
+    0x5 if imm_mode => Instruction::AndI { dr: r9, sr1: r6, imm: sext(instr, 5) },
+    0x5 => Instruction::And { dr: r9, sr1: r6, sr2: instr & 0x7 },
+    0x6 => Instruction::Ldr { dr: r9, base: r6, offset: sext(instr, 6) },
+    0x7 => Instruction::Str { sr: r9, base: r6, offset: sext(instr, 6) },
+    0x8 => Instruction::Rti,
+    0x9 => Instruction::Not { dr: r9, sr: r6 },
+    0xA => Instruction::Ldi { dr: r9, offset: sext(instr, 9) },
+    0xB => Instruction::Sti { sr: r9, offset: sext(instr, 9) },
+    0xC => Instruction::Jmp { base: r6 },
+    0xE => Instruction::Lea { dr: r9, offset: sext(instr, 9) },
+    0xF => Instruction::Trap { vector: (instr & 0xFF) as u8 },
+    _ => Instruction::Res { word: instr },
+  }
+}
+
+// registers as masks, bit r for Rr
+impl Instruction {
+  // the registers the instruction reads. TRAPs read R0 for the routines
+  // that print it.
+  pub fn reads(&self) -> u8 {
+    let r = |r: u16| 1u8 << r;
+    match *self {
+      Instruction::Add { sr1, sr2, .. } | Instruction::And { sr1, sr2, .. } => r(sr1) | r(sr2),
+      Instruction::AddI { sr1, .. } | Instruction::AndI { sr1, .. } => r(sr1),
+      Instruction::Not { sr, .. } => r(sr),
+      Instruction::Jmp { base } | Instruction::Jsrr { base } | Instruction::Ldr { base, .. } => r(base),
+      Instruction::St { sr, .. } | Instruction::Sti { sr, .. } => r(sr),
+      Instruction::Str { sr, base, .. } => r(sr) | r(base),
+      Instruction::Trap { vector: 0x21 } | Instruction::Trap { vector: 0x22 } | Instruction::Trap { vector: 0x24 } => r(0),
+      _ => 0,
+    }
+  }
+
+  // the registers it writes: R7 for calls and TRAPs, which also write R0
+  // for the routines that read a key
+  pub fn writes(&self) -> u8 {
+    let r = |r: u16| 1u8 << r;
+    match *self {
+      Instruction::Add { dr, .. } | Instruction::AddI { dr, .. } | Instruction::And { dr, .. } | Instruction::AndI { dr, .. }
+      | Instruction::Ld { dr, .. } | Instruction::Ldi { dr, .. } | Instruction::Ldr { dr, .. } | Instruction::Lea { dr, .. }
+      | Instruction::Not { dr, .. } => r(dr),
+      Instruction::Jsr { .. } | Instruction::Jsrr { .. } => r(7),
+      Instruction::Trap { vector: 0x20 } | Instruction::Trap { vector: 0x23 } => r(0) | r(7),
+      Instruction::Trap { .. } => r(7),
+      _ => 0,
+    }
+  }
+}
+
+impl fmt::Display for Instruction {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      Instruction::Br { n: false, z: false, p: false, .. } => write!(f, "NOP"),
+      Instruction::Br { n, z, p, offset } => {
+        write!(f, "BR")?;
+        if n { write!(f, "n")?; }
+        if z { write!(f, "z")?; }
+        if p { write!(f, "p")?; }
+        write!(f, " #{}", offset)
+      },
+      Instruction::Add { dr, sr1, sr2 }  => write!(f, "ADD R{}, R{}, R{}", dr, sr1, sr2),
+      Instruction::AddI { dr, sr1, imm } => write!(f, "ADD R{}, R{}, #{}", dr, sr1, imm),
+      Instruction::And { dr, sr1, sr2 }  => write!(f, "AND R{}, R{}, R{}", dr, sr1, sr2),
+      Instruction::AndI { dr, sr1, imm } => write!(f, "AND R{}, R{}, #{}", dr, sr1, imm),
+      Instruction::Jmp { base: 7 }       => write!(f, "RET"),
+      Instruction::Jmp { base }          => write!(f, "JMP R{}", base),
+      Instruction::Jsr { offset }        => write!(f, "JSR #{}", offset),
+      Instruction::Jsrr { base }         => write!(f, "JSRR R{}", base),
+      Instruction::Ld { dr, offset }     => write!(f, "LD R{}, #{}", dr, offset),
+      Instruction::Ldi { dr, offset }    => write!(f, "LDI R{}, #{}", dr, offset),
+      Instruction::Ldr { dr, base, offset } => write!(f, "LDR R{}, R{}, #{}", dr, base, offset),
+      Instruction::Lea { dr, offset }    => write!(f, "LEA R{}, #{}", dr, offset),
+      Instruction::Not { dr, sr }        => write!(f, "NOT R{}, R{}", dr, sr),
+      Instruction::Rti                   => write!(f, "RTI"),
+      Instruction::St { sr, offset }     => write!(f, "ST R{}, #{}", sr, offset),
+      Instruction::Sti { sr, offset }    => write!(f, "STI R{}, #{}", sr, offset),
+      Instruction::Str { sr, base, offset } => write!(f, "STR R{}, R{}, #{}", sr, base, offset),
+      Instruction::Trap { vector: 0x20 } => write!(f, "GETC"),
+      Instruction::Trap { vector: 0x21 } => write!(f, "OUT"),
+      Instruction::Trap { vector: 0x22 } => write!(f, "PUTS"),
+      Instruction::Trap { vector: 0x23 } => write!(f, "IN"),
+      Instruction::Trap { vector: 0x24 } => write!(f, "PUTSP"),
+      Instruction::Trap { vector: 0x25 } => write!(f, "HALT"),
+      Instruction::Trap { vector }       => write!(f, "TRAP x{:02X}", vector),
+      Instruction::Res { word }          => write!(f, ".FILL x{:04X}", word),
+    }
+  }
+}
+
+// inverse of decode(); bits decode() ignores, such as the low six bits of
+// NOT, are produced in their canonical form
+pub fn encode(instr: Instruction) -> u16 {
+  let field = |v: i16, bits: u16| -> u16 { (v as u16) & ((1 << bits) - 1) };
+
+  match instr {
+    Instruction::Br { n, z, p, offset } => (n as u16) << 11 | (z as u16) << 10 | (p as u16) << 9 | field(offset, 9),
+    Instruction::Add { dr, sr1, sr2 }   => 0x1 << 12 | dr << 9 | sr1 << 6 | sr2,
+    Instruction::AddI { dr, sr1, imm }  => 0x1 << 12 | dr << 9 | sr1 << 6 | 1 << 5 | field(imm, 5),
+    Instruction::And { dr, sr1, sr2 }   => 0x5 << 12 | dr << 9 | sr1 << 6 | sr2,
+    Instruction::AndI { dr, sr1, imm }  => 0x5 << 12 | dr << 9 | sr1 << 6 | 1 << 5 | field(imm, 5),
+    Instruction::Jmp { base }           => 0xC << 12 | base << 6,
+    Instruction::Jsr { offset }         => 0x4 << 12 | 1 << 11 | field(offset, 11),
+    Instruction::Jsrr { base }          => 0x4 << 12 | base << 6,
+    Instruction::Ld { dr, offset }      => 0x2 << 12 | dr << 9 | field(offset, 9),
+    Instruction::Ldi { dr, offset }     => 0xA << 12 | dr << 9 | field(offset, 9),
+    Instruction::Ldr { dr, base, offset } => 0x6 << 12 | dr << 9 | base << 6 | field(offset, 6),
+    Instruction::Lea { dr, offset }     => 0xE << 12 | dr << 9 | field(offset, 9),
+    Instruction::Not { dr, sr }         => 0x9 << 12 | dr << 9 | sr << 6 | 0x3F,
+    Instruction::Rti                    => 0x8 << 12,
+    Instruction::St { sr, offset }      => 0x3 << 12 | sr << 9 | field(offset, 9),
+    Instruction::Sti { sr, offset }     => 0xB << 12 | sr << 9 | field(offset, 9),
+    Instruction::Str { sr, base, offset } => 0x7 << 12 | sr << 9 | base << 6 | field(offset, 6),
+    Instruction::Trap { vector }        => 0xF << 12 | vector as u16,
+    Instruction::Res { word }           => word,
+  }
+}
@@ -0,0 +1,284 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::iter::Peekable;
+use core::str::Chars;
+
+use utils::FormatError;
+
+// just enough JSON for the debug adapter and structured traces. Objects
+// keep their keys in insertion order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+  Null,
+  Bool(bool),
+  Num(f64),
+  Str(String),
+  Array(Vec<Json>),
+  Object(Vec<(String, Json)>),
+}
+
+impl Json {
+  pub fn object(fields: Vec<(&str, Json)>) -> Json {
+    Json::Object(fields.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+  }
+
+  pub fn get(&self, key: &str) -> Option<&Json> {
+    match self {
+      Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+      _ => None,
+    }
+  }
+
+  pub fn as_str(&self) -> Option<&str> {
+    match self {
+      Json::Str(s) => Some(s),
+      _ => None,
+    }
+  }
+
+  pub fn as_bool(&self) -> Option<bool> {
+    match *self {
+      Json::Bool(b) => Some(b),
+      _ => None,
+    }
+  }
+
+  pub fn as_i64(&self) -> Option<i64> {
+    match *self {
+      Json::Num(n) if is_int(n) => Some(n as i64),
+      _ => None,
+    }
+  }
+
+  pub fn as_array(&self) -> Option<&[Json]> {
+    match self {
+      Json::Array(items) => Some(items),
+      _ => None,
+    }
+  }
+
+  pub fn parse(src: &str) -> Result<Json, FormatError> {
+    let mut p = Parser { chars: src.chars().peekable() };
+    let val: Json = p.value()?;
+    p.ws();
+    match p.chars.next() {
+      None => Ok(val),
+      Some(c) => p.fail(&format!("unexpected `{}` after value", c)),
+    }
+  }
+}
+
+impl From<bool> for Json {
+  fn from(b: bool) -> Json {
+    Json::Bool(b)
+  }
+}
+
+impl From<&str> for Json {
+  fn from(s: &str) -> Json {
+    Json::Str(s.to_string())
+  }
+}
+
+impl From<String> for Json {
+  fn from(s: String) -> Json {
+    Json::Str(s)
+  }
+}
+
+impl From<i64> for Json {
+  fn from(n: i64) -> Json {
+    Json::Num(n as f64)
+  }
+}
+
+impl From<u64> for Json {
+  fn from(n: u64) -> Json {
+    Json::Num(n as f64)
+  }
+}
+
+impl From<u16> for Json {
+  fn from(n: u16) -> Json {
+    Json::Num(n as f64)
+  }
+}
+
+impl From<Vec<Json>> for Json {
+  fn from(items: Vec<Json>) -> Json {
+    Json::Array(items)
+  }
+}
+
+// whole numbers exactly representable as f64, printed without a fraction.
+// f64::fract() isn't in core.
+fn is_int(n: f64) -> bool {
+  n > -1e15 && n < 1e15 && n as i64 as f64 == n
+}
+
+fn write_str(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+  f.write_str("\"")?;
+  for c in s.chars() {
+    match c {
+      '"' => f.write_str("\\\"")?,
+      '\\' => f.write_str("\\\\")?,
+      '\n' => f.write_str("\\n")?,
+      '\r' => f.write_str("\\r")?,
+      '\t' => f.write_str("\\t")?,
+      c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+      c => write!(f, "{}", c)?,
+    }
+  }
+  f.write_str("\"")
+}
+
+// compact, no whitespace between tokens
+impl fmt::Display for Json {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Json::Null => f.write_str("null"),
+      Json::Bool(b) => write!(f, "{}", b),
+      Json::Num(n) if is_int(*n) => write!(f, "{}", *n as i64),
+      Json::Num(n) if n.is_finite() => write!(f, "{}", n),
+      Json::Num(_) => f.write_str("null"),
+      Json::Str(s) => write_str(f, s),
+      Json::Array(items) => {
+        f.write_str("[")?;
+        for (i, v) in items.iter().enumerate() {
+          if i > 0 {
+            f.write_str(",")?;
+          }
+          write!(f, "{}", v)?;
+        }
+        f.write_str("]")
+      },
+      Json::Object(fields) => {
+        f.write_str("{")?;
+        for (i, (k, v)) in fields.iter().enumerate() {
+          if i > 0 {
+            f.write_str(",")?;
+          }
+          write_str(f, k)?;
+          write!(f, ":{}", v)?;
+        }
+        f.write_str("}")
+      },
+    }
+  }
+}
+
+struct Parser<'a> {
+  chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> Parser<'a> {
+  fn fail<T>(&self, msg: &str) -> Result<T, FormatError> {
+    Err(FormatError(format!("invalid JSON: {}", msg)))
+  }
+
+  fn ws(&mut self) {
+    while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+  }
+
+  fn expect(&mut self, word: &str) -> Result<(), FormatError> {
+    for c in word.chars() {
+      if self.chars.next() != Some(c) {
+        return self.fail(&format!("expected `{}`", word));
+      }
+    }
+    Ok(())
+  }
+
+  fn value(&mut self) -> Result<Json, FormatError> {
+    self.ws();
+    match self.chars.peek() {
+      Some('n') => self.expect("null").map(|_| Json::Null),
+      Some('t') => self.expect("true").map(|_| Json::Bool(true)),
+      Some('f') => self.expect("false").map(|_| Json::Bool(false)),
+      Some('"') => self.string().map(Json::Str),
+      Some('[') => {
+        self.chars.next();
+        let mut items: Vec<Json> = Vec::new();
+        self.ws();
+        if self.chars.next_if_eq(&']').is_some() {
+          return Ok(Json::Array(items));
+        }
+        loop {
+          items.push(self.value()?);
+          self.ws();
+          match self.chars.next() {
+            Some(',') => {},
+            Some(']') => return Ok(Json::Array(items)),
+            _ => return self.fail("expected `,` or `]`"),
+          }
+        }
+      },
+      Some('{') => {
+        self.chars.next();
+        let mut fields: Vec<(String, Json)> = Vec::new();
+        self.ws();
+        if self.chars.next_if_eq(&'}').is_some() {
+          return Ok(Json::Object(fields));
+        }
+        loop {
+          self.ws();
+          if self.chars.peek() != Some(&'"') {
+            return self.fail("expected a key");
+          }
+          let key: String = self.string()?;
+          self.ws();
+          self.expect(":")?;
+          fields.push((key, self.value()?));
+          self.ws();
+          match self.chars.next() {
+            Some(',') => {},
+            Some('}') => return Ok(Json::Object(fields)),
+            _ => return self.fail("expected `,` or `}`"),
+          }
+        }
+      },
+      Some(&c) if c == '-' || c.is_ascii_digit() => {
+        let mut num: String = String::new();
+        while let Some(c) = self.chars.next_if(|&c| c.is_ascii_digit() || "+-.eE".contains(c)) {
+          num.push(c);
+        }
+        match num.parse() {
+          Ok(n) => Ok(Json::Num(n)),
+          Err(_) => self.fail(&format!("invalid number `{}`", num)),
+        }
+      },
+      Some(&c) => self.fail(&format!("unexpected `{}`", c)),
+      None => self.fail("unexpected end of input"),
+    }
+  }
+
+  fn string(&mut self) -> Result<String, FormatError> {
+    self.chars.next();
+    let mut s: String = String::new();
+    loop {
+      match self.chars.next() {
+        Some('"') => return Ok(s),
+        Some('\\') => match self.chars.next() {
+          Some('n') => s.push('\n'),
+          Some('t') => s.push('\t'),
+          Some('r') => s.push('\r'),
+          Some('b') => s.push('\u{8}'),
+          Some('f') => s.push('\u{c}'),
+          Some('u') => {
+            let hex: String = (0..4).filter_map(|_| self.chars.next()).collect();
+            match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+              Some(c) => s.push(c),
+              // surrogate pairs aren't needed for anything we read
+              None => s.push(char::REPLACEMENT_CHARACTER),
+            }
+          },
+          Some(c) => s.push(c),
+          None => return self.fail("unterminated string"),
+        },
+        Some(c) => s.push(c),
+        None => return self.fail("unterminated string"),
+      }
+    }
+  }
+}
@@ -0,0 +1,370 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use console::{Console, NullConsole};
+use loader::{self, ImageFormat, LoadedImage};
+use machine::{MachineError, Reg, Run, StopReason, COND, DDR, DSR, KBDR, KBSR, KBSR_READY, MEM_SIZE, NEG, PC, POS, ZRO};
+use utils::FormatError;
+
+// the LC-3b, the byte-addressable variant of the LC-3: addresses are of
+// bytes, words are little-endian and must be aligned, LDB/STB move single
+// bytes, LDW/STW/BR/JSR/LEA/TRAP offsets are scaled by two, opcode x9 is
+// XOR (NOT being XOR with #-1), xD is SHF and LDI/STI are gone.
+//
+// There is no privilege, no interrupts and no devices past the keyboard
+// and display registers, which sit at the same (byte) addresses as on the
+// LC-3. TRAPs go through the word vector table at x0000 when it has an
+// entry, otherwise GETC, OUT, PUTS, IN and HALT are handled on the host,
+// PUTS printing one byte per character.
+pub struct Lc3bMachine {
+  reg: [u16; 10],
+  mem: Vec<u8>,
+  pub halt: bool,
+  io: Box<dyn Console>,
+  key: Option<u8>,
+}
+
+fn sext(val: u16, bits: u16) -> u16 {
+  let shift: u16 = 16 - bits;
+  (((val << shift) as i16) >> shift) as u16
+}
+
+impl Lc3bMachine {
+  pub fn new(io: Box<dyn Console>) -> Lc3bMachine {
+    let mut reg: [u16; 10] = [0; 10];
+    reg[PC as usize] = 0x3000;
+    reg[COND as usize] = ZRO;
+    Lc3bMachine { reg, mem: vec![0; MEM_SIZE], halt: false, io, key: None }
+  }
+
+  // loads an .obj image: a byte address, then big-endian words as for the
+  // LC-3, stored little-endian from that address on, where the PC starts
+  pub fn load_image_bytes(&mut self, bytes: &[u8]) -> Result<(), FormatError> {
+    let image: LoadedImage = loader::parse(bytes, ImageFormat::Obj)?;
+    for seg in &image.segments {
+      if seg.origin & 1 != 0 {
+        return Err(FormatError("origin is not word aligned".into()));
+      }
+      if seg.origin as usize + 2 * seg.words.len() > MEM_SIZE {
+        return Err(FormatError("image does not fit in memory".into()));
+      }
+      for (i, &word) in seg.words.iter().enumerate() {
+        self.write_word(seg.origin + 2 * i as u16, word);
+      }
+    }
+    if let Some(seg) = image.segments.first() {
+      self.reg[PC as usize] = seg.origin;
+    }
+    Ok(())
+  }
+
+  // words to place at origin, for tests and small programs
+  pub fn load(&mut self, origin: u16, words: &[u16]) {
+    for (i, &word) in words.iter().enumerate() {
+      self.write_word(origin.wrapping_add(2 * i as u16), word);
+    }
+  }
+
+  pub fn read_reg(&self, r: Reg) -> u16 {
+    self.reg[r as usize]
+  }
+
+  pub fn write_reg(&mut self, r: Reg, val: u16) {
+    self.reg[r as usize] = val;
+  }
+
+  // raw memory access, bypassing the device registers; words are at
+  // addr & !1
+  pub fn read_byte(&self, addr: u16) -> u8 {
+    self.mem[addr as usize]
+  }
+
+  pub fn write_byte(&mut self, addr: u16, val: u8) {
+    self.mem[addr as usize] = val;
+  }
+
+  pub fn read_word(&self, addr: u16) -> u16 {
+    let a: usize = (addr & !1) as usize;
+    u16::from_le_bytes([self.mem[a], self.mem[a + 1]])
+  }
+
+  pub fn write_word(&mut self, addr: u16, val: u16) {
+    let a: usize = (addr & !1) as usize;
+    self.mem[a..a + 2].copy_from_slice(&val.to_le_bytes());
+  }
+
+  fn getr(&self, r: u16) -> u16 {
+    self.reg[r as usize]
+  }
+
+  fn setr(&mut self, r: u16, val: u16) {
+    self.reg[r as usize] = val;
+  }
+
+  fn set_cond(&mut self, r: u16) {
+    let cc: u16 = match self.getr(r) as i16 {
+      0 => ZRO,
+      v if v < 0 => NEG,
+      _ => POS,
+    };
+    self.setr(COND, cc);
+  }
+
+  fn poll_key(&mut self) {
+    if self.key.is_none() {
+      self.key = self.io.poll_key();
+    }
+  }
+
+  fn getc(&mut self) -> u16 {
+    self.key.take().or_else(|| self.io.read_char()).map_or(0, |c| c as u16)
+  }
+
+  // word loads and stores, through the device registers
+  fn getm(&mut self, addr: u16) -> u16 {
+    match addr {
+      KBSR => {
+        self.poll_key();
+        if self.key.is_some() { KBSR_READY } else { 0 }
+      },
+      KBDR => {
+        self.poll_key();
+        self.key.take().map_or(0, |c| c as u16)
+      },
+      DSR => 1 << 15,
+      DDR => 0,
+      _ => self.read_word(addr),
+    }
+  }
+
+  fn setm(&mut self, addr: u16, val: u16) {
+    match addr {
+      KBSR | KBDR | DSR => {},
+      DDR => {
+        self.io.write_char(val as u8);
+        self.io.flush();
+      },
+      _ => self.write_word(addr, val),
+    }
+  }
+
+  // a byte of a device register is that half of the word, read or
+  // written whole
+  fn getb(&mut self, addr: u16) -> u8 {
+    match addr & !1 {
+      KBSR | KBDR | DSR | DDR => {
+        let word: u16 = self.getm(addr & !1);
+        (if addr & 1 == 0 { word } else { word >> 8 }) as u8
+      },
+      _ => self.read_byte(addr),
+    }
+  }
+
+  fn setb(&mut self, addr: u16, val: u8) {
+    match addr & !1 {
+      KBSR | KBDR | DSR | DDR if addr & 1 == 0 => self.setm(addr, val as u16),
+      KBSR | KBDR | DSR | DDR => {},
+      _ => self.write_byte(addr, val),
+    }
+  }
+
+  fn trap(&mut self, pc: u16, vector: u8) -> Result<(), MachineError> {
+    match vector {
+      0x20 => {
+        let c: u16 = self.getc();
+        self.setr(0, c);
+      },
+      0x21 => {
+        self.io.write_char(self.getr(0) as u8);
+      },
+      0x22 => {
+        let mut addr: u16 = self.getr(0);
+        loop {
+          let c: u8 = self.getb(addr);
+          if c == 0 {
+            break;
+          }
+          self.io.write_char(c);
+          addr = addr.wrapping_add(1);
+        }
+      },
+      0x23 => {
+        for &c in b"Enter a character: " {
+          self.io.write_char(c);
+        }
+        let c: u16 = self.getc();
+        self.io.write_char(c as u8);
+        self.setr(0, c);
+      },
+      0x25 => {
+        for &c in b"\nHALT\n" {
+          self.io.write_char(c);
+        }
+        self.halt = true;
+      },
+      _ => return Err(MachineError::UnknownTrap { pc, vector }),
+    }
+    self.io.flush();
+    Ok(())
+  }
+
+  // executes one instruction and reports whether execution should stop
+  pub fn step(&mut self) -> Result<Option<StopReason>, MachineError> {
+    self.execute()?;
+    Ok(if self.halt { Some(StopReason::Halt) } else { None })
+  }
+
+  pub fn run(&mut self) -> Run {
+    self.run_until(|_| false)
+  }
+
+  pub fn run_for(&mut self, n: u64) -> Run {
+    if n == 0 {
+      return Run { steps: 0, reason: StopReason::StepLimit };
+    }
+    let mut left: u64 = n;
+    let run: Run = self.run_until(|_| {
+      left -= 1;
+      left == 0
+    });
+    match run.reason {
+      StopReason::Condition => Run { steps: run.steps, reason: StopReason::StepLimit },
+      _ => run,
+    }
+  }
+
+  // steps until execution stops or pred, checked after every instruction,
+  // returns true
+  pub fn run_until<F: FnMut(&Lc3bMachine) -> bool>(&mut self, mut pred: F) -> Run {
+    let mut steps: u64 = 0;
+    loop {
+      if self.halt {
+        return Run { steps, reason: StopReason::Halt };
+      }
+      let result = self.step();
+      steps += 1;
+      match result {
+        Ok(Some(reason)) => return Run { steps, reason },
+        Ok(None) => {},
+        Err(e) => return Run { steps, reason: StopReason::Fault(e) },
+      }
+      if pred(self) {
+        return Run { steps, reason: StopReason::Condition };
+      }
+    }
+  }
+
+  // a word access at addr, faulting if it's odd
+  fn aligned(pc: u16, addr: u16) -> Result<u16, MachineError> {
+    if addr & 1 != 0 {
+      return Err(MachineError::Unaligned { pc, addr });
+    }
+    Ok(addr)
+  }
+
+  fn execute(&mut self) -> Result<(), MachineError> {
+    let pc: u16 = Lc3bMachine::aligned(self.getr(PC), self.getr(PC))?;
+    let instr: u16 = self.getm(pc);
+    self.setr(PC, pc.wrapping_add(2));
+    let npc: u16 = self.getr(PC);
+
+    let dr: u16 = (instr >> 9) & 0x7;
+    let sr1: u16 = (instr >> 6) & 0x7;
+    // the second operand of ADD, AND and XOR
+    let op2: u16 = if instr & (1 << 5) != 0 { sext(instr, 5) } else { self.getr(instr & 0x7) };
+    let offset6: u16 = sext(instr, 6);
+
+    match instr >> 12 {
+      // BR
+      0x0 => {
+        if (instr >> 9) & self.getr(COND) != 0 {
+          self.setr(PC, npc.wrapping_add(sext(instr, 9) << 1));
+        }
+      },
+      // ADD
+      0x1 => {
+        self.setr(dr, self.getr(sr1).wrapping_add(op2));
+        self.set_cond(dr);
+      },
+      // LDB
+      0x2 => {
+        let val: u8 = self.getb(self.getr(sr1).wrapping_add(offset6));
+        self.setr(dr, sext(val as u16, 8));
+        self.set_cond(dr);
+      },
+      // STB
+      0x3 => {
+        let val: u8 = self.getr(dr) as u8;
+        self.setb(self.getr(sr1).wrapping_add(offset6), val);
+      },
+      // JSR, JSRR; the target is read before R7 is overwritten
+      0x4 => {
+        let target: u16 = if instr & (1 << 11) != 0 { npc.wrapping_add(sext(instr, 11) << 1) } else { self.getr(sr1) };
+        self.setr(7, npc);
+        self.setr(PC, target);
+      },
+      // AND
+      0x5 => {
+        self.setr(dr, self.getr(sr1) & op2);
+        self.set_cond(dr);
+      },
+      // LDW
+      0x6 => {
+        let addr: u16 = Lc3bMachine::aligned(pc, self.getr(sr1).wrapping_add(offset6 << 1))?;
+        let val: u16 = self.getm(addr);
+        self.setr(dr, val);
+        self.set_cond(dr);
+      },
+      // STW
+      0x7 => {
+        let addr: u16 = Lc3bMachine::aligned(pc, self.getr(sr1).wrapping_add(offset6 << 1))?;
+        self.setm(addr, self.getr(dr));
+      },
+      // XOR, NOT
+      0x9 => {
+        self.setr(dr, self.getr(sr1) ^ op2);
+        self.set_cond(dr);
+      },
+      // JMP, RET
+      0xC => {
+        self.setr(PC, self.getr(sr1));
+      },
+      // SHF by the low four bits: bit 4 clear is LSHF, then bit 5 picks
+      // RSHFA over RSHFL
+      0xD => {
+        let (val, amount): (u16, u16) = (self.getr(sr1), instr & 0xF);
+        let res: u16 = match (instr >> 4) & 0x3 {
+          0 | 2 => val << amount,
+          1 => val >> amount,
+          _ => ((val as i16) >> amount) as u16,
+        };
+        self.setr(dr, res);
+        self.set_cond(dr);
+      },
+      // LEA, which leaves the condition codes alone on the LC-3b
+      0xE => {
+        self.setr(dr, npc.wrapping_add(sext(instr, 9) << 1));
+      },
+      // TRAP
+      0xF => {
+        let vector: u8 = instr as u8;
+        self.setr(7, npc);
+        let handler: u16 = self.read_word((vector as u16) << 1);
+        if handler != 0 {
+          self.setr(PC, handler);
+        } else {
+          self.trap(pc, vector)?;
+        }
+      },
+      // RTI, with no interrupts to return from, and the unused xA and xB
+      _ => return Err(MachineError::IllegalOpcode { pc, instr }),
+    }
+    Ok(())
+  }
+}
+
+impl Default for Lc3bMachine {
+  fn default() -> Lc3bMachine {
+    Lc3bMachine::new(Box::new(NullConsole))
+  }
+}
@@ -1,14 +1,69 @@
+#![cfg_attr(not(feature = "std"), no_std)]
 #![allow(dead_code)]
 #![allow(non_snake_case)]
+#![allow(clippy::upper_case_acronyms)]
 
+#[macro_use]
+extern crate alloc;
+// injected by no_std, needed for core:: paths otherwise
+#[cfg(feature = "std")]
+extern crate core;
 extern crate num_derive;
 extern crate num_traits;
 extern crate log;
+#[cfg(feature = "serde")]
+extern crate serde;
 
+pub mod analysis;
+pub mod assembler;
+pub mod cluster;
+pub mod console;
+pub mod coverage;
+#[cfg(feature = "std")]
+pub mod controller;
+#[cfg(feature = "std")]
+pub mod debugger;
+pub mod disasm;
+pub mod encode;
+pub mod expr;
+pub mod framebuffer;
+pub mod fuzz;
+pub mod harness;
+pub mod instr;
+pub mod json;
+pub mod lc3b;
+pub mod linker;
+pub mod loader;
 pub mod machine;
+pub mod profile;
+pub mod replay;
+pub mod script;
+#[cfg(feature = "std")]
+pub mod repl;
+pub mod stats;
+pub mod symbols;
+#[cfg(feature = "std")]
+pub mod term;
+pub mod trace;
 pub mod utils;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
+#[cfg(feature = "std")]
+pub use console::StdConsole;
 pub use {
+  cluster::{Cluster, ClusterRun, Schedule},
+  console::{Console, NullConsole},
+  coverage::Coverage,
+  expr::Expr,
+  framebuffer::{Frame, Framebuffer},
+  lc3b::Lc3bMachine,
+  loader::ImageFormat,
   machine::*,
+  profile::{Profile, Subroutine},
+  replay::Recording,
+  script::InputScript,
+  stats::Stats,
+  symbols::SymbolTable,
   utils::*,
 };
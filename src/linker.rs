@@ -0,0 +1,160 @@
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::{error, fmt};
+
+use assembler::{self, AsmError, Include, Program};
+use loader::{self, ImageFormat, LoadedImage, Segment};
+use symbols::SymbolTable;
+
+// one part of a program being linked
+#[derive(Debug, Clone, Copy)]
+pub enum Module<'a> {
+  // assembly source, its name also the key of the file for includes. Its
+  // labels are visible to the other modules.
+  Source { name: &'a str, src: &'a str },
+  // an assembled image and the labels it defines, e.g. an .obj and its
+  // .sym
+  Image { name: &'a str, image: &'a LoadedImage, symbols: &'a SymbolTable },
+}
+
+impl<'a> Module<'a> {
+  pub fn name(&self) -> &'a str {
+    match *self {
+      Module::Source { name, .. } | Module::Image { name, .. } => name,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkError {
+  Asm { module: String, error: AsmError },
+  DuplicateLabel { label: String, first: String, second: String },
+  // inclusive addresses both modules have words at
+  Overlap { first: String, second: String, start: u16, end: u16 },
+}
+
+impl fmt::Display for LinkError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      LinkError::Asm { module, error } => write!(f, "{}: {}", module, error),
+      LinkError::DuplicateLabel { label, first, second } => write!(f, "label `{}` is defined in both {} and {}", label, first, second),
+      LinkError::Overlap { first, second, start, end } if start == end => write!(f, "{} and {} overlap at x{:04X}", first, second, start),
+      LinkError::Overlap { first, second, start, end } => write!(f, "{} and {} overlap at x{:04X}-x{:04X}", first, second, start, end),
+    }
+  }
+}
+
+impl error::Error for LinkError {}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Linked {
+  // segments in address order, entry that of the first module
+  pub image: LoadedImage,
+  // the labels of every module
+  pub symbols: SymbolTable,
+}
+
+impl Linked {
+  // the image as one run of words from its lowest address, the gaps
+  // between modules zero, for formats with a single origin such as .obj
+  pub fn flatten(&self) -> (u16, Vec<u16>) {
+    let origin: u16 = match self.image.segments.first() {
+      Some(seg) => seg.origin,
+      None => return (0, Vec::new()),
+    };
+    let mut words: Vec<u16> = Vec::new();
+    for seg in &self.image.segments {
+      words.resize((seg.origin - origin) as usize, 0);
+      words.extend_from_slice(&seg.words);
+    }
+    (origin, words)
+  }
+
+  pub fn to_obj(&self) -> Vec<u8> {
+    let (origin, words): (u16, Vec<u16>) = self.flatten();
+    loader::write(origin, &words, ImageFormat::Obj)
+  }
+}
+
+// links modules into one image. Each keeps the origin it was assembled
+// at, so they must not overlap; labels are shared, so each must be
+// defined once. Execution starts where the first module says, or at its
+// lowest address.
+pub fn link(modules: &[Module], include: &mut Include) -> Result<Linked, LinkError> {
+  let asm = |module: &Module, error: AsmError| LinkError::Asm { module: module.name().to_string(), error };
+
+  // the labels of each module first, for the others to refer to
+  let mut tables: Vec<SymbolTable> = Vec::new();
+  let mut owners: BTreeMap<String, usize> = BTreeMap::new();
+  for (i, module) in modules.iter().enumerate() {
+    let table: SymbolTable = match *module {
+      Module::Source { name, src } => assembler::labels(src, Some(name), include).map_err(|e| asm(module, e))?,
+      Module::Image { symbols, .. } => symbols.clone(),
+    };
+    for (label, _) in table.iter() {
+      if let Some(&j) = owners.get(label) {
+        return Err(LinkError::DuplicateLabel {
+          label: label.to_string(),
+          first: modules[j].name().to_string(),
+          second: module.name().to_string(),
+        });
+      }
+      owners.insert(label.to_string(), i);
+    }
+    tables.push(table);
+  }
+
+  let mut symbols: SymbolTable = SymbolTable::new();
+  for table in &tables {
+    symbols.extend(table);
+  }
+
+  let mut placed: Vec<(usize, Segment)> = Vec::new();
+  let mut entry: Option<u16> = None;
+  for (i, module) in modules.iter().enumerate() {
+    let segments: Vec<Segment> = match *module {
+      Module::Source { name, src } => {
+        let mut externs: SymbolTable = SymbolTable::new();
+        for (_, table) in tables.iter().enumerate().filter(|&(j, _)| j != i) {
+          externs.extend(table);
+        }
+        let prog: Program = assembler::assemble_linked(src, Some(name), include, &externs).map_err(|e| asm(module, e))?;
+        vec![Segment { origin: prog.origin, words: prog.words }]
+      },
+      Module::Image { image, .. } => {
+        if i == 0 {
+          entry = image.entry;
+        }
+        image.segments.clone()
+      },
+    };
+    if i == 0 && entry.is_none() {
+      entry = segments.iter().filter(|s| !s.words.is_empty()).map(|s| s.origin).min();
+    }
+    placed.extend(segments.into_iter().filter(|s| !s.words.is_empty()).map(|s| (i, s)));
+  }
+
+  // in address order, each must start past the end of everything before
+  // it; adjacent ones merge
+  placed.sort_by_key(|(_, s)| s.origin);
+  let mut segments: Vec<Segment> = Vec::new();
+  let mut last: Option<(usize, u32)> = None;
+  for (i, seg) in placed {
+    let start: u32 = seg.origin as u32;
+    let end: u32 = start + seg.words.len() as u32;
+    match last {
+      Some((j, prev)) if start < prev => return Err(LinkError::Overlap {
+        first: modules[j].name().to_string(),
+        second: modules[i].name().to_string(),
+        start: seg.origin,
+        end: (prev.min(end) - 1) as u16,
+      }),
+      Some((_, prev)) if start == prev => segments.last_mut().unwrap().words.extend(seg.words),
+      _ => segments.push(seg),
+    }
+    last = Some((i, end));
+  }
+
+  Ok(Linked { image: LoadedImage { segments, entry }, symbols })
+}
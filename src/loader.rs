@@ -0,0 +1,213 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use machine::MEM_SIZE;
+use utils::FormatError;
+
+// on-disk program image layouts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+  // big-endian words, origin first
+  Obj,
+  // bare big-endian or little-endian words, loaded at the given origin
+  RawBigEndian { origin: u16 },
+  RawLittleEndian { origin: u16 },
+  // Intel HEX records. Addresses are byte addresses, two per LC-3 word with
+  // the high byte first, as a byte-oriented toolchain dumps .obj contents
+  IntelHex,
+}
+
+impl ImageFormat {
+  // the format for a --format name, raw images load at `origin`
+  pub fn from_name(name: &str, origin: u16) -> Option<ImageFormat> {
+    match name {
+      "obj" => Some(ImageFormat::Obj),
+      "bin" | "bin-be" => Some(ImageFormat::RawBigEndian { origin }),
+      "bin-le" => Some(ImageFormat::RawLittleEndian { origin }),
+      "hex" | "ihex" => Some(ImageFormat::IntelHex),
+      _ => None,
+    }
+  }
+}
+
+// words to place at an origin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+  pub origin: u16,
+  pub words: Vec<u16>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadedImage {
+  pub segments: Vec<Segment>,
+  // start address, when the format records one
+  pub entry: Option<u16>,
+}
+
+fn err<T>(msg: impl Into<String>) -> Result<T, FormatError> {
+  Err(FormatError(msg.into()))
+}
+
+pub fn parse(bytes: &[u8], format: ImageFormat) -> Result<LoadedImage, FormatError> {
+  match format {
+    ImageFormat::Obj => {
+      if bytes.len() < 2 || !bytes.len().is_multiple_of(2) {
+        return err("malformed object file");
+      }
+      let origin: u16 = u16::from_be_bytes([bytes[0], bytes[1]]);
+      raw(&bytes[2..], origin, u16::from_be_bytes)
+    },
+    ImageFormat::RawBigEndian { origin } => raw(bytes, origin, u16::from_be_bytes),
+    ImageFormat::RawLittleEndian { origin } => raw(bytes, origin, u16::from_le_bytes),
+    ImageFormat::IntelHex => match core::str::from_utf8(bytes) {
+      Ok(src) => intel_hex(src),
+      Err(_) => err("Intel HEX image is not text"),
+    },
+  }
+}
+
+fn raw(bytes: &[u8], origin: u16, word: fn([u8; 2]) -> u16) -> Result<LoadedImage, FormatError> {
+  if !bytes.len().is_multiple_of(2) {
+    return err("image has an odd number of bytes");
+  }
+  let words: Vec<u16> = bytes.chunks(2).map(|w| word([w[0], w[1]])).collect();
+  if origin as usize + words.len() > MEM_SIZE {
+    return err("image does not fit in memory");
+  }
+  Ok(LoadedImage { segments: vec![Segment { origin, words }], entry: None })
+}
+
+fn hex_bytes(line: usize, s: &str) -> Result<Vec<u8>, FormatError> {
+  if !s.len().is_multiple_of(2) || !s.is_ascii() {
+    return err(format!("line {}: malformed record", line));
+  }
+  (0..s.len()).step_by(2)
+    .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|_| FormatError(format!("line {}: invalid hex digits", line))))
+    .collect()
+}
+
+fn intel_hex(src: &str) -> Result<LoadedImage, FormatError> {
+  let mut bytes: BTreeMap<u32, u8> = BTreeMap::new();
+  let mut entry: Option<u16> = None;
+  let mut base: u32 = 0;
+  let mut eof: bool = false;
+
+  for (i, line) in src.lines().enumerate() {
+    let num: usize = i + 1;
+    let line: &str = line.trim();
+    if line.is_empty() {
+      continue;
+    }
+    if eof {
+      return err(format!("line {}: record after end of file", num));
+    }
+    let rec: Vec<u8> = match line.strip_prefix(':') {
+      Some(hex) => hex_bytes(num, hex)?,
+      None => return err(format!("line {}: expected `:`", num)),
+    };
+    if rec.len() < 5 || rec.len() != rec[0] as usize + 5 {
+      return err(format!("line {}: wrong record length", num));
+    }
+    if rec.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) != 0 {
+      return err(format!("line {}: bad checksum", num));
+    }
+
+    let addr: u32 = u16::from_be_bytes([rec[1], rec[2]]) as u32;
+    let data: &[u8] = &rec[4..rec.len() - 1];
+    let field = |n: usize| -> Result<u32, FormatError> {
+      if data.len() != n {
+        return err(format!("line {}: wrong record length", num));
+      }
+      Ok(data.iter().fold(0u32, |v, &b| v << 8 | b as u32))
+    };
+
+    match rec[3] {
+      0x00 => for (j, &b) in data.iter().enumerate() {
+        let a: u32 = base + addr + j as u32;
+        if a as usize >= 2 * MEM_SIZE {
+          return err(format!("line {}: data outside of memory", num));
+        }
+        bytes.insert(a, b);
+      },
+      0x01 => eof = true,
+      0x02 => base = field(2)? << 4,
+      0x03 => {
+        let start: u32 = field(4)?;
+        entry = Some((((start >> 16) << 4) + (start & 0xFFFF)) as u16 >> 1);
+      },
+      0x04 => base = field(2)? << 16,
+      0x05 => entry = Some((field(4)? >> 1) as u16),
+      t => return err(format!("line {}: unknown record type {:02X}", num, t)),
+    }
+  }
+  if !eof {
+    return err("missing end of file record");
+  }
+
+  // pair bytes into words, a missing half reads as zero, then split into
+  // runs of consecutive words
+  let mut segments: Vec<Segment> = Vec::new();
+  let mut last: Option<u16> = None;
+  for (&a, &b) in &bytes {
+    let addr: u16 = (a >> 1) as u16;
+    let shift: u16 = if a & 1 == 0 { 8 } else { 0 };
+    match segments.last_mut() {
+      Some(seg) if last == Some(addr) => *seg.words.last_mut().unwrap() |= (b as u16) << shift,
+      Some(seg) if last.map(|l| l.wrapping_add(1)) == Some(addr) && addr != 0 => seg.words.push((b as u16) << shift),
+      _ => segments.push(Segment { origin: addr, words: vec![(b as u16) << shift] }),
+    }
+    last = Some(addr);
+  }
+
+  Ok(LoadedImage { segments, entry })
+}
+
+// words at origin in the given layout, as parse() reads them back
+pub fn write(origin: u16, words: &[u16], format: ImageFormat) -> Vec<u8> {
+  match format {
+    ImageFormat::Obj => {
+      let mut out: Vec<u8> = origin.to_be_bytes().to_vec();
+      out.extend(words.iter().flat_map(|w| w.to_be_bytes()));
+      out
+    },
+    ImageFormat::RawBigEndian { .. } => words.iter().flat_map(|w| w.to_be_bytes()).collect(),
+    ImageFormat::RawLittleEndian { .. } => words.iter().flat_map(|w| w.to_le_bytes()).collect(),
+    ImageFormat::IntelHex => write_intel_hex(origin, words).into_bytes(),
+  }
+}
+
+// data records of up to 16 bytes, none crossing a 64K boundary, with an
+// extended linear address record where the upper half of the address
+// changes, then end of file. No start address, a dump is data
+fn write_intel_hex(origin: u16, words: &[u16]) -> String {
+  fn record(out: &mut String, addr: u16, kind: u8, data: &[u8]) {
+    let mut rec: Vec<u8> = vec![data.len() as u8, (addr >> 8) as u8, addr as u8, kind];
+    rec.extend_from_slice(data);
+    let sum: u8 = rec.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+    rec.push(sum.wrapping_neg());
+    out.push(':');
+    for b in rec {
+      out.push_str(&format!("{:02X}", b));
+    }
+    out.push('\n');
+  }
+
+  let bytes: Vec<u8> = words.iter().flat_map(|w| w.to_be_bytes()).collect();
+  let mut out: String = String::new();
+  let mut upper: u32 = 0;
+  let mut addr: u32 = origin as u32 * 2;
+  let mut rest: &[u8] = &bytes;
+  while !rest.is_empty() {
+    if addr >> 16 != upper {
+      upper = addr >> 16;
+      record(&mut out, 0, 0x04, &(upper as u16).to_be_bytes());
+    }
+    let n: usize = rest.len().min(16).min((0x10000 - (addr & 0xFFFF)) as usize);
+    record(&mut out, addr as u16, 0x00, &rest[..n]);
+    rest = &rest[n..];
+    addr += n as u32;
+  }
+  record(&mut out, 0, 0x01, &[]);
+  out
+}
@@ -1,31 +1,107 @@
-use log::{warn, trace};
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::error;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::fs;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
+use std::path::Path;
+
+use log::LevelFilter;
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 
-use utils::sign_extend;
+use assembler::{self, LineEntry};
+use console::Console;
+#[cfg(feature = "std")]
+use console::StdConsole;
+use coverage::Coverage;
+use disasm;
+use expr::Expr;
+use instr::{decode, Instruction};
+use loader::{self, ImageFormat, LoadedImage};
+use profile::Profile;
+use replay::{self, Input, Recording};
+use script::{InputScript, When};
+use stats::Stats;
+use symbols::SymbolTable;
+use utils::{sign_extend, FormatError};
 
-#[derive(FromPrimitive)]
-#[repr(u16)]
-enum OP {
-  BR,   // branch
-  ADD,  // add
-  LD,   // load
-  ST,   // store
-  JSR,  // jump register
-  AND,  // bitwise and
-  LDR,  // load register
-  STR,  // store register
-  RTI,  // return from interrupt (unimplemented)
-  NOT,  // bitwise not
-  LDI,  // load indirect
-  STI,  // store indirect
-  JMP,  // jump
-  RES,  // reserved (unused)
-  LEA,  // load effective address
-  TRAP, // execute trap
-}
+#[macro_use]
+mod logging;
+mod audit;
+mod builder;
+mod cache;
+mod calls;
+mod capture;
+#[cfg(feature = "std")]
+mod clock;
+mod devices;
+mod discipline;
+mod dump;
+mod events;
+#[cfg(feature = "extensions")]
+mod files;
+mod future;
+mod history;
+#[cfg(feature = "jit")]
+mod jit;
+mod hooks;
+mod isa;
+mod limits;
+mod locations;
+mod memory;
+mod observer;
+mod os;
+mod progress;
+mod randomize;
+mod regions;
+#[cfg(feature = "serde")]
+mod serialize;
+mod smc;
+mod snapshot;
+mod stepping;
+mod strictness;
+mod timeline;
+#[cfg(feature = "std")]
+mod tracer;
+mod typing;
+#[cfg(feature = "std")]
+mod uart;
+mod uninit;
+mod watches;
 
-#[derive(FromPrimitive)]
+pub use self::builder::MachineBuilder;
+pub use self::calls::CallFrame;
+pub use self::devices::{Device, Mailbox, Random, Timer, IO_PAGE, MBX_READY, TMR_IE, TMR_READY};
+pub use self::discipline::{Violation, ViolationKind, CALLEE_SAVED};
+pub use self::events::{Event, Events};
+pub use self::future::{KeySource, RunAsync};
+pub use self::hooks::{Hook, HookFn};
+pub use self::isa::IsaExtensions;
+pub use self::logging::LogTarget;
+pub use self::memory::{FlatMemory, Memory, SparseMemory};
+pub use self::observer::MemObserver;
+pub use self::regions::{Region, RegionKind};
+pub use self::smc::{CodeWrite, SmcCheck};
+pub use self::snapshot::{Snapshot, StateDiff};
+pub use self::stepping::Goal;
+pub use self::strictness::Strictness;
+pub use self::timeline::Timeline;
+pub use self::uninit::{Uninit, UninitCheck, UninitRead};
+pub use self::watches::Watch;
+#[cfg(feature = "extensions")]
+pub use self::files::{FILE_APPEND, FILE_READ, FILE_WRITE, TRAP_FCLOSE, TRAP_FGETC, TRAP_FOPEN, TRAP_FPUTC};
+#[cfg(feature = "std")]
+pub use self::tracer::{TraceFormat, TraceSink};
+#[cfg(feature = "std")]
+pub use self::uart::{Uart, UART_IE, UART_READY};
+
+#[derive(FromPrimitive, Clone, Copy)]
 #[repr(u16)]
 enum TRAP {
   GETC  = 0x20, // get character from keyboard
@@ -37,52 +113,837 @@ enum TRAP {
 }
 
 pub const MEM_SIZE: usize = 1<<16;
-pub const REG_SIZE: usize = 10;
+pub const REG_SIZE: usize = 11;
+// names of the reg array slots, for traces and diffs
+const REG_NAMES: [&str; REG_SIZE] = ["R0", "R1", "R2", "R3", "R4", "R5", "R6", "R7", "PC", "CC", "PSR"];
 
+pub const SP    : u16 = 6;
 pub const PC    : u16 = 8;
 pub const COND  : u16 = 9;
+pub const PSR   : u16 = 10; // privilege and priority bits, see psr()
 pub const POS   : u16 = 1 << 0;
 pub const ZRO   : u16 = 1 << 1;
 pub const NEG   : u16 = 1 << 2;
 
+pub const PSR_USER : u16 = 1 << 15;
+pub const PSR_PRIO : u16 = 0x7 << 8;
+
+pub const IVT   : u16 = 0x0100; // interrupt vector table
+pub const SSP   : u16 = 0x3000; // initial supervisor stack pointer
+pub const USER_SPACE : u16 = 0x3000; // lowest address user mode may access
+
+pub const EXC_PRIVILEGE : u8 = 0x00;
+pub const EXC_ILLEGAL   : u8 = 0x01;
+pub const EXC_ACCESS    : u8 = 0x02;
+pub const INT_KEYBOARD  : u8 = 0x80;
+
+pub const INT_TIMER     : u8 = 0x81;
+pub const INT_UART      : u8 = 0x82;
+
+pub const KBD_PRIORITY  : u8 = 4;
+pub const TIMER_PRIORITY: u8 = 6;
+pub const UART_PRIORITY : u8 = 4;
+
+pub const KBSR_READY : u16 = 1 << 15;
+pub const KBSR_IE    : u16 = 1 << 14;
+
+pub const KBSR  : u16 = 0xFE00; // keyboard status
+pub const KBDR  : u16 = 0xFE02; // keyboard data
+pub const DSR   : u16 = 0xFE04; // display status
+pub const DDR   : u16 = 0xFE06; // display data
+pub const TMR   : u16 = 0xFE08; // timer status, see Timer
+pub const TMI   : u16 = 0xFE0A; // timer interval
+pub const RNG   : u16 = 0xFE0C; // random numbers, see Random
+pub const URSR  : u16 = 0xFE10; // serial receive status, see Uart
+pub const URDR  : u16 = 0xFE12; // serial receive data
+pub const UTSR  : u16 = 0xFE14; // serial transmit status
+pub const UTDR  : u16 = 0xFE16; // serial transmit data
+pub const MBSR  : u16 = 0xFE18; // mailbox status, see Mailbox
+pub const MBDR  : u16 = 0xFE1A; // mailbox data
+pub const MBID  : u16 = 0xFE1C; // mailbox number and destination
+pub const MBTX  : u16 = 0xFE1E; // mailbox send
+pub const MCR   : u16 = 0xFFFE; // machine control, clearing bit 15 halts
+
+pub const MCR_CLOCK : u16 = 1 << 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+#[repr(u16)]
+pub enum Reg {
+  R0, R1, R2, R3, R4, R5, R6, R7,
+  PC,
+  COND,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+  Read,
+  Write,
+  Access, // read or write
+}
+
+impl WatchKind {
+  fn matches(self, write: bool) -> bool {
+    match self {
+      WatchKind::Read => !write,
+      WatchKind::Write => write,
+      WatchKind::Access => true,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+  Breakpoint(u16),
+  Watchpoint(u16, WatchKind),
+  Halt,
+  Trap(u8),
+  StepLimit,  // run_for() executed all requested steps
+  Condition,  // the run_until() predicate returned true
+  GoalReached, // run_to_goal() got where it was going
+  LimitExceeded, // a limit from set_limits() was reached
+  NoProgress, // stuck in a loop, see set_progress_check()
+  Fault(MachineError),
+}
+
+// outcome of one of the run loops
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Run {
+  pub steps: u64,
+  pub reason: StopReason,
+}
+
+// faults raised by step(); pc is the address of the faulting instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MachineError {
+  IllegalOpcode { pc: u16, instr: u16 },
+  UnknownTrap { pc: u16, vector: u8 },
+  PrivilegeViolation { pc: u16 },
+  AccessViolation { pc: u16, addr: u16 },
+  Unaligned { pc: u16, addr: u16 }, // an odd word address on the LC-3b
+  ConditionCodes { pc: u16, expected: u16, found: u16 }, // see set_cc_audit()
+  Uninitialized { pc: u16, read: Uninit }, // see set_uninit_check()
+  CodeWrite { pc: u16, addr: u16 }, // see set_smc_check()
+}
+
+impl fmt::Display for MachineError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      MachineError::IllegalOpcode { pc, instr } =>
+        write!(f, "illegal opcode {:#06x} at {:#06x}", instr, pc),
+      MachineError::UnknownTrap { pc, vector } =>
+        write!(f, "unknown trap {:#04x} at {:#06x}", vector, pc),
+      MachineError::PrivilegeViolation { pc } =>
+        write!(f, "privilege violation at {:#06x}", pc),
+      MachineError::AccessViolation { pc, addr } =>
+        write!(f, "access violation on {:#06x} at {:#06x}", addr, pc),
+      MachineError::Unaligned { pc, addr } =>
+        write!(f, "unaligned access to {:#06x} at {:#06x}", addr, pc),
+      MachineError::ConditionCodes { pc, expected, found } =>
+        write!(f, "condition codes {:#05b} after {:#06x}, expected {:#05b}", found, pc, expected),
+      MachineError::Uninitialized { pc, read } =>
+        write!(f, "read of uninitialized {} at {:#06x}", read, pc),
+      MachineError::CodeWrite { pc, addr } =>
+        write!(f, "store to code at {:#06x} by {:#06x}", addr, pc),
+    }
+  }
+}
+
+impl error::Error for MachineError {}
+
+// host-side implementation of a trap vector, see set_trap_handler()
+pub type TrapHandler = Box<dyn FnMut(&mut Machine)>;
+
 pub struct Machine {
   reg: [u16; REG_SIZE],
-  mem: [u16; MEM_SIZE],
+  mem: Box<dyn Memory>,
   pub halt: bool,
+  saved_usp: u16,
+  saved_ssp: u16,
+  pending: Vec<(u8, u8)>,
+  io: Box<dyn Console>,
+  keyboard: self::devices::Keyboard,
+  display: self::devices::Display,
+  breakpoints: Vec<u16>,
+  // the breakpoints that only stop when their expression holds
+  conditions: BTreeMap<u16, Expr>,
+  // the shadow call stack, see note_call()
+  calls: Vec<self::calls::Call>,
+  discipline: Option<self::discipline::Discipline>,
+  uninit: Option<Box<self::uninit::Tracker>>,
+  smc: Option<Box<self::smc::SmcTracker>>,
+  progress: Option<self::progress::Progress>,
+  watchpoints: Vec<(u16, WatchKind)>,
+  watches: Vec<Watch>,
+  // the last id add_watch() gave out
+  watch_ids: usize,
+  catch_traps: bool,
+  os: bool,
+  trap_handlers: BTreeMap<u8, TrapHandler>,
+  hooks: Vec<Hook>,
+  cc_audit: bool,
+  isa_ext: IsaExtensions,
+  strictness: Strictness,
+  decode_cache: Option<Box<self::cache::DecodeCache>>,
+  #[cfg(feature = "jit")]
+  jit: Option<Box<self::jit::Jit>>,
+  observers: Vec<Box<dyn MemObserver>>,
+  devices: Vec<Box<dyn Device>>,
+  stop: Option<StopReason>,
+  symbols: SymbolTable,
+  regions: Vec<Region>,
+  // per source file, see add_line_info()
+  line_info: Vec<(String, Vec<LineEntry>)>,
+  stats: Stats,
+  count: u64,
+  recording: Option<(u64, Recording)>,
+  coverage: Option<Coverage>,
+  profile: Option<Profile>,
+  capture: Option<self::capture::Capture>,
+  replaying: Option<VecDeque<replay::Event>>,
+  typing: Option<self::typing::Typing>,
+  history: Option<self::history::History>,
+  limits: Option<self::limits::Limits>,
+  events: Option<self::events::EventSink>,
+  log_filter: [LevelFilter; self::logging::LOG_TARGETS],
+  #[cfg(feature = "std")]
+  clock: Option<self::clock::Clock>,
+  #[cfg(feature = "std")]
+  tracer: Option<self::tracer::TraceSink>,
+  #[cfg(feature = "extensions")]
+  files: self::files::Files,
+}
+
+#[cfg(feature = "std")]
+impl Default for Machine {
+  fn default() -> Machine {
+    Machine::new()
+  }
 }
 
 impl Machine {
+  #[cfg(feature = "std")]
   pub fn new() -> Machine {
+    Machine::with_io(Box::new(StdConsole::new()))
+  }
+
+  pub fn with_io(io: Box<dyn Console>) -> Machine {
+    Machine::with_backend(Box::new(FlatMemory::new()), io)
+  }
+
+  // runs out of a caller-provided buffer, e.g. a static on targets where
+  // the machine shouldn't allocate its memory; the buffer is used as is
+  pub fn with_memory(mem: &'static mut [u16; MEM_SIZE], io: Box<dyn Console>) -> Machine {
+    Machine::with_backend(Box::new(mem), io)
+  }
+
+  // runs out of a heap buffer without copying it, see
+  // FlatMemory::from_boxed_slice()
+  pub fn from_boxed_slice(mem: Box<[u16]>, io: Box<dyn Console>) -> Machine {
+    Machine::with_backend(Box::new(FlatMemory::from_boxed_slice(mem)), io)
+  }
+
+  // runs out of any Memory implementation, used as is
+  pub fn with_backend(mem: Box<dyn Memory>, io: Box<dyn Console>) -> Machine {
     Machine {
       reg: [0; REG_SIZE],
-      mem: [0; MEM_SIZE],
+      mem,
       halt: true,
+      saved_usp: 0,
+      saved_ssp: SSP,
+      pending: Vec::new(),
+      io,
+      keyboard: self::devices::Keyboard::new(),
+      display: self::devices::Display { out: None },
+      breakpoints: Vec::new(),
+      conditions: BTreeMap::new(),
+      calls: Vec::new(),
+      discipline: None,
+      uninit: None,
+      smc: None,
+      progress: None,
+      watchpoints: Vec::new(),
+      watches: Vec::new(),
+      watch_ids: 0,
+      catch_traps: false,
+      os: false,
+      trap_handlers: BTreeMap::new(),
+      hooks: Vec::new(),
+      cc_audit: false,
+      isa_ext: IsaExtensions::None,
+      strictness: Strictness::Strict,
+      decode_cache: None,
+      #[cfg(feature = "jit")]
+      jit: None,
+      observers: Vec::new(),
+      devices: Vec::new(),
+      stop: None,
+      symbols: SymbolTable::new(),
+      regions: Vec::new(),
+      line_info: Vec::new(),
+      stats: Stats::default(),
+      count: 0,
+      recording: None,
+      coverage: None,
+      profile: None,
+      capture: None,
+      replaying: None,
+      typing: None,
+      history: None,
+      limits: None,
+      events: None,
+      log_filter: [LevelFilter::Trace; self::logging::LOG_TARGETS],
+      #[cfg(feature = "std")]
+      clock: None,
+      #[cfg(feature = "std")]
+      tracer: None,
+      #[cfg(feature = "extensions")]
+      files: self::files::Files::default(),
     }
   }
   
   pub fn init(&mut self) {
     self.halt = false;
     self.setr(PC, 0x3000);
+    self.set_psr(PSR_USER | ZRO);
+  }
+
+  pub fn psr(&self) -> u16 {
+    self.getr(PSR) | self.getr(COND)
+  }
+
+  // a CC field without exactly one of N, Z and P set, e.g. from a
+  // corrupted stack under RTI, keeps its highest set bit, Z when clear
+  pub fn set_psr(&mut self, val: u16) {
+    self.setr(PSR, val & (PSR_USER | PSR_PRIO));
+    let cc: u16 = match val & (NEG | ZRO | POS) {
+      0 => ZRO,
+      cc if cc & NEG != 0 => NEG,
+      cc if cc & ZRO != 0 => ZRO,
+      _ => POS,
+    };
+    self.setr(COND, cc);
+  }
+
+  pub fn user_mode(&self) -> bool {
+    self.getr(PSR) & PSR_USER != 0
+  }
+
+  pub fn priority(&self) -> u16 {
+    (self.getr(PSR) & PSR_PRIO) >> 8
   }
   
+  #[cfg(feature = "std")]
+  pub fn load_image(&mut self, path: &Path) -> io::Result<()> {
+    let bytes: Vec<u8> = fs::read(path)?;
+    Ok(self.load_image_bytes(&bytes)?)
+  }
+
+  pub fn load_image_bytes(&mut self, bytes: &[u8]) -> Result<(), FormatError> {
+    self.load_bytes(bytes, ImageFormat::Obj)
+  }
+
+  // loads an image in any of the supported formats, moving the PC to its
+  // entry point, or to its first segment's origin when it records none
+  pub fn load_bytes(&mut self, bytes: &[u8], format: ImageFormat) -> Result<(), FormatError> {
+    let image: LoadedImage = loader::parse(bytes, format)?;
+    for seg in &image.segments {
+      log_to!(self, Mem, Trace, "loading {} words at {:#06x}", seg.words.len(), seg.origin);
+      for (i, &word) in seg.words.iter().enumerate() {
+        self.store(seg.origin.wrapping_add(i as u16), word);
+      }
+    }
+    if let Some(start) = image.entry.or(image.segments.first().map(|seg| seg.origin)) {
+      self.setr(PC, start);
+    }
+    Ok(())
+  }
+
+  // loads an .obj image, along with a .sym file next to it if there is
+  // one, an Intel HEX .hex image, or assembles an .asm source
+  #[cfg(feature = "std")]
+  pub fn load_program(&mut self, path: &Path) -> Result<(), Box<dyn error::Error>> {
+    if path.extension().is_some_and(|e| e == "asm") {
+      let src: String = fs::read_to_string(path)?;
+      let prog = assembler::assemble_file(&src, path)?;
+      self.load_image_bytes(&prog.to_obj())?;
+      self.symbols.extend(&prog.symbols);
+      self.add_line_info(&path.to_string_lossy(), &prog.lines);
+    } else if path.extension().is_some_and(|e| e == "hex") {
+      self.load_bytes(&fs::read(path)?, ImageFormat::IntelHex)?;
+    } else {
+      self.load_image(path)?;
+      let sym = path.with_extension("sym");
+      if sym.exists() {
+        self.load_symbols(&sym)?;
+      }
+    }
+    Ok(())
+  }
+
+  #[cfg(feature = "std")]
+  pub fn load_symbols(&mut self, path: &Path) -> io::Result<()> {
+    let table: SymbolTable = SymbolTable::load(path)?;
+    self.symbols.extend(&table);
+    Ok(())
+  }
+
+  pub fn symbols(&self) -> &SymbolTable {
+    &self.symbols
+  }
+
+  pub fn symbols_mut(&mut self) -> &mut SymbolTable {
+    &mut self.symbols
+  }
+
+  // instructions executed since the machine was created
+  pub fn instructions(&self) -> u64 {
+    self.count
+  }
+
+  // logs keyboard input and interrupt() calls until stop_recording()
+  pub fn start_recording(&mut self) {
+    self.recording = Some((self.count, Recording::new()));
+  }
+
+  pub fn stop_recording(&mut self) -> Option<Recording> {
+    self.recording.take().map(|(_, rec)| rec)
+  }
+
+  // marks the addresses executed, read and written until stop_coverage()
+  pub fn start_coverage(&mut self) {
+    self.coverage = Some(Coverage::new());
+  }
+
+  pub fn coverage(&self) -> Option<&Coverage> {
+    self.coverage.as_ref()
+  }
+
+  pub fn stop_coverage(&mut self) -> Option<Coverage> {
+    self.coverage.take()
+  }
+
+  // counts the instructions executed by address and call stack until
+  // stop_profile()
+  pub fn start_profile(&mut self) {
+    self.profile = Some(Profile::new(self.getr(PC)));
+  }
+
+  pub fn profile(&self) -> Option<&Profile> {
+    self.profile.as_ref()
+  }
+
+  pub fn stop_profile(&mut self) -> Option<Profile> {
+    self.profile.take()
+  }
+
+  // feeds recorded inputs back at the instruction counts they were observed
+  // at, reading the console again once the recording is exhausted; the
+  // machine must be in the state it was in when recording started
+  pub fn replay(&mut self, recording: &Recording) {
+    let start: u64 = self.count;
+    self.replaying = Some(recording.events.iter()
+      .map(|e| replay::Event { step: start + e.step, input: e.input })
+      .collect());
+  }
+
+  pub fn replaying(&self) -> bool {
+    self.replaying.as_ref().is_some_and(|r| !r.is_empty())
+  }
+
+  fn record(&mut self, input: Input) {
+    let count: u64 = self.count;
+    if let Some((start, rec)) = self.recording.as_mut() {
+      rec.events.push(replay::Event { step: count - *start, input });
+    }
+  }
+
+  // next recorded key, if due at the current step (or at any step when
+  // blocking)
+  fn replay_key(&mut self, blocking: bool) -> Option<u8> {
+    let count: u64 = self.count;
+    let queue = self.replaying.as_mut()?;
+    match queue.front() {
+      Some(&replay::Event { step, input: Input::Key(c) }) if blocking || step <= count => {
+        queue.pop_front();
+        Some(c)
+      },
+      _ => None,
+    }
+  }
+
+  fn replay_interrupts(&mut self) {
+    let count: u64 = self.count;
+    while let Some(&replay::Event { step, input: Input::Interrupt { vector, priority } }) =
+      self.replaying.as_ref().and_then(|q| q.front()) {
+      if step > count {
+        break;
+      }
+      self.replaying.as_mut().unwrap().pop_front();
+      self.raise(vector, priority);
+    }
+  }
+
+  pub fn stats(&self) -> &Stats {
+    &self.stats
+  }
+
+  pub fn reset_stats(&mut self) {
+    self.stats = Stats::default();
+  }
+
+  pub fn read_reg(&self, r: Reg) -> u16 {
+    self.getr(r as u16)
+  }
+
+  pub fn write_reg(&mut self, r: Reg, val: u16) {
+    self.setr(r as u16, val);
+  }
+
+  // raw memory access, bypassing devices and watchpoints
+  pub fn read_mem(&self, addr: u16) -> u16 {
+    self.mem.read(addr)
+  }
+
+  pub fn write_mem(&mut self, addr: u16, val: u16) {
+    self.note_progress();
+    self.note_write(addr);
+    self.store(addr, val);
+  }
+
+  pub fn add_breakpoint(&mut self, addr: u16) {
+    self.conditions.remove(&addr);
+    if !self.breakpoints.contains(&addr) {
+      self.breakpoints.push(addr);
+    }
+  }
+
+  // a breakpoint that stops only if cond holds when PC reaches addr,
+  // replacing any breakpoint there
+  pub fn add_conditional_breakpoint(&mut self, addr: u16, cond: Expr) {
+    self.add_breakpoint(addr);
+    self.conditions.insert(addr, cond);
+  }
+
+  pub fn breakpoint_condition(&self, addr: u16) -> Option<&Expr> {
+    self.conditions.get(&addr)
+  }
+
+  pub fn remove_breakpoint(&mut self, addr: u16) {
+    self.conditions.remove(&addr);
+    self.breakpoints.retain(|&b| b != addr);
+  }
+
+  pub fn breakpoints(&self) -> &[u16] {
+    &self.breakpoints
+  }
+
+  pub fn add_watchpoint(&mut self, addr: u16, kind: WatchKind) {
+    self.remove_watchpoint(addr);
+    self.watchpoints.push((addr, kind));
+  }
+
+  pub fn remove_watchpoint(&mut self, addr: u16) {
+    self.watchpoints.retain(|&(a, _)| a != addr);
+  }
+
+  pub fn watchpoints(&self) -> &[(u16, WatchKind)] {
+    &self.watchpoints
+  }
+
+  // stop with StopReason::Trap whenever a TRAP instruction executes
+  pub fn catch_traps(&mut self, enable: bool) {
+    self.catch_traps = enable;
+  }
+
+  // runs handler in place of the routine for vector, whether built in or
+  // in an OS image. R7 already holds the return address and PC points past
+  // the TRAP when it is called.
+  pub fn set_trap_handler(&mut self, vector: u8, handler: TrapHandler) {
+    self.trap_handlers.insert(vector, handler);
+  }
+
+  pub fn remove_trap_handler(&mut self, vector: u8) {
+    self.trap_handlers.remove(&vector);
+  }
+
+  fn watch(&mut self, addr: u16, write: bool) {
+    if self.stop.is_some() {
+      return;
+    }
+    if let Some(&(a, kind)) = self.watchpoints.iter().find(|&&(a, k)| a == addr && k.matches(write)) {
+      self.stop = Some(StopReason::Watchpoint(a, kind));
+    }
+  }
+
   fn getr(&self, r: u16) -> u16 {
     self.reg[r as usize]
   }
 
   fn setr(&mut self, r: u16, val: u16) {
     self.reg[r as usize] = val;
+    self.mark_reg_written(r);
   }
 
+  // wrapping, as all LC-3 arithmetic is modulo 2^16
   fn addr(&mut self, r: u16, val: u16) {
-    self.reg[r as usize] += val;
+    self.reg[r as usize] = self.reg[r as usize].wrapping_add(val);
   }
 
-  fn getm(&self, addr: u16) -> u16 {
-    self.mem[addr as usize]
+  // effective addresses; PC has already been incremented past the
+  // instruction
+  fn pc_relative(&self, offset: i16) -> u16 {
+    self.getr(PC).wrapping_add(offset as u16)
+  }
+
+  fn base_offset(&self, base: u16, offset: i16) -> u16 {
+    self.getr(base).wrapping_add(offset as u16)
+  }
+
+  fn getm(&mut self, addr: u16) -> u16 {
+    self.watch(addr, false);
+    self.stats.mem_reads += 1;
+    if IO_PAGE.contains(&addr) {
+      self.note_progress();
+    }
+
+    let val: u16 = match self.device_read(addr) {
+      Some(val) => val,
+      // the machine's own register rather than a device's, kept in memory
+      // for snapshots and step_back(). The clock enable bit mirrors halt,
+      // the others are plain storage.
+      None if addr == MCR => self.mem.read(MCR) & !MCR_CLOCK | if self.halt { 0 } else { MCR_CLOCK },
+      None => {
+        self.check_read(addr);
+        self.mem.read(addr)
+      },
+    };
+
+    for o in self.observers.iter_mut() {
+      o.on_read(addr, val);
+    }
+    if let Some(c) = &mut self.coverage {
+      c.mark_read(addr);
+    }
+    val
   }
   
   fn setm(&mut self, addr: u16, val: u16){
-    self.mem[addr as usize] = val;
+    self.watch(addr, true);
+    self.stats.mem_writes += 1;
+
+    let old: u16 = self.mem.read(addr);
+    if old != val || IO_PAGE.contains(&addr) {
+      self.note_progress();
+    }
+    for o in self.observers.iter_mut() {
+      o.on_write(addr, old, val);
+    }
+    if let Some(c) = &mut self.coverage {
+      c.mark_written(addr);
+    }
+
+    match addr {
+      _ if self.device_write(addr, val) => {},
+      MCR => {
+        self.note_write(MCR);
+        self.store(MCR, val & !MCR_CLOCK);
+        if val & MCR_CLOCK == 0 {
+          self.halt = true;
+        }
+      },
+      _ => {
+        self.check_code_write(addr);
+        self.note_write(addr);
+        self.store(addr, val);
+      },
+    }
+  }
+
+  pub fn set_io(&mut self, io: Box<dyn Console>) {
+    self.io = io;
+    self.keyboard.key = None;
+  }
+
+  // reads a line of host input through the machine's console, so frontends
+  // don't race the program for input
+  pub(crate) fn read_line(&mut self) -> Option<String> {
+    let mut line: Vec<u8> = Vec::new();
+    if let Some(c) = self.keyboard.key.take() {
+      line.push(c);
+    }
+    while line.last() != Some(&b'\n') {
+      match self.io.read_char() {
+        Some(c) => line.push(c),
+        None if line.is_empty() => return None,
+        None => break,
+      }
+    }
+    Some(String::from_utf8_lossy(&line).into_owned())
+  }
+
+  fn poll_key(&mut self) {
+    if self.keyboard.key.is_some() {
+      return;
+    }
+    if self.replaying.is_some() {
+      self.keyboard.key = self.replay_key(false);
+    }
+    if self.keyboard.key.is_none() {
+      self.keyboard.key = if self.typing() { self.typed_key(false) } else { self.io.poll_key() };
+      if let Some(c) = self.keyboard.key {
+        self.record(Input::Key(c));
+      }
+    }
+    if let Some(c) = self.keyboard.key {
+      self.note_key(c);
+    }
+  }
+
+  fn push(&mut self, val: u16) {
+    let sp: u16 = self.getr(SP).wrapping_sub(1);
+    self.setr(SP, sp);
+    self.setm(sp, val);
+  }
+
+  fn pop(&mut self) -> u16 {
+    let sp: u16 = self.getr(SP);
+    let val: u16 = self.getm(sp);
+    self.setr(SP, sp.wrapping_add(1));
+    val
+  }
+
+  // requests an interrupt, serviced before the next instruction once its
+  // priority is higher than the running program's
+  pub fn interrupt(&mut self, vector: u8, priority: u8) {
+    self.record(Input::Interrupt { vector, priority });
+    self.raise(vector, priority);
+  }
+
+  fn raise(&mut self, vector: u8, priority: u8) {
+    log_to!(self, Irq, Trace, "raising interrupt {:#04x} at priority {}", vector, priority);
+    self.pending.push((vector, priority & 0x7));
+  }
+
+  fn check_interrupts(&mut self) {
+    if self.replaying.is_some() {
+      self.replay_interrupts();
+    }
+
+    if self.keyboard.ie && !self.pending.iter().any(|&(v, _)| v == INT_KEYBOARD) {
+      self.poll_key();
+      if self.keyboard.key.is_some() {
+        self.raise(INT_KEYBOARD, KBD_PRIORITY);
+      }
+    }
+
+    let next = self.pending.iter()
+      .enumerate()
+      .filter(|&(_, &(_, p))| p as u16 > self.priority())
+      .max_by_key(|&(i, &(_, p))| (p, usize::MAX - i))
+      .map(|(i, _)| i);
+
+    if let Some(i) = next {
+      let (vector, priority) = self.pending.remove(i);
+      self.enter(vector, priority as u16);
+    }
+  }
+
+  fn exception(&mut self, vector: u8) {
+    self.enter(vector, self.priority());
+  }
+
+  // switches to the supervisor stack, saves PSR and PC and jumps through
+  // the vector table
+  fn enter(&mut self, vector: u8, priority: u16) {
+    log_to!(self, Irq, Trace, "entering vector {:#04x}", vector);
+    self.emit(Event::Interrupt(vector));
+    let ret: u16 = self.getr(PC);
+    self.supervisor(priority);
+    let pc: u16 = self.getm(IVT + vector as u16);
+    self.setr(PC, pc);
+    self.note_call(ret, ret, false);
+  }
+
+  // saves PSR and PC on the supervisor stack for RTI
+  fn supervisor(&mut self, priority: u16) {
+    let psr: u16 = self.psr();
+
+    if self.user_mode() {
+      self.saved_usp = self.getr(SP);
+      self.setr(SP, self.saved_ssp);
+    }
+
+    self.push(psr);
+    self.push(self.getr(PC));
+    self.setr(PSR, priority << 8);
+  }
+
+  // user mode may not touch the system space below x3000 or the device
+  // registers
+  pub(super) fn accessible(&self, addr: u16) -> bool {
+    !self.user_mode() || addr >= USER_SPACE && !IO_PAGE.contains(&addr)
+  }
+
+  // false when a data access to addr raised ACV and the instruction should
+  // be abandoned
+  fn access(&mut self, addr: u16) -> Result<bool, MachineError> {
+    if self.accessible(addr) && self.mapped(addr) {
+      return Ok(true);
+    }
+    if self.accessible(addr) {
+      log_to!(self, Mem, Warn, "no device register at {:#06x}", addr);
+      if self.permissive() {
+        return Ok(true);
+      }
+    }
+    self.access_violation(self.getr(PC).wrapping_sub(1), addr)?;
+    Ok(false)
+  }
+
+  fn access_violation(&mut self, pc: u16, addr: u16) -> Result<(), MachineError> {
+    log_to!(self, Mem, Warn, "access violation at {:#06x}", addr);
+    // without a handler installed there is nothing to vector to
+    if self.mem.read(IVT + EXC_ACCESS as u16) == 0 {
+      return Err(MachineError::AccessViolation { pc, addr });
+    }
+    self.exception(EXC_ACCESS);
+    Ok(())
+  }
+
+  // takes the illegal opcode exception, false when no handler is installed
+  // and the fault is left to the host
+  fn illegal_opcode(&mut self) -> bool {
+    if self.mem.read(IVT + EXC_ILLEGAL as u16) == 0 {
+      return false;
+    }
+    self.exception(EXC_ILLEGAL);
+    true
+  }
+
+  fn rti(&mut self) -> Result<(), MachineError> {
+    let at: u16 = self.getr(PC).wrapping_sub(1);
+    if self.user_mode() {
+      log_to!(self, Irq, Warn, "RTI executed in user mode");
+      // without a handler installed there is nothing to vector to
+      if self.mem.read(IVT + EXC_PRIVILEGE as u16) == 0 {
+        return Err(MachineError::PrivilegeViolation { pc: self.getr(PC).wrapping_sub(1) });
+      }
+      self.exception(EXC_PRIVILEGE);
+      return Ok(());
+    }
+
+    let pc: u16 = self.pop();
+    let psr: u16 = self.pop();
+    self.setr(PC, pc);
+    self.set_psr(psr);
+    self.note_return(at);
+
+    if self.user_mode() {
+      self.saved_ssp = self.getr(SP);
+      self.setr(SP, self.saved_usp);
+    }
+
+    Ok(())
   }
 
   fn set_cond(&mut self, r: u16) {
@@ -97,142 +958,426 @@ impl Machine {
     }
   }
 
-  pub fn step(&mut self) {
-    trace!("fetching address {:#06x}", self.getr(PC));
-    let instr: u16 = self.getm(self.getr(PC));
-    self.addr(PC, 1);
+  fn getc(&mut self) -> u16 {
+    if let Some(c) = self.keyboard.key.take() {
+      return c as u16;
+    }
+    let c: Option<u8> = if self.replaying() {
+      self.replay_key(true)
+    } else {
+      let c: Option<u8> = if self.typing() { self.typed_key(true) } else { self.io.read_char() };
+      if let Some(c) = c {
+        self.record(Input::Key(c));
+      }
+      c
+    };
 
-    trace!("read instruction {:#06x}", instr);
+    match c {
+      Some(c) => {
+        self.note_key(c);
+        c as u16
+      },
+      None => 0,
+    }
+  }
 
-    if let Some(op) = OP::from_u16(instr >> 12) {
-      match op {
-        OP::ADD => {
-          let dr: u16 = (instr >> 9) & 0x7;
-          let sr1: u16 = (instr >> 6) & 0x7;
+  fn putc(&mut self, c: u8) {
+    self.emit(Event::OutputChar(c));
+    if self.capture_char(c) {
+      self.io.write_char(c);
+    }
+  }
 
-          if (instr >> 5) & 0x1 == 1 {
-            let imm: u16 = sign_extend(instr & 0x1F, 5);
-            self.setr(dr, self.getr(sr1) + imm);
-          } else {
-            let sr2: u16 = instr & 0x7;
-            self.setr(dr, self.getr(sr1) + self.getr(sr2));
-          }
+  fn flush(&mut self) {
+    self.io.flush();
+  }
+
+  fn trap(&mut self, trap: TRAP) {
+    log_to!(self, Io, Trace, "executing trap {:#x}", trap as u16);
 
-          self.set_cond(dr);
-        },
+    match trap {
+      TRAP::GETC => {
+        let c: u16 = self.getc();
+        self.setr(0x0, c);
+      },
 
-        OP::AND => {
-          let dr: u16 = (instr >> 9) & 0x7;
-          let sr1: u16 = (instr >> 6) & 0x7;
+      TRAP::OUT => {
+        self.putc(self.getr(0x0) as u8);
+        self.flush();
+      },
 
-          if (instr >> 5) & 0x1 == 1 {
-            let imm: u16 = sign_extend(instr & 0x1F, 5);
-            self.setr(dr, self.getr(sr1) & imm);
-          } else {
-            let sr2: u16 = instr & 0x7;
-            self.setr(dr, self.getr(sr1) & self.getr(sr2));
+      TRAP::PUTS => {
+        let mut addr: u16 = self.getr(0x0);
+        loop {
+          let c: u16 = self.getm(addr);
+          if c == 0 {
+            break;
           }
-        },
+          self.putc(c as u8);
+          addr = addr.wrapping_add(1);
+        }
+        self.flush();
+      },
 
-        OP::BR => {
-          let n: bool = ((instr >> 11) & 0x1) == 0;
-          let z: bool = ((instr >> 10) & 0x1) == 0;
-          let p: bool = ((instr >>  9) & 0x1) == 0;
-          let offset: u16 = sign_extend(instr & 0x1FF, 9);
+      TRAP::IN => {
+        for &c in b"Enter a character: " {
+          self.putc(c);
+        }
+        self.flush();
 
-          let N: bool = self.getr(COND) == NEG;
-          let Z: bool = self.getr(COND) == ZRO;
-          let P: bool = self.getr(COND) == POS;
+        let c: u16 = self.getc();
+        self.putc(c as u8);
+        self.flush();
+        self.setr(0x0, c);
+      },
 
-          if (n && N) || (z && Z) || (p || P) {
-            self.addr(PC, offset);
-          }
-        },
-
-        OP::JMP => {
-          let base: u16 = (instr >> 6) & 0x7;
-          self.setr(PC, base);
-        },
-
-        OP::JSR => {
-          self.setr(0x7, self.getr(PC)); 
-
-          if (instr >> 11) & 0x1 == 1 {
-            let offset: u16 = sign_extend(instr & 0x7FF, 11);
-            self.addr(PC, offset);
-          } else {
-            let base: u16 = (instr >> 6) & 0x7;
-            self.setr(PC, base);
+      TRAP::PUTSP => {
+        // two characters per word, low byte first; a null in either byte
+        // ends the string, so odd-length strings end in x00cc
+        let mut addr: u16 = self.getr(0x0);
+        loop {
+          let c: u16 = self.getm(addr);
+          let (lo, hi): (u8, u8) = ((c & 0xFF) as u8, (c >> 8) as u8);
+          if lo == 0 {
+            break;
           }
-        },
-
-        OP::LD => {
-          let dr: u16 = (instr >> 9) & 0x7;
-          let offset: u16 = sign_extend(instr & 0x1FF, 9);
-          self.setr(dr, self.getm(self.getr(PC) + offset));
-          self.set_cond(dr);
-        },
-        
-        OP::LDI => {
-          let dr: u16 = (instr >> 9) & 0x7;
-          let offset: u16 = sign_extend(instr & 0x1FF, 9);
-          self.setr(dr, self.getm(self.getm(self.getr(PC) + offset)));
-          self.set_cond(dr);
-        },
-
-        OP::LDR => {
-          let dr: u16 = (instr >> 9) & 0x7;
-          let base: u16 = (instr >> 6) & 0x7;
-          let offset: u16 = sign_extend(instr & 0x3F, 6);
-          self.setr(dr, self.getm(base + offset));
-        },
-
-        OP::LEA => {
-          let dr: u16 = (instr >> 9) & 0x7;
-          let offset: u16 = sign_extend(instr & 0x1FF, 9);
-          self.setr(dr, self.getr(PC) + offset);
-          self.set_cond(dr);
-        },
-
-        OP::NOT => {
-          let dr: u16 = (instr >> 9) & 0x7;
-          self.setr(dr, !self.getr(dr));
-          self.set_cond(dr);
-        },
-
-        OP::RES | OP::RTI => {
-          warn!("ignoring instruction {:#x}", op as u16);
-        },
-
-        OP::ST => {
-          let sr: u16 = (instr >> 9) & 0x7;
-          let offset = sign_extend(instr & 0x1FF, 9);
-          self.setm(PC + offset, self.getr(sr));
-        },
-
-        OP::STI => {
-          let sr: u16 = (instr >> 9) & 0x7;
-          let offset = sign_extend(instr & 0x1FF, 9);
-          self.setm(self.getm(PC + offset), self.getr(sr));
-        },
-
-        OP::STR => {
-          let sr: u16 = (instr >> 9) & 0x7;
-          let base: u16 = (instr >> 6) & 0x7;
-          let offset = sign_extend(instr & 0x3F, 6);
-          self.setm(base + offset, self.getr(sr));
-        },
-
-        OP::TRAP => {
-          self.setr(0x7, self.getr(PC));
-
-          if let Some(trap) = TRAP::from_u16(instr & 0xFF) {
-            warn!("unimplemented trap {:#x}", trap as u16);
-          } else {
-            panic!("unknow trap {:#x}", instr & 0xFF);
+          self.putc(lo);
+          if hi == 0 {
+            break;
           }
+          self.putc(hi);
+          addr = addr.wrapping_add(1);
+        }
+        self.flush();
+      },
+
+      TRAP::HALT => {
+        for &c in b"\nHALT\n" {
+          self.putc(c);
+        }
+        self.flush();
+        self.halt = true;
+      },
+    }
+  }
+
+  // executes one instruction and reports whether execution should stop
+  pub fn step(&mut self) -> Result<Option<StopReason>, MachineError> {
+    #[cfg(feature = "std")]
+    self.pace();
+
+    self.stop = None;
+    self.begin_delta();
+    let result: Result<(), MachineError> = self.execute();
+    self.end_delta();
+    self.update_watches();
+    if let Err(e) = result {
+      self.emit(Event::Fault(e));
+      return Err(e);
+    }
+
+    if self.halt {
+      self.emit(Event::Halted);
+      return Ok(Some(StopReason::Halt));
+    }
+    if self.stop.is_some() {
+      return Ok(self.stop.take());
+    }
+    if self.breakpoints.contains(&self.getr(PC)) && self.conditions.get(&self.getr(PC)).is_none_or(|c| c.holds(self)) {
+      return Ok(Some(StopReason::Breakpoint(self.getr(PC))));
+    }
+    if self.no_progress() {
+      return Ok(Some(StopReason::NoProgress));
+    }
+    if self.limit_exceeded() {
+      return Ok(Some(StopReason::LimitExceeded));
+    }
+    Ok(None)
+  }
+
+  pub fn run(&mut self) -> Run {
+    self.run_until(|_| false)
+  }
+
+  pub fn run_for(&mut self, n: u64) -> Run {
+    if n == 0 {
+      return Run { steps: 0, reason: StopReason::StepLimit };
+    }
+
+    let mut left: u64 = n;
+    let run: Run = self.run_until(|_| {
+      left -= 1;
+      left == 0
+    });
+
+    match run.reason {
+      StopReason::Condition => Run { steps: run.steps, reason: StopReason::StepLimit },
+      _ => run,
+    }
+  }
+
+  // steps until execution stops or pred, checked after every instruction,
+  // returns true
+  pub fn run_until<F: FnMut(&Machine) -> bool>(&mut self, mut pred: F) -> Run {
+    let mut steps: u64 = 0;
+
+    loop {
+      if self.halt {
+        return Run { steps, reason: StopReason::Halt };
+      }
+
+      #[cfg(feature = "jit")]
+      if self.jit_ready() {
+        if let Some(run) = self.run_block(&mut steps, &mut pred) {
+          return run;
         }
+        continue;
       }
+
+      let result = self.step();
+      steps += 1;
+
+      match result {
+        Ok(Some(reason)) => return Run { steps, reason },
+        Ok(None) => {},
+        Err(e) => return Run { steps, reason: StopReason::Fault(e) },
+      }
+
+      if pred(self) {
+        return Run { steps, reason: StopReason::Condition };
+      }
+    }
+  }
+
+  fn execute(&mut self) -> Result<(), MachineError> {
+    if !self.devices.is_empty() {
+      self.tick_devices();
+    }
+    self.check_interrupts();
+
+    let pc: u16 = self.getr(PC);
+    log_to!(self, Exec, Trace, "fetching address {:#06x}", pc);
+    // PC is left on the instruction, RTI from the handler retries it
+    if !self.accessible(pc) {
+      return self.access_violation(pc, pc);
+    }
+    let (instr, op): (u16, Instruction) = self.fetch(pc);
+    self.check_operands(pc, op)?;
+    self.mark_code(pc);
+    self.emit(Event::Fetched { pc, instr });
+    for o in self.observers.iter_mut() {
+      o.on_fetch(pc, instr);
+    }
+    if let Some(c) = &mut self.coverage {
+      c.mark_executed(pc);
+    }
+    if self.profile.is_some() {
+      self.profile_sample(pc);
+    }
+    if !self.hooks.is_empty() {
+      self.run_hooks(true, pc, op);
     }
+    self.addr(PC, 1);
+
+    self.stats.instructions += 1;
+    self.count += 1;
+    self.stats.opcodes[(instr >> 12) as usize] += 1;
+    log_to!(self, Exec, Trace, "read instruction {:#06x} ({})", instr, disasm::disassemble(instr, pc, &self.symbols));
+
+    let cc: u16 = self.getr(COND);
+    #[cfg(feature = "std")]
+    let before: Option<[u16; REG_SIZE]> = self.tracer.as_ref().map(|_| self.reg);
+    self.dispatch(op)?;
+    self.uninit_fault()?;
+    self.smc_fault()?;
+    if self.cc_audit {
+      self.audit_cc(pc, op, cc)?;
+    }
+    #[cfg(feature = "std")]
+    if let Some(before) = before {
+      self.write_trace(pc, instr, &before);
+    }
+
+    if !self.hooks.is_empty() {
+      self.run_hooks(false, pc, op);
+    }
+    Ok(())
+  }
+
+  fn dispatch(&mut self, op: Instruction) -> Result<(), MachineError> {
+    match op {
+      Instruction::Add { dr, sr1, sr2 } => {
+        self.setr(dr, self.getr(sr1).wrapping_add(self.getr(sr2)));
+        self.set_cond(dr);
+      },
+
+      Instruction::AddI { dr, sr1, imm } => {
+        self.setr(dr, self.getr(sr1).wrapping_add(imm as u16));
+        self.set_cond(dr);
+      },
+
+      Instruction::And { dr, sr1, sr2 } => {
+        self.setr(dr, self.getr(sr1) & self.getr(sr2));
+        self.set_cond(dr);
+      },
+
+      Instruction::AndI { dr, sr1, imm } => {
+        self.setr(dr, self.getr(sr1) & imm as u16);
+        self.set_cond(dr);
+      },
+
+      Instruction::Br { n, z, p, offset } => {
+        let cond: u16 = self.getr(COND);
+
+        if (n && cond == NEG) || (z && cond == ZRO) || (p && cond == POS) {
+          self.addr(PC, offset as u16);
+        }
+      },
+
+      Instruction::Jmp { base } => {
+        let at: u16 = self.getr(PC).wrapping_sub(1);
+        self.setr(PC, self.getr(base));
+        self.note_return(at);
+      },
+
+      Instruction::Jsr { offset } => {
+        self.setr(0x7, self.getr(PC));
+        self.addr(PC, offset as u16);
+        self.note_call(self.getr(0x7).wrapping_sub(1), self.getr(0x7), true);
+      },
+
+      Instruction::Jsrr { base } => {
+        // read before R7 is overwritten, JSRR R7 jumps to the old value
+        let target: u16 = self.getr(base);
+        self.setr(0x7, self.getr(PC));
+        self.setr(PC, target);
+        self.note_call(self.getr(0x7).wrapping_sub(1), self.getr(0x7), true);
+      },
+
+      Instruction::Ld { dr, offset } => {
+        let addr: u16 = self.pc_relative(offset);
+        if !self.access(addr)? {
+          return Ok(());
+        }
+        let val: u16 = self.getm(addr);
+        self.setr(dr, val);
+        self.set_cond(dr);
+      },
+
+      Instruction::Ldi { dr, offset } => {
+        let ptr: u16 = self.pc_relative(offset);
+        if !self.access(ptr)? {
+          return Ok(());
+        }
+        let addr: u16 = self.getm(ptr);
+        if !self.access(addr)? {
+          return Ok(());
+        }
+        let val: u16 = self.getm(addr);
+        self.setr(dr, val);
+        self.set_cond(dr);
+      },
+
+      Instruction::Ldr { dr, base, offset } => {
+        let addr: u16 = self.base_offset(base, offset);
+        if !self.access(addr)? {
+          return Ok(());
+        }
+        let val: u16 = self.getm(addr);
+        self.setr(dr, val);
+        self.set_cond(dr);
+      },
+
+      Instruction::Lea { dr, offset } => {
+        self.setr(dr, self.pc_relative(offset));
+        self.set_cond(dr);
+      },
+
+      Instruction::Not { dr, sr } => {
+        self.setr(dr, !self.getr(sr));
+        self.set_cond(dr);
+      },
+
+      Instruction::Rti => self.rti()?,
+
+      Instruction::Res { word } if self.extended(word) => {},
+
+      Instruction::Res { word } => {
+        log_to!(self, Exec, Warn, "illegal opcode {:#06x}", word);
+        if !self.permissive() && !self.illegal_opcode() {
+          return Err(MachineError::IllegalOpcode { pc: self.getr(PC).wrapping_sub(1), instr: word });
+        }
+      },
+
+      Instruction::St { sr, offset } => {
+        let addr: u16 = self.pc_relative(offset);
+        if !self.access(addr)? {
+          return Ok(());
+        }
+        self.setm(addr, self.getr(sr));
+      },
+
+      Instruction::Sti { sr, offset } => {
+        let ptr: u16 = self.pc_relative(offset);
+        if !self.access(ptr)? {
+          return Ok(());
+        }
+        let addr: u16 = self.getm(ptr);
+        if !self.access(addr)? {
+          return Ok(());
+        }
+        self.setm(addr, self.getr(sr));
+      },
+
+      Instruction::Str { sr, base, offset } => {
+        let addr: u16 = self.base_offset(base, offset);
+        if !self.access(addr)? {
+          return Ok(());
+        }
+        self.setm(addr, self.getr(sr));
+      },
+
+      Instruction::Trap { vector } => {
+        self.note_progress();
+        self.emit(Event::TrapEntered(vector));
+        self.stats.traps[vector as usize] += 1;
+        self.setr(0x7, self.getr(PC));
+
+        if self.catch_traps {
+          self.stop = Some(StopReason::Trap(vector));
+        }
+
+        // taken out while it runs so it can borrow the machine
+        if let Some(mut handler) = self.trap_handlers.remove(&vector) {
+          handler(self);
+          self.trap_handlers.entry(vector).or_insert(handler);
+          return Ok(());
+        }
+        #[cfg(feature = "extensions")]
+        if self.file_trap(vector) {
+          return Ok(());
+        }
+
+        if self.os {
+          self.supervisor(self.priority());
+          let pc: u16 = self.getm(vector as u16);
+          self.setr(PC, pc);
+          self.note_call(self.getr(0x7).wrapping_sub(1), self.getr(0x7), false);
+          return Ok(());
+        }
+
+        match TRAP::from_u8(vector) {
+          Some(trap) => self.trap(trap),
+          None => {
+            log_to!(self, Exec, Warn, "unknown trap {:#04x}", vector);
+            if !self.permissive() && !self.illegal_opcode() {
+              return Err(MachineError::UnknownTrap { pc: self.getr(PC).wrapping_sub(1), vector });
+            }
+          },
+        }
+      },
+    }
+
+    Ok(())
   }
 }
@@ -0,0 +1,45 @@
+use super::*;
+
+// condition codes an instruction that writes dr must leave, computed
+// independently of set_cond()
+fn reference_cc(val: u16) -> u16 {
+  match val as i16 {
+    0 => ZRO,
+    v if v < 0 => NEG,
+    _ => POS,
+  }
+}
+
+impl Machine {
+  // checks the condition codes after every instruction against a
+  // reference model, faulting with MachineError::ConditionCodes on a
+  // mismatch. Meant for testing the interpreter, it costs a little on
+  // every step.
+  pub fn set_cc_audit(&mut self, enable: bool) {
+    self.cc_audit = enable;
+  }
+
+  pub(super) fn audit_cc(&self, pc: u16, op: Instruction, before: u16) -> Result<(), MachineError> {
+    let expected: u16 = match op {
+      Instruction::Add { dr, .. } | Instruction::AddI { dr, .. } |
+      Instruction::And { dr, .. } | Instruction::AndI { dr, .. } |
+      Instruction::Not { dr, .. } | Instruction::Lea { dr, .. } |
+      Instruction::Ld { dr, .. } | Instruction::Ldi { dr, .. } | Instruction::Ldr { dr, .. } =>
+        reference_cc(self.getr(dr)),
+
+      Instruction::Br { .. } | Instruction::Jmp { .. } | Instruction::Jsr { .. } |
+      Instruction::Jsrr { .. } | Instruction::St { .. } | Instruction::Sti { .. } |
+      Instruction::Str { .. } => before,
+
+      // RTI restores saved codes, trap routines and exception handlers may
+      // leave anything
+      Instruction::Rti | Instruction::Trap { .. } | Instruction::Res { .. } => return Ok(()),
+    };
+
+    let found: u16 = self.getr(COND);
+    if found != expected {
+      return Err(MachineError::ConditionCodes { pc, expected, found });
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,151 @@
+use super::*;
+#[cfg(not(feature = "std"))]
+use console::NullConsole;
+
+// configures a Machine before it starts: Machine::builder().load(&words).build()
+// gives a machine in user mode with words at x3000 and PC pointing there
+pub struct MachineBuilder {
+  origin: u16,
+  words: Vec<u16>,
+  io: Option<Box<dyn Console>>,
+  memory: Option<Box<dyn Memory>>,
+  devices: Vec<Box<dyn Device>>,
+  os: bool,
+  supervisor: bool,
+  isa_ext: IsaExtensions,
+  uninit: UninitCheck,
+  randomize: Option<u16>,
+}
+
+impl Default for MachineBuilder {
+  fn default() -> MachineBuilder {
+    MachineBuilder { origin: 0x3000, words: Vec::new(), io: None, memory: None, devices: Vec::new(), os: false, supervisor: false, isa_ext: IsaExtensions::None, uninit: UninitCheck::Off, randomize: None }
+  }
+}
+
+impl MachineBuilder {
+  pub fn new() -> MachineBuilder {
+    MachineBuilder::default()
+  }
+
+  // where load() places its words and execution starts
+  pub fn origin(mut self, addr: u16) -> MachineBuilder {
+    self.origin = addr;
+    self
+  }
+
+  // appends words to the image loaded at origin
+  pub fn load(mut self, words: &[u16]) -> MachineBuilder {
+    self.words.extend_from_slice(words);
+    self
+  }
+
+  // the console used by TRAPs and the devices, StdConsole by default
+  pub fn io(mut self, io: Box<dyn Console>) -> MachineBuilder {
+    self.io = Some(io);
+    self
+  }
+
+  // runs out of a caller-provided buffer, see Machine::with_memory()
+  pub fn memory(mut self, mem: &'static mut [u16; MEM_SIZE]) -> MachineBuilder {
+    self.memory = Some(Box::new(mem));
+    self
+  }
+
+  // runs out of another memory backend, see Machine::with_backend()
+  pub fn backend(mut self, mem: Box<dyn Memory>) -> MachineBuilder {
+    self.memory = Some(mem);
+    self
+  }
+
+  // attaches a device to the bus, see Machine::add_device()
+  pub fn device(mut self, dev: Box<dyn Device>) -> MachineBuilder {
+    self.devices.push(dev);
+    self
+  }
+
+  // loads the OS image, see Machine::init_with_os()
+  pub fn os(mut self, os: bool) -> MachineBuilder {
+    self.os = os;
+    self
+  }
+
+  // starts the program in supervisor mode and on the supervisor stack, free
+  // to reach the whole address space including the device registers
+  pub fn supervisor(mut self, supervisor: bool) -> MachineBuilder {
+    self.supervisor = supervisor;
+    self
+  }
+
+  // gives the reserved opcode one of the extensions, see IsaExtensions
+  pub fn isa_extensions(mut self, ext: IsaExtensions) -> MachineBuilder {
+    self.isa_ext = ext;
+    self
+  }
+
+  // tracks reads of never-written memory and registers from the start, so
+  // the OS and the program loaded count as written
+  pub fn uninit_check(mut self, check: UninitCheck) -> MachineBuilder {
+    self.uninit = check;
+    self
+  }
+
+  // starts from pseudo-random registers and memory, the OS and the
+  // program loaded over them, see Machine::init_randomized()
+  pub fn randomize(mut self, seed: u16) -> MachineBuilder {
+    self.randomize = Some(seed);
+    self
+  }
+
+  pub fn build(self) -> Machine {
+    let io: Box<dyn Console> = match self.io {
+      Some(io) => io,
+      None => default_console(),
+    };
+    let mut m: Machine = match self.memory {
+      Some(mem) => Machine::with_backend(mem, io),
+      None => Machine::with_io(io),
+    };
+
+    m.set_uninit_check(self.uninit);
+    if let Some(seed) = self.randomize {
+      m.init_randomized(seed);
+    }
+    for dev in self.devices {
+      m.add_device(dev);
+    }
+    m.isa_ext = self.isa_ext;
+    if self.os {
+      m.init_with_os();
+    } else {
+      m.init();
+    }
+
+    for (i, &word) in self.words.iter().enumerate() {
+      m.store(self.origin.wrapping_add(i as u16), word);
+    }
+    m.setr(PC, self.origin);
+    if self.supervisor {
+      m.setr(PSR, m.getr(PSR) & !PSR_USER);
+      m.saved_usp = m.getr(SP);
+      m.setr(SP, m.saved_ssp);
+    }
+    m
+  }
+}
+
+#[cfg(feature = "std")]
+fn default_console() -> Box<dyn Console> {
+  Box::new(StdConsole::new())
+}
+
+#[cfg(not(feature = "std"))]
+fn default_console() -> Box<dyn Console> {
+  Box::new(NullConsole)
+}
+
+impl Machine {
+  pub fn builder() -> MachineBuilder {
+    MachineBuilder::new()
+  }
+}
@@ -0,0 +1,65 @@
+use super::*;
+
+// instructions decoded at each address with the word they came from, so
+// hot loops skip the memory backend and decode(). Entries are dropped when
+// the machine writes their address; a Memory backend changed behind the
+// machine's back isn't noticed.
+pub(super) struct DecodeCache {
+  entries: Vec<Option<(u16, Instruction)>>,
+}
+
+impl DecodeCache {
+  fn new() -> DecodeCache {
+    DecodeCache { entries: vec![None; MEM_SIZE] }
+  }
+
+  pub(super) fn invalidate(&mut self, addr: u16) {
+    self.entries[addr as usize] = None;
+  }
+}
+
+impl Machine {
+  // caches decoded instructions per address, for long runs of code that
+  // doesn't modify itself much
+  pub fn set_decode_cache(&mut self, enable: bool) {
+    if enable != self.decode_cache.is_some() {
+      self.decode_cache = if enable { Some(Box::new(DecodeCache::new())) } else { None };
+    }
+  }
+
+  pub fn decode_cache_enabled(&self) -> bool {
+    self.decode_cache.is_some()
+  }
+
+  // the word at addr and its decoding
+  pub(super) fn fetch(&mut self, addr: u16) -> (u16, Instruction) {
+    let cache: &mut DecodeCache = match &mut self.decode_cache {
+      Some(cache) => cache,
+      None => {
+        let word: u16 = self.mem.read(addr);
+        return (word, decode(word));
+      },
+    };
+    if let Some(entry) = cache.entries[addr as usize] {
+      return entry;
+    }
+    let word: u16 = self.mem.read(addr);
+    let entry: (u16, Instruction) = (word, decode(word));
+    cache.entries[addr as usize] = Some(entry);
+    entry
+  }
+
+  // writes memory, keeping the decode cache and translated blocks up to
+  // date
+  pub(super) fn store(&mut self, addr: u16, val: u16) {
+    if let Some(cache) = &mut self.decode_cache {
+      cache.invalidate(addr);
+    }
+    #[cfg(feature = "jit")]
+    if let Some(jit) = &mut self.jit {
+      jit.invalidate(addr);
+    }
+    self.mark_written(addr);
+    self.mem.write(addr, val);
+  }
+}
@@ -0,0 +1,86 @@
+use super::*;
+
+// calls kept on the shadow stack; past this the oldest are forgotten, e.g.
+// under runaway recursion
+const CALL_DEPTH: usize = 1024;
+
+// a subroutine, trap or interrupt entered and not yet returned from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Call {
+  site: u16,
+  entry: u16,
+  ret: u16,
+  // R0-R7 at a JSR or JSRR, for check_return()
+  regs: Option<[u16; 8]>,
+}
+
+// one frame of a backtrace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallFrame {
+  // where the frame is executing: PC for the innermost, the call site
+  // for the others
+  pub pc: u16,
+  // the address the frame was called at, None for the outermost
+  pub entry: Option<u16>,
+  // the label at entry, or else the nearest label before pc
+  pub name: Option<String>,
+}
+
+impl Machine {
+  // the shadow call stack, maintained heuristically: JSR, JSRR, TRAPs into
+  // the OS and interrupts push a call, and a JMP (RET, usually) or RTI to
+  // the return address of a call pops it and those above it. Subroutines
+  // that return some other way are left on the stack until an outer one
+  // returns. subroutine is false for TRAPs and interrupts.
+  pub(super) fn note_call(&mut self, site: u16, ret: u16, subroutine: bool) {
+    if self.calls.len() == CALL_DEPTH {
+      self.calls.remove(0);
+    }
+    let mut regs: [u16; 8] = [0; 8];
+    regs.copy_from_slice(&self.reg[..8]);
+    let entry: u16 = self.getr(PC);
+    self.calls.push(Call { site, entry, ret, regs: if subroutine { Some(regs) } else { None } });
+    if let Some(p) = &mut self.profile {
+      p.note_call(entry);
+    }
+  }
+
+  pub(super) fn profile_sample(&mut self, pc: u16) {
+    if let Some(p) = &mut self.profile {
+      p.sample(pc, self.calls.iter().map(|c| c.entry));
+    }
+  }
+
+  // after the JMP or RTI at pc
+  pub(super) fn note_return(&mut self, pc: u16) {
+    let target: u16 = self.getr(PC);
+    if let Some(i) = self.calls.iter().rposition(|c| c.ret == target) {
+      if let Some(regs) = self.calls[i].regs {
+        self.check_return(pc, self.calls[i].entry, &regs);
+      }
+      self.calls.truncate(i);
+    }
+  }
+
+  pub fn call_depth(&self) -> usize {
+    self.calls.len()
+  }
+
+  // the frames of the call stack, innermost first
+  pub fn backtrace(&self) -> Vec<CallFrame> {
+    let mut frames: Vec<CallFrame> = Vec::with_capacity(self.calls.len() + 1);
+    let mut pc: u16 = self.getr(PC);
+    for call in self.calls.iter().rev() {
+      frames.push(self.frame(pc, Some(call.entry)));
+      pc = call.site;
+    }
+    frames.push(self.frame(pc, None));
+    frames
+  }
+
+  fn frame(&self, pc: u16, entry: Option<u16>) -> CallFrame {
+    let name: Option<&str> = entry.and_then(|e| self.symbols.label(e))
+      .or_else(|| self.symbols.enclosing(pc).map(|(name, _)| name));
+    CallFrame { pc, entry, name: name.map(|n| n.to_string()) }
+  }
+}
@@ -0,0 +1,45 @@
+use core::mem;
+
+use super::*;
+
+// display output kept for take_output()
+pub(super) struct Capture {
+  out: String,
+  // whether the console still gets it
+  tee: bool,
+}
+
+impl Machine {
+  // keeps everything the program writes to the display, by OUT, PUTS,
+  // PUTSP or a store to DDR, with tee still writing it to the console too.
+  // Each byte is a char, so output that isn't ASCII reads as Latin-1.
+  pub fn capture_output(&mut self, tee: bool) {
+    self.capture = Some(Capture { out: String::new(), tee });
+  }
+
+  // the output captured since capturing started or the last
+  // take_output(), empty when not capturing
+  pub fn output(&self) -> &str {
+    self.capture.as_ref().map_or("", |c| c.out.as_str())
+  }
+
+  pub fn take_output(&mut self) -> String {
+    self.capture.as_mut().map(|c| mem::take(&mut c.out)).unwrap_or_default()
+  }
+
+  // stops capturing, giving what hasn't been taken
+  pub fn stop_capture(&mut self) -> String {
+    self.capture.take().map(|c| c.out).unwrap_or_default()
+  }
+
+  // keeps c if capturing, and whether the console should get it
+  pub(super) fn capture_char(&mut self, c: u8) -> bool {
+    match &mut self.capture {
+      Some(cap) => {
+        cap.out.push(c as char);
+        cap.tee
+      },
+      None => true,
+    }
+  }
+}
@@ -0,0 +1,47 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::*;
+
+// if execution falls further behind than this, e.g. while a debugger has
+// the machine stopped, pacing restarts from now instead of catching up
+const MAX_LAG: Duration = Duration::from_millis(100);
+
+pub(super) struct Clock {
+  hz: u32,
+  start: Instant,
+  count: u64, // instruction count at start
+}
+
+impl Machine {
+  // limits execution to hz instructions per second; 0, the default, runs
+  // as fast as the host allows
+  pub fn set_clock_hz(&mut self, hz: u32) {
+    self.clock = match hz {
+      0 => None,
+      hz => Some(Clock { hz, start: Instant::now(), count: self.count }),
+    };
+  }
+
+  pub fn clock_hz(&self) -> u32 {
+    self.clock.as_ref().map_or(0, |c| c.hz)
+  }
+
+  // sleeps until the next instruction is due
+  pub(super) fn pace(&mut self) {
+    let count: u64 = self.count;
+    let clock: &mut Clock = match self.clock.as_mut() {
+      Some(c) => c,
+      None => return,
+    };
+
+    let due: Duration = Duration::from_secs_f64(count.saturating_sub(clock.count) as f64 / clock.hz as f64);
+    let elapsed: Duration = clock.start.elapsed();
+    if due > elapsed {
+      thread::sleep(due - elapsed);
+    } else if elapsed - due > MAX_LAG {
+      clock.start = Instant::now();
+      clock.count = count;
+    }
+  }
+}
@@ -0,0 +1,391 @@
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::ops::RangeInclusive;
+
+use super::*;
+
+// the I/O page, where device registers live
+pub const IO_PAGE: RangeInclusive<u16> = 0xFE00..=0xFFFF;
+
+// a memory-mapped device on the machine's bus, see add_device(). Loads and
+// stores to addresses in addr_range() go to the device instead of memory;
+// instruction fetches don't. tick() runs once before every instruction and
+// may request an interrupt as (vector, priority).
+pub trait Device {
+  fn addr_range(&self) -> RangeInclusive<u16>;
+
+  fn read(&mut self, addr: u16) -> u16;
+
+  fn write(&mut self, addr: u16, val: u16);
+
+  fn tick(&mut self) -> Option<(u8, u8)> {
+    None
+  }
+
+  // internal state as words, kept in snapshots and handed back to
+  // restore(); devices with nothing worth keeping leave both alone
+  fn save(&self) -> Vec<u16> {
+    Vec::new()
+  }
+
+  fn restore(&mut self, _state: &[u16]) {}
+}
+
+// the built-in keyboard: KBSR, with KBSR_READY set while a key waits in
+// KBDR and KBSR_IE enabling its interrupt, and KBDR, a load from which
+// takes the key. Keys come from the console, the input script or a
+// replay, which the machine checks for before the registers are read.
+pub(super) struct Keyboard {
+  pub(super) key: Option<u8>,
+  pub(super) ie: bool,
+  // KBSR was read without a key waiting, see run_async()
+  pub(super) starved: bool,
+}
+
+impl Keyboard {
+  pub(super) fn new() -> Keyboard {
+    Keyboard { key: None, ie: false, starved: false }
+  }
+}
+
+impl Device for Keyboard {
+  fn addr_range(&self) -> RangeInclusive<u16> {
+    KBSR..=KBDR
+  }
+
+  fn read(&mut self, addr: u16) -> u16 {
+    match addr {
+      KBSR => {
+        self.starved = self.key.is_none();
+        let ready: u16 = if self.key.is_some() { KBSR_READY } else { 0 };
+        let ie: u16 = if self.ie { KBSR_IE } else { 0 };
+        ready | ie
+      },
+      KBDR => self.key.take().map_or(0, |c| c as u16),
+      _ => 0,
+    }
+  }
+
+  fn write(&mut self, addr: u16, val: u16) {
+    if addr == KBSR {
+      self.ie = val & KBSR_IE != 0;
+    }
+  }
+
+  // the key as 0x100 | key, 0 for none
+  fn save(&self) -> Vec<u16> {
+    vec![self.ie as u16, self.key.map_or(0, |c| 0x100 | c as u16)]
+  }
+
+  fn restore(&mut self, state: &[u16]) {
+    if let &[ie, key] = state {
+      self.ie = ie != 0;
+      self.key = if key & 0x100 != 0 { Some(key as u8) } else { None };
+    }
+  }
+}
+
+// the built-in display: DSR, always ready, and DDR, the chars stored to
+// which the machine writes to the console once the store is done
+pub(super) struct Display {
+  pub(super) out: Option<u8>,
+}
+
+impl Device for Display {
+  fn addr_range(&self) -> RangeInclusive<u16> {
+    DSR..=DDR
+  }
+
+  fn read(&mut self, addr: u16) -> u16 {
+    if addr == DSR { 1 << 15 } else { 0 }
+  }
+
+  fn write(&mut self, addr: u16, val: u16) {
+    if addr == DDR {
+      self.out = Some(val as u8);
+    }
+  }
+}
+
+pub const TMR_READY : u16 = 1 << 15;
+pub const TMR_IE    : u16 = 1 << 14;
+
+// interval timer with two registers, spaced like the keyboard's: status at
+// base, with TMR_READY set once the interval has elapsed since it was last
+// read and TMR_IE enabling its interrupt, and the interval in instructions
+// at base + 2, zero stopping it. Timer::default() is the TMR/TMI timer of
+// the textbook exercises, interrupting through INT_TIMER; counting
+// instructions instead of milliseconds keeps runs reproducible.
+pub struct Timer {
+  base: u16,
+  vector: u8,
+  priority: u8,
+  interval: u16,
+  count: u16,
+  ready: bool,
+  ie: bool,
+}
+
+impl Timer {
+  pub fn new(base: u16, vector: u8, priority: u8) -> Timer {
+    Timer { base, vector, priority, interval: 0, count: 0, ready: false, ie: false }
+  }
+}
+
+impl Default for Timer {
+  fn default() -> Timer {
+    Timer::new(TMR, INT_TIMER, TIMER_PRIORITY)
+  }
+}
+
+impl Device for Timer {
+  fn addr_range(&self) -> RangeInclusive<u16> {
+    self.base..=self.base.wrapping_add(2)
+  }
+
+  fn read(&mut self, addr: u16) -> u16 {
+    if addr == self.base {
+      let status: u16 = if self.ready { TMR_READY } else { 0 } | if self.ie { TMR_IE } else { 0 };
+      self.ready = false;
+      status
+    } else if addr == self.base.wrapping_add(2) {
+      self.interval
+    } else {
+      0
+    }
+  }
+
+  fn write(&mut self, addr: u16, val: u16) {
+    if addr == self.base {
+      self.ie = val & TMR_IE != 0;
+    } else if addr == self.base.wrapping_add(2) {
+      self.interval = val;
+      self.count = 0;
+    }
+  }
+
+  fn tick(&mut self) -> Option<(u8, u8)> {
+    if self.interval == 0 {
+      return None;
+    }
+    self.count += 1;
+    if self.count < self.interval {
+      return None;
+    }
+    self.count = 0;
+    self.ready = true;
+    if self.ie { Some((self.vector, self.priority)) } else { None }
+  }
+
+  fn save(&self) -> Vec<u16> {
+    vec![self.interval, self.count, self.ready as u16, self.ie as u16]
+  }
+
+  fn restore(&mut self, state: &[u16]) {
+    if let &[interval, count, ready, ie] = state {
+      self.interval = interval;
+      self.count = count;
+      self.ready = ready != 0;
+      self.ie = ie != 0;
+    }
+  }
+}
+
+// pseudo-random numbers at one register, RNG by default: loads return the
+// next word of an xorshift sequence, stores seed it. The same seed always
+// gives the same sequence, so runs can be reproduced.
+pub struct Random {
+  addr: u16,
+  state: u32,
+}
+
+impl Random {
+  pub fn new(addr: u16, seed: u16) -> Random {
+    let mut rng = Random { addr, state: 0 };
+    rng.seed(seed);
+    rng
+  }
+
+  pub fn seed(&mut self, seed: u16) {
+    // never zero, where xorshift gets stuck
+    self.state = 0x9E37_79B9 ^ seed as u32;
+  }
+
+  pub fn next_word(&mut self) -> u16 {
+    let mut x: u32 = self.state;
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    self.state = x;
+    (x >> 16) as u16
+  }
+}
+
+impl Default for Random {
+  fn default() -> Random {
+    Random::new(RNG, 0)
+  }
+}
+
+impl Device for Random {
+  fn addr_range(&self) -> RangeInclusive<u16> {
+    self.addr..=self.addr
+  }
+
+  fn read(&mut self, _addr: u16) -> u16 {
+    self.next_word()
+  }
+
+  fn write(&mut self, _addr: u16, val: u16) {
+    self.seed(val);
+  }
+
+  fn save(&self) -> Vec<u16> {
+    vec![(self.state >> 16) as u16, self.state as u16]
+  }
+
+  fn restore(&mut self, state: &[u16]) {
+    if let &[hi, lo] = state {
+      self.state = (hi as u32) << 16 | lo as u32;
+    }
+  }
+}
+
+pub const MBX_READY : u16 = 1 << 15;
+
+// one end of a set of mailboxes connecting machines, e.g. in a Cluster.
+// Mailbox::network(n) gives a device per machine, each with its own inbox
+// of words. Registers from base, MBSR by default:
+//
+//   base      status, MBX_READY while the inbox isn't empty
+//   base + 2  data, taking the oldest word from the inbox, zero when empty
+//   base + 4  reads as this mailbox's number, stores pick where sends go
+//   base + 6  sends a word to the picked mailbox
+pub struct Mailbox {
+  base: u16,
+  id: u16,
+  dest: u16,
+  inboxes: Rc<RefCell<Vec<VecDeque<u16>>>>,
+}
+
+impl Mailbox {
+  pub fn network(n: usize) -> Vec<Mailbox> {
+    let inboxes: Rc<RefCell<Vec<VecDeque<u16>>>> = Rc::new(RefCell::new(vec![VecDeque::new(); n]));
+    (0..n).map(|i| Mailbox { base: MBSR, id: i as u16, dest: 0, inboxes: inboxes.clone() }).collect()
+  }
+
+  // words waiting in this mailbox's inbox
+  pub fn pending(&self) -> usize {
+    self.inboxes.borrow()[self.id as usize].len()
+  }
+}
+
+impl Device for Mailbox {
+  fn addr_range(&self) -> RangeInclusive<u16> {
+    self.base..=self.base.wrapping_add(6)
+  }
+
+  fn read(&mut self, addr: u16) -> u16 {
+    match addr.wrapping_sub(self.base) {
+      0 if self.pending() > 0 => MBX_READY,
+      2 => self.inboxes.borrow_mut()[self.id as usize].pop_front().unwrap_or(0),
+      4 => self.id,
+      _ => 0,
+    }
+  }
+
+  fn write(&mut self, addr: u16, val: u16) {
+    match addr.wrapping_sub(self.base) {
+      4 => self.dest = val,
+      // sends to a mailbox that doesn't exist are dropped
+      6 => if let Some(inbox) = self.inboxes.borrow_mut().get_mut(self.dest as usize) {
+        inbox.push_back(val);
+      },
+      _ => {},
+    }
+  }
+
+  // the picked destination, then the words in the inbox
+  fn save(&self) -> Vec<u16> {
+    let mut state: Vec<u16> = vec![self.dest];
+    state.extend(self.inboxes.borrow()[self.id as usize].iter());
+    state
+  }
+
+  fn restore(&mut self, state: &[u16]) {
+    if let Some((&dest, words)) = state.split_first() {
+      self.dest = dest;
+      self.inboxes.borrow_mut()[self.id as usize] = words.iter().cloned().collect();
+    }
+  }
+}
+
+impl Machine {
+  // devices take over their addresses from the built-in keyboard, display
+  // and machine control registers, and from each other in the order they
+  // were added. Their ranges have to be within IO_PAGE.
+  pub fn add_device(&mut self, dev: Box<dyn Device>) {
+    let range: RangeInclusive<u16> = dev.addr_range();
+    assert!(IO_PAGE.contains(range.start()) && IO_PAGE.contains(range.end()),
+      "device registers x{:04X}-x{:04X} outside of the I/O page", range.start(), range.end());
+    self.devices.push(dev);
+  }
+
+  pub fn clear_devices(&mut self) {
+    self.devices.clear();
+  }
+
+  // whether addr is memory or a register something decodes, rather than a
+  // hole in the I/O page
+  pub(super) fn mapped(&self, addr: u16) -> bool {
+    !IO_PAGE.contains(&addr) || [KBSR, KBDR, DSR, DDR, MCR].contains(&addr)
+      || self.devices.iter().any(|d| d.addr_range().contains(&addr))
+  }
+
+  // the device decoding addr, an added one or the keyboard or display
+  fn device_at(&mut self, addr: u16) -> Option<&mut dyn Device> {
+    if addr < *IO_PAGE.start() {
+      return None;
+    }
+    if let Some(d) = self.devices.iter_mut().find(|d| d.addr_range().contains(&addr)) {
+      return Some(d.as_mut());
+    }
+    match addr {
+      KBSR | KBDR => Some(&mut self.keyboard),
+      DSR | DDR => Some(&mut self.display),
+      _ => None,
+    }
+  }
+
+  pub(super) fn device_read(&mut self, addr: u16) -> Option<u16> {
+    // the key the program looks for, unless a device replaced the keyboard
+    if (addr == KBSR || addr == KBDR) && !self.devices.iter().any(|d| d.addr_range().contains(&addr)) {
+      self.poll_key();
+    }
+    self.device_at(addr).map(|d| d.read(addr))
+  }
+
+  pub(super) fn device_write(&mut self, addr: u16, val: u16) -> bool {
+    match self.device_at(addr) {
+      Some(d) => d.write(addr, val),
+      None => return false,
+    }
+    if let Some(c) = self.display.out.take() {
+      self.putc(c);
+      self.flush();
+    }
+    true
+  }
+
+  pub(super) fn tick_devices(&mut self) {
+    for i in 0..self.devices.len() {
+      // like the keyboard, a device can't queue up a second request for an
+      // interrupt that is still pending
+      if let Some((vector, priority)) = self.devices[i].tick() {
+        if !self.pending.iter().any(|&(v, _)| v == vector) {
+          self.raise(vector, priority);
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,92 @@
+use super::*;
+
+// violations kept; a checker left on over a long buggy run stops
+// collecting past this
+const MAX_VIOLATIONS: usize = 1024;
+
+// the registers a subroutine must hand back unchanged under the course
+// convention: R0 carries the result, R6 is checked for balance on its
+// own and R7 is the return address
+pub const CALLEE_SAVED: &[Reg] = &[Reg::R1, Reg::R2, Reg::R3, Reg::R4, Reg::R5];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViolationKind {
+  // R6 at the return differs from R6 at the call
+  StackImbalance { expected: u16, found: u16 },
+  // a callee-saved register wasn't restored
+  Clobbered { reg: Reg, expected: u16, found: u16 },
+}
+
+// a subroutine return that broke the calling convention
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Violation {
+  // the returning instruction
+  pub pc: u16,
+  // the subroutine it returned from
+  pub entry: u16,
+  pub kind: ViolationKind,
+}
+
+impl fmt::Display for Violation {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "x{:04X}: return from x{:04X} ", self.pc, self.entry)?;
+    match self.kind {
+      ViolationKind::StackImbalance { expected, found } =>
+        write!(f, "left R6 at x{:04X}, it was x{:04X} at the call", found, expected),
+      ViolationKind::Clobbered { reg, expected, found } =>
+        write!(f, "left {:?} at x{:04X}, it was x{:04X} at the call", reg, found, expected),
+    }
+  }
+}
+
+pub(super) struct Discipline {
+  callee_saved: Vec<Reg>,
+  violations: Vec<Violation>,
+}
+
+impl Machine {
+  // checks every return from a JSR or JSRR on the shadow call stack (see
+  // note_call()) against the calling convention: R6 must be back where it
+  // was at the call and each of callee_saved, CALLEE_SAVED by the course
+  // convention, must hold its value from the call. Violations are logged to
+  // lc3::exec and kept for violations(); TRAPs and interrupts aren't
+  // checked.
+  pub fn enable_stack_check(&mut self, callee_saved: &[Reg]) {
+    self.discipline = Some(Discipline { callee_saved: callee_saved.to_vec(), violations: Vec::new() });
+  }
+
+  pub fn disable_stack_check(&mut self) {
+    self.discipline = None;
+  }
+
+  pub fn violations(&self) -> &[Violation] {
+    self.discipline.as_ref().map_or(&[], |d| &d.violations)
+  }
+
+  // checks a return to the call that saved regs, its registers at the call
+  pub(super) fn check_return(&mut self, pc: u16, entry: u16, regs: &[u16; 8]) {
+    let d: &mut Discipline = match self.discipline.as_mut() {
+      Some(d) => d,
+      None => return,
+    };
+
+    let mut found: Vec<ViolationKind> = Vec::new();
+    if self.reg[SP as usize] != regs[SP as usize] {
+      found.push(ViolationKind::StackImbalance { expected: regs[SP as usize], found: self.reg[SP as usize] });
+    }
+    for &reg in &d.callee_saved {
+      if self.reg[reg as usize] != regs[reg as usize] {
+        found.push(ViolationKind::Clobbered { reg, expected: regs[reg as usize], found: self.reg[reg as usize] });
+      }
+    }
+
+    for kind in found {
+      if d.violations.len() == MAX_VIOLATIONS {
+        break;
+      }
+      let v: Violation = Violation { pc, entry, kind };
+      d.violations.push(v);
+      log_to!(self, Exec, Warn, "{}", v);
+    }
+  }
+}
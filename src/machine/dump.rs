@@ -0,0 +1,86 @@
+use core::ops::{Bound, RangeBounds};
+
+use super::*;
+
+// words shown before and after PC
+const BEFORE: u16 = 4;
+const AFTER: u16 = 5;
+
+// the addresses of range as start..end, which may be empty
+pub(super) fn span(range: impl RangeBounds<u16>) -> (u32, u32) {
+  let start: u32 = match range.start_bound() {
+    Bound::Included(&a) => a as u32,
+    Bound::Excluded(&a) => a as u32 + 1,
+    Bound::Unbounded => 0,
+  };
+  let end: u32 = match range.end_bound() {
+    Bound::Included(&a) => a as u32 + 1,
+    Bound::Excluded(&a) => a as u32,
+    Bound::Unbounded => MEM_SIZE as u32,
+  };
+  (start, end)
+}
+
+impl Machine {
+  // the machine as Display renders it
+  pub fn dump_state(&self) -> String {
+    self.to_string()
+  }
+
+  // the words in range as an .obj image, e.g. to compare what a program
+  // left in memory. Device registers read as stored, without side effects
+  pub fn dump_image(&self, range: impl RangeBounds<u16>) -> Vec<u8> {
+    self.dump_image_as(range, ImageFormat::Obj)
+  }
+
+  // dump_image() in any of the formats the loader reads; the origin of raw
+  // formats is the start of range
+  pub fn dump_image_as(&self, range: impl RangeBounds<u16>, format: ImageFormat) -> Vec<u8> {
+    let (start, end): (u32, u32) = span(range);
+    let words: Vec<u16> = (start..end.max(start)).map(|a| self.mem.read(a as u16)).collect();
+    loader::write(start as u16, &words, format)
+  }
+}
+
+// registers as hex, signed and unsigned, the PSR, and the memory around PC
+// disassembled, PC marked with >:
+//
+//   R0 x0005      5      5    R4 xFFFF     -1  65535
+//   ...
+//   PC x3001  PSR x8001  user  priority 0  CC P
+//   USP x0000  SSP x3000
+//
+//     x3000  x2002  LD R0, x3003
+//   > x3001  xF025  HALT
+impl fmt::Display for Machine {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    for r in 0..4 {
+      let (a, b): (u16, u16) = (self.getr(r), self.getr(r + 4));
+      writeln!(f, "R{} x{:04X} {:>6} {:>6}    R{} x{:04X} {:>6} {:>6}",
+        r, a, a as i16, a, r + 4, b, b as i16, b)?;
+    }
+
+    let cc: &str = match self.getr(COND) {
+      NEG => "N",
+      ZRO => "Z",
+      POS => "P",
+      _ => "-",
+    };
+    writeln!(f, "PC x{:04X}  PSR x{:04X}  {}  priority {}  CC {}{}", self.getr(PC), self.psr(),
+      if self.user_mode() { "user" } else { "supervisor" }, self.priority(), cc, if self.halt { "  halted" } else { "" })?;
+    // R6 is the stack of the current mode, the other one is saved
+    let (usp, ssp): (u16, u16) = if self.user_mode() { (self.getr(SP), self.saved_ssp) } else { (self.saved_usp, self.getr(SP)) };
+    writeln!(f, "USP x{:04X}  SSP x{:04X}", usp, ssp)?;
+
+    writeln!(f)?;
+    let pc: u16 = self.getr(PC);
+    for i in 0..=BEFORE + AFTER {
+      let addr: u16 = pc.wrapping_sub(BEFORE).wrapping_add(i);
+      let word: u16 = self.mem.read(addr);
+      let label: String = self.symbols.label(addr).map_or(String::new(), |l| format!("{}: ", l));
+      writeln!(f, "{} x{:04X}  x{:04X}  {}{}", if addr == pc { ">" } else { " " }, addr, word, label,
+        disasm::disassemble(word, addr, &self.symbols))?;
+    }
+    Ok(())
+  }
+}
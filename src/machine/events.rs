@@ -0,0 +1,93 @@
+#[cfg(feature = "std")]
+use std::sync::mpsc::Sender;
+
+use super::*;
+
+// what the machine did, as seen by a UI or grader following along instead
+// of polling registers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+  Fetched { pc: u16, instr: u16 },
+  TrapEntered(u8),
+  // an interrupt or exception, entered through the vector table
+  Interrupt(u8),
+  OutputChar(u8),
+  // the word at index of a watch, 0 unless it's a range, is now value
+  WatchChanged { id: usize, index: usize, value: u16 },
+  Halted,
+  Fault(MachineError),
+}
+
+pub(super) enum EventSink {
+  Queue(VecDeque<Event>),
+  #[cfg(feature = "std")]
+  Channel(Sender<Event>),
+}
+
+// Machine::events(), running the machine one instruction at a time and
+// ending when it stops
+pub struct Events<'a> {
+  m: &'a mut Machine,
+  // whoever was listening before, put back after
+  previous: Option<EventSink>,
+  done: bool,
+}
+
+impl<'a> Iterator for Events<'a> {
+  type Item = Event;
+
+  fn next(&mut self) -> Option<Event> {
+    loop {
+      if let Some(EventSink::Queue(queue)) = &mut self.m.events {
+        if let Some(e) = queue.pop_front() {
+          return Some(e);
+        }
+      }
+      if self.done {
+        return None;
+      }
+      if !matches!(self.m.step(), Ok(None)) {
+        self.done = true;
+      }
+    }
+  }
+}
+
+impl<'a> Drop for Events<'a> {
+  fn drop(&mut self) {
+    self.m.events = self.previous.take();
+  }
+}
+
+impl Machine {
+  // runs the machine, yielding what it does until it halts, faults or
+  // stops for another reason, e.g. a breakpoint
+  //
+  //   for e in m.events() {
+  //     if let Event::OutputChar(c) = e { ... }
+  //   }
+  pub fn events(&mut self) -> Events<'_> {
+    let previous: Option<EventSink> = self.events.replace(EventSink::Queue(VecDeque::new()));
+    Events { m: self, previous, done: false }
+  }
+
+  // sends events to tx from now on, however the machine is run, until
+  // stop_events() or the receiver goes away
+  #[cfg(feature = "std")]
+  pub fn send_events(&mut self, tx: Sender<Event>) {
+    self.events = Some(EventSink::Channel(tx));
+  }
+
+  pub fn stop_events(&mut self) {
+    self.events = None;
+  }
+
+  pub(super) fn emit(&mut self, e: Event) {
+    match &mut self.events {
+      Some(EventSink::Queue(queue)) => queue.push_back(e),
+      #[cfg(feature = "std")]
+      Some(EventSink::Channel(tx)) if tx.send(e).is_err() => self.events = None,
+      _ => {},
+    }
+  }
+}
@@ -0,0 +1,124 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use super::*;
+
+// extended traps for host file access. Paths are null-terminated strings,
+// one character per word like PUTS, and only files under a path passed to
+// allow_path() can be opened; everything fails until one is. Results come
+// back in R0, xFFFF for failure.
+//
+//   FOPEN  R0 path, R1 FILE_READ, FILE_WRITE or FILE_APPEND -> handle
+//   FCLOSE R0 handle -> 0
+//   FGETC  R0 handle -> next byte, xFFFF at end of file
+//   FPUTC  R0 handle, R1 byte -> 0
+pub const TRAP_FOPEN  : u8 = 0x30;
+pub const TRAP_FCLOSE : u8 = 0x31;
+pub const TRAP_FGETC  : u8 = 0x32;
+pub const TRAP_FPUTC  : u8 = 0x33;
+
+pub const FILE_READ   : u16 = 0;
+pub const FILE_WRITE  : u16 = 1; // created or truncated
+pub const FILE_APPEND : u16 = 2; // created if missing
+
+const MAX_FILES: usize = 16;
+const FAIL: u16 = 0xFFFF;
+
+#[derive(Default)]
+pub(super) struct Files {
+  allowed: Vec<PathBuf>,
+  open: Vec<Option<File>>,
+}
+
+impl Files {
+  // the canonical path of name if it is inside the sandbox. Files that
+  // don't exist yet are checked by their directory.
+  fn resolve(&self, name: &str) -> Option<PathBuf> {
+    let path: &Path = Path::new(name);
+    let full: PathBuf = match path.canonicalize() {
+      Ok(full) => full,
+      Err(_) => {
+        let dir: &Path = match path.parent() {
+          Some(p) if !p.as_os_str().is_empty() => p,
+          _ => Path::new("."),
+        };
+        dir.canonicalize().ok()?.join(path.file_name()?)
+      },
+    };
+    if self.allowed.iter().any(|a| full.starts_with(a)) {
+      Some(full)
+    } else {
+      None
+    }
+  }
+
+  fn open(&mut self, name: &str, mode: u16) -> Option<u16> {
+    let path: PathBuf = self.resolve(name)?;
+    let mut opts: OpenOptions = OpenOptions::new();
+    match mode {
+      FILE_READ => opts.read(true),
+      FILE_WRITE => opts.write(true).create(true).truncate(true),
+      FILE_APPEND => opts.append(true).create(true),
+      _ => return None,
+    };
+    let file: File = opts.open(&path).ok()?;
+
+    let slot: usize = match self.open.iter().position(|f| f.is_none()) {
+      Some(i) => i,
+      None if self.open.len() < MAX_FILES => {
+        self.open.push(None);
+        self.open.len() - 1
+      },
+      None => return None,
+    };
+    self.open[slot] = Some(file);
+    Some(slot as u16)
+  }
+
+  fn file(&mut self, handle: u16) -> Option<&mut File> {
+    self.open.get_mut(handle as usize).and_then(|f| f.as_mut())
+  }
+}
+
+impl Machine {
+  // lets FOPEN open files at or below path, which has to exist
+  pub fn allow_path(&mut self, path: &Path) -> io::Result<()> {
+    let full: PathBuf = path.canonicalize()?;
+    self.files.allowed.push(full);
+    Ok(())
+  }
+
+  // runs one of the file traps, false for other vectors
+  pub(super) fn file_trap(&mut self, vector: u8) -> bool {
+    let (r0, r1): (u16, u16) = (self.getr(0), self.getr(1));
+    let result: Option<u16> = match vector {
+      TRAP_FOPEN => {
+        let mut name: String = String::new();
+        let mut addr: u16 = r0;
+        loop {
+          let c: u16 = self.getm(addr);
+          if c == 0 {
+            break;
+          }
+          name.push((c & 0xFF) as u8 as char);
+          addr = addr.wrapping_add(1);
+        }
+        log_to!(self, Io, Trace, "opening {:?} in mode {}", name, r1);
+        self.files.open(&name, r1)
+      },
+      TRAP_FCLOSE => self.files.open.get_mut(r0 as usize).and_then(|f| f.take()).map(|_| 0),
+      TRAP_FGETC => self.files.file(r0).and_then(|f| {
+        let mut b: [u8; 1] = [0];
+        match f.read(&mut b) {
+          Ok(1) => Some(b[0] as u16),
+          _ => None,
+        }
+      }),
+      TRAP_FPUTC => self.files.file(r0).and_then(|f| f.write_all(&[r1 as u8]).ok()).map(|_| 0),
+      _ => return false,
+    };
+    self.setr(0, result.unwrap_or(FAIL));
+    true
+  }
+}
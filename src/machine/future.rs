@@ -0,0 +1,88 @@
+use alloc::collections::VecDeque;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use super::*;
+
+// instructions run per poll before yielding back to the executor
+const SLICE: u64 = 4096;
+
+// keyboard input for run_async(), ready when a key has arrived. None means
+// the input has ended, after which key reads get 0 like Console::read_char()
+// returning None.
+pub trait KeySource {
+  fn poll_key(&mut self, cx: &mut Context<'_>) -> Poll<Option<u8>>;
+}
+
+// scripted input, always ready
+impl KeySource for VecDeque<u8> {
+  fn poll_key(&mut self, _cx: &mut Context<'_>) -> Poll<Option<u8>> {
+    Poll::Ready(self.pop_front())
+  }
+}
+
+// the future returned by Machine::run_async()
+pub struct RunAsync<'a, K: KeySource> {
+  m: &'a mut Machine,
+  keys: K,
+  eof: bool,
+  steps: u64,
+}
+
+impl Machine {
+  // like run(), but waits for keys from an async source instead of
+  // blocking the thread: when the next instruction is a GETC or IN, or the
+  // last one found KBSR without a key, the future is pending until keys
+  // has one. It also yields every few thousand instructions. The console
+  // is still used for output.
+  pub fn run_async<K: KeySource>(&mut self, keys: K) -> RunAsync<'_, K> {
+    RunAsync { m: self, keys, eof: false, steps: 0 }
+  }
+
+  // whether the next instruction is a built-in TRAP reading the keyboard
+  fn reads_key_next(&self) -> bool {
+    if self.os {
+      return false;
+    }
+    match decode(self.mem.read(self.getr(PC))) {
+      Instruction::Trap { vector } => (vector == 0x20 || vector == 0x23) && !self.trap_handlers.contains_key(&vector),
+      _ => false,
+    }
+  }
+}
+
+impl<'a, K: KeySource + Unpin> Future for RunAsync<'a, K> {
+  type Output = Run;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Run> {
+    let this = self.get_mut();
+    for _ in 0..SLICE {
+      if this.m.halt {
+        return Poll::Ready(Run { steps: this.steps, reason: StopReason::Halt });
+      }
+
+      if !this.eof && this.m.keyboard.key.is_none() && (this.m.keyboard.starved || this.m.reads_key_next()) {
+        match this.keys.poll_key(cx) {
+          Poll::Ready(Some(c)) => {
+            this.m.record(Input::Key(c));
+            this.m.keyboard.key = Some(c);
+          },
+          Poll::Ready(None) => this.eof = true,
+          Poll::Pending => return Poll::Pending,
+        }
+      }
+      this.m.keyboard.starved = false;
+
+      let result = this.m.step();
+      this.steps += 1;
+      match result {
+        Ok(Some(reason)) => return Poll::Ready(Run { steps: this.steps, reason }),
+        Ok(None) => {},
+        Err(e) => return Poll::Ready(Run { steps: this.steps, reason: StopReason::Fault(e) }),
+      }
+    }
+    cx.waker().wake_by_ref();
+    Poll::Pending
+  }
+}
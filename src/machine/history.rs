@@ -0,0 +1,116 @@
+use super::*;
+
+// state needed to undo one instruction
+struct Delta {
+  reg: [u16; REG_SIZE],
+  halt: bool,
+  saved_usp: u16,
+  saved_ssp: u16,
+  pending: Vec<(u8, u8)>,
+  keyboard: Vec<u16>,
+  count: u64,
+  calls: Vec<super::calls::Call>,
+  mem: Vec<(u16, u16)>, // (address, old value), in write order
+  keys: Vec<u8>,        // input consumed from the console
+}
+
+pub(super) struct History {
+  capacity: usize,
+  entries: VecDeque<Delta>,
+  current: Option<Delta>,
+}
+
+impl Machine {
+  // keeps the last `capacity` instructions undoable with step_back()
+  pub fn enable_history(&mut self, capacity: usize) {
+    self.history = Some(History { capacity, entries: VecDeque::with_capacity(capacity), current: None });
+  }
+
+  pub fn disable_history(&mut self) {
+    self.history = None;
+  }
+
+  pub fn history_len(&self) -> usize {
+    self.history.as_ref().map_or(0, |h| h.entries.len())
+  }
+
+  // undoes the last instruction, returning false when there is nothing left
+  // to undo. Console input consumed by the instruction is queued again so
+  // re-executing it sees the same keys; output already written stays.
+  pub fn step_back(&mut self) -> bool {
+    let delta: Delta = match self.history.as_mut().and_then(|h| h.entries.pop_back()) {
+      Some(d) => d,
+      None => return false,
+    };
+
+    for &(addr, old) in delta.mem.iter().rev() {
+      self.store(addr, old);
+    }
+    self.reg = delta.reg;
+    self.halt = delta.halt;
+    self.saved_usp = delta.saved_usp;
+    self.saved_ssp = delta.saved_ssp;
+    self.pending = delta.pending;
+    self.keyboard.restore(&delta.keyboard);
+    self.count = delta.count;
+    self.calls = delta.calls;
+
+    if !delta.keys.is_empty() {
+      let queue = self.replaying.get_or_insert_with(VecDeque::new);
+      for &c in delta.keys.iter().rev() {
+        queue.push_front(replay::Event { step: 0, input: Input::Key(c) });
+      }
+    }
+
+    true
+  }
+
+  pub(super) fn begin_delta(&mut self) {
+    if self.history.is_none() {
+      return;
+    }
+    let delta: Delta = Delta {
+      reg: self.reg,
+      halt: self.halt,
+      saved_usp: self.saved_usp,
+      saved_ssp: self.saved_ssp,
+      pending: self.pending.clone(),
+      keyboard: self.keyboard.save(),
+      count: self.count,
+      calls: self.calls.clone(),
+      mem: Vec::new(),
+      keys: Vec::new(),
+    };
+    if let Some(h) = self.history.as_mut() {
+      h.current = Some(delta);
+    }
+  }
+
+  pub(super) fn end_delta(&mut self) {
+    if let Some(h) = self.history.as_mut() {
+      if let Some(delta) = h.current.take() {
+        if h.entries.len() == h.capacity {
+          h.entries.pop_front();
+        }
+        if h.capacity > 0 {
+          h.entries.push_back(delta);
+        }
+      }
+    }
+  }
+
+  pub(super) fn note_write(&mut self, addr: u16) {
+    #[cfg(feature = "std")]
+    self.note_trace_write(addr);
+    let old: u16 = self.mem.read(addr);
+    if let Some(d) = self.history.as_mut().and_then(|h| h.current.as_mut()) {
+      d.mem.push((addr, old));
+    }
+  }
+
+  pub(super) fn note_key(&mut self, c: u8) {
+    if let Some(d) = self.history.as_mut().and_then(|h| h.current.as_mut()) {
+      d.keys.push(c);
+    }
+  }
+}
@@ -0,0 +1,39 @@
+use core::mem;
+
+use super::*;
+
+// callbacks run around every instruction with the machine, the address the
+// instruction was fetched from and the decoded instruction. PreExecute sees
+// the state before it executes, PostExecute the state after; instructions
+// that fault skip the PostExecute hooks.
+pub enum Hook {
+  PreExecute(HookFn),
+  PostExecute(HookFn),
+}
+
+pub type HookFn = Box<dyn FnMut(&Machine, u16, Instruction)>;
+
+impl Machine {
+  pub fn add_hook(&mut self, hook: Hook) {
+    self.hooks.push(hook);
+  }
+
+  pub fn clear_hooks(&mut self) {
+    self.hooks.clear();
+  }
+
+  pub(super) fn run_hooks(&mut self, pre: bool, pc: u16, op: Instruction) {
+    // taken out while they run so they can borrow the machine
+    let mut hooks: Vec<Hook> = mem::take(&mut self.hooks);
+    for hook in hooks.iter_mut() {
+      match hook {
+        Hook::PreExecute(f) if pre => f(self, pc, op),
+        Hook::PostExecute(f) if !pre => f(self, pc, op),
+        _ => {},
+      }
+    }
+    // keep hooks added by a hook
+    hooks.append(&mut self.hooks);
+    self.hooks = hooks;
+  }
+}
@@ -0,0 +1,70 @@
+use super::*;
+
+// what the reserved opcode xD does. Off by default, where it is an
+// illegal opcode as the LC-3 has it; course variants that extend the ISA
+// give it one of:
+//
+//   Shift  xD | DR | SR | 0 0 kind amount4, kind 00 LSHF, 01 RSHFL
+//          (logical), 11 RSHFA (arithmetic), as SHF on the LC-3b
+//   Mul    xD | DR | SR1 | 0 00 SR2, or 1 imm5 as ADD: DR = SR1 * SR2,
+//          the low 16 bits of the product
+//
+// Both set the condition codes from DR.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IsaExtensions {
+  #[default]
+  None,
+  Shift,
+  Mul,
+}
+
+impl IsaExtensions {
+  // the extension for a --isa-ext name
+  pub fn from_name(name: &str) -> Option<IsaExtensions> {
+    match name {
+      "none" => Some(IsaExtensions::None),
+      "shift" => Some(IsaExtensions::Shift),
+      "mul" => Some(IsaExtensions::Mul),
+      _ => None,
+    }
+  }
+}
+
+impl Machine {
+  pub fn set_isa_extensions(&mut self, ext: IsaExtensions) {
+    self.isa_ext = ext;
+  }
+
+  pub fn isa_extensions(&self) -> IsaExtensions {
+    self.isa_ext
+  }
+
+  // executes a reserved word as the configured extension, false if there is
+  // none for it
+  pub(super) fn extended(&mut self, word: u16) -> bool {
+    if word >> 12 != 0xD {
+      return false;
+    }
+    let dr: u16 = (word >> 9) & 0x7;
+    let sr1: u16 = self.getr((word >> 6) & 0x7);
+    let val: u16 = match self.isa_ext {
+      IsaExtensions::None => return false,
+      IsaExtensions::Shift => {
+        let amount: u16 = word & 0xF;
+        match (word >> 4) & 0x3 {
+          0b00 => sr1 << amount,
+          0b01 => sr1 >> amount,
+          0b11 => ((sr1 as i16) >> amount) as u16,
+          _ => return false,
+        }
+      },
+      IsaExtensions::Mul => {
+        let sr2: u16 = if word & (1 << 5) != 0 { sign_extend(word & 0x1F, 5) } else { self.getr(word & 0x7) };
+        sr1.wrapping_mul(sr2)
+      },
+    };
+    self.setr(dr, val);
+    self.set_cond(dr);
+    true
+  }
+}
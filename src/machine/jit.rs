@@ -0,0 +1,150 @@
+use alloc::rc::Rc;
+
+use super::*;
+
+// longest run of instructions translated as one block
+const MAX_BLOCK: usize = 256;
+
+// threaded-code backend: straight-line runs of code are decoded once into
+// blocks ending at the first instruction that can change the PC, and run
+// without the per-step bookkeeping of step(). Anything that needs that
+// bookkeeping - observers, hooks, tracing, history, coverage, breakpoints
+// and watchpoints, devices, interrupts, recording and the clock - sends the
+// machine back to the interpreter for as long as it is in use.
+pub(super) struct Jit {
+  blocks: Vec<Option<Rc<Block>>>,
+  // addresses inside some block
+  translated: Vec<u64>,
+  // set when a write drops the blocks, so the running one stops
+  flushed: bool,
+}
+
+struct Block {
+  start: u16,
+  ops: Vec<(u16, Instruction)>,
+}
+
+fn ends_block(op: Instruction) -> bool {
+  matches!(op,
+    Instruction::Br { .. } | Instruction::Jmp { .. } | Instruction::Jsr { .. } | Instruction::Jsrr { .. }
+      | Instruction::Trap { .. } | Instruction::Rti | Instruction::Res { .. })
+}
+
+// whether user mode may fetch from addr + 1 but not addr, or the other way
+// round. Blocks stop there, so the check jit_ready() makes of the first
+// address holds for all of them.
+fn ends_access(addr: u16) -> bool {
+  let next: u16 = addr.wrapping_add(1);
+  next == USER_SPACE || next == *IO_PAGE.start() || addr == *IO_PAGE.end()
+}
+
+impl Jit {
+  fn new() -> Jit {
+    Jit { blocks: vec![None; MEM_SIZE], translated: vec![0; MEM_SIZE / 64], flushed: false }
+  }
+
+  // drops every block if addr is part of one
+  pub(super) fn invalidate(&mut self, addr: u16) {
+    if self.translated[addr as usize / 64] & (1 << (addr % 64)) != 0 {
+      self.blocks.iter_mut().for_each(|b| *b = None);
+      self.translated.iter_mut().for_each(|w| *w = 0);
+      self.flushed = true;
+    }
+  }
+}
+
+impl Machine {
+  // runs code through the threaded-code backend where it can
+  pub fn set_jit(&mut self, enable: bool) {
+    if enable != self.jit.is_some() {
+      self.jit = if enable { Some(Box::new(Jit::new())) } else { None };
+    }
+  }
+
+  pub fn jit_enabled(&self) -> bool {
+    self.jit.is_some()
+  }
+
+  // whether the next instructions can skip step()'s bookkeeping
+  pub(super) fn jit_ready(&self) -> bool {
+    #[cfg(feature = "std")]
+    if self.clock.is_some() || self.tracer.is_some() {
+      return false;
+    }
+    self.jit.is_some() && !self.halt && !self.keyboard.ie && !self.cc_audit
+      && self.uninit.is_none() && self.smc.is_none() && self.progress.is_none()
+      && self.pending.is_empty() && self.devices.is_empty()
+      && self.observers.is_empty() && self.hooks.is_empty()
+      && self.breakpoints.is_empty() && self.watchpoints.is_empty() && self.watches.is_empty()
+      && self.history.is_none() && self.coverage.is_none() && self.profile.is_none()
+      && self.recording.is_none() && self.replaying.is_none() && self.typing.is_none() && self.events.is_none()
+      && self.accessible(self.getr(PC))
+  }
+
+  fn translate(&mut self, start: u16) -> Rc<Block> {
+    let mut ops: Vec<(u16, Instruction)> = Vec::new();
+    let mut addr: u16 = start;
+    loop {
+      let word: u16 = self.mem.read(addr);
+      let op: Instruction = decode(word);
+      ops.push((word, op));
+      if ends_block(op) || ops.len() == MAX_BLOCK || ends_access(addr) {
+        break;
+      }
+      addr += 1;
+    }
+
+    let block: Rc<Block> = Rc::new(Block { start, ops });
+    let jit: &mut Jit = self.jit.as_mut().unwrap();
+    for a in start..=addr {
+      jit.translated[a as usize / 64] |= 1 << (a % 64);
+    }
+    jit.blocks[start as usize] = Some(block.clone());
+    block
+  }
+
+  // runs the block at PC as run_until() would, counting each instruction
+  // in steps and checking pred after it. None when the block finished
+  // without stopping.
+  pub(super) fn run_block<F: FnMut(&Machine) -> bool>(&mut self, steps: &mut u64, pred: &mut F) -> Option<Run> {
+    let pc: u16 = self.getr(PC);
+    let block: Rc<Block> = match self.jit.as_ref().and_then(|j| j.blocks[pc as usize].clone()) {
+      Some(b) => b,
+      None => self.translate(pc),
+    };
+    self.jit.as_mut().unwrap().flushed = false;
+    self.stop = None;
+
+    for (i, &(word, op)) in block.ops.iter().enumerate() {
+      let next: u16 = block.start.wrapping_add(i as u16).wrapping_add(1);
+      self.setr(PC, next);
+      self.stats.instructions += 1;
+      self.count += 1;
+      self.stats.opcodes[(word >> 12) as usize] += 1;
+
+      let result: Result<(), MachineError> = self.dispatch(op);
+      *steps += 1;
+      if let Err(e) = result {
+        return Some(Run { steps: *steps, reason: StopReason::Fault(e) });
+      }
+      if self.halt {
+        return Some(Run { steps: *steps, reason: StopReason::Halt });
+      }
+      if let Some(reason) = self.stop.take() {
+        return Some(Run { steps: *steps, reason });
+      }
+      if self.limit_exceeded() {
+        return Some(Run { steps: *steps, reason: StopReason::LimitExceeded });
+      }
+      if pred(self) {
+        return Some(Run { steps: *steps, reason: StopReason::Condition });
+      }
+      // an exception, a write over translated code, or a store that
+      // turned on interrupts
+      if self.getr(PC) != next || self.jit.as_ref().is_none_or(|j| j.flushed) || self.keyboard.ie {
+        break;
+      }
+    }
+    None
+  }
+}
@@ -0,0 +1,199 @@
+; operating system image loaded by Machine::init_with_os(): trap service
+; routines and exception handlers. Traps and exceptions enter supervisor
+; mode with PSR and PC saved on the supervisor stack, so every routine
+; returns with RTI. Registers other than R0 are preserved.
+
+        .ORIG x0000
+
+; trap vector table, x0000-x00FF
+        .BLKW x20 BAD_TRAP
+        .FILL TRAP_GETC         ; x20
+        .FILL TRAP_OUT          ; x21
+        .FILL TRAP_PUTS         ; x22
+        .FILL TRAP_IN           ; x23
+        .FILL TRAP_PUTSP        ; x24
+        .FILL TRAP_HALT         ; x25
+        .BLKW xDA BAD_TRAP
+
+; interrupt vector table, x0100-x01FF
+        .FILL EXC_PRIV          ; x00 privilege mode violation
+        .FILL EXC_ILLEGAL       ; x01 illegal opcode
+        .FILL EXC_ACV           ; x02 access control violation
+        .BLKW xFD BAD_INT
+
+OS_KBSR .FILL xFE00
+OS_KBDR .FILL xFE02
+OS_DSR  .FILL xFE04
+OS_DDR  .FILL xFE06
+OS_MCR  .FILL xFFFE
+MASK_LO .FILL x00FF
+MASK_MCR .FILL x7FFF
+
+; R0 <- next key, without echo
+TRAP_GETC
+        LDI R0, OS_KBSR
+        BRzp TRAP_GETC
+        LDI R0, OS_KBDR
+        RTI
+
+; writes R0[7:0]
+TRAP_OUT
+        ADD R6, R6, #-1
+        STR R7, R6, #0
+        JSR OS_PUTC
+        LDR R7, R6, #0
+        ADD R6, R6, #1
+        RTI
+
+; writes the string at R0, one character per word
+TRAP_PUTS
+        ADD R6, R6, #-1
+        STR R0, R6, #0
+        ADD R6, R6, #-1
+        STR R7, R6, #0
+        JSR OS_PUTS
+        LDR R7, R6, #0
+        ADD R6, R6, #1
+        LDR R0, R6, #0
+        ADD R6, R6, #1
+        RTI
+
+; prompts for a key and echoes it, R0 <- key
+TRAP_IN
+        ADD R6, R6, #-1
+        STR R7, R6, #0
+        LEA R0, IN_PROMPT
+        JSR OS_PUTS
+IN_WAIT LDI R0, OS_KBSR
+        BRzp IN_WAIT
+        LDI R0, OS_KBDR
+        JSR OS_PUTC
+        LDR R7, R6, #0
+        ADD R6, R6, #1
+        RTI
+
+IN_PROMPT .STRINGZ "Enter a character: "
+
+; writes the string at R0, two characters per word, low byte first
+TRAP_PUTSP
+        ADD R6, R6, #-1
+        STR R0, R6, #0
+        ADD R6, R6, #-1
+        STR R1, R6, #0
+        ADD R6, R6, #-1
+        STR R2, R6, #0
+        ADD R6, R6, #-1
+        STR R3, R6, #0
+        ADD R6, R6, #-1
+        STR R7, R6, #0
+        ADD R1, R0, #0
+PUTSP_LOOP
+        LDR R2, R1, #0
+        BRz PUTSP_DONE
+        LD R0, MASK_LO
+        AND R0, R2, R0
+        BRz PUTSP_DONE
+        JSR OS_PUTC
+        ; high byte, shifted in from the top bit one at a time
+        AND R0, R0, #0
+        AND R3, R3, #0
+        ADD R3, R3, #8
+PUTSP_SHIFT
+        ADD R0, R0, R0
+        ADD R2, R2, #0
+        BRzp PUTSP_NEXT
+        ADD R0, R0, #1
+PUTSP_NEXT
+        ADD R2, R2, R2
+        ADD R3, R3, #-1
+        BRp PUTSP_SHIFT
+        ADD R0, R0, #0
+        BRz PUTSP_DONE
+        JSR OS_PUTC
+        ADD R1, R1, #1
+        BRnzp PUTSP_LOOP
+PUTSP_DONE
+        LDR R7, R6, #0
+        ADD R6, R6, #1
+        LDR R3, R6, #0
+        ADD R6, R6, #1
+        LDR R2, R6, #0
+        ADD R6, R6, #1
+        LDR R1, R6, #0
+        ADD R6, R6, #1
+        LDR R0, R6, #0
+        ADD R6, R6, #1
+        RTI
+
+; stops the clock by clearing MCR[15]
+TRAP_HALT
+        LEA R0, HALT_MSG
+        JSR OS_PUTS
+HALT_AGAIN
+        LDI R0, OS_MCR
+        LD R1, MASK_MCR
+        AND R0, R0, R1
+        STI R0, OS_MCR
+        BRnzp HALT_AGAIN
+
+HALT_MSG .STRINGZ "\nHALT\n"
+
+EXC_PRIV
+        LEA R0, PRIV_MSG
+        BRnzp OS_FATAL
+EXC_ILLEGAL
+        LEA R0, ILLEGAL_MSG
+        BRnzp OS_FATAL
+EXC_ACV
+        LEA R0, ACV_MSG
+        BRnzp OS_FATAL
+BAD_INT
+        LEA R0, INT_MSG
+        BRnzp OS_FATAL
+BAD_TRAP
+        LEA R0, TRAP_MSG
+OS_FATAL
+        JSR OS_PUTS
+        BRnzp TRAP_HALT
+
+PRIV_MSG    .STRINGZ "\nprivilege mode violation"
+ILLEGAL_MSG .STRINGZ "\nillegal opcode"
+ACV_MSG     .STRINGZ "\naccess control violation"
+INT_MSG     .STRINGZ "\nunhandled interrupt"
+TRAP_MSG    .STRINGZ "\nunknown trap"
+
+; subroutines for the routines above; they preserve everything but R7
+
+; writes R0[7:0] once the display is ready
+OS_PUTC
+        ADD R6, R6, #-1
+        STR R1, R6, #0
+PUTC_WAIT
+        LDI R1, OS_DSR
+        BRzp PUTC_WAIT
+        STI R0, OS_DDR
+        LDR R1, R6, #0
+        ADD R6, R6, #1
+        RET
+
+; writes the string at R0, clobbering R0
+OS_PUTS
+        ADD R6, R6, #-1
+        STR R1, R6, #0
+        ADD R6, R6, #-1
+        STR R7, R6, #0
+        ADD R1, R0, #0
+PUTS_LOOP
+        LDR R0, R1, #0
+        BRz PUTS_DONE
+        JSR OS_PUTC
+        ADD R1, R1, #1
+        BRnzp PUTS_LOOP
+PUTS_DONE
+        LDR R7, R6, #0
+        ADD R6, R6, #1
+        LDR R1, R6, #0
+        ADD R6, R6, #1
+        RET
+
+        .END
@@ -0,0 +1,48 @@
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+use super::*;
+
+// instructions between looks at the wall clock
+#[cfg(feature = "std")]
+const CLOCK_EVERY: u64 = 1024;
+
+pub(super) struct Limits {
+  // instruction count to stop at
+  until: Option<u64>,
+  #[cfg(feature = "std")]
+  deadline: Option<Instant>,
+}
+
+impl Machine {
+  // stops execution with StopReason::LimitExceeded once max_instructions
+  // more instructions have run or max_wall_time has passed, both counted
+  // from now. None lifts a limit. The wall time is only kept with the std
+  // feature, there being no clock without it.
+  pub fn set_limits(&mut self, max_instructions: Option<u64>, max_wall_time: Option<Duration>) {
+    #[cfg(not(feature = "std"))]
+    let _ = max_wall_time;
+    self.limits = Some(Limits {
+      until: max_instructions.map(|n| self.count.saturating_add(n)),
+      #[cfg(feature = "std")]
+      deadline: max_wall_time.map(|t| Instant::now() + t),
+    });
+  }
+
+  // whether a limit set by set_limits() has been reached
+  pub(super) fn limit_exceeded(&self) -> bool {
+    let limits: &Limits = match &self.limits {
+      Some(l) => l,
+      None => return false,
+    };
+    if limits.until.is_some_and(|n| self.count >= n) {
+      return true;
+    }
+    #[cfg(feature = "std")]
+    if self.count.is_multiple_of(CLOCK_EVERY) && limits.deadline.is_some_and(|d| Instant::now() >= d) {
+      return true;
+    }
+    false
+  }
+}
@@ -0,0 +1,48 @@
+use utils::parse_addr;
+
+use super::*;
+
+impl Machine {
+  // remembers where the lines of an assembled source file went, for
+  // file:line locations; load_program() does this for .asm sources
+  pub fn add_line_info(&mut self, file: &str, lines: &[LineEntry]) {
+    self.line_info.retain(|(f, _)| f != file);
+    self.line_info.push((file.to_string(), lines.to_vec()));
+  }
+
+  // the first address of line in file, or of the next line with code if it
+  // has none, e.g. a comment or a label on a line of its own. file matches
+  // the name given to add_line_info() or its last path component
+  pub fn line_addr(&self, file: &str, line: usize) -> Option<u16> {
+    let name = |f: &str| -> bool { f == file || f.rsplit(['/', '\\']).next() == Some(file) };
+    let (_, lines) = self.line_info.iter().find(|(f, _)| name(f))?;
+    lines.iter().filter(|e| e.line >= line).min_by_key(|e| e.line).map(|e| e.addr)
+  }
+
+  // a location as typed by a user: a label, file.asm:42, or an address
+  // (see parse_addr()); labels win over addresses they spell, e.g. ADD1
+  pub fn resolve(&self, location: &str) -> Option<u16> {
+    if let Some((file, line)) = location.rsplit_once(':') {
+      if let Ok(line) = line.parse::<usize>() {
+        return self.line_addr(file, line);
+      }
+    }
+    self.symbols.lookup(location).or_else(|| parse_addr(location))
+  }
+
+  // add_breakpoint() at a location, returning the address it resolved to
+  pub fn break_at(&mut self, location: &str) -> Option<u16> {
+    let addr: u16 = self.resolve(location)?;
+    self.add_breakpoint(addr);
+    Some(addr)
+  }
+
+  // add_conditional_breakpoint() at a location, stopping when cond, an
+  // Expr over the machine's symbols, holds
+  pub fn break_at_if(&mut self, location: &str, cond: &str) -> Result<u16, FormatError> {
+    let addr: u16 = self.resolve(location).ok_or_else(|| FormatError(format!("invalid location `{}`", location)))?;
+    let cond: Expr = Expr::parse(cond, &self.symbols)?;
+    self.add_conditional_breakpoint(addr, cond);
+    Ok(addr)
+  }
+}
@@ -0,0 +1,55 @@
+use log::LevelFilter;
+
+use super::*;
+
+// logs to a LogTarget subsystem if the machine's filter, see
+// Machine::set_log_filter(), lets the level through; the logger's own
+// filtering still applies after that
+macro_rules! log_to {
+  ($m:expr, $target:ident, $level:ident, $($arg:tt)+) => {
+    if ::log::Level::$level <= $m.log_filter[LogTarget::$target as usize] {
+      ::log::log!(target: LogTarget::$target.name(), ::log::Level::$level, $($arg)+);
+    }
+  };
+}
+
+// the subsystems the machine logs under, each with its own log target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogTarget {
+  Exec, // fetching and executing instructions, lc3::exec
+  Mem,  // loading images and protected accesses, lc3::mem
+  Io,   // traps, files, devices and traces, lc3::io
+  Irq,  // interrupts and exceptions, lc3::irq
+}
+
+pub(super) const LOG_TARGETS: usize = 4;
+
+impl LogTarget {
+  pub const ALL: [LogTarget; LOG_TARGETS] = [LogTarget::Exec, LogTarget::Mem, LogTarget::Io, LogTarget::Irq];
+
+  pub fn name(self) -> &'static str {
+    match self {
+      LogTarget::Exec => "lc3::exec",
+      LogTarget::Mem => "lc3::mem",
+      LogTarget::Io => "lc3::io",
+      LogTarget::Irq => "lc3::irq",
+    }
+  }
+
+  // exec, mem, io or irq
+  pub fn from_name(name: &str) -> Option<LogTarget> {
+    LogTarget::ALL.iter().cloned().find(|t| t.name().strip_prefix("lc3::") == Some(name))
+  }
+}
+
+impl Machine {
+  // the most verbose level the machine logs for target; everything, the
+  // default, leaves it to the logger
+  pub fn set_log_filter(&mut self, target: LogTarget, level: LevelFilter) {
+    self.log_filter[target as usize] = level;
+  }
+
+  pub fn log_filter(&self, target: LogTarget) -> LevelFilter {
+    self.log_filter[target as usize]
+  }
+}
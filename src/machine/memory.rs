@@ -0,0 +1,103 @@
+use super::*;
+
+// backing store for the address space. The machine goes through read() and
+// write() for every access that isn't a device register, so a backend only
+// has to map each of the 2^16 addresses to a word. Machine::with_backend()
+// and MachineBuilder::backend() take any implementation, e.g. a buffer in a
+// memory-mapped file.
+pub trait Memory {
+  fn read(&self, addr: u16) -> u16;
+  fn write(&mut self, addr: u16, val: u16);
+}
+
+// a word per address on the heap, what Machine::with_io() uses. Built
+// smaller than MEM_SIZE, addresses past the end read as zero and ignore
+// writes.
+pub struct FlatMemory {
+  words: Box<[u16]>,
+}
+
+impl FlatMemory {
+  pub fn new() -> FlatMemory {
+    FlatMemory::with_size(MEM_SIZE)
+  }
+
+  pub fn with_size(size: usize) -> FlatMemory {
+    FlatMemory { words: vec![0; size.min(MEM_SIZE)].into_boxed_slice() }
+  }
+
+  // takes over an existing allocation without copying it: word i is
+  // address i, words past MEM_SIZE are never touched
+  pub fn from_boxed_slice(words: Box<[u16]>) -> FlatMemory {
+    FlatMemory { words }
+  }
+
+  pub fn into_boxed_slice(self) -> Box<[u16]> {
+    self.words
+  }
+}
+
+impl Default for FlatMemory {
+  fn default() -> FlatMemory {
+    FlatMemory::new()
+  }
+}
+
+impl Memory for FlatMemory {
+  fn read(&self, addr: u16) -> u16 {
+    self.words.get(addr as usize).cloned().unwrap_or(0)
+  }
+
+  fn write(&mut self, addr: u16, val: u16) {
+    if let Some(w) = self.words.get_mut(addr as usize) {
+      *w = val;
+    }
+  }
+}
+
+// only the words that were written, everything else reads as zero. Cheap
+// to create for short tests and for running many machines at once.
+#[derive(Debug, Clone, Default)]
+pub struct SparseMemory {
+  words: BTreeMap<u16, u16>,
+}
+
+impl SparseMemory {
+  pub fn new() -> SparseMemory {
+    SparseMemory::default()
+  }
+
+  // words currently stored, zero writes free theirs
+  pub fn len(&self) -> usize {
+    self.words.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.words.is_empty()
+  }
+}
+
+impl Memory for SparseMemory {
+  fn read(&self, addr: u16) -> u16 {
+    self.words.get(&addr).cloned().unwrap_or(0)
+  }
+
+  fn write(&mut self, addr: u16, val: u16) {
+    if val == 0 {
+      self.words.remove(&addr);
+    } else {
+      self.words.insert(addr, val);
+    }
+  }
+}
+
+// a caller-provided buffer, see Machine::with_memory()
+impl Memory for &'static mut [u16; MEM_SIZE] {
+  fn read(&self, addr: u16) -> u16 {
+    self[addr as usize]
+  }
+
+  fn write(&mut self, addr: u16, val: u16) {
+    self[addr as usize] = val;
+  }
+}
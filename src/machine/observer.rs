@@ -0,0 +1,23 @@
+use super::*;
+
+// notified of memory traffic as the machine executes. Reads and writes
+// are data accesses made by instructions and trap routines, including the
+// device registers, where val is what the device returned; instruction
+// fetches are reported separately.
+pub trait MemObserver {
+  fn on_read(&mut self, _addr: u16, _val: u16) {}
+
+  fn on_write(&mut self, _addr: u16, _old: u16, _new: u16) {}
+
+  fn on_fetch(&mut self, _addr: u16, _instr: u16) {}
+}
+
+impl Machine {
+  pub fn add_observer(&mut self, observer: Box<dyn MemObserver>) {
+    self.observers.push(observer);
+  }
+
+  pub fn clear_observers(&mut self) {
+    self.observers.clear();
+  }
+}
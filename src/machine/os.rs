@@ -0,0 +1,24 @@
+use super::*;
+
+// trap service routines and exception handlers, assembled on load
+const IMAGE: &str = include_str!("lc3os.asm");
+
+impl Machine {
+  // like init(), but with an operating system in low memory: TRAPs run its
+  // service routines instead of the built-in host shortcuts, and its
+  // handlers fill the interrupt vector table
+  pub fn init_with_os(&mut self) {
+    let os = assembler::assemble(IMAGE).expect("lc3os.asm does not assemble");
+    for (i, &word) in os.words.iter().enumerate() {
+      self.store(os.origin.wrapping_add(i as u16), word);
+    }
+    self.symbols.extend(&os.symbols);
+    self.os = true;
+    self.init();
+  }
+
+  // whether TRAPs go through the trap vector table
+  pub fn os_loaded(&self) -> bool {
+    self.os
+  }
+}
@@ -0,0 +1,66 @@
+use super::*;
+
+// what a step can change besides memory
+type State = ([u16; REG_SIZE], u16, u16);
+
+pub(super) struct Progress {
+  saved: State,
+  // Brent's cycle detection: saved is replaced every power steps, which
+  // doubles each time
+  power: u64,
+  steps: u64,
+  // memory written or I/O done since saved
+  changed: bool,
+}
+
+impl Machine {
+  // stops runs with StopReason::NoProgress once the machine is provably
+  // stuck: the registers come back to an earlier state with no memory
+  // having changed and no I/O done in between, so it will go round the same
+  // loop forever, e.g. BR to itself or a loop whose counter isn't updated.
+  // With devices attached or an interrupt enabled or pending something
+  // else may break the loop and it isn't checked. Costs a comparison of
+  // the registers every step.
+  pub fn set_progress_check(&mut self, enable: bool) {
+    self.progress = if enable {
+      Some(Progress { saved: self.state(), power: 1, steps: 0, changed: false })
+    } else {
+      None
+    };
+  }
+
+  fn state(&self) -> State {
+    (self.reg, self.saved_usp, self.saved_ssp)
+  }
+
+  // a store that changed memory, an access to the I/O page or a TRAP
+  pub(super) fn note_progress(&mut self) {
+    if let Some(p) = &mut self.progress {
+      p.changed = true;
+    }
+  }
+
+  // checked after each step
+  pub(super) fn no_progress(&mut self) -> bool {
+    let quiet: bool = self.devices.is_empty() && !self.keyboard.ie && self.pending.is_empty();
+    let state: State = self.state();
+    let p: &mut Progress = match &mut self.progress {
+      Some(p) => p,
+      None => return false,
+    };
+    if p.changed || !quiet {
+      *p = Progress { saved: state, power: 1, steps: 0, changed: false };
+      return false;
+    }
+    if state == p.saved {
+      return true;
+    }
+    p.steps += 1;
+    if p.steps == p.power {
+      p.saved = state;
+      p.power *= 2;
+      p.steps = 0;
+    }
+    false
+  }
+}
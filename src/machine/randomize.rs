@@ -0,0 +1,27 @@
+use super::*;
+
+impl Machine {
+  // fills R0-R7 and all memory below the device registers with
+  // pseudo-random words, as lc3tools' randomized mode does, to show up
+  // programs that count on a register or word starting out as zero. The
+  // same seed always gives the same state. Everything is overwritten, so
+  // it comes before loading the OS and the program, see
+  // MachineBuilder::randomize(). None of it counts as written for
+  // set_uninit_check().
+  pub fn init_randomized(&mut self, seed: u16) {
+    let mut rng: Random = Random::new(RNG, seed);
+    for r in 0..8 {
+      self.reg[r] = rng.next_word();
+    }
+    for addr in 0..*IO_PAGE.start() {
+      if let Some(cache) = &mut self.decode_cache {
+        cache.invalidate(addr);
+      }
+      #[cfg(feature = "jit")]
+      if let Some(jit) = &mut self.jit {
+        jit.invalidate(addr);
+      }
+      self.mem.write(addr, rng.next_word());
+    }
+  }
+}
@@ -0,0 +1,199 @@
+use core::ops::RangeBounds;
+
+use utils::parse_addr;
+
+use super::dump::span;
+use super::*;
+
+// longest string read_string() decodes
+const MAX_STRING: usize = 1024;
+
+// what an annotated range of memory holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionKind {
+  Code,
+  Data,
+  Stack,
+  Device,
+}
+
+impl RegionKind {
+  pub fn from_name(name: &str) -> Option<RegionKind> {
+    match name {
+      "code" => Some(RegionKind::Code),
+      "data" => Some(RegionKind::Data),
+      "stack" => Some(RegionKind::Stack),
+      "device" => Some(RegionKind::Device),
+      _ => None,
+    }
+  }
+
+  pub fn name(self) -> &'static str {
+    match self {
+      RegionKind::Code => "code",
+      RegionKind::Data => "data",
+      RegionKind::Stack => "stack",
+      RegionKind::Device => "device",
+    }
+  }
+}
+
+// an annotated range of memory, start through end
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+  pub start: u16,
+  pub end: u16,
+  pub kind: RegionKind,
+  pub name: String,
+}
+
+impl Region {
+  // None when range is empty
+  pub fn new(range: impl RangeBounds<u16>, kind: RegionKind, name: &str) -> Option<Region> {
+    let (start, end): (u32, u32) = span(range);
+    if start >= end {
+      return None;
+    }
+    Some(Region { start: start as u16, end: (end - 1) as u16, kind, name: name.to_string() })
+  }
+
+  // a region as typed by a user, <start>..[<end>]:<kind>[:<name>], the
+  // end left out for the rest of memory: x4000..x5000:stack:user
+  pub fn parse(s: &str) -> Result<Region, FormatError> {
+    let invalid = || FormatError(format!("invalid region `{}`", s));
+    let mut parts = s.splitn(3, ':');
+    let (start, end): (&str, &str) = parts.next().and_then(|r| r.split_once("..")).ok_or_else(invalid)?;
+    let kind: RegionKind = parts.next().and_then(RegionKind::from_name).ok_or_else(invalid)?;
+    let name: &str = parts.next().unwrap_or("");
+    let start: u16 = parse_addr(start).ok_or_else(invalid)?;
+    let region: Option<Region> = match end {
+      "" => Region::new(start.., kind, name),
+      end => Region::new(start..parse_addr(end).ok_or_else(invalid)?, kind, name),
+    };
+    region.ok_or_else(invalid)
+  }
+
+  pub fn contains(&self, addr: u16) -> bool {
+    self.start <= addr && addr <= self.end
+  }
+}
+
+// `name (kind) x4000..x4FFF`, or without the name when it has none
+impl fmt::Display for Region {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    if !self.name.is_empty() {
+      write!(f, "{} ", self.name)?;
+    }
+    write!(f, "({}) x{:04X}..x{:04X}", self.kind.name(), self.start, self.end)
+  }
+}
+
+// a word of a .STRINGZ that reads as text
+fn text_char(word: u16) -> Option<char> {
+  match word {
+    0x20..=0x7E | 0x09 | 0x0A | 0x0D => Some(word as u8 as char),
+    _ => None,
+  }
+}
+
+impl Machine {
+  // marks range as holding kind, for dump_text(), the debugger and the
+  // TUI to show it as such. Regions added later win where they overlap
+  // earlier ones; one with the name of an earlier one replaces it.
+  pub fn annotate(&mut self, range: impl RangeBounds<u16>, kind: RegionKind, name: &str) {
+    if let Some(region) = Region::new(range, kind, name) {
+      self.add_region(region);
+    }
+  }
+
+  pub fn add_region(&mut self, region: Region) {
+    if !region.name.is_empty() {
+      self.regions.retain(|r| r.name != region.name);
+    }
+    self.regions.push(region);
+  }
+
+  pub fn remove_region(&mut self, name: &str) -> bool {
+    let before: usize = self.regions.len();
+    self.regions.retain(|r| r.name != name);
+    self.regions.len() < before
+  }
+
+  // in the order added
+  pub fn regions(&self) -> &[Region] {
+    &self.regions
+  }
+
+  pub fn region_at(&self, addr: u16) -> Option<&Region> {
+    self.regions.iter().rev().find(|r| r.contains(addr))
+  }
+
+  // the words in range as signed numbers, copied out as memory is behind
+  // a Memory backend. Device registers read as stored
+  pub fn as_i16_slice(&self, range: impl RangeBounds<u16>) -> Vec<i16> {
+    let (start, end): (u32, u32) = span(range);
+    (start..end.max(start)).map(|a| self.mem.read(a as u16) as i16).collect()
+  }
+
+  // the string .STRINGZ left at addr, a char per word up to the zero
+  // word, or as much of it as fits before the end of memory. Words that
+  // aren't chars come out as U+FFFD.
+  pub fn read_string(&self, addr: u16) -> String {
+    (addr as usize..MEM_SIZE).take(MAX_STRING)
+      .map(|a| self.mem.read(a as u16))
+      .take_while(|&w| w != 0)
+      .map(|w| if w < 0x100 { w as u8 as char } else { char::REPLACEMENT_CHARACTER })
+      .collect()
+  }
+
+  // the words in range a line each, rendered as their region says: code
+  // disassembled, data as numbers and chars with each .STRINGZ on a line
+  // of its own, the stack with R6 marked, device registers as stored.
+  // Words outside any region are disassembled, and a region's first line
+  // comes after a `; <region>` header:
+  //
+  //   ; msg (data) x3010..x301F
+  //   x3010  "Hi\n"
+  //   x3014  x0005      5
+  pub fn dump_text(&self, range: impl RangeBounds<u16>) -> String {
+    let (start, end): (u32, u32) = span(range);
+    let sp: u16 = self.getr(SP);
+    let mut out: String = String::new();
+    let mut current: Option<&Region> = None;
+    let mut a: u32 = start;
+    while a < end {
+      let addr: u16 = a as u16;
+      let region: Option<&Region> = self.region_at(addr);
+      if let Some(r) = region.filter(|&r| current != Some(r)) {
+        out.push_str(&format!("; {}\n", r));
+      }
+      current = region;
+
+      let word: u16 = self.mem.read(addr);
+      let label: String = self.symbols.label(addr).map_or(String::new(), |l| format!("{}: ", l));
+      let line: String = match region.map(|r| (r.kind, r.end)) {
+        Some((RegionKind::Data, region_end)) => {
+          // as far as the zero word, within the region and the range
+          let last: u32 = end.min(region_end as u32 + 1);
+          let len: u32 = (a..last).take_while(|&b| text_char(self.mem.read(b as u16)).is_some()).count() as u32;
+          if len > 0 && a + len < last && self.mem.read((a + len) as u16) == 0 {
+            let text: String = (a..a + len).filter_map(|b| text_char(self.mem.read(b as u16))).collect();
+            out.push_str(&format!("x{:04X}  {}{:?}\n", addr, label, text));
+            a += len + 1;
+            continue;
+          }
+          match text_char(word) {
+            Some(c) if c > ' ' => format!("x{:04X} {:>6}  {:?}", word, word as i16, c),
+            _ => format!("x{:04X} {:>6}", word, word as i16),
+          }
+        },
+        Some((RegionKind::Stack, _)) if addr == sp => format!("x{:04X}  <- R6", word),
+        Some((RegionKind::Stack, _)) | Some((RegionKind::Device, _)) => format!("x{:04X}", word),
+        Some((RegionKind::Code, _)) | None => format!("x{:04X}  {}", word, disasm::disassemble(word, addr, &self.symbols)),
+      };
+      out.push_str(&format!("x{:04X}  {}{}\n", addr, label, line));
+      a += 1;
+    }
+    out
+  }
+}
@@ -0,0 +1,124 @@
+use core::fmt;
+
+use serde::de::{self, Deserialize, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use super::*;
+use super::snapshot::VERSION;
+
+// Snapshot, and through it the whole machine, with serde: a struct of
+// version, reg, mem, halt, saved_usp, saved_ssp, kbd_ie, pending and
+// devices, as in Snapshot::to_bytes(). Older versions load with what they
+// lack defaulted; unknown fields are skipped.
+const FIELDS: &[&str] = &["version", "reg", "mem", "halt", "saved_usp", "saved_ssp", "kbd_ie", "pending", "devices"];
+
+impl Serialize for Snapshot {
+  fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+    let mut st = s.serialize_struct("Snapshot", FIELDS.len())?;
+    st.serialize_field("version", &VERSION)?;
+    st.serialize_field("reg", &self.reg)?;
+    st.serialize_field("mem", &self.mem)?;
+    st.serialize_field("halt", &self.halt)?;
+    st.serialize_field("saved_usp", &self.saved_usp)?;
+    st.serialize_field("saved_ssp", &self.saved_ssp)?;
+    st.serialize_field("kbd_ie", &self.kbd_ie)?;
+    st.serialize_field("pending", &self.pending)?;
+    st.serialize_field("devices", &self.devices)?;
+    st.end()
+  }
+}
+
+// the machine serializes as its snapshot, and is brought back by
+// deserializing a Snapshot and passing it to restore()
+impl Serialize for Machine {
+  fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+    self.snapshot().serialize(s)
+  }
+}
+
+impl<'de> Deserialize<'de> for Snapshot {
+  fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Snapshot, D::Error> {
+    d.deserialize_struct("Snapshot", FIELDS, SnapshotVisitor)
+  }
+}
+
+struct SnapshotVisitor;
+
+fn check<E: de::Error>(version: u16, snap: Snapshot) -> Result<Snapshot, E> {
+  if version == 0 || version > VERSION {
+    return Err(E::custom(format!("unsupported snapshot version {}", version)));
+  }
+  if snap.mem.len() != MEM_SIZE {
+    return Err(E::invalid_length(snap.mem.len(), &"65536 words of memory"));
+  }
+  Ok(snap)
+}
+
+fn empty() -> Snapshot {
+  Snapshot {
+    reg: [0; REG_SIZE],
+    mem: Vec::new(),
+    halt: false,
+    saved_usp: 0,
+    saved_ssp: SSP,
+    kbd_ie: false,
+    pending: Vec::new(),
+    devices: Vec::new(),
+  }
+}
+
+impl<'de> Visitor<'de> for SnapshotVisitor {
+  type Value = Snapshot;
+
+  fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str("an LC-3 machine snapshot")
+  }
+
+  // fields in order, for formats that don't name them
+  fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Snapshot, A::Error> {
+    let missing = |i: usize| de::Error::invalid_length(i, &self);
+    let version: u16 = seq.next_element()?.ok_or_else(|| missing(0))?;
+    let mut snap: Snapshot = empty();
+    snap.reg = seq.next_element()?.ok_or_else(|| missing(1))?;
+    snap.mem = seq.next_element()?.ok_or_else(|| missing(2))?;
+    snap.halt = seq.next_element()?.ok_or_else(|| missing(3))?;
+    snap.saved_usp = seq.next_element()?.ok_or_else(|| missing(4))?;
+    snap.saved_ssp = seq.next_element()?.ok_or_else(|| missing(5))?;
+    snap.kbd_ie = seq.next_element()?.ok_or_else(|| missing(6))?;
+    snap.pending = seq.next_element()?.ok_or_else(|| missing(7))?;
+    if version >= 2 {
+      snap.devices = seq.next_element()?.ok_or_else(|| missing(8))?;
+    }
+    check(version, snap)
+  }
+
+  fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Snapshot, A::Error> {
+    let mut version: Option<u16> = None;
+    let mut snap: Snapshot = empty();
+    let mut mem: bool = false;
+    while let Some(key) = map.next_key::<String>()? {
+      match key.as_str() {
+        "version" => version = Some(map.next_value()?),
+        "reg" => snap.reg = map.next_value()?,
+        "mem" => {
+          snap.mem = map.next_value()?;
+          mem = true;
+        },
+        "halt" => snap.halt = map.next_value()?,
+        "saved_usp" => snap.saved_usp = map.next_value()?,
+        "saved_ssp" => snap.saved_ssp = map.next_value()?,
+        "kbd_ie" => snap.kbd_ie = map.next_value()?,
+        "pending" => snap.pending = map.next_value()?,
+        "devices" => snap.devices = map.next_value()?,
+        _ => {
+          map.next_value::<IgnoredAny>()?;
+        },
+      }
+    }
+    let version: u16 = version.ok_or_else(|| de::Error::missing_field("version"))?;
+    if !mem {
+      return Err(de::Error::missing_field("mem"));
+    }
+    check(version, snap)
+  }
+}
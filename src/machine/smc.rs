@@ -0,0 +1,148 @@
+use core::ops::RangeBounds;
+
+use super::dump::span;
+use super::*;
+
+// writes kept for code_writes(); a program rewriting itself in a loop stops
+// collecting past this
+const MAX_WRITES: usize = 1024;
+
+// what the machine does about self-modifying code: stores by the program
+// to words it has already executed, or marked as code with
+// protect_code(). Off by default; Report logs a warning to lc3::mem and
+// keeps the write for code_writes(), Fault stops with
+// MachineError::CodeWrite once the store is done. Addresses passed to
+// allow_code_writes() are never flagged, e.g. a patch area a program is
+// allowed to rewrite.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SmcCheck {
+  #[default]
+  Off,
+  Report,
+  Fault,
+}
+
+impl SmcCheck {
+  // the policy for a --smc name
+  pub fn from_name(name: &str) -> Option<SmcCheck> {
+    match name {
+      "off" => Some(SmcCheck::Off),
+      "report" => Some(SmcCheck::Report),
+      "fault" => Some(SmcCheck::Fault),
+      _ => None,
+    }
+  }
+}
+
+// a store into code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodeWrite {
+  // the storing instruction
+  pub pc: u16,
+  pub addr: u16,
+}
+
+pub(super) struct SmcTracker {
+  fault: bool,
+  // words executed or protected
+  code: Vec<u64>,
+  allowed: Vec<u64>,
+  writes: Vec<CodeWrite>,
+  // a Fault waiting for execute() to return it
+  pending: Option<MachineError>,
+}
+
+fn set(bits: &mut [u64], range: impl RangeBounds<u16>, on: bool) {
+  let (start, end): (u32, u32) = span(range);
+  for a in start..end {
+    let (word, bit): (usize, u32) = (a as usize / 64, a % 64);
+    if on {
+      bits[word] |= 1 << bit;
+    } else {
+      bits[word] &= !(1 << bit);
+    }
+  }
+}
+
+fn get(bits: &[u64], addr: u16) -> bool {
+  bits[addr as usize / 64] & 1 << (addr % 64) != 0
+}
+
+impl Machine {
+  // see SmcCheck; turning it on forgets what was executed, protected and
+  // allowed before
+  pub fn set_smc_check(&mut self, check: SmcCheck) {
+    self.smc = match check {
+      SmcCheck::Off => None,
+      _ => Some(Box::new(SmcTracker {
+        fault: check == SmcCheck::Fault,
+        code: vec![0; MEM_SIZE / 64],
+        allowed: vec![0; MEM_SIZE / 64],
+        writes: Vec::new(),
+        pending: None,
+      })),
+    };
+  }
+
+  pub fn smc_check(&self) -> SmcCheck {
+    match &self.smc {
+      None => SmcCheck::Off,
+      Some(t) if t.fault => SmcCheck::Fault,
+      Some(_) => SmcCheck::Report,
+    }
+  }
+
+  // treats range as code before it has run, e.g. a program's loaded
+  // instructions; does nothing while the check is off
+  pub fn protect_code(&mut self, range: impl RangeBounds<u16>) {
+    if let Some(t) = &mut self.smc {
+      set(&mut t.code, range, true);
+    }
+  }
+
+  // lets the program store into range even once it has run there
+  pub fn allow_code_writes(&mut self, range: impl RangeBounds<u16>) {
+    if let Some(t) = &mut self.smc {
+      set(&mut t.allowed, range, true);
+    }
+  }
+
+  // each address is reported once
+  pub fn code_writes(&self) -> &[CodeWrite] {
+    self.smc.as_ref().map_or(&[], |t| &t.writes)
+  }
+
+  pub(super) fn mark_code(&mut self, pc: u16) {
+    if let Some(t) = &mut self.smc {
+      t.code[pc as usize / 64] |= 1 << (pc % 64);
+    }
+  }
+
+  // a store by the program to addr
+  pub(super) fn check_code_write(&mut self, addr: u16) {
+    let t: &mut SmcTracker = match &mut self.smc {
+      Some(t) => t,
+      None => return,
+    };
+    if !get(&t.code, addr) || get(&t.allowed, addr) || t.writes.iter().any(|w| w.addr == addr) {
+      return;
+    }
+
+    let w: CodeWrite = CodeWrite { pc: self.reg[PC as usize].wrapping_sub(1), addr };
+    if t.fault {
+      t.pending.get_or_insert(MachineError::CodeWrite { pc: w.pc, addr });
+    }
+    if t.writes.len() < MAX_WRITES {
+      t.writes.push(w);
+    }
+    log_to!(self, Mem, Warn, "store to code at {:#06x} by {:#06x}", addr, w.pc);
+  }
+
+  // a Fault raised by a store since the last call
+  pub(super) fn smc_fault(&mut self) -> Result<(), MachineError> {
+    match self.smc.as_mut().and_then(|t| t.pending.take()) {
+      Some(e) => Err(e),
+      None => Ok(()),
+    }
+  }
+}
@@ -0,0 +1,213 @@
+use super::*;
+
+const MAGIC: &[u8; 4] = b"LC3S";
+// version 1 had no device state
+pub(super) const VERSION: u16 = 2;
+
+// architectural state captured by Machine::snapshot(); host-side state
+// such as breakpoints and buffered input is not included
+#[derive(Clone, PartialEq, Eq)]
+pub struct Snapshot {
+  pub reg: [u16; REG_SIZE],
+  pub mem: Vec<u16>,
+  pub halt: bool,
+  pub saved_usp: u16,
+  pub saved_ssp: u16,
+  pub kbd_ie: bool,
+  pub pending: Vec<(u8, u8)>,
+  // Device::save() of each device, in the order they were added
+  pub devices: Vec<Vec<u16>>,
+}
+
+impl Snapshot {
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut out: Vec<u8> = Vec::with_capacity(2 * (MEM_SIZE + REG_SIZE) + 32);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_be_bytes());
+    for r in &self.reg {
+      out.extend_from_slice(&r.to_be_bytes());
+    }
+    out.extend_from_slice(&self.saved_usp.to_be_bytes());
+    out.extend_from_slice(&self.saved_ssp.to_be_bytes());
+    out.push(self.halt as u8);
+    out.push(self.kbd_ie as u8);
+    out.extend_from_slice(&(self.pending.len() as u16).to_be_bytes());
+    for &(vector, priority) in &self.pending {
+      out.push(vector);
+      out.push(priority);
+    }
+    for w in &self.mem {
+      out.extend_from_slice(&w.to_be_bytes());
+    }
+    out.extend_from_slice(&(self.devices.len() as u16).to_be_bytes());
+    for state in &self.devices {
+      out.extend_from_slice(&(state.len() as u16).to_be_bytes());
+      for w in state {
+        out.extend_from_slice(&w.to_be_bytes());
+      }
+    }
+    out
+  }
+
+  pub fn from_bytes(bytes: &[u8]) -> Result<Snapshot, FormatError> {
+    let mut r = Reader { bytes, pos: 0 };
+
+    if r.take(4)? != MAGIC {
+      return Err(invalid("not a snapshot"));
+    }
+    let version: u16 = r.u16()?;
+    if version == 0 || version > VERSION {
+      return Err(invalid("unsupported snapshot version"));
+    }
+
+    let mut reg: [u16; REG_SIZE] = [0; REG_SIZE];
+    for v in reg.iter_mut() {
+      *v = r.u16()?;
+    }
+    let saved_usp: u16 = r.u16()?;
+    let saved_ssp: u16 = r.u16()?;
+    let halt: bool = r.u8()? != 0;
+    let kbd_ie: bool = r.u8()? != 0;
+
+    let n: u16 = r.u16()?;
+    let mut pending: Vec<(u8, u8)> = Vec::with_capacity(n as usize);
+    for _ in 0..n {
+      pending.push((r.u8()?, r.u8()?));
+    }
+
+    let mut mem: Vec<u16> = Vec::with_capacity(MEM_SIZE);
+    for _ in 0..MEM_SIZE {
+      mem.push(r.u16()?);
+    }
+
+    let mut devices: Vec<Vec<u16>> = Vec::new();
+    if version >= 2 {
+      for _ in 0..r.u16()? {
+        let n: u16 = r.u16()?;
+        devices.push((0..n).map(|_| r.u16()).collect::<Result<Vec<u16>, FormatError>>()?);
+      }
+    }
+
+    Ok(Snapshot { reg, mem, halt, saved_usp, saved_ssp, kbd_ie, pending, devices })
+  }
+}
+
+// differences between two snapshots, from Snapshot::diff(). Registers are
+// named as in traces, with USP and SSP for the saved stack pointers;
+// changes are (old, new) pairs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateDiff {
+  pub regs: Vec<(&'static str, u16, u16)>,
+  pub mem: Vec<(u16, u16, u16)>, // address, old, new
+  pub halt: Option<(bool, bool)>,
+}
+
+impl StateDiff {
+  pub fn is_empty(&self) -> bool {
+    self.regs.is_empty() && self.mem.is_empty() && self.halt.is_none()
+  }
+}
+
+// one change per line, e.g. "R0: x0000 -> x0005" or "x4000: x0000 -> x0001"
+impl fmt::Display for StateDiff {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    for &(name, old, new) in &self.regs {
+      writeln!(f, "{}: x{:04X} -> x{:04X}", name, old, new)?;
+    }
+    for &(addr, old, new) in &self.mem {
+      writeln!(f, "x{:04X}: x{:04X} -> x{:04X}", addr, old, new)?;
+    }
+    if let Some((old, new)) = self.halt {
+      writeln!(f, "halt: {} -> {}", old, new)?;
+    }
+    Ok(())
+  }
+}
+
+impl Snapshot {
+  // what changed going from self to other
+  pub fn diff(&self, other: &Snapshot) -> StateDiff {
+    let mut regs: Vec<(&'static str, u16, u16)> = (0..REG_SIZE)
+      .filter(|&r| self.reg[r] != other.reg[r])
+      .map(|r| (REG_NAMES[r], self.reg[r], other.reg[r]))
+      .collect();
+    if self.saved_usp != other.saved_usp {
+      regs.push(("USP", self.saved_usp, other.saved_usp));
+    }
+    if self.saved_ssp != other.saved_ssp {
+      regs.push(("SSP", self.saved_ssp, other.saved_ssp));
+    }
+
+    let mem: Vec<(u16, u16, u16)> = self.mem.iter().zip(other.mem.iter()).enumerate()
+      .filter(|&(_, (a, b))| a != b)
+      .map(|(addr, (&a, &b))| (addr as u16, a, b))
+      .collect();
+
+    let halt: Option<(bool, bool)> = if self.halt != other.halt { Some((self.halt, other.halt)) } else { None };
+    StateDiff { regs, mem, halt }
+  }
+}
+
+fn invalid(msg: &str) -> FormatError {
+  FormatError(msg.to_string())
+}
+
+struct Reader<'a> {
+  bytes: &'a [u8],
+  pos: usize,
+}
+
+impl<'a> Reader<'a> {
+  fn take(&mut self, n: usize) -> Result<&'a [u8], FormatError> {
+    if self.pos + n > self.bytes.len() {
+      return Err(invalid("truncated snapshot"));
+    }
+    let s: &'a [u8] = &self.bytes[self.pos..self.pos + n];
+    self.pos += n;
+    Ok(s)
+  }
+
+  fn u8(&mut self) -> Result<u8, FormatError> {
+    Ok(self.take(1)?[0])
+  }
+
+  fn u16(&mut self) -> Result<u16, FormatError> {
+    let b: &[u8] = self.take(2)?;
+    Ok(u16::from_be_bytes([b[0], b[1]]))
+  }
+}
+
+impl Machine {
+  pub fn snapshot(&self) -> Snapshot {
+    Snapshot {
+      reg: self.reg,
+      mem: (0..MEM_SIZE).map(|a| self.mem.read(a as u16)).collect(),
+      halt: self.halt,
+      saved_usp: self.saved_usp,
+      saved_ssp: self.saved_ssp,
+      kbd_ie: self.keyboard.ie,
+      pending: self.pending.clone(),
+      devices: self.devices.iter().map(|d| d.save()).collect(),
+    }
+  }
+
+  pub fn restore(&mut self, snap: &Snapshot) {
+    self.reg = snap.reg;
+    for (a, &w) in snap.mem.iter().enumerate() {
+      self.store(a as u16, w);
+    }
+    self.halt = snap.halt;
+    self.saved_usp = snap.saved_usp;
+    self.saved_ssp = snap.saved_ssp;
+    self.keyboard.ie = snap.kbd_ie;
+    self.pending = snap.pending.clone();
+    // the calls that led here aren't known
+    self.calls.clear();
+    for r in 0..8 {
+      self.mark_reg_written(r);
+    }
+    for (dev, state) in self.devices.iter_mut().zip(snap.devices.iter()) {
+      dev.restore(state);
+    }
+  }
+}
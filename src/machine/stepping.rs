@@ -0,0 +1,54 @@
+use super::*;
+
+// where run_to_goal() runs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Goal {
+  // the next instruction in the current frame: a JSR, JSRR, TRAP into
+  // the OS or interrupt taken runs until it returns
+  Over,
+  // out of the current subroutine, trap or interrupt handler, to where it
+  // returns to
+  Out,
+  // PC at the address
+  To(u16),
+}
+
+impl Machine {
+  // executes at least one instruction and stops once goal is reached,
+  // with StopReason::GoalReached, or when execution stops on its own or
+  // stop, checked after every instruction, returns true (Condition). Calls and
+  // returns are those of the shadow call stack, see backtrace(), so a
+  // subroutine that doesn't return to its caller is only left when an
+  // outer one returns, and there's no way out of the outermost frame.
+  pub fn run_to_goal<F: FnMut(&Machine) -> bool>(&mut self, goal: Goal, mut stop: F) -> Run {
+    let depth: usize = self.call_depth();
+    let run: Run = self.run_until(|m| m.reached(goal, depth) || stop(m));
+    match run.reason {
+      StopReason::Condition if self.reached(goal, depth) => Run { steps: run.steps, reason: StopReason::GoalReached },
+      _ => run,
+    }
+  }
+
+  fn reached(&self, goal: Goal, depth: usize) -> bool {
+    match goal {
+      Goal::Over => self.call_depth() <= depth,
+      Goal::Out => self.call_depth() < depth,
+      Goal::To(addr) => self.getr(PC) == addr,
+    }
+  }
+
+  // step(), a call counting as one instruction
+  pub fn step_over(&mut self) -> Run {
+    self.run_to_goal(Goal::Over, |_| false)
+  }
+
+  // runs until the current subroutine returns
+  pub fn step_out(&mut self) -> Run {
+    self.run_to_goal(Goal::Out, |_| false)
+  }
+
+  // runs until PC is at addr, stopping at breakpoints on the way
+  pub fn run_to(&mut self, addr: u16) -> Run {
+    self.run_to_goal(Goal::To(addr), |_| false)
+  }
+}
@@ -0,0 +1,40 @@
+use super::*;
+
+// how the machine treats behavior the ISA leaves undefined: the RES
+// opcode (xD, unless IsaExtensions gives it a meaning), TRAPs to vectors
+// no routine exists for, and loads and stores to I/O page addresses no
+// device register decodes. Strict, the default, faults on all of them,
+// through the exception table when a handler is installed; Permissive
+// logs a warning and carries on, RES and unknown TRAPs doing nothing and
+// holes reading and writing as memory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Strictness {
+  #[default]
+  Strict,
+  Permissive,
+}
+
+impl Strictness {
+  // the level for a --strictness name
+  pub fn from_name(name: &str) -> Option<Strictness> {
+    match name {
+      "strict" => Some(Strictness::Strict),
+      "permissive" => Some(Strictness::Permissive),
+      _ => None,
+    }
+  }
+}
+
+impl Machine {
+  pub fn set_strictness(&mut self, strictness: Strictness) {
+    self.strictness = strictness;
+  }
+
+  pub fn strictness(&self) -> Strictness {
+    self.strictness
+  }
+
+  pub(super) fn permissive(&self) -> bool {
+    self.strictness == Strictness::Permissive
+  }
+}
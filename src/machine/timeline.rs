@@ -0,0 +1,146 @@
+use console::NullConsole;
+
+use super::*;
+
+// the state after instruction `at` of the run, with the key the machine
+// was holding (not part of a Snapshot) and how many recorded inputs it
+// had already seen
+struct Checkpoint {
+  at: u64,
+  snap: Snapshot,
+  key: Option<u8>,
+  events: usize,
+}
+
+// a run that can be looked back on: the machine as it was after any
+// instruction of it, rebuilt from the nearest earlier checkpoint by
+// replaying the inputs recorded since.
+//
+//   let mut t = Timeline::new(|| Machine::builder().load(&words).build(), 1000);
+//   t.run();
+//   let before: Snapshot = t.state_at(t.len() - 1);
+//
+// make builds the machine that runs and, once more, the one replaying for
+// state_at(), so it should build the same machine both times. The replay
+// doesn't touch the console. Input the run is given, keys or interrupt()
+// calls, is recorded by the timeline; stopping that recording through
+// machine_mut() leaves state_at() without it.
+pub struct Timeline {
+  live: Machine,
+  replayer: Machine,
+  interval: u64,
+  // the live machine's instruction count when the timeline started
+  start: u64,
+  checkpoints: Vec<Checkpoint>,
+}
+
+impl Timeline {
+  // a checkpoint every interval instructions, each the size of a Snapshot
+  pub fn new<F: Fn() -> Machine>(make: F, interval: u64) -> Timeline {
+    let mut live: Machine = make();
+    let mut replayer: Machine = make();
+    replayer.set_io(Box::new(NullConsole));
+    replayer.typing = None;
+    replayer.breakpoints.clear();
+    replayer.watchpoints.clear();
+    live.start_recording();
+    let mut t: Timeline = Timeline { start: live.count, live, replayer, interval: interval.max(1), checkpoints: Vec::new() };
+    t.checkpoint();
+    t
+  }
+
+  pub fn machine(&self) -> &Machine {
+    &self.live
+  }
+
+  pub fn machine_mut(&mut self) -> &mut Machine {
+    &mut self.live
+  }
+
+  pub fn into_machine(mut self) -> Machine {
+    self.live.stop_recording();
+    self.live
+  }
+
+  // instructions executed since the timeline started
+  pub fn len(&self) -> u64 {
+    self.live.count - self.start
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  fn checkpoint(&mut self) {
+    let at: u64 = self.len();
+    let events: usize = self.live.recording.as_ref().map_or(0, |(_, rec)| rec.events.len());
+    self.checkpoints.push(Checkpoint { at, snap: self.live.snapshot(), key: self.live.keyboard.key, events });
+  }
+
+  fn checkpoint_if_due(&mut self) {
+    if self.len() >= self.checkpoints.last().map_or(0, |c| c.at) + self.interval {
+      self.checkpoint();
+    }
+  }
+
+  // Machine::run_for(), checkpointing along the way
+  pub fn run_for(&mut self, n: u64) -> Run {
+    let mut steps: u64 = 0;
+    loop {
+      self.checkpoint_if_due();
+      if steps == n {
+        return Run { steps, reason: StopReason::StepLimit };
+      }
+      let due: u64 = self.checkpoints.last().map_or(0, |c| c.at) + self.interval - self.len();
+      let run: Run = self.live.run_for((n - steps).min(due));
+      steps += run.steps;
+      if run.reason != StopReason::StepLimit {
+        self.checkpoint_if_due();
+        return Run { steps, reason: run.reason };
+      }
+    }
+  }
+
+  pub fn run(&mut self) -> Run {
+    self.run_for(u64::MAX)
+  }
+
+  // the machine's state after n instructions of the run (as it started
+  // for 0), or after the last one for n past len(). Exceptions taken
+  // before the next instruction, which are steps but not instructions,
+  // count as part of instruction n. Replays from the nearest checkpoint
+  // on a machine of its own.
+  pub fn state_at(&mut self, n: u64) -> Snapshot {
+    if n >= self.len() {
+      return self.live.snapshot();
+    }
+    let c: &Checkpoint = &self.checkpoints[self.checkpoints.partition_point(|c| c.at <= n) - 1];
+    let events: &[replay::Event] = self.live.recording.as_ref().map_or(&[], |(_, rec)| &rec.events);
+    let inputs: Recording = Recording {
+      events: events.iter().skip(c.events)
+        .map(|e| replay::Event { step: e.step.saturating_sub(c.at), input: e.input })
+        .collect(),
+    };
+
+    self.replayer.restore(&c.snap);
+    self.replayer.keyboard.key = c.key;
+    self.replayer.replay(&inputs);
+    let target: u64 = self.replayer.count + (n - c.at);
+    while self.replayer.count < target {
+      // a run stopping without an instruction would stop there again
+      let before: u64 = self.replayer.count;
+      self.replayer.run_until(|m| m.count >= target);
+      if self.replayer.count == before {
+        break;
+      }
+    }
+    // then the steps up to the next instruction
+    loop {
+      let snap: Snapshot = self.replayer.snapshot();
+      let run: Run = self.replayer.run_for(1);
+      if run.steps == 0 || run.reason != StopReason::StepLimit || self.replayer.count > target {
+        return snap;
+      }
+    }
+  }
+}
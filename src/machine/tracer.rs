@@ -0,0 +1,167 @@
+use std::io::{self, Write};
+
+use json::Json;
+use trace::{put_varint, zigzag, InstrTable};
+
+use super::*;
+
+// layout of enable_trace() records
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+  // one object per line:
+  //   {"step":1,"pc":12288,"instr":4129,"asm":"ADD R0, R0, #1","regs":{"R0":1,"CC":1}}
+  JsonLines,
+  // a "LC3T" header, then per instruction little-endian u16s: pc, instr, a
+  // mask of changed registers (bit i for register i in R0-R7, PC, CC, PSR
+  // order) and the new value of each changed register
+  Binary,
+  // changes from one instruction to the next, with the stores each made,
+  // in a few bytes per instruction. See lc3::trace for the layout and for
+  // reading it back.
+  Compact,
+}
+
+// where trace records go. Register values are compared with the state
+// after the PC increment, so the PC only shows up as changed when
+// execution doesn't fall through to the next word.
+pub struct TraceSink {
+  format: TraceFormat,
+  out: Box<dyn Write>,
+  started: bool,
+  // Compact: the registers as a reader of the trace so far has them, the
+  // instructions it knows, and the addresses stored to by the instruction
+  // executing
+  regs: [u16; REG_SIZE],
+  instrs: Option<InstrTable>,
+  writes: Vec<u16>,
+}
+
+impl TraceSink {
+  pub fn new(format: TraceFormat, out: Box<dyn Write>) -> TraceSink {
+    TraceSink { format, out, started: false, regs: [0; REG_SIZE], instrs: None, writes: Vec::new() }
+  }
+
+  fn write(&mut self, m: &Machine, pc: u16, instr: u16, before: &[u16; REG_SIZE]) -> io::Result<()> {
+    let changed: Vec<usize> = (0..REG_SIZE)
+      .filter(|&r| m.reg[r] != before[r])
+      .collect();
+
+    match self.format {
+      TraceFormat::JsonLines => {
+        let regs: Vec<(&str, Json)> = changed.iter().map(|&r| (REG_NAMES[r], Json::from(m.reg[r]))).collect();
+        let record: Json = Json::object(vec![
+          ("step", Json::from(m.count)),
+          ("pc", Json::from(pc)),
+          ("instr", Json::from(instr)),
+          ("asm", Json::from(disasm::disassemble(instr, pc, &m.symbols))),
+          ("regs", Json::object(regs)),
+        ]);
+        writeln!(self.out, "{}", record)
+      },
+      TraceFormat::Binary => {
+        if !self.started {
+          self.out.write_all(b"LC3T")?;
+          self.started = true;
+        }
+        let mask: u16 = changed.iter().fold(0, |mask, &r| mask | 1 << r);
+        let mut rec: Vec<u8> = Vec::with_capacity(6 + 2 * changed.len());
+        for w in [pc, instr, mask].iter().chain(changed.iter().map(|&r| &m.reg[r])) {
+          rec.extend_from_slice(&w.to_le_bytes());
+        }
+        self.out.write_all(&rec)
+      },
+      TraceFormat::Compact => {
+        let mut rec: Vec<u8> = Vec::new();
+        if !self.started {
+          rec.extend_from_slice(b"LC3C");
+          put_varint(&mut rec, m.count - 1);
+          // what the registers were before the fetch
+          self.regs = *before;
+          self.regs[PC as usize] = pc;
+          for &r in &self.regs {
+            put_varint(&mut rec, r as u64);
+          }
+          self.started = true;
+        }
+        let instrs: &mut InstrTable = self.instrs.get_or_insert_with(InstrTable::new);
+        let mut tag: u8 = 0;
+        let mut fields: Vec<u8> = Vec::new();
+        if pc != self.regs[PC as usize] {
+          tag |= 1;
+          put_varint(&mut fields, zigzag(pc.wrapping_sub(self.regs[PC as usize])) as u64);
+        }
+        if instrs.update(pc, instr) {
+          tag |= 2;
+          put_varint(&mut fields, instr as u64);
+        }
+        // against state the reader has, so changes made outside of
+        // instructions, e.g. by an interrupt, are kept too
+        self.regs[PC as usize] = pc.wrapping_add(1);
+        let changed: Vec<usize> = (0..REG_SIZE).filter(|&r| m.reg[r] != self.regs[r]).collect();
+        if !changed.is_empty() {
+          tag |= 4;
+          put_varint(&mut fields, changed.iter().fold(0, |mask, &r| mask | 1 << r));
+          for &r in &changed {
+            put_varint(&mut fields, zigzag(m.reg[r].wrapping_sub(self.regs[r])) as u64);
+            self.regs[r] = m.reg[r];
+          }
+        }
+        if !self.writes.is_empty() {
+          tag |= 8;
+          put_varint(&mut fields, self.writes.len() as u64);
+          let mut last: u16 = 0;
+          for addr in self.writes.drain(..) {
+            put_varint(&mut fields, zigzag(addr.wrapping_sub(last)) as u64);
+            put_varint(&mut fields, m.mem.read(addr) as u64);
+            last = addr;
+          }
+        }
+        rec.push(tag);
+        rec.extend_from_slice(&fields);
+        self.out.write_all(&rec)
+      },
+    }
+  }
+}
+
+impl Machine {
+  // writes a record for every instruction that executes without faulting,
+  // replacing any earlier sink. A write error ends tracing.
+  pub fn enable_trace(&mut self, sink: TraceSink) {
+    self.tracer = Some(sink);
+  }
+
+  // stops tracing and hands back the sink, flushed
+  pub fn disable_trace(&mut self) -> Option<TraceSink> {
+    let mut sink: TraceSink = self.tracer.take()?;
+    if let Err(e) = sink.out.flush() {
+      log_to!(self, Io, Warn, "failed to flush trace: {}", e);
+    }
+    Some(sink)
+  }
+
+  pub fn tracing(&self) -> bool {
+    self.tracer.is_some()
+  }
+
+  pub(super) fn note_trace_write(&mut self, addr: u16) {
+    if let Some(sink) = self.tracer.as_mut().filter(|s| s.format == TraceFormat::Compact) {
+      sink.writes.push(addr);
+    }
+  }
+
+  pub(super) fn write_trace(&mut self, pc: u16, instr: u16, before: &[u16; REG_SIZE]) {
+    if let Some(mut sink) = self.tracer.take() {
+      match sink.write(self, pc, instr, before) {
+        Ok(()) => self.tracer = Some(sink),
+        Err(e) => log_to!(self, Io, Warn, "trace stopped: {}", e),
+      }
+    }
+  }
+}
+
+impl Drop for TraceSink {
+  fn drop(&mut self) {
+    let _ = self.out.flush();
+  }
+}
@@ -0,0 +1,43 @@
+use super::*;
+
+// an InputScript being typed
+pub(super) struct Typing {
+  keys: VecDeque<(When, u8)>,
+  // instruction counts when the script was set and the last key read
+  start: u64,
+  last: u64,
+}
+
+impl Machine {
+  // types script as the program runs, the console only read again once
+  // it's all been read. A key is only seen by polling KBSR once due, a
+  // GETC or IN waiting for one takes it without waiting, as an
+  // instruction count doesn't pass while the console blocks.
+  pub fn set_input_script(&mut self, script: &InputScript) {
+    self.typing = Some(Typing { keys: script.keys().collect(), start: self.count, last: self.count });
+  }
+
+  // whether keys of the input script are left to type
+  pub fn typing(&self) -> bool {
+    self.typing.as_ref().is_some_and(|t| !t.keys.is_empty())
+  }
+
+  // next key of the script, if due at the current step (or at any step
+  // when blocking)
+  pub(super) fn typed_key(&mut self, blocking: bool) -> Option<u8> {
+    let count: u64 = self.count;
+    let t: &mut Typing = self.typing.as_mut()?;
+    let &(when, c) = t.keys.front()?;
+    let due: u64 = match when {
+      When::Next => t.last,
+      When::After(n) => t.last.saturating_add(n),
+      When::At(n) => t.start.saturating_add(n),
+    };
+    if !blocking && due > count {
+      return None;
+    }
+    t.keys.pop_front();
+    t.last = count;
+    Some(c)
+  }
+}
@@ -0,0 +1,99 @@
+use core::ops::RangeInclusive;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use super::*;
+
+pub const UART_READY : u16 = 1 << 15;
+pub const UART_IE    : u16 = 1 << 14;
+
+// serial port bridged to a TCP connection, with registers laid out like
+// the keyboard and display pair from base, URSR by default:
+//
+//   base      receive status, UART_READY when a byte has arrived, UART_IE
+//             to interrupt through INT_UART while one is waiting
+//   base + 2  receive data, taking the byte
+//   base + 4  transmit status, always ready
+//   base + 6  transmit data, sent as its low byte
+//
+// Bytes are read on a background thread like StdConsole's stdin. Once the
+// connection closes nothing more arrives and sends are dropped.
+pub struct Uart {
+  base: u16,
+  stream: TcpStream,
+  rx: Receiver<u8>,
+  byte: Option<u8>,
+  ie: bool,
+}
+
+impl Uart {
+  pub fn new(base: u16, stream: TcpStream) -> io::Result<Uart> {
+    let mut reader: TcpStream = stream.try_clone()?;
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+      let mut buf: [u8; 256] = [0; 256];
+      while let Ok(n) = reader.read(&mut buf) {
+        if n == 0 || buf[..n].iter().any(|&c| tx.send(c).is_err()) {
+          break;
+        }
+      }
+    });
+    Ok(Uart { base, stream, rx, byte: None, ie: false })
+  }
+
+  pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<Uart> {
+    Uart::new(URSR, TcpStream::connect(addr)?)
+  }
+
+  // waits for one peer to connect
+  pub fn accept(listener: &TcpListener) -> io::Result<Uart> {
+    let (stream, _) = listener.accept()?;
+    Uart::new(URSR, stream)
+  }
+
+  fn poll(&mut self) {
+    if self.byte.is_none() {
+      self.byte = self.rx.try_recv().ok();
+    }
+  }
+}
+
+impl Device for Uart {
+  fn addr_range(&self) -> RangeInclusive<u16> {
+    self.base..=self.base.wrapping_add(6)
+  }
+
+  fn read(&mut self, addr: u16) -> u16 {
+    self.poll();
+    match addr.wrapping_sub(self.base) {
+      0 => {
+        let ready: u16 = if self.byte.is_some() { UART_READY } else { 0 };
+        let ie: u16 = if self.ie { UART_IE } else { 0 };
+        ready | ie
+      },
+      2 => self.byte.take().map_or(0, |c| c as u16),
+      4 => UART_READY,
+      _ => 0,
+    }
+  }
+
+  fn write(&mut self, addr: u16, val: u16) {
+    match addr.wrapping_sub(self.base) {
+      0 => self.ie = val & UART_IE != 0,
+      6 => if let Err(e) = self.stream.write_all(&[val as u8]) {
+        log::warn!(target: LogTarget::Io.name(), "uart: {}", e);
+      },
+      _ => {},
+    }
+  }
+
+  fn tick(&mut self) -> Option<(u8, u8)> {
+    if !self.ie {
+      return None;
+    }
+    self.poll();
+    self.byte.map(|_| (INT_UART, UART_PRIORITY))
+  }
+}
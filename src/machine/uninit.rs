@@ -0,0 +1,176 @@
+use super::*;
+
+// reads kept for uninit_reads(); a long run over garbage stops collecting
+// past this
+const MAX_READS: usize = 1024;
+
+// what the machine does about reads of words and registers that were never
+// written, lc3tools' "read before write". Off by default; Report logs a
+// warning to lc3::exec and keeps the read for uninit_reads(), Fault stops
+// with MachineError::Uninitialized. Memory counts as written once stored
+// to by the program, an image load or write_mem(), registers once an
+// instruction or write_reg() sets them; the device registers always do.
+//
+// Only what is written while tracking counts, so it has to be on before
+// the program and OS are loaded, see MachineBuilder::uninit_check().
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UninitCheck {
+  #[default]
+  Off,
+  Report,
+  Fault,
+}
+
+impl UninitCheck {
+  // the policy for a --uninit name
+  pub fn from_name(name: &str) -> Option<UninitCheck> {
+    match name {
+      "off" => Some(UninitCheck::Off),
+      "report" => Some(UninitCheck::Report),
+      "fault" => Some(UninitCheck::Fault),
+      _ => None,
+    }
+  }
+}
+
+// a word or register read before anything was written to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Uninit {
+  Mem(u16),
+  Reg(Reg),
+}
+
+impl fmt::Display for Uninit {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      Uninit::Mem(addr) => write!(f, "x{:04X}", addr),
+      Uninit::Reg(reg) => write!(f, "{:?}", reg),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UninitRead {
+  // the instruction that read it
+  pub pc: u16,
+  pub read: Uninit,
+}
+
+pub(super) struct Tracker {
+  fault: bool,
+  mem: Vec<u64>,
+  // R0-R7
+  regs: u8,
+  // the instruction executing, for reads made on its behalf
+  pc: u16,
+  reads: Vec<UninitRead>,
+  // a Fault waiting for execute() to return it
+  pending: Option<MachineError>,
+}
+
+impl Machine {
+  // see UninitCheck; turning it on forgets what was written before
+  pub fn set_uninit_check(&mut self, check: UninitCheck) {
+    self.uninit = match check {
+      UninitCheck::Off => None,
+      _ => Some(Box::new(Tracker {
+        fault: check == UninitCheck::Fault,
+        mem: vec![0; MEM_SIZE / 64],
+        regs: 0,
+        pc: self.getr(PC),
+        reads: Vec::new(),
+        pending: None,
+      })),
+    };
+  }
+
+  pub fn uninit_check(&self) -> UninitCheck {
+    match &self.uninit {
+      None => UninitCheck::Off,
+      Some(t) if t.fault => UninitCheck::Fault,
+      Some(_) => UninitCheck::Report,
+    }
+  }
+
+  // each word and register is reported once, the first time it's read
+  pub fn uninit_reads(&self) -> &[UninitRead] {
+    self.uninit.as_ref().map_or(&[], |t| &t.reads)
+  }
+
+  pub(super) fn mark_written(&mut self, addr: u16) {
+    if let Some(t) = &mut self.uninit {
+      t.mem[addr as usize / 64] |= 1 << (addr % 64);
+    }
+  }
+
+  pub(super) fn mark_reg_written(&mut self, r: u16) {
+    if let Some(t) = &mut self.uninit {
+      if r < 8 {
+        t.regs |= 1 << r;
+      }
+    }
+  }
+
+  // a memory read by the instruction at the tracker's pc
+  pub(super) fn check_read(&mut self, addr: u16) {
+    let written: bool = match &self.uninit {
+      Some(t) => t.mem[addr as usize / 64] & 1 << (addr % 64) != 0,
+      None => return,
+    };
+    if !written && !IO_PAGE.contains(&addr) {
+      self.mark_written(addr);
+      let pc: u16 = self.uninit.as_ref().map_or(0, |t| t.pc);
+      self.uninit_read(UninitRead { pc, read: Uninit::Mem(addr) });
+    }
+  }
+
+  // checks the fetch of op at pc and the registers it reads, before it
+  // executes
+  pub(super) fn check_operands(&mut self, pc: u16, op: Instruction) -> Result<(), MachineError> {
+    match &mut self.uninit {
+      Some(t) => t.pc = pc,
+      None => return Ok(()),
+    }
+    self.check_read(pc);
+
+    // the register a store writes out isn't checked, saving whatever a
+    // caller left in it being how subroutines and the OS preserve registers,
+    // nor is the source of AND #0, the usual way to clear one
+    let sources: [Option<u16>; 2] = match op {
+      Instruction::AndI { imm: 0, .. } => [None, None],
+      Instruction::Add { sr1, sr2, .. } | Instruction::And { sr1, sr2, .. } => [Some(sr1), Some(sr2)],
+      Instruction::AddI { sr1, .. } | Instruction::AndI { sr1, .. } | Instruction::Not { sr: sr1, .. } => [Some(sr1), None],
+      Instruction::Jmp { base } | Instruction::Jsrr { base } |
+      Instruction::Ldr { base, .. } | Instruction::Str { base, .. } => [Some(base), None],
+      _ => [None, None],
+    };
+    for r in sources.iter().flatten() {
+      let written: bool = self.uninit.as_ref().is_some_and(|t| t.regs & 1 << r != 0);
+      if !written {
+        self.mark_reg_written(*r);
+        self.uninit_read(UninitRead { pc, read: Uninit::Reg(Reg::from_u16(*r).unwrap()) });
+      }
+    }
+    self.uninit_fault()
+  }
+
+  // a Fault raised by a read since the last call
+  pub(super) fn uninit_fault(&mut self) -> Result<(), MachineError> {
+    match self.uninit.as_mut().and_then(|t| t.pending.take()) {
+      Some(e) => Err(e),
+      None => Ok(()),
+    }
+  }
+
+  fn uninit_read(&mut self, read: UninitRead) {
+    log_to!(self, Exec, Warn, "read of uninitialized {} at {:#06x}", read.read, read.pc);
+    if let Some(t) = &mut self.uninit {
+      if t.fault {
+        t.pending.get_or_insert(MachineError::Uninitialized { pc: read.pc, read: read.read });
+      }
+      if t.reads.len() < MAX_READS {
+        t.reads.push(read);
+      }
+    }
+  }
+}
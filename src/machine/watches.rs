@@ -0,0 +1,90 @@
+use super::*;
+
+// an expression kept up to date as the machine runs, see add_watch()
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watch {
+  pub id: usize,
+  // as given to add_watch()
+  pub src: String,
+  // after the last instruction, a word for each address of a range
+  pub value: Vec<u16>,
+  expr: WatchExpr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum WatchExpr {
+  Value(Expr),
+  // MEM[start..end], end excluded
+  Range(Expr, Expr),
+}
+
+impl WatchExpr {
+  fn parse(src: &str, symbols: &SymbolTable) -> Result<WatchExpr, FormatError> {
+    let s: &str = src.trim();
+    let inner: Option<&str> = s.get(..4).filter(|m| m.eq_ignore_ascii_case("MEM[")).and_then(|_| s[4..].strip_suffix(']'));
+    match inner.and_then(|i| i.split_once("..")) {
+      Some((start, end)) => Ok(WatchExpr::Range(Expr::parse(start, symbols)?, Expr::parse(end, symbols)?)),
+      None => Ok(WatchExpr::Value(Expr::parse(s, symbols)?)),
+    }
+  }
+
+  fn eval(&self, m: &Machine) -> Vec<u16> {
+    match self {
+      WatchExpr::Value(e) => vec![e.eval(m)],
+      WatchExpr::Range(start, end) => (start.eval(m)..end.eval(m)).map(|a| m.read_mem(a)).collect(),
+    }
+  }
+}
+
+// `R3 = x0005`, a range's words after the `=` in order
+impl fmt::Display for Watch {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{} =", self.src)?;
+    for w in &self.value {
+      write!(f, " x{:04X}", w)?;
+    }
+    Ok(())
+  }
+}
+
+impl Machine {
+  // watches an Expr, or the words of a range as MEM[<start>..<end>] with
+  // the end excluded, e.g. `R3`, `MEM[SP]` or `MEM[x4000..x4010]`. It's
+  // evaluated after every instruction, and when a word of it changes an
+  // Event::WatchChanged is sent. Returns the id for remove_watch().
+  pub fn add_watch(&mut self, src: &str) -> Result<usize, FormatError> {
+    let expr: WatchExpr = WatchExpr::parse(src, &self.symbols)?;
+    self.watch_ids += 1;
+    let value: Vec<u16> = expr.eval(self);
+    self.watches.push(Watch { id: self.watch_ids, src: src.trim().to_string(), value, expr });
+    Ok(self.watch_ids)
+  }
+
+  pub fn remove_watch(&mut self, id: usize) -> bool {
+    let before: usize = self.watches.len();
+    self.watches.retain(|w| w.id != id);
+    self.watches.len() < before
+  }
+
+  // in the order added
+  pub fn watches(&self) -> &[Watch] {
+    &self.watches
+  }
+
+  pub(super) fn update_watches(&mut self) {
+    if self.watches.is_empty() {
+      return;
+    }
+    let mut watches: Vec<Watch> = core::mem::take(&mut self.watches);
+    for w in &mut watches {
+      let value: Vec<u16> = w.expr.eval(self);
+      for (index, &v) in value.iter().enumerate() {
+        if w.value.get(index) != Some(&v) {
+          self.emit(Event::WatchChanged { id: w.id, index, value: v });
+        }
+      }
+      w.value = value;
+    }
+    self.watches = watches;
+  }
+}
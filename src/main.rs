@@ -1,13 +1,1158 @@
 #![allow(dead_code)]
 #![allow(non_snake_case)]
 
-fn main() {
-  env_logger::init();
+extern crate env_logger;
+extern crate lc3;
+extern crate log;
+
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::process;
+use std::time::Duration;
+
+use lc3::harness::{self, Report, TestCase};
+use lc3::assembler::{Case, Lint, LintKind, Style};
+use lc3::json::Json;
+use lc3::linker::{self, LinkError, Linked, Module};
+use lc3::loader::{self, LoadedImage};
+use lc3::script::{InputScript, When};
+use lc3::trace::{self, Hit, Query};
+use lc3::{assembler, disasm, Coverage, Framebuffer, ImageFormat, IsaExtensions, Lc3bMachine, LogTarget, Machine, Random, Recording, Reg, Region, SmcCheck, StdConsole, StopReason, Strictness, SymbolTable, Timer, TraceFormat, TraceSink, Uart, UninitCheck, CALLEE_SAVED, RNG};
+use lc3::debugger::{self, Debugger};
+use lc3::repl::Repl;
+use lc3::term::RawMode;
+use log::LevelFilter;
+
+const USAGE: &str = "\
+usage: lc3 run <program> [options]
+       lc3 debug <program> [options]
+       lc3 repl [<program>] [options]
+       lc3 dump <program> [--range <start>..[<end>]] [--format <fmt>]
+                [--output <file>] [options]
+       lc3 asm <source> [--listing] [--symbols]
+       lc3 fmt <source> [--write | --check] [--op-column <n>]
+               [--comment-column <n>] [--case <case>]
+       lc3 lint <source> [--allow <lint>]... [--json]
+       lc3 link <module>... [--output <file>] [--format <fmt>]
+                [--symbols <file>]
+       lc3 grade --spec <tests.toml> <program> [--json] [--coverage <file>]
+       lc3 trace query <trace> <query> <addr>
+
+programs are .obj or Intel HEX .hex images, or .asm sources
+
+options:
+  --format <fmt>     load the program as obj, hex, bin-be or bin-le
+  --origin <addr>    load address of bin-be and bin-le images (default x3000)
+  --entry <addr>     start execution at <addr> (default the image's entry
+                     point, or its origin)
+  --max-steps <n>    stop after executing <n> instructions
+  --time-limit <s>   stop after running for <s> seconds
+  --clock <hz>       execute at most <hz> instructions per second
+  --isa-ext <ext>    give the reserved opcode xD the shift (LSHF, RSHFL,
+                     RSHFA) or mul (MUL) extension
+  --strictness <s>   strict (default) faults on the RES opcode, unknown
+                     TRAPs and device register holes, permissive warns
+                     and carries on
+  --audit-cc         check condition codes after every instruction
+  --check-stack      report subroutines that return with R6 moved or
+                     R1-R5 changed, on stderr on exit
+  --uninit <policy>  report (on stderr on exit) or fault on reads of
+                     memory and registers nothing was written to
+  --randomize <seed> start with pseudo-random registers and memory rather
+                     than zeros
+  --smc <policy>     report (on stderr on exit) or fault on stores to
+                     instructions that have already run
+  --detect-loops     stop when the program is stuck in a loop that can't
+                     make progress
+  --jit              run code through the threaded-code backend where it
+                     can (needs the jit feature)
+  --uart <host:port> bridge the serial port at URSR (xFE10) to a TCP server
+  --uart-listen <addr>
+                     wait for a TCP connection to bridge the serial port to
+  --allow-path <p>   let the file TRAPs x30-x33 open files under <p>, may be
+                     repeated (needs the extensions feature)
+  --lc3b             run an LC-3b .obj image on the byte-addressable
+                     LC-3b; of the other options only --entry and
+                     --max-steps apply
+  --os               load the LC-3 OS and run TRAPs through its routines
+  --supervisor       run the program in supervisor mode, e.g. to reach the
+                     device registers without the OS
+  --timer            attach the interval timer at TMR (xFE08) and TMI (xFE0A),
+                     interrupting through vector x81
+  --rng <seed>       attach the random number generator at RNG (xFE0C),
+                     seeded with <seed>
+  --region <spec>    annotate memory as code, data, stack or device for the
+                     debugger and text dumps, <start>..[<end>]:<kind>[:<name>],
+                     e.g. x4000..x4100:stack:user
+  --trace            print every executed instruction to stderr
+  --log <list>       log the given subsystems to stderr: a comma separated
+                     list of exec, mem, io and irq, each optionally
+                     followed by =<level>, e.g. exec,irq=warn
+  --trace-file <file>
+                     write a structured trace of every instruction to <file>
+  --trace-format <f> jsonl (default), bin or compact, a few bytes per
+                     instruction including the stores it made
+  --stats            print execution statistics to stderr on exit
+  --coverage <file>  write a listing of an .asm program to <file> marking
+                     the lines executed, read and written
+  --profile <file>   write the instructions executed on each call stack to
+                     <file> in the folded format of flamegraph.pl, and a
+                     table of them by subroutine to stderr on exit
+  --frame <file>     write the video memory at xC000 (128x124) to <file> as
+                     a PBM image on exit
+  --record <file>    log keyboard input to <file> for later replay
+  --replay <file>    take keyboard input from a recording
+  --input <file>     type the contents of <file> as keyboard input before
+                     reading the terminal
+  --input-script <file>
+                     type keyboard input from a script of quoted text,
+                     each line optionally after `wait <n>` or `at <n>`
+                     instructions
+
+dump runs the program to HALT, its output on stderr, then writes the memory in range, end
+excluded (default all of it), as an obj (default), Intel HEX hex or bare
+big-endian bin image to <file> or stdout, or as text, a word per line
+rendered as the --region annotations say. The program loads as for run, by
+its extension
+
+asm writes <source>.obj, and with --listing and --symbols an lc3as style
+<source>.lst listing and <source>.sym symbol table
+
+fmt prints an .asm source with its labels, opcodes, operands and comments
+lined up, or with --write rewrites it in place. --check prints nothing and
+exits with status 1 if the source isn't formatted. Opcodes start one past
+the longest label and comments one past the longest line of code, unless
+--op-column and --comment-column say otherwise. --case sets the case of
+opcodes and registers: upper (default), lower or keep
+
+lint warns about likely mistakes in an .asm program: unreachable code,
+branch-to-data, fall-into-data, missing-halt, and clobbered-register for
+a JSR to a subroutine that overwrites a register the caller still needs.
+--allow turns one off. --json prints the warnings as an array for CI. It
+exits with status 1 if there are any
+
+link merges .asm, .obj and .hex modules into one image, written as an obj
+(default) or Intel HEX hex image to <file> or stdout, with gaps between
+modules zero. Each module keeps its origin, so none may overlap; labels
+defined in one, including in the .sym next to an .obj, can be used in the
+.asm modules. Execution starts where the first module does. --symbols
+writes the labels of all of them as a .sym file
+
+repl assembles lines as they are typed into the machine, optionally with a
+program already loaded, and executes them one at a time
+
+grade runs each test case in the spec against an .obj or .asm program and
+prints a summary, or with --json a report for CI. It exits with status 1
+if any case fails. Output is expected as exact text (expect_output), a
+regex (expect_output_regex) or a template with numeric placeholders like
+{3.14+-0.01} (expect_output_template), with ignore_whitespace = true to
+compare lines with their spacing normalized; failures show a diff.
+--coverage writes the listing of an .asm program with the coverage of all
+cases together
+
+trace query searches a bin or compact trace from --trace-file, printing a
+line per instruction found: with `writes` those that stored to <addr>,
+`last-write` the last of them, `executions` every run of the instruction
+at <addr>. Stores are only recorded in compact traces";
+
+struct Options {
+  program: Option<String>,
+  format: Option<ImageFormat>,
+  entry: Option<u16>,
+  max_steps: Option<u64>,
+  time_limit: Option<Duration>,
+  clock: u32,
+  lc3b: bool,
+  os: bool,
+  supervisor: bool,
+  timer: bool,
+  rng: Option<u16>,
+  randomize: Option<u16>,
+  uart: Option<String>,
+  uart_listen: Option<String>,
+  audit_cc: bool,
+  check_stack: bool,
+  uninit: UninitCheck,
+  smc: SmcCheck,
+  detect_loops: bool,
+  isa_ext: IsaExtensions,
+  strictness: Strictness,
+  jit: bool,
+  allow_paths: Vec<String>,
+  regions: Vec<Region>,
+  trace: bool,
+  trace_file: Option<String>,
+  trace_format: TraceFormat,
+  stats: bool,
+  log: Option<Vec<(LogTarget, LevelFilter)>>,
+  coverage: Option<String>,
+  profile: Option<String>,
+  frame: Option<String>,
+  record: Option<String>,
+  replay: Option<String>,
+  input: Option<String>,
+  input_script: Option<String>,
+  // the program's output goes to stderr, stdout is for the host
+  console_stderr: bool,
+}
+
+fn usage() -> ! {
+  eprintln!("{}", USAGE);
+  process::exit(2);
+}
+
+fn parse_options(args: &[String], need_program: bool) -> Options {
+  let mut program: Option<String> = None;
+  let mut format: Option<String> = None;
+  let mut origin: u16 = 0x3000;
+  let mut entry: Option<u16> = None;
+  let mut max_steps: Option<u64> = None;
+  let mut time_limit: Option<Duration> = None;
+  let mut clock: u32 = 0;
+  let mut lc3b: bool = false;
+  let mut os: bool = false;
+  let mut supervisor: bool = false;
+  let mut timer: bool = false;
+  let mut rng: Option<u16> = None;
+  let mut randomize: Option<u16> = None;
+  let mut uart: Option<String> = None;
+  let mut uart_listen: Option<String> = None;
+  let mut audit_cc: bool = false;
+  let mut check_stack: bool = false;
+  let mut uninit: UninitCheck = UninitCheck::Off;
+  let mut smc: SmcCheck = SmcCheck::Off;
+  let mut detect_loops: bool = false;
+  let mut isa_ext: IsaExtensions = IsaExtensions::None;
+  let mut strictness: Strictness = Strictness::Strict;
+  let mut jit: bool = false;
+  let mut allow_paths: Vec<String> = Vec::new();
+  let mut regions: Vec<Region> = Vec::new();
+  let mut trace: bool = false;
+  let mut trace_file: Option<String> = None;
+  let mut trace_format: TraceFormat = TraceFormat::JsonLines;
+  let mut stats: bool = false;
+  let mut log: Option<Vec<(LogTarget, LevelFilter)>> = None;
+  let mut coverage: Option<String> = None;
+  let mut profile: Option<String> = None;
+  let mut frame: Option<String> = None;
+  let mut record: Option<String> = None;
+  let mut replay: Option<String> = None;
+  let mut input: Option<String> = None;
+  let mut input_script: Option<String> = None;
+
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--format" => match args.next() {
+        Some(name) => format = Some(name.clone()),
+        None => usage(),
+      },
+      "--origin" => match args.next().and_then(|a| debugger::parse_addr(a)) {
+        Some(addr) => origin = addr,
+        None => usage(),
+      },
+      "--entry" => match args.next().and_then(|a| debugger::parse_addr(a)) {
+        Some(addr) => entry = Some(addr),
+        None => usage(),
+      },
+      "--max-steps" => match args.next().and_then(|a| a.parse().ok()) {
+        Some(n) => max_steps = Some(n),
+        None => usage(),
+      },
+      "--time-limit" => match args.next().and_then(|a| a.parse().ok()).and_then(|s: f64| Duration::try_from_secs_f64(s).ok()) {
+        Some(t) => time_limit = Some(t),
+        None => usage(),
+      },
+      "--clock" => match args.next().and_then(|a| a.parse().ok()) {
+        Some(hz) => clock = hz,
+        None => usage(),
+      },
+      "--lc3b" => lc3b = true,
+      "--os" => os = true,
+      "--supervisor" => supervisor = true,
+      "--timer" => timer = true,
+      "--rng" => match args.next().and_then(|a| a.parse().ok().or_else(|| debugger::parse_addr(a))) {
+        Some(seed) => rng = Some(seed),
+        None => usage(),
+      },
+      "--randomize" => match args.next().and_then(|a| a.parse().ok().or_else(|| debugger::parse_addr(a))) {
+        Some(seed) => randomize = Some(seed),
+        None => usage(),
+      },
+      "--uart" => match args.next() {
+        Some(addr) => uart = Some(addr.clone()),
+        None => usage(),
+      },
+      "--uart-listen" => match args.next() {
+        Some(addr) => uart_listen = Some(addr.clone()),
+        None => usage(),
+      },
+      "--audit-cc" => audit_cc = true,
+      "--check-stack" => check_stack = true,
+      "--detect-loops" => detect_loops = true,
+      "--isa-ext" => match args.next().and_then(|a| IsaExtensions::from_name(a)) {
+        Some(ext) => isa_ext = ext,
+        None => usage(),
+      },
+      "--strictness" => match args.next().and_then(|a| Strictness::from_name(a)) {
+        Some(s) => strictness = s,
+        None => usage(),
+      },
+      "--uninit" => match args.next().and_then(|a| UninitCheck::from_name(a)) {
+        Some(u) => uninit = u,
+        None => usage(),
+      },
+      "--smc" => match args.next().and_then(|a| SmcCheck::from_name(a)) {
+        Some(c) => smc = c,
+        None => usage(),
+      },
+      "--jit" => jit = true,
+      "--allow-path" => match args.next() {
+        Some(path) => allow_paths.push(path.clone()),
+        None => usage(),
+      },
+      "--region" => match args.next().and_then(|a| Region::parse(a).ok()) {
+        Some(region) => regions.push(region),
+        None => usage(),
+      },
+      "--trace" => trace = true,
+      "--trace-file" => match args.next() {
+        Some(path) => trace_file = Some(path.clone()),
+        None => usage(),
+      },
+      "--trace-format" => match args.next().map(|a| a.as_str()) {
+        Some("jsonl") => trace_format = TraceFormat::JsonLines,
+        Some("bin") => trace_format = TraceFormat::Binary,
+        Some("compact") => trace_format = TraceFormat::Compact,
+        _ => usage(),
+      },
+      "--stats" => stats = true,
+      "--log" => match args.next().and_then(|a| parse_log(a)) {
+        Some(filters) => log = Some(filters),
+        None => usage(),
+      },
+      "--coverage" => match args.next() {
+        Some(path) => coverage = Some(path.clone()),
+        None => usage(),
+      },
+      "--profile" => match args.next() {
+        Some(path) => profile = Some(path.clone()),
+        None => usage(),
+      },
+      "--frame" => match args.next() {
+        Some(path) => frame = Some(path.clone()),
+        None => usage(),
+      },
+      "--record" => match args.next() {
+        Some(path) => record = Some(path.clone()),
+        None => usage(),
+      },
+      "--replay" => match args.next() {
+        Some(path) => replay = Some(path.clone()),
+        None => usage(),
+      },
+      "--input" => match args.next() {
+        Some(path) => input = Some(path.clone()),
+        None => usage(),
+      },
+      "--input-script" => match args.next() {
+        Some(path) => input_script = Some(path.clone()),
+        None => usage(),
+      },
+      a if a.starts_with("--") => usage(),
+      _ if program.is_none() => program = Some(arg.clone()),
+      _ => usage(),
+    }
+  }
+
+  let format: Option<ImageFormat> = match format {
+    Some(name) => match ImageFormat::from_name(&name, origin) {
+      Some(format) => Some(format),
+      None => usage(),
+    },
+    None => None,
+  };
+
+  if need_program && program.is_none() {
+    usage();
+  }
+  Options { program, format, entry, max_steps, time_limit, clock, lc3b, os, supervisor, timer, rng, randomize, uart, uart_listen, audit_cc, check_stack, uninit, smc, detect_loops, isa_ext, strictness, jit, allow_paths, regions, trace, trace_file, trace_format, stats, log, coverage, profile, frame, record, replay, input, input_script, console_stderr: false }
+}
+
+// exec,irq=warn: each subsystem at the given level, trace by default
+fn parse_log(list: &str) -> Option<Vec<(LogTarget, LevelFilter)>> {
+  list.split(',').map(|item| {
+    let (name, level) = item.split_once('=').unwrap_or((item, "trace"));
+    Some((LogTarget::from_name(name)?, level.parse().ok()?))
+  }).collect()
+}
+
+fn setup(opts: &Options) -> Machine {
+  let mut builder = Machine::builder().os(opts.os).supervisor(opts.supervisor).uninit_check(opts.uninit);
+  if let Some(seed) = opts.randomize {
+    builder = builder.randomize(seed);
+  }
+  if opts.console_stderr {
+    builder = builder.io(Box::new(StdConsole::stderr()));
+  }
+  if opts.timer {
+    builder = builder.device(Box::new(Timer::default()));
+  }
+  if let Some(seed) = opts.rng {
+    builder = builder.device(Box::new(Random::new(RNG, seed)));
+  }
+  if let Some(addr) = &opts.uart {
+    match Uart::connect(addr.as_str()) {
+      Ok(uart) => builder = builder.device(Box::new(uart)),
+      Err(e) => {
+        eprintln!("failed to connect to {}: {}", addr, e);
+        process::exit(1);
+      },
+    }
+  }
+  if let Some(addr) = &opts.uart_listen {
+    eprintln!("waiting for a connection on {}", addr);
+    match TcpListener::bind(addr.as_str()).and_then(|l| Uart::accept(&l)) {
+      Ok(uart) => builder = builder.device(Box::new(uart)),
+      Err(e) => {
+        eprintln!("failed to listen on {}: {}", addr, e);
+        process::exit(1);
+      },
+    }
+  }
+  let mut m = builder.build();
+  if let Some(filters) = &opts.log {
+    for &target in LogTarget::ALL.iter() {
+      let level: LevelFilter = filters.iter().find(|f| f.0 == target).map_or(LevelFilter::Off, |f| f.1);
+      m.set_log_filter(target, level);
+    }
+  }
 
-  let mut m = lc3::Machine::new();
-  m.init();
+  if let Some(program) = &opts.program {
+    let loaded: Result<(), Box<dyn Error>> = match opts.format {
+      Some(format) => fs::read(program).map_err(Box::from)
+        .and_then(|bytes| Ok(m.load_bytes(&bytes, format)?)),
+      None => m.load_program(Path::new(program)),
+    };
+    if let Err(e) = loaded {
+      eprintln!("failed to load {}: {}", program, e);
+      process::exit(1);
+    }
+  }
+  if let Some(entry) = opts.entry {
+    m.write_reg(Reg::PC, entry);
+  }
+
+  if let Some(path) = &opts.replay {
+    match fs::read_to_string(path).and_then(|src| Ok(Recording::parse(&src)?)) {
+      Ok(rec) => m.replay(&rec),
+      Err(e) => {
+        eprintln!("failed to load {}: {}", path, e);
+        process::exit(1);
+      },
+    }
+  }
+  if let Some(path) = &opts.input {
+    match fs::read(path) {
+      Ok(text) => m.set_input_script(&InputScript { pieces: vec![(When::Next, text)] }),
+      Err(e) => {
+        eprintln!("failed to read {}: {}", path, e);
+        process::exit(1);
+      },
+    }
+  }
+  if let Some(path) = &opts.input_script {
+    match fs::read_to_string(path).and_then(|src| Ok(InputScript::parse(&src)?)) {
+      Ok(script) => m.set_input_script(&script),
+      Err(e) => {
+        eprintln!("failed to load {}: {}", path, e);
+        process::exit(1);
+      },
+    }
+  }
+  m.set_clock_hz(opts.clock);
+  m.set_cc_audit(opts.audit_cc);
+  m.set_smc_check(opts.smc);
+  m.set_progress_check(opts.detect_loops);
+  if opts.check_stack {
+    m.enable_stack_check(CALLEE_SAVED);
+  }
+  m.set_isa_extensions(opts.isa_ext);
+  m.set_strictness(opts.strictness);
+  set_jit(&mut m, opts.jit);
+  allow_paths(&mut m, &opts.allow_paths);
+  for region in &opts.regions {
+    m.add_region(region.clone());
+  }
+  if let Some(path) = &opts.trace_file {
+    match fs::File::create(path) {
+      Ok(f) => m.enable_trace(TraceSink::new(opts.trace_format, Box::new(BufWriter::new(f)))),
+      Err(e) => {
+        eprintln!("failed to create {}: {}", path, e);
+        process::exit(1);
+      },
+    }
+  }
+  if opts.record.is_some() {
+    m.start_recording();
+  }
+  if opts.coverage.is_some() {
+    m.start_coverage();
+  }
+  if opts.profile.is_some() {
+    m.start_profile();
+  }
+  m
+}
+
+// writes the coverage listing of an .asm program to path
+fn write_coverage(path: &str, program: &Path, coverage: &Coverage) {
+  if program.extension().is_none_or(|e| e != "asm") {
+    eprintln!("--coverage needs an .asm program");
+    return;
+  }
+  let listing: Result<String, Box<dyn Error>> = fs::read_to_string(program).map_err(Box::from)
+    .and_then(|src| Ok(coverage.listing(&assembler::assemble_file(&src, program)?, &src)));
+  if let Err(e) = listing.and_then(|l| Ok(fs::write(path, l)?)) {
+    eprintln!("failed to write {}: {}", path, e);
+  }
+}
+
+#[cfg(feature = "extensions")]
+fn allow_paths(m: &mut Machine, paths: &[String]) {
+  for path in paths {
+    if let Err(e) = m.allow_path(Path::new(path)) {
+      eprintln!("failed to allow {}: {}", path, e);
+      process::exit(1);
+    }
+  }
+}
+
+#[cfg(not(feature = "extensions"))]
+fn allow_paths(_m: &mut Machine, paths: &[String]) {
+  if !paths.is_empty() {
+    eprintln!("--allow-path needs lc3 built with the extensions feature");
+    process::exit(2);
+  }
+}
+
+#[cfg(feature = "jit")]
+fn set_jit(m: &mut Machine, enable: bool) {
+  m.set_jit(enable);
+}
+
+#[cfg(not(feature = "jit"))]
+fn set_jit(_m: &mut Machine, enable: bool) {
+  if enable {
+    eprintln!("--jit needs lc3 built with the jit feature");
+    process::exit(2);
+  }
+}
+
+fn trace(m: &Machine) {
+  let pc: u16 = m.read_reg(Reg::PC);
+  let word: u16 = m.read_mem(pc);
+  eprintln!("x{:04X}: x{:04X}  {}", pc, word, disasm::disassemble(word, pc, m.symbols()));
+}
+
+// runs the machine to HALT, exiting if it stops any other way
+fn run(opts: &Options) -> Machine {
+  let mut m = setup(opts);
+
+  // process::exit() skips destructors, so the terminal is restored as soon
+  // as the machine stops
+  let raw: RawMode = RawMode::enter();
+  m.set_limits(None, opts.time_limit);
+  let run = if opts.trace {
+    trace(&m);
+    let mut steps: u64 = 0;
+    m.run_until(|m| {
+      steps += 1;
+      let done: bool = opts.max_steps.is_some_and(|max| steps >= max);
+      if !done && !m.halt {
+        trace(m);
+      }
+      done
+    })
+  } else {
+    match opts.max_steps {
+      Some(n) => m.run_for(n),
+      None => m.run(),
+    }
+  };
+  drop(raw);
+  // flushed here, process::exit() below skips the machine's destructor
+  m.disable_trace();
+
+  if opts.stats {
+    eprint!("{}", m.stats());
+  }
+  for v in m.violations() {
+    eprintln!("{}", v);
+  }
+  // under fault the first read is the fault, reported below
+  if m.uninit_check() == UninitCheck::Report {
+    for r in m.uninit_reads() {
+      eprintln!("x{:04X}: read of uninitialized {}", r.pc, r.read);
+    }
+  }
+  if m.smc_check() == SmcCheck::Report {
+    for w in m.code_writes() {
+      eprintln!("x{:04X}: store to code at x{:04X}", w.pc, w.addr);
+    }
+  }
+
+  if let (Some(path), Some(program), Some(coverage)) = (&opts.coverage, &opts.program, m.coverage()) {
+    write_coverage(path, Path::new(program), coverage);
+  }
+  if let (Some(path), Some(profile)) = (&opts.profile, m.profile()) {
+    eprint!("{}", profile.report(m.symbols()));
+    if let Err(e) = fs::write(path, profile.folded(m.symbols())) {
+      eprintln!("failed to write {}: {}", path, e);
+    }
+  }
+
+  if let Some(path) = &opts.frame {
+    if let Err(e) = fs::write(path, Framebuffer::default().frame(&m).to_pbm()) {
+      eprintln!("failed to write {}: {}", path, e);
+    }
+  }
+
+  if let (Some(path), Some(rec)) = (&opts.record, m.stop_recording()) {
+    if let Err(e) = fs::write(path, rec.to_string()) {
+      eprintln!("failed to write {}: {}", path, e);
+    }
+  }
+
+  match run.reason {
+    StopReason::Halt => m,
+    StopReason::StepLimit | StopReason::Condition => {
+      eprintln!("step limit of {} reached", run.steps);
+      process::exit(3);
+    },
+    StopReason::LimitExceeded => {
+      eprintln!("time limit reached after {} steps", run.steps);
+      process::exit(3);
+    },
+    StopReason::NoProgress => {
+      eprintln!("stuck in a loop at x{:04X} after {} steps", m.read_reg(Reg::PC), run.steps);
+      process::exit(3);
+    },
+    StopReason::Fault(e) => {
+      eprintln!("{}", e);
+      process::exit(1);
+    },
+    reason => {
+      eprintln!("stopped: {:?}", reason);
+      process::exit(1);
+    },
+  }
+}
+
+// x3000..x3100, or x3000.. up to the end of memory
+fn parse_range(s: &str) -> Option<(u16, Option<u16>)> {
+  let (start, end) = s.split_once("..")?;
+  let end: Option<u16> = if end.is_empty() { None } else { Some(debugger::parse_addr(end)?) };
+  Some((debugger::parse_addr(start)?, end))
+}
+
+fn dump(args: &[String]) {
+  let mut range: (u16, Option<u16>) = (0, None);
+  // None for text
+  let mut format: Option<ImageFormat> = Some(ImageFormat::Obj);
+  let mut output: Option<&String> = None;
+  // the rest are run options
+  let mut rest: Vec<String> = Vec::new();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--range" => match args.next().and_then(|a| parse_range(a)) {
+        Some(r) => range = r,
+        None => usage(),
+      },
+      "--format" => match args.next().map(|a| a.as_str()) {
+        Some("obj") => format = Some(ImageFormat::Obj),
+        Some("hex") => format = Some(ImageFormat::IntelHex),
+        // the origin is left out, the range says where it goes
+        Some("bin") => format = Some(ImageFormat::RawBigEndian { origin: 0 }),
+        Some("text") => format = None,
+        _ => usage(),
+      },
+      "--output" => match args.next() {
+        Some(path) => output = Some(path),
+        None => usage(),
+      },
+      _ => rest.push(arg.clone()),
+    }
+  }
+
+  let mut opts: Options = parse_options(&rest, true);
+  opts.console_stderr = true;
+  let m: Machine = run(&opts);
+  let image: Vec<u8> = match (range, format) {
+    ((start, Some(end)), Some(format)) => m.dump_image_as(start..end, format),
+    ((start, None), Some(format)) => m.dump_image_as(start.., format),
+    ((start, Some(end)), None) => m.dump_text(start..end).into_bytes(),
+    ((start, None), None) => m.dump_text(start..).into_bytes(),
+  };
+  let written: io::Result<()> = match output {
+    Some(path) => fs::write(path, &image),
+    None => io::stdout().write_all(&image),
+  };
+  if let Err(e) = written {
+    eprintln!("failed to write {}: {}", output.map_or("stdout", |p| p.as_str()), e);
+    process::exit(1);
+  }
+}
+
+fn run_lc3b(opts: &Options) {
+  let mut m: Lc3bMachine = Lc3bMachine::new(Box::new(StdConsole::new()));
+  let program: &String = opts.program.as_ref().unwrap();
+  if let Err(e) = fs::read(program).map_err(Box::<dyn Error>::from).and_then(|bytes| Ok(m.load_image_bytes(&bytes)?)) {
+    eprintln!("failed to load {}: {}", program, e);
+    process::exit(1);
+  }
+  if let Some(entry) = opts.entry {
+    m.write_reg(Reg::PC, entry);
+  }
+
+  let raw: RawMode = RawMode::enter();
+  let run = match opts.max_steps {
+    Some(n) => m.run_for(n),
+    None => m.run(),
+  };
+  drop(raw);
+  match run.reason {
+    StopReason::Halt => {},
+    StopReason::StepLimit => {
+      eprintln!("step limit of {} reached", run.steps);
+      process::exit(3);
+    },
+    StopReason::Fault(e) => {
+      eprintln!("{}", e);
+      process::exit(1);
+    },
+    reason => {
+      eprintln!("stopped: {:?}", reason);
+      process::exit(1);
+    },
+  }
+}
+
+fn asm(args: &[String]) {
+  let mut source: Option<&String> = None;
+  let mut listing: bool = false;
+  let mut symbols: bool = false;
+  for arg in args {
+    match arg.as_str() {
+      "--listing" => listing = true,
+      "--symbols" => symbols = true,
+      a if a.starts_with("--") => usage(),
+      _ if source.is_none() => source = Some(arg),
+      _ => usage(),
+    }
+  }
+  let source: &Path = match source {
+    Some(s) => Path::new(s),
+    None => usage(),
+  };
+
+  let src: String = match fs::read_to_string(source) {
+    Ok(src) => src,
+    Err(e) => {
+      eprintln!("failed to read {}: {}", source.display(), e);
+      process::exit(1);
+    },
+  };
+  let prog = match assembler::assemble_file(&src, source) {
+    Ok(prog) => prog,
+    Err(e) => {
+      for d in &e.diagnostics {
+        eprint!("{}", d.render(&source.display().to_string()));
+      }
+      process::exit(1);
+    },
+  };
+
+  let mut outputs: Vec<(&str, Vec<u8>)> = vec![("obj", prog.to_obj())];
+  if listing {
+    outputs.push(("lst", prog.listing(&src).into_bytes()));
+  }
+  if symbols {
+    outputs.push(("sym", prog.symbols.to_sym().into_bytes()));
+  }
+  for (ext, bytes) in outputs {
+    let path = source.with_extension(ext);
+    if let Err(e) = fs::write(&path, bytes) {
+      eprintln!("failed to write {}: {}", path.display(), e);
+      process::exit(1);
+    }
+  }
+}
+
+fn fmt(args: &[String]) {
+  let mut source: Option<&String> = None;
+  let mut write: bool = false;
+  let mut check: bool = false;
+  let mut style: Style = Style::default();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--write" => write = true,
+      "--check" => check = true,
+      "--op-column" => match args.next().and_then(|n| n.parse().ok()) {
+        Some(n) => style.op_column = Some(n),
+        None => usage(),
+      },
+      "--comment-column" => match args.next().and_then(|n| n.parse().ok()) {
+        Some(n) => style.comment_column = Some(n),
+        None => usage(),
+      },
+      "--case" => match args.next().map(|a| a.as_str()) {
+        Some("upper") => style.case = Case::Upper,
+        Some("lower") => style.case = Case::Lower,
+        Some("keep") => style.case = Case::Keep,
+        _ => usage(),
+      },
+      a if a.starts_with("--") => usage(),
+      _ if source.is_none() => source = Some(arg),
+      _ => usage(),
+    }
+  }
+  let source: &String = match source {
+    Some(s) if !(write && check) => s,
+    _ => usage(),
+  };
+
+  let src: String = match fs::read_to_string(source) {
+    Ok(src) => src,
+    Err(e) => {
+      eprintln!("failed to read {}: {}", source, e);
+      process::exit(1);
+    },
+  };
+  let formatted: String = assembler::format_source(&src, &style);
+  if check {
+    if formatted != src {
+      eprintln!("{} is not formatted", source);
+      process::exit(1);
+    }
+  } else if write {
+    if let Err(e) = fs::write(source, formatted) {
+      eprintln!("failed to write {}: {}", source, e);
+      process::exit(1);
+    }
+  } else {
+    print!("{}", formatted);
+  }
+}
+
+fn lint(args: &[String]) {
+  let mut source: Option<&String> = None;
+  let mut allowed: Vec<LintKind> = Vec::new();
+  let mut json: bool = false;
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--allow" => match args.next().and_then(|a| LintKind::from_name(a)) {
+        Some(kind) => allowed.push(kind),
+        None => usage(),
+      },
+      "--json" => json = true,
+      a if a.starts_with("--") => usage(),
+      _ if source.is_none() => source = Some(arg),
+      _ => usage(),
+    }
+  }
+  let source: &Path = match source {
+    Some(s) => Path::new(s),
+    None => usage(),
+  };
+
+  let src: String = match fs::read_to_string(source) {
+    Ok(src) => src,
+    Err(e) => {
+      eprintln!("failed to read {}: {}", source.display(), e);
+      process::exit(1);
+    },
+  };
+  let lints: Vec<Lint> = match assembler::lint(&src, Some(&assembler::file_key(source)), &mut assembler::include_file) {
+    Ok(lints) => lints.into_iter().filter(|l| !allowed.contains(&l.kind)).collect(),
+    Err(e) => {
+      for d in &e.diagnostics {
+        eprint!("{}", d.render(&source.display().to_string()));
+      }
+      process::exit(1);
+    },
+  };
+
+  if json {
+    println!("{}", Json::from(lints.iter().map(Lint::to_json).collect::<Vec<Json>>()));
+  } else {
+    for l in &lints {
+      eprint!("{}", l.render(&source.display().to_string()));
+    }
+  }
+  if !lints.is_empty() {
+    process::exit(1);
+  }
+}
+
+fn trace_query(args: &[String]) {
+  let (path, q): (&String, Query) = match args {
+    [path, q, addr] => match (q.as_str(), debugger::parse_addr(addr)) {
+      ("writes", Some(addr)) => (path, Query::Writes(addr)),
+      ("last-write", Some(addr)) => (path, Query::LastWrite(addr)),
+      ("executions", Some(addr)) => (path, Query::Executions(addr)),
+      _ => usage(),
+    },
+    _ => usage(),
+  };
+  let bytes: Vec<u8> = match fs::read(path) {
+    Ok(bytes) => bytes,
+    Err(e) => {
+      eprintln!("failed to read {}: {}", path, e);
+      process::exit(1);
+    },
+  };
+  let hits: Vec<Hit> = match trace::query(&bytes, q) {
+    Ok(hits) => hits,
+    Err(e) => {
+      eprintln!("{}: {}", path, e);
+      process::exit(1);
+    },
+  };
+  let symbols: SymbolTable = SymbolTable::new();
+  let out = io::stdout();
+  let mut out = out.lock();
+  for hit in hits {
+    let asm: String = disasm::disassemble(hit.instr, hit.pc, &symbols);
+    let _ = match hit.value {
+      Some(value) => writeln!(out, "step {:<10} x{:04X}  {:<20} x{:04X}", hit.step, hit.pc, asm, value),
+      None => writeln!(out, "step {:<10} x{:04X}  {}", hit.step, hit.pc, asm),
+    };
+  }
+}
+
+fn link(args: &[String]) {
+  let mut paths: Vec<&String> = Vec::new();
+  let mut format: ImageFormat = ImageFormat::Obj;
+  let mut output: Option<&String> = None;
+  let mut symbols: Option<&String> = None;
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--format" => match args.next().map(|a| a.as_str()) {
+        Some("obj") => format = ImageFormat::Obj,
+        Some("hex") => format = ImageFormat::IntelHex,
+        _ => usage(),
+      },
+      "--output" => match args.next() {
+        Some(path) => output = Some(path),
+        None => usage(),
+      },
+      "--symbols" => match args.next() {
+        Some(path) => symbols = Some(path),
+        None => usage(),
+      },
+      a if a.starts_with("--") => usage(),
+      _ => paths.push(arg),
+    }
+  }
+  if paths.is_empty() {
+    usage();
+  }
+
+  // sources are named by their keys for includes, images as given
+  let mut names: Vec<String> = Vec::new();
+  let mut srcs: Vec<Option<String>> = Vec::new();
+  let mut images: Vec<Option<(LoadedImage, SymbolTable)>> = Vec::new();
+  for path in &paths {
+    let path: &Path = Path::new(path.as_str());
+    let read: Result<(), Box<dyn Error>> = if path.extension().is_some_and(|e| e == "asm") {
+      fs::read_to_string(path).map_err(Box::from).map(|src| {
+        names.push(assembler::file_key(path));
+        srcs.push(Some(src));
+        images.push(None);
+      })
+    } else {
+      let format: ImageFormat = if path.extension().is_some_and(|e| e == "hex") { ImageFormat::IntelHex } else { ImageFormat::Obj };
+      let sym = path.with_extension("sym");
+      fs::read(path).map_err(Box::<dyn Error>::from)
+        .and_then(|bytes| Ok(loader::parse(&bytes, format)?))
+        .and_then(|image| Ok((image, if sym.exists() { SymbolTable::load(&sym)? } else { SymbolTable::new() })))
+        .map(|module| {
+          names.push(path.display().to_string());
+          srcs.push(None);
+          images.push(Some(module));
+        })
+    };
+    if let Err(e) = read {
+      eprintln!("failed to read {}: {}", path.display(), e);
+      process::exit(1);
+    }
+  }
+  let modules: Vec<Module> = (0..paths.len()).map(|i| match (&srcs[i], &images[i]) {
+    (Some(src), _) => Module::Source { name: &names[i], src },
+    (None, Some((image, symbols))) => Module::Image { name: &names[i], image, symbols },
+    (None, None) => unreachable!(),
+  }).collect();
+
+  let linked: Linked = match linker::link(&modules, &mut assembler::include_file) {
+    Ok(linked) => linked,
+    Err(LinkError::Asm { module, error }) => {
+      let i: usize = names.iter().position(|n| *n == module).unwrap();
+      for d in &error.diagnostics {
+        eprint!("{}", d.render(paths[i]));
+      }
+      process::exit(1);
+    },
+    Err(e) => {
+      // the names of sources are their keys, the paths they were given as
+      // read better
+      let mut msg: String = e.to_string();
+      for (name, path) in names.iter().zip(&paths) {
+        msg = msg.replace(name.as_str(), path);
+      }
+      eprintln!("{}", msg);
+      process::exit(1);
+    },
+  };
+
+  let (origin, words): (u16, Vec<u16>) = linked.flatten();
+  let image: Vec<u8> = loader::write(origin, &words, format);
+  let written: io::Result<()> = match output {
+    Some(path) => fs::write(path, &image),
+    None => io::stdout().write_all(&image),
+  };
+  if let Err(e) = written {
+    eprintln!("failed to write {}: {}", output.map_or("stdout", |p| p.as_str()), e);
+    process::exit(1);
+  }
+  if let Some(path) = symbols {
+    if let Err(e) = fs::write(path, linked.symbols.to_sym()) {
+      eprintln!("failed to write {}: {}", path, e);
+      process::exit(1);
+    }
+  }
+}
+
+fn grade(args: &[String]) {
+  let mut spec: Option<&String> = None;
+  let mut program: Option<&String> = None;
+  let mut json: bool = false;
+  let mut coverage: Option<&String> = None;
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--spec" => match args.next() {
+        Some(path) => spec = Some(path),
+        None => usage(),
+      },
+      "--json" => json = true,
+      "--coverage" => match args.next() {
+        Some(path) => coverage = Some(path),
+        None => usage(),
+      },
+      a if a.starts_with("--") => usage(),
+      _ if program.is_none() => program = Some(arg),
+      _ => usage(),
+    }
+  }
+  let (spec, program): (&String, &Path) = match (spec, program) {
+    (Some(spec), Some(program)) => (spec, Path::new(program)),
+    _ => usage(),
+  };
+
+  let cases: Result<Vec<TestCase>, Box<dyn Error>> = fs::read_to_string(spec).map_err(Box::from)
+    .and_then(|src| Ok(harness::parse_spec(&src)?));
+  let cases: Vec<TestCase> = match cases {
+    Ok(cases) => cases,
+    Err(e) => {
+      eprintln!("failed to load {}: {}", spec, e);
+      process::exit(1);
+    },
+  };
+  let obj: Result<Vec<u8>, Box<dyn Error>> = if program.extension().is_some_and(|e| e == "asm") {
+    fs::read_to_string(program).map_err(Box::from)
+      .and_then(|src| Ok(assembler::assemble_file(&src, program)?.to_obj()))
+  } else {
+    fs::read(program).map_err(Box::from)
+  };
+  let obj: Vec<u8> = match obj {
+    Ok(obj) => obj,
+    Err(e) => {
+      eprintln!("failed to load {}: {}", program.display(), e);
+      process::exit(1);
+    },
+  };
+
+  let reports: Vec<Report> = cases.iter().map(|c| c.run_obj(&obj)).collect();
+  let passed: usize = reports.iter().filter(|r| r.passed()).count();
+  if let Some(path) = coverage {
+    let mut total: Coverage = Coverage::new();
+    for r in &reports {
+      total.merge(&r.coverage);
+    }
+    write_coverage(path, program, &total);
+  }
+  if json {
+    println!("{}", Json::object(vec![
+      ("passed", Json::from(passed as u64)),
+      ("failed", Json::from((reports.len() - passed) as u64)),
+      ("tests", Json::from(reports.iter().map(Report::to_json).collect::<Vec<Json>>())),
+    ]));
+  } else {
+    for r in &reports {
+      print!("{}", r);
+    }
+    println!("{} of {} passed", passed, reports.len());
+  }
+  if passed < reports.len() {
+    process::exit(1);
+  }
+}
+
+fn main() {
+  // RUST_LOG applies as usual, --log lets the machine decide what it logs
+  let mut logger = env_logger::Builder::from_default_env();
+  if env::args().any(|a| a == "--log") {
+    logger.filter_module("lc3", LevelFilter::Trace);
+  }
+  logger.init();
 
-  while !m.halt {
-    m.step();
+  let args: Vec<String> = env::args().skip(1).collect();
+  match args.split_first() {
+    Some((cmd, rest)) if cmd == "run" => {
+      let opts: Options = parse_options(rest, true);
+      if opts.lc3b {
+        run_lc3b(&opts);
+      } else {
+        run(&opts);
+      }
+    },
+    Some((cmd, rest)) if cmd == "dump" => dump(rest),
+    Some((cmd, rest)) if cmd == "asm" => asm(rest),
+    Some((cmd, rest)) if cmd == "fmt" => fmt(rest),
+    Some((cmd, rest)) if cmd == "lint" => lint(rest),
+    Some((cmd, rest)) if cmd == "link" => link(rest),
+    Some((cmd, rest)) if cmd == "grade" => grade(rest),
+    Some((cmd, rest)) if cmd == "trace" => match rest.split_first() {
+      Some((sub, rest)) if sub == "query" => trace_query(rest),
+      _ => usage(),
+    },
+    Some((cmd, rest)) if cmd == "debug" => {
+      let mut m = setup(&parse_options(rest, true));
+      Debugger::new().run(&mut m);
+    },
+    Some((cmd, rest)) if cmd == "repl" => {
+      let mut m = setup(&parse_options(rest, false));
+      Repl::new(&m).run(&mut m);
+    },
+    _ => usage(),
   }
 }
@@ -0,0 +1,158 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use machine::MEM_SIZE;
+use symbols::SymbolTable;
+
+// instructions executed while Machine::start_profile() is in effect, by
+// address and by the calls on the shadow call stack when they ran
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+  // the PC when profiling started, naming the outermost frame
+  start: u16,
+  counts: Vec<u64>,
+  // the entries of the calls on the stack, outermost first
+  stacks: BTreeMap<Vec<u16>, u64>,
+  // the stack of the last instruction and the instructions run on it
+  // since it changed, not yet in stacks
+  current: Vec<u16>,
+  pending: u64,
+  // by entry
+  calls: BTreeMap<u16, u64>,
+}
+
+// the instructions run in a subroutine, see Profile::subroutines()
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subroutine {
+  // None for the code profiling started in
+  pub entry: Option<u16>,
+  pub name: String,
+  pub calls: u64,
+  // in the subroutine itself
+  pub own: u64,
+  // in it and everything it called
+  pub total: u64,
+}
+
+impl Profile {
+  pub fn new(start: u16) -> Profile {
+    Profile {
+      start,
+      counts: vec![0; MEM_SIZE],
+      stacks: BTreeMap::new(),
+      current: Vec::new(),
+      pending: 0,
+      calls: BTreeMap::new(),
+    }
+  }
+
+  // counts an instruction at pc, run with the calls entered at stack on
+  // the call stack
+  pub fn sample<I: Iterator<Item = u16> + Clone>(&mut self, pc: u16, stack: I) {
+    self.counts[pc as usize] += 1;
+    if !self.current.iter().copied().eq(stack.clone()) {
+      if self.pending > 0 {
+        *self.stacks.entry(self.current.clone()).or_insert(0) += self.pending;
+      }
+      self.current = stack.collect();
+      self.pending = 0;
+    }
+    self.pending += 1;
+  }
+
+  pub fn note_call(&mut self, entry: u16) {
+    *self.calls.entry(entry).or_insert(0) += 1;
+  }
+
+  // instructions executed at addr
+  pub fn count(&self, addr: u16) -> u64 {
+    self.counts[addr as usize]
+  }
+
+  pub fn total(&self) -> u64 {
+    self.counts.iter().sum()
+  }
+
+  // the n addresses executed most, most first
+  pub fn hottest(&self, n: usize) -> Vec<(u16, u64)> {
+    let mut hot: Vec<(u16, u64)> = self.counts.iter().enumerate()
+      .filter(|&(_, &c)| c > 0)
+      .map(|(a, &c)| (a as u16, c))
+      .collect();
+    hot.sort_by_key(|&(a, c)| (u64::MAX - c, a));
+    hot.truncate(n);
+    hot
+  }
+
+  fn stacks(&self) -> BTreeMap<Vec<u16>, u64> {
+    let mut stacks: BTreeMap<Vec<u16>, u64> = self.stacks.clone();
+    if self.pending > 0 {
+      *stacks.entry(self.current.clone()).or_insert(0) += self.pending;
+    }
+    stacks
+  }
+
+  fn name(&self, entry: Option<u16>, symbols: &SymbolTable) -> String {
+    let addr: u16 = entry.unwrap_or(self.start);
+    match symbols.label(addr) {
+      Some(label) => label.to_string(),
+      None => format!("x{:04X}", addr),
+    }
+  }
+
+  // every subroutine that ran, the code profiling started in first, then
+  // by most instructions in total. Recursive calls count once towards a
+  // subroutine's total.
+  pub fn subroutines(&self, symbols: &SymbolTable) -> Vec<Subroutine> {
+    let mut by_entry: BTreeMap<Option<u16>, (u64, u64)> = BTreeMap::new();
+    for (stack, &n) in &self.stacks() {
+      let frames: BTreeSet<Option<u16>> = stack.iter().map(|&e| Some(e)).chain([None]).collect();
+      for frame in frames {
+        by_entry.entry(frame).or_insert((0, 0)).1 += n;
+      }
+      by_entry.entry(stack.last().copied()).or_insert((0, 0)).0 += n;
+    }
+
+    let mut subs: Vec<Subroutine> = by_entry.into_iter()
+      .map(|(entry, (own, total))| Subroutine {
+        entry,
+        name: self.name(entry, symbols),
+        calls: entry.map_or(0, |e| self.calls.get(&e).copied().unwrap_or(0)),
+        own,
+        total,
+      })
+      .collect();
+    subs.sort_by_key(|s| (s.entry.is_some(), u64::MAX - s.total, s.entry));
+    subs
+  }
+
+  // the call stacks in the folded format of flamegraph.pl and inferno, a
+  // line each with the frames outermost first and the instructions run
+  // on it:
+  //
+  //   MAIN;PRINT;x0420 96
+  pub fn folded(&self, symbols: &SymbolTable) -> String {
+    let mut out: String = String::new();
+    for (stack, n) in self.stacks() {
+      out.push_str(&self.name(None, symbols));
+      for &entry in &stack {
+        out.push(';');
+        out.push_str(&self.name(Some(entry), symbols));
+      }
+      out.push_str(&format!(" {}\n", n));
+    }
+    out
+  }
+
+  // a table of subroutines(), for showing where the instructions went
+  pub fn report(&self, symbols: &SymbolTable) -> String {
+    let all: f64 = self.total().max(1) as f64;
+    let mut out: String = format!("{:>10} {:>6} {:>10} {:>6} {:>7}  subroutine\n", "total", "", "self", "", "calls");
+    for s in self.subroutines(symbols) {
+      out.push_str(&format!("{:>10} {:>5.1}% {:>10} {:>5.1}% {:>7}  {}\n",
+        s.total, 100.0 * s.total as f64 / all, s.own, 100.0 * s.own as f64 / all, s.calls, s.name));
+    }
+    out
+  }
+}
@@ -0,0 +1,218 @@
+use std::io::{self, Write};
+
+use assembler::{self, Line, Program};
+use debugger::parse_addr;
+use disasm;
+use instr::{decode, Instruction};
+use machine::*;
+
+const HELP: &str = "\
+type assembly to place it at the next address and execute it. Labels are
+remembered, directives like .FILL and .STRINGZ only store their words and
+.ORIG moves to another address.
+
+commands:
+  :regs              show registers
+  :mem <addr> [len]  dump memory
+  :run               run from PC until the machine halts
+  :help              show this help
+  :quit              exit";
+
+// steps a call at the prompt may take before it is given up on
+const CALL_LIMIT: u64 = 1_000_000;
+
+const GPRS: [Reg; 8] = [Reg::R0, Reg::R1, Reg::R2, Reg::R3, Reg::R4, Reg::R5, Reg::R6, Reg::R7];
+
+const CC: [(u16, &str); 3] = [(NEG, "N"), (ZRO, "Z"), (POS, "P")];
+
+fn cc_name(cc: u16) -> &'static str {
+  CC.iter().find(|&&(c, _)| c == cc).map_or("-", |&(_, name)| name)
+}
+
+// interactive assembler: each line goes into memory at the next address and
+// instructions are executed on the spot, reporting what they changed
+pub struct Repl {
+  next: u16,
+}
+
+impl Repl {
+  // starts placing lines at the machine's PC
+  pub fn new(m: &Machine) -> Repl {
+    Repl { next: m.read_reg(Reg::PC) }
+  }
+
+  // where the next line will be assembled
+  pub fn next_addr(&self) -> u16 {
+    self.next
+  }
+
+  pub fn run(&mut self, m: &mut Machine) {
+    println!("lc3 repl, type `:help` for commands");
+    loop {
+      print!("x{:04X}> ", self.next);
+      io::stdout().flush().unwrap();
+      let line: String = match m.read_line() {
+        Some(line) => line,
+        None => break,
+      };
+      if matches!(line.trim(), ":q" | ":quit") {
+        break;
+      }
+      print!("{}", self.eval(m, &line));
+    }
+  }
+
+  // handles one line of input, returning the report to print
+  pub fn eval(&mut self, m: &mut Machine, input: &str) -> String {
+    let args: Vec<&str> = input.split_whitespace().collect();
+    match args.as_slice() {
+      [] => String::new(),
+      [":regs"] | [":r"] => regs(m),
+      [":mem", a] | [":m", a] => self.mem(m, a, "8"),
+      [":mem", a, n] | [":m", a, n] => self.mem(m, a, n),
+      [":run"] => {
+        m.halt = false;
+        let before: Snapshot = m.snapshot();
+        let run: Run = m.run();
+        let mut out: String = changes(&before, m);
+        out.push_str(&format!("{:?} after {} steps\n", run.reason, run.steps));
+        out
+      },
+      [":help"] | [":h"] => format!("{}\n", HELP),
+      [cmd, ..] if cmd.starts_with(':') => format!("unknown command `{}`, type `:help` for commands\n", cmd),
+      _ => match self.assemble(m, input) {
+        Ok(out) => out,
+        Err(e) => format!("error: {}\n", e),
+      },
+    }
+  }
+
+  fn mem(&self, m: &Machine, addr: &str, len: &str) -> String {
+    match (parse_addr(addr), len.parse::<u16>()) {
+      (Some(addr), Ok(len)) => {
+        let mut out: String = String::new();
+        for i in 0..len {
+          let a: u16 = addr.wrapping_add(i);
+          out.push_str(&format!("x{:04X}: x{:04X}\n", a, m.read_mem(a)));
+        }
+        out
+      },
+      _ => format!("invalid range `{} {}`\n", addr, len),
+    }
+  }
+
+  fn assemble(&mut self, m: &mut Machine, src: &str) -> Result<String, String> {
+    let line: Line = match assembler::parse(src).map_err(|e| e.msg)?.pop() {
+      Some(line) => line,
+      None => return Ok(String::new()),
+    };
+    match line.op.as_deref() {
+      Some(".ORIG") => {
+        return match line.operands.first() {
+          Some(&assembler::Operand::Imm(n)) => {
+            self.next = n as u16;
+            Ok(String::new())
+          },
+          _ => Err(".ORIG expects an address".to_string()),
+        };
+      },
+      Some(".END") => return Ok(String::new()),
+      _ => {},
+    }
+
+    let prog: Program = assembler::assemble_at(src, self.next, m.symbols()).map_err(|e| e.msg)?;
+    let addr: u16 = self.next;
+    if let Some(label) = &line.label {
+      m.symbols_mut().insert(label, addr);
+    }
+    for (i, &w) in prog.words.iter().enumerate() {
+      m.write_mem(addr.wrapping_add(i as u16), w);
+    }
+    self.next = addr.wrapping_add(prog.words.len() as u16);
+
+    let directive: bool = line.op.as_deref().is_none_or(|op| op.starts_with('.'));
+    let mut out: String = String::new();
+    if directive || prog.words.is_empty() {
+      for (i, &w) in prog.words.iter().enumerate() {
+        out.push_str(&format!("x{:04X}: x{:04X}\n", addr.wrapping_add(i as u16), w));
+      }
+      return Ok(out);
+    }
+
+    let word: u16 = prog.words[0];
+    out.push_str(&format!("x{:04X}: x{:04X}  {}\n", addr, word, disasm::disassemble(word, addr, m.symbols())));
+    out.push_str(&self.execute(m, addr));
+    Ok(out)
+  }
+
+  // runs the instruction at addr, and a call made by it to completion
+  fn execute(&self, m: &mut Machine, addr: u16) -> String {
+    m.halt = false;
+    m.write_reg(Reg::PC, addr);
+    let before: Snapshot = m.snapshot();
+
+    let call: bool = matches!(decode(m.read_mem(addr)),
+      Instruction::Jsr { .. } | Instruction::Jsrr { .. } | Instruction::Trap { .. });
+    let ret: u16 = addr.wrapping_add(1);
+    let mut stop: Option<StopReason> = match m.step() {
+      Ok(reason) => reason,
+      Err(e) => Some(StopReason::Fault(e)),
+    };
+    if call && stop.is_none() && m.read_reg(Reg::PC) != ret {
+      let mut steps: u64 = 0;
+      let run: Run = m.run_until(|m| {
+        steps += 1;
+        m.read_reg(Reg::PC) == ret || steps > CALL_LIMIT
+      });
+      stop = match run.reason {
+        StopReason::Condition if m.read_reg(Reg::PC) == ret => None,
+        StopReason::Condition => Some(StopReason::StepLimit),
+        reason => Some(reason),
+      };
+    }
+
+    let mut out: String = changes(&before, m);
+    let pc: u16 = m.read_reg(Reg::PC);
+    if pc != self.next {
+      out.push_str(&format!("PC x{:04X}\n", pc));
+    }
+    match stop {
+      None => {},
+      Some(StopReason::Halt) => out.push_str("machine halted\n"),
+      Some(StopReason::Fault(e)) => out.push_str(&format!("{}\n", e)),
+      Some(reason) => out.push_str(&format!("stopped: {:?}\n", reason)),
+    }
+    out
+  }
+}
+
+// registers, condition codes and memory that differ from before
+fn changes(before: &Snapshot, m: &Machine) -> String {
+  let mut out: String = String::new();
+  for (r, &reg) in GPRS.iter().enumerate() {
+    let (old, new): (u16, u16) = (before.reg[r], m.read_reg(reg));
+    if old != new {
+      out.push_str(&format!("R{} x{:04X} -> x{:04X}\n", r, old, new));
+    }
+  }
+  let (old, new): (u16, u16) = (before.reg[Reg::COND as usize], m.read_reg(Reg::COND));
+  if old != new {
+    out.push_str(&format!("CC {} -> {}\n", cc_name(old), cc_name(new)));
+  }
+  for (a, &old) in before.mem.iter().enumerate() {
+    let new: u16 = m.read_mem(a as u16);
+    if old != new {
+      out.push_str(&format!("x{:04X}: x{:04X} -> x{:04X}\n", a, old, new));
+    }
+  }
+  out
+}
+
+fn regs(m: &Machine) -> String {
+  let mut out: String = String::new();
+  for (r, &reg) in GPRS.iter().enumerate() {
+    out.push_str(&format!("R{} x{:04X}{}", r, m.read_reg(reg), if r == 3 || r == 7 { "\n" } else { "  " }));
+  }
+  out.push_str(&format!("PC x{:04X}  PSR x{:04X}  CC {}\n", m.read_reg(Reg::PC), m.psr(), cc_name(m.read_reg(Reg::COND))));
+  out
+}
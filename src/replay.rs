@@ -0,0 +1,76 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use utils::FormatError;
+
+// a nondeterministic input observed while running
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+  Key(u8),
+  Interrupt { vector: u8, priority: u8 },
+}
+
+// an input and the instruction count, relative to the start of the
+// recording, at which the machine observed it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Event {
+  pub step: u64,
+  pub input: Input,
+}
+
+// inputs logged by Machine::start_recording(), replayable with
+// Machine::replay(). The text form has one event per line:
+//
+//   <step> key <hex>
+//   <step> irq <vector hex> <priority>
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recording {
+  pub events: Vec<Event>,
+}
+
+impl Recording {
+  pub fn new() -> Recording {
+    Recording::default()
+  }
+
+  pub fn parse(src: &str) -> Result<Recording, FormatError> {
+    let mut events: Vec<Event> = Vec::new();
+
+    for line in src.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+      let fields: Vec<&str> = line.split_whitespace().collect();
+      let hex = |s: &str| u8::from_str_radix(s.trim_start_matches("0x"), 16).ok();
+
+      let event: Option<Event> = match fields.as_slice() {
+        [step, "key", c] => match (step.parse().ok(), hex(c)) {
+          (Some(step), Some(c)) => Some(Event { step, input: Input::Key(c) }),
+          _ => None,
+        },
+        [step, "irq", v, p] => match (step.parse().ok(), hex(v), p.parse().ok()) {
+          (Some(step), Some(vector), Some(priority)) =>
+            Some(Event { step, input: Input::Interrupt { vector, priority } }),
+          _ => None,
+        },
+        _ => None,
+      };
+
+      match event {
+        Some(e) => events.push(e),
+        None => return Err(FormatError(format!("invalid event `{}`", line))),
+      }
+    }
+
+    Ok(Recording { events })
+  }
+}
+
+impl fmt::Display for Recording {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    for e in &self.events {
+      match e.input {
+        Input::Key(c) => writeln!(f, "{} key {:#04x}", e.step, c)?,
+        Input::Interrupt { vector, priority } => writeln!(f, "{} irq {:#04x} {}", e.step, vector, priority)?,
+      }
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,128 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use utils::FormatError;
+
+// when a piece of an InputScript is typed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum When {
+  // as soon as what comes before has been read
+  Next,
+  // that many instructions after what comes before has been read
+  After(u64),
+  // once that many instructions have run since the script was set
+  At(u64),
+}
+
+// keyboard input for Machine::set_input_script(), typed as the program
+// runs. Each piece of text is typed a key at a time, each key as soon as
+// the one before has been read, the first when its When says. The text
+// form has a piece per line, optionally after `wait <n>` or `at <n>`,
+// quoted with \n, \r, \t, \\, \" and \xHH escapes:
+//
+//   # the name, then the menu choice a while later
+//   "alice\n"
+//   wait 5000 "2\n"
+//   at 100000 "q"
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputScript {
+  pub pieces: Vec<(When, Vec<u8>)>,
+}
+
+impl InputScript {
+  pub fn new() -> InputScript {
+    InputScript::default()
+  }
+
+  pub fn then(mut self, when: When, text: &str) -> InputScript {
+    self.pieces.push((when, text.as_bytes().to_vec()));
+    self
+  }
+
+  pub fn text(self, text: &str) -> InputScript {
+    self.then(When::Next, text)
+  }
+
+  // every key the script types and when, the first of each piece with
+  // the piece's When and the others When::Next
+  pub fn keys(&self) -> impl Iterator<Item = (When, u8)> + '_ {
+    self.pieces.iter().flat_map(|(when, text)| {
+      text.iter().enumerate().map(move |(i, &c)| (if i == 0 { *when } else { When::Next }, c))
+    })
+  }
+
+  pub fn parse(src: &str) -> Result<InputScript, FormatError> {
+    let mut pieces: Vec<(When, Vec<u8>)> = Vec::new();
+
+    for line in src.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+      let invalid = || FormatError(format!("invalid input `{}`", line));
+      let (when, quoted): (When, &str) = match line.split_once(char::is_whitespace) {
+        Some(("wait", rest)) | Some(("at", rest)) => {
+          let (n, quoted): (&str, &str) = rest.trim_start().split_once(char::is_whitespace).ok_or_else(invalid)?;
+          let n: u64 = n.parse().map_err(|_| invalid())?;
+          (if line.starts_with("wait") { When::After(n) } else { When::At(n) }, quoted.trim_start())
+        },
+        _ => (When::Next, line),
+      };
+      pieces.push((when, unquote(quoted).ok_or_else(invalid)?));
+    }
+
+    Ok(InputScript { pieces })
+  }
+}
+
+fn unquote(s: &str) -> Option<Vec<u8>> {
+  let inner: &str = s.strip_prefix('"')?.strip_suffix('"')?;
+  let mut out: Vec<u8> = Vec::new();
+  let mut chars = inner.chars();
+  while let Some(c) = chars.next() {
+    let c: char = match c {
+      '\\' => match chars.next()? {
+        'n' => '\n',
+        'r' => '\r',
+        't' => '\t',
+        '\\' => '\\',
+        '"' => '"',
+        'x' => {
+          let hex: String = chars.by_ref().take(2).collect();
+          let byte: u8 = u8::from_str_radix(&hex, 16).ok().filter(|_| hex.len() == 2)?;
+          out.push(byte);
+          continue;
+        },
+        _ => return None,
+      },
+      '"' => return None,
+      c => c,
+    };
+    let mut buf: [u8; 4] = [0; 4];
+    out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+  }
+  Some(out)
+}
+
+impl fmt::Display for InputScript {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    for (when, text) in &self.pieces {
+      match when {
+        When::Next => {},
+        When::After(n) => write!(f, "wait {} ", n)?,
+        When::At(n) => write!(f, "at {} ", n)?,
+      }
+      write!(f, "\"")?;
+      for &c in text {
+        match c {
+          b'\n' => write!(f, "\\n")?,
+          b'\r' => write!(f, "\\r")?,
+          b'\t' => write!(f, "\\t")?,
+          b'\\' => write!(f, "\\\\")?,
+          b'"' => write!(f, "\\\"")?,
+          0x20..=0x7E => write!(f, "{}", c as char)?,
+          _ => write!(f, "\\x{:02X}", c)?,
+        }
+      }
+      writeln!(f, "\"")?;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,60 @@
+use core::fmt;
+
+const MNEMONICS: [&str; 16] = [
+  "BR", "ADD", "LD", "ST", "JSR", "AND", "LDR", "STR",
+  "RTI", "NOT", "LDI", "STI", "JMP", "RES", "LEA", "TRAP",
+];
+
+// execution counters collected by the Machine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stats {
+  pub instructions: u64,
+  pub opcodes: [u64; 16], // indexed by opcode
+  pub mem_reads: u64,     // data reads, excluding instruction fetch
+  pub mem_writes: u64,
+  pub traps: [u64; 256],  // indexed by trap vector
+}
+
+impl Default for Stats {
+  fn default() -> Stats {
+    Stats {
+      instructions: 0,
+      opcodes: [0; 16],
+      mem_reads: 0,
+      mem_writes: 0,
+      traps: [0; 256],
+    }
+  }
+}
+
+impl Stats {
+  pub fn opcode(&self, mnemonic: &str) -> u64 {
+    MNEMONICS.iter()
+      .position(|&m| m.eq_ignore_ascii_case(mnemonic))
+      .map_or(0, |i| self.opcodes[i])
+  }
+
+  pub fn trap_count(&self) -> u64 {
+    self.traps.iter().sum()
+  }
+}
+
+impl fmt::Display for Stats {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(f, "instructions: {}", self.instructions)?;
+    writeln!(f, "memory reads: {}", self.mem_reads)?;
+    writeln!(f, "memory writes: {}", self.mem_writes)?;
+
+    for (i, &n) in self.opcodes.iter().enumerate() {
+      if n > 0 {
+        writeln!(f, "  {:<5} {}", MNEMONICS[i], n)?;
+      }
+    }
+    for (v, &n) in self.traps.iter().enumerate() {
+      if n > 0 {
+        writeln!(f, "  trap x{:02X} {}", v, n)?;
+      }
+    }
+    Ok(())
+  }
+}
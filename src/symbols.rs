@@ -0,0 +1,107 @@
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::fs;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
+use std::path::Path;
+
+use utils::FormatError;
+
+// label <-> address mapping, as read from lc3as/lc3tools .sym files
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolTable {
+  by_name: BTreeMap<String, u16>,
+  by_addr: BTreeMap<u16, String>,
+}
+
+impl SymbolTable {
+  pub fn new() -> SymbolTable {
+    SymbolTable::default()
+  }
+
+  #[cfg(feature = "std")]
+  pub fn load(path: &Path) -> io::Result<SymbolTable> {
+    Ok(SymbolTable::parse(&fs::read_to_string(path)?)?)
+  }
+
+  // accepts the commented lc3as layout ("//\tLOOP   3002") as well as bare
+  // "LABEL ADDR" lines; addresses are hex with an optional x prefix
+  pub fn parse(src: &str) -> Result<SymbolTable, FormatError> {
+    let mut table: SymbolTable = SymbolTable::new();
+
+    for line in src.lines() {
+      let commented: bool = line.trim().starts_with("//");
+      let line: &str = line.trim().trim_start_matches("//").trim();
+      let fields: Vec<&str> = line.split_whitespace().collect();
+      if fields.is_empty() {
+        continue;
+      }
+
+      let hex: &str = fields.last().unwrap().trim_start_matches("0x").trim_start_matches(['x', 'X']);
+      match u16::from_str_radix(hex, 16) {
+        Ok(addr) if fields.len() == 2 => table.insert(fields[0], addr),
+        // header lines of the lc3as layout
+        _ if commented => {},
+        _ => {
+          return Err(FormatError(format!("invalid symbol line `{}`", line)));
+        },
+      }
+    }
+
+    Ok(table)
+  }
+
+  pub fn insert(&mut self, name: &str, addr: u16) {
+    if let Some(old) = self.by_name.insert(name.to_string(), addr) {
+      self.by_addr.remove(&old);
+    }
+    self.by_addr.insert(addr, name.to_string());
+  }
+
+  pub fn extend(&mut self, other: &SymbolTable) {
+    for (name, &addr) in &other.by_name {
+      self.insert(name, addr);
+    }
+  }
+
+  pub fn lookup(&self, name: &str) -> Option<u16> {
+    self.by_name.get(name).cloned()
+  }
+
+  pub fn label(&self, addr: u16) -> Option<&str> {
+    self.by_addr.get(&addr).map(|s| s.as_str())
+  }
+
+  // the nearest label at or before addr, and its address
+  pub fn enclosing(&self, addr: u16) -> Option<(&str, u16)> {
+    self.by_addr.range(..=addr).next_back().map(|(&a, name)| (name.as_str(), a))
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.by_name.is_empty()
+  }
+
+  pub fn len(&self) -> usize {
+    self.by_name.len()
+  }
+
+  // the lc3as .sym layout, which parse() reads back
+  pub fn to_sym(&self) -> String {
+    let mut out: String = String::from("// Symbol table\n// Scope level 0:\n");
+    out.push_str("//\tSymbol Name       Page Address\n");
+    out.push_str("//\t----------------  ------------\n");
+    for (name, addr) in self.iter() {
+      out.push_str(&format!("//\t{:<16}  {:04X}\n", name, addr));
+    }
+    out.push('\n');
+    out
+  }
+
+  // symbols in address order
+  pub fn iter(&self) -> impl Iterator<Item = (&str, u16)> {
+    self.by_addr.iter().map(|(&addr, name)| (name.as_str(), addr))
+  }
+}
@@ -0,0 +1,50 @@
+use std::io::{self, IsTerminal};
+use std::process::{Command, Stdio};
+
+// switches the controlling terminal to non-canonical, no-echo input so
+// GETC sees single keystrokes, and restores the previous settings when
+// dropped, including while unwinding from a panic. Signal keys stay
+// enabled so ^C still interrupts the emulator.
+pub struct RawMode {
+  saved: Option<String>,
+}
+
+impl RawMode {
+  // does nothing when stdin isn't a terminal or stty isn't available
+  pub fn enter() -> RawMode {
+    if !io::stdin().is_terminal() {
+      return RawMode { saved: None };
+    }
+    let saved: Option<String> = stty(&["-g"]).filter(|s| !s.is_empty());
+    if saved.is_some() {
+      stty(&["-icanon", "-echo", "min", "1", "time", "0"]);
+    }
+    RawMode { saved }
+  }
+
+  pub fn active(&self) -> bool {
+    self.saved.is_some()
+  }
+}
+
+impl Drop for RawMode {
+  fn drop(&mut self) {
+    if let Some(saved) = &self.saved {
+      stty(&[saved]);
+    }
+  }
+}
+
+// stty acts on its stdin, which has to be the terminal
+fn stty(args: &[&str]) -> Option<String> {
+  let out = Command::new("stty")
+    .args(args)
+    .stdin(Stdio::inherit())
+    .stderr(Stdio::null())
+    .output()
+    .ok()?;
+  if !out.status.success() {
+    return None;
+  }
+  Some(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
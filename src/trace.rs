@@ -0,0 +1,235 @@
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+use machine::{PC, REG_SIZE};
+use utils::FormatError;
+
+// reading back traces written by Machine::enable_trace() in the binary
+// formats, and searching them. A compact trace (TraceFormat::Compact)
+// is "LC3C", the instruction count before the first record as a varint,
+// the registers then, each a varint, and a record per instruction:
+//
+//   tag      bit 0 pc, 1 instr, 2 regs, 3 writes, each present if set
+//   pc       zigzag varint, from the PC as the machine left it; absent
+//            when it's that
+//   instr    varint; absent when the same as at the last execution of pc
+//   regs     varint mask (bit i for register i in R0-R7, PC, CC, PSR
+//            order) and for each register a zigzag varint from its old
+//            value, the PC's old value being pc + 1
+//   writes   varint count, then for each the address as a zigzag varint
+//            from the one before (0 for the first) and the value stored
+//            as a varint
+//
+// Varints are LEB128, zigzag maps 0, -1, 1, -2.. to 0, 1, 2, 3.. so small
+// deltas of either sign take a byte.
+
+// an instruction as the trace recorded it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceRecord {
+  // the machine's instruction count after it
+  pub step: u64,
+  pub pc: u16,
+  pub instr: u16,
+  // after it, in R0-R7, PC, CC, PSR order
+  pub regs: [u16; REG_SIZE],
+  // the words it stored, in order. Always empty in LC3T traces.
+  pub writes: Vec<(u16, u16)>,
+}
+
+pub(crate) fn zigzag(delta: u16) -> u16 {
+  let d: i16 = delta as i16;
+  ((d << 1) ^ (d >> 15)) as u16
+}
+
+fn unzigzag(z: u16) -> u16 {
+  (z >> 1) ^ (z & 1).wrapping_neg()
+}
+
+pub(crate) fn put_varint(out: &mut Vec<u8>, mut n: u64) {
+  while n >= 0x80 {
+    out.push(n as u8 | 0x80);
+    n >>= 7;
+  }
+  out.push(n as u8);
+}
+
+// the last instruction seen at each address, for records to leave out
+pub(crate) struct InstrTable(Vec<Option<u16>>);
+
+impl InstrTable {
+  pub(crate) fn new() -> InstrTable {
+    InstrTable(vec![None; 1 << 16])
+  }
+
+  // whether instr is new at pc, remembering it
+  pub(crate) fn update(&mut self, pc: u16, instr: u16) -> bool {
+    let fresh: bool = self.0[pc as usize] != Some(instr);
+    self.0[pc as usize] = Some(instr);
+    fresh
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+  Compact,
+  Binary,
+}
+
+// the records of a trace in order, see read()
+pub struct TraceReader<'a> {
+  bytes: &'a [u8],
+  pos: usize,
+  layout: Layout,
+  step: u64,
+  regs: [u16; REG_SIZE],
+  instrs: InstrTable,
+  failed: bool,
+}
+
+fn truncated() -> FormatError {
+  FormatError("truncated trace".to_string())
+}
+
+impl<'a> TraceReader<'a> {
+  fn byte(&mut self) -> Result<u8, FormatError> {
+    let b: u8 = *self.bytes.get(self.pos).ok_or_else(truncated)?;
+    self.pos += 1;
+    Ok(b)
+  }
+
+  fn varint(&mut self) -> Result<u64, FormatError> {
+    let mut n: u64 = 0;
+    for shift in (0..64).step_by(7) {
+      let b: u8 = self.byte()?;
+      n |= ((b & 0x7F) as u64) << shift;
+      if b & 0x80 == 0 {
+        return Ok(n);
+      }
+    }
+    Err(FormatError("invalid varint in trace".to_string()))
+  }
+
+  fn word(&mut self) -> Result<u16, FormatError> {
+    match self.layout {
+      Layout::Compact => u16::try_from(self.varint()?).map_err(|_| FormatError("invalid word in trace".to_string())),
+      Layout::Binary => Ok(u16::from_le_bytes([self.byte()?, self.byte()?])),
+    }
+  }
+
+  fn record(&mut self) -> Result<TraceRecord, FormatError> {
+    self.step += 1;
+    if self.layout == Layout::Binary {
+      let (pc, instr, mask): (u16, u16, u16) = (self.word()?, self.word()?, self.word()?);
+      for r in (0..REG_SIZE).filter(|r| mask & 1 << r != 0) {
+        self.regs[r] = self.word()?;
+      }
+      return Ok(TraceRecord { step: self.step, pc, instr, regs: self.regs, writes: Vec::new() });
+    }
+
+    let tag: u8 = self.byte()?;
+    let mut pc: u16 = self.regs[PC as usize];
+    if tag & 1 != 0 {
+      pc = pc.wrapping_add(unzigzag(self.word()?));
+    }
+    let instr: u16 = if tag & 2 != 0 {
+      self.word()?
+    } else {
+      self.instrs.0[pc as usize].ok_or_else(|| FormatError(format!("no instruction for x{:04X} in trace", pc)))?
+    };
+    self.instrs.update(pc, instr);
+    self.regs[PC as usize] = pc.wrapping_add(1);
+    if tag & 4 != 0 {
+      let mask: u16 = self.word()?;
+      for r in (0..REG_SIZE).filter(|r| mask & 1 << r != 0) {
+        self.regs[r] = self.regs[r].wrapping_add(unzigzag(self.word()?));
+      }
+    }
+    let mut writes: Vec<(u16, u16)> = Vec::new();
+    if tag & 8 != 0 {
+      let mut addr: u16 = 0;
+      for _ in 0..self.varint()? {
+        addr = addr.wrapping_add(unzigzag(self.word()?));
+        writes.push((addr, self.word()?));
+      }
+    }
+    Ok(TraceRecord { step: self.step, pc, instr, regs: self.regs, writes })
+  }
+}
+
+impl<'a> Iterator for TraceReader<'a> {
+  type Item = Result<TraceRecord, FormatError>;
+
+  // an error ends the trace
+  fn next(&mut self) -> Option<Result<TraceRecord, FormatError>> {
+    if self.failed || self.pos == self.bytes.len() {
+      return None;
+    }
+    let record: Result<TraceRecord, FormatError> = self.record();
+    self.failed = record.is_err();
+    Some(record)
+  }
+}
+
+// reads a compact trace or an LC3T one (TraceFormat::Binary). An LC3T
+// trace doesn't say what the registers started out as, they read as 0
+// until first changed, and counts steps from 1.
+pub fn read(bytes: &[u8]) -> Result<TraceReader<'_>, FormatError> {
+  let layout: Layout = match bytes.get(..4) {
+    Some(b"LC3C") => Layout::Compact,
+    Some(b"LC3T") => Layout::Binary,
+    _ => return Err(FormatError("not an lc3 trace".to_string())),
+  };
+  let mut reader: TraceReader = TraceReader {
+    bytes, pos: 4, layout, step: 0, regs: [0; REG_SIZE], instrs: InstrTable::new(), failed: false,
+  };
+  if layout == Layout::Compact {
+    reader.step = reader.varint()?;
+    for r in 0..REG_SIZE {
+      reader.regs[r] = reader.word()?;
+    }
+  }
+  Ok(reader)
+}
+
+// what to look for in a trace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Query {
+  // every store to the address
+  Writes(u16),
+  // the last store to it
+  LastWrite(u16),
+  // every time the instruction at the address ran
+  Executions(u16),
+}
+
+// a record that answers a Query, with the value stored for Writes and
+// LastWrite
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hit {
+  pub step: u64,
+  pub pc: u16,
+  pub instr: u16,
+  pub value: Option<u16>,
+}
+
+// the records of a trace that answer q, in order
+pub fn query(bytes: &[u8], q: Query) -> Result<Vec<Hit>, FormatError> {
+  let mut hits: Vec<Hit> = Vec::new();
+  for record in read(bytes)? {
+    let r: TraceRecord = record?;
+    match q {
+      Query::Executions(addr) if r.pc == addr => hits.push(Hit { step: r.step, pc: r.pc, instr: r.instr, value: None }),
+      Query::Writes(addr) | Query::LastWrite(addr) => {
+        for &(_, value) in r.writes.iter().filter(|w| w.0 == addr) {
+          hits.push(Hit { step: r.step, pc: r.pc, instr: r.instr, value: Some(value) });
+        }
+      },
+      _ => {},
+    }
+  }
+  if let Query::LastWrite(_) = q {
+    hits = hits.pop().into_iter().collect();
+  }
+  Ok(hits)
+}
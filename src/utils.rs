@@ -1,7 +1,41 @@
+use alloc::string::String;
+use core::error;
+use core::fmt;
+
 pub fn sign_extend(n: u16, size: usize) -> u16 {
   if (n >> (size-1)) & 0x1 == 0 {
       n
   } else {
-      n | (0xFFFF << size)
+      // a 16-bit field has nothing to extend into
+      n | 0xFFFFu16.checked_shl(size as u32).unwrap_or(0)
+  }
+}
+
+// an address as typed by a user: x3000, 0x3000, 3000 or #12288
+pub fn parse_addr(s: &str) -> Option<u16> {
+  if let Some(d) = s.strip_prefix('#') {
+    return d.parse().ok();
+  }
+  let h: &str = s.strip_prefix("0x").or_else(|| s.strip_prefix('x')).unwrap_or(s);
+  u16::from_str_radix(h, 16).ok()
+}
+
+// malformed input to one of the parsers: object images, snapshots, symbol
+// tables and recordings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatError(pub String);
+
+impl fmt::Display for FormatError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(&self.0)
+  }
+}
+
+impl error::Error for FormatError {}
+
+#[cfg(feature = "std")]
+impl From<FormatError> for std::io::Error {
+  fn from(e: FormatError) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, e)
   }
 }
@@ -0,0 +1,211 @@
+// C ABI exports for running the machine inside a WebAssembly host. Build
+// with
+//
+//   cargo rustc --lib --release --features wasm --target wasm32-unknown-unknown --crate-type cdylib
+//
+// and instantiate it with the callbacks imported:
+//
+//   const { instance } = await WebAssembly.instantiate(bytes, {
+//     lc3: { write_char: c => term.write(String.fromCharCode(c)),
+//            read_char: () => nextKey() ?? -1 },
+//   });
+//
+// Output goes through the imported write_char callback. Keys pushed with
+// lc3_push_key() are seen by KBSR/KBDR polling and GETC; when none are
+// queued GETC falls back to the imported read_char (-1 meaning no input).
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::slice;
+
+use assembler;
+use console::Console;
+use machine::*;
+
+#[cfg(target_arch = "wasm32")]
+#[link(wasm_import_module = "lc3")]
+extern "C" {
+  fn write_char(c: u32);
+  fn read_char() -> i32;
+}
+
+// native builds have no host to call back into
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn write_char(_c: u32) {}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn read_char() -> i32 {
+  -1
+}
+
+// keys are queued outside the machine so the host can push them while the
+// console is owned by it
+thread_local! {
+  static MACHINE: RefCell<Option<Machine>> = const { RefCell::new(None) };
+  static KEYS: RefCell<VecDeque<u8>> = const { RefCell::new(VecDeque::new()) };
+}
+
+struct HostConsole;
+
+impl Console for HostConsole {
+  fn read_char(&mut self) -> Option<u8> {
+    if let Some(c) = self.poll_key() {
+      return Some(c);
+    }
+    match unsafe { read_char() } {
+      c if (0..=0xFF).contains(&c) => Some(c as u8),
+      _ => None,
+    }
+  }
+
+  fn write_char(&mut self, c: u8) {
+    unsafe { write_char(c as u32) }
+  }
+
+  fn poll_key(&mut self) -> Option<u8> {
+    KEYS.with(|k| k.borrow_mut().pop_front())
+  }
+}
+
+fn with<T, F: FnOnce(&mut Machine) -> T>(default: T, f: F) -> T {
+  MACHINE.with(|m| match m.borrow_mut().as_mut() {
+    Some(m) => f(m),
+    None => default,
+  })
+}
+
+// stop codes returned by lc3_step() and lc3_run()
+pub const RUNNING: i32 = 0;
+pub const HALTED: i32 = 1;
+pub const BREAKPOINT: i32 = 2;
+pub const STOPPED: i32 = 3;
+pub const FAULT: i32 = -1;
+
+fn code(reason: Option<StopReason>) -> i32 {
+  match reason {
+    None | Some(StopReason::StepLimit) => RUNNING,
+    Some(StopReason::Halt) => HALTED,
+    Some(StopReason::Breakpoint(_)) => BREAKPOINT,
+    Some(StopReason::Fault(_)) => FAULT,
+    Some(_) => STOPPED,
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn lc3_init() {
+  let mut m = Machine::with_io(Box::new(HostConsole));
+  m.init();
+  MACHINE.with(|cell| *cell.borrow_mut() = Some(m));
+  KEYS.with(|k| k.borrow_mut().clear());
+}
+
+// allocates len bytes in linear memory for passing images and sources
+#[no_mangle]
+pub extern "C" fn lc3_alloc(len: usize) -> *mut u8 {
+  let mut buf: Vec<u8> = vec![0; len];
+  let ptr: *mut u8 = buf.as_mut_ptr();
+  std::mem::forget(buf);
+  ptr
+}
+
+/// # Safety
+/// `ptr` must come from `lc3_alloc(len)` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn lc3_free(ptr: *mut u8, len: usize) {
+  drop(Vec::from_raw_parts(ptr, len, len));
+}
+
+/// # Safety
+/// `ptr` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn lc3_load(ptr: *const u8, len: usize) -> i32 {
+  let bytes: &[u8] = slice::from_raw_parts(ptr, len);
+  with(-1, |m| if m.load_image_bytes(bytes).is_ok() { 0 } else { -1 })
+}
+
+/// # Safety
+/// `ptr` must point to `len` bytes of UTF-8 assembly source.
+#[no_mangle]
+pub unsafe extern "C" fn lc3_assemble(ptr: *const u8, len: usize) -> i32 {
+  let src: &str = match std::str::from_utf8(slice::from_raw_parts(ptr, len)) {
+    Ok(src) => src,
+    Err(_) => return -1,
+  };
+  let prog = match assembler::assemble(src) {
+    Ok(prog) => prog,
+    Err(e) => return -(e.line as i32).max(1),
+  };
+  with(-1, |m| {
+    if m.load_image_bytes(&prog.to_obj()).is_err() {
+      return -1;
+    }
+    m.symbols_mut().extend(&prog.symbols);
+    m.write_reg(Reg::PC, prog.origin);
+    0
+  })
+}
+
+#[no_mangle]
+pub extern "C" fn lc3_push_key(c: u32) {
+  KEYS.with(|k| k.borrow_mut().push_back(c as u8));
+}
+
+#[no_mangle]
+pub extern "C" fn lc3_step() -> i32 {
+  with(FAULT, |m| match m.step() {
+    Ok(reason) => code(reason),
+    Err(_) => FAULT,
+  })
+}
+
+#[no_mangle]
+pub extern "C" fn lc3_run(max_steps: u32) -> i32 {
+  with(FAULT, |m| code(Some(m.run_for(max_steps as u64).reason)))
+}
+
+#[no_mangle]
+pub extern "C" fn lc3_halted() -> i32 {
+  with(1, |m| m.halt as i32)
+}
+
+#[no_mangle]
+pub extern "C" fn lc3_add_breakpoint(addr: u32) {
+  with((), |m| m.add_breakpoint(addr as u16))
+}
+
+#[no_mangle]
+pub extern "C" fn lc3_remove_breakpoint(addr: u32) {
+  with((), |m| m.remove_breakpoint(addr as u16))
+}
+
+// r is a Reg discriminant: 0-7 for R0-R7, 8 for PC, 9 for COND
+#[no_mangle]
+pub extern "C" fn lc3_read_reg(r: u32) -> u32 {
+  use num_traits::FromPrimitive;
+  match Reg::from_u32(r) {
+    Some(r) => with(0, |m| m.read_reg(r) as u32),
+    None => 0,
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn lc3_write_reg(r: u32, val: u32) {
+  use num_traits::FromPrimitive;
+  if let Some(r) = Reg::from_u32(r) {
+    with((), |m| m.write_reg(r, val as u16))
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn lc3_psr() -> u32 {
+  with(0, |m| m.psr() as u32)
+}
+
+#[no_mangle]
+pub extern "C" fn lc3_read_mem(addr: u32) -> u32 {
+  with(0, |m| m.read_mem(addr as u16) as u32)
+}
+
+#[no_mangle]
+pub extern "C" fn lc3_write_mem(addr: u32, val: u32) {
+  with((), |m| m.write_mem(addr as u16, val as u16))
+}
@@ -0,0 +1,81 @@
+extern crate lc3;
+
+use lc3::analysis::{self, Block, Cfg, Edge, EdgeKind};
+use lc3::assembler::{self, Program};
+use lc3::loader::{self, ImageFormat, LoadedImage};
+use lc3::{Machine, NullConsole, Reg, StopReason};
+
+const PROGRAM: &str = "\
+.ORIG x3000
+  LEA R1, SUB
+  JSRR R1
+  AND R0, R0, #0
+LOOP ADD R0, R0, #1
+  BRp DONE
+  BRnzp LOOP
+DONE HALT
+SUB ADD R2, R2, #1
+  RET
+.END";
+
+fn starts(g: &Cfg) -> Vec<(u16, usize)> {
+  g.blocks.iter().map(|b| (b.start, b.words.len())).collect()
+}
+
+fn edge(from: u16, to: u16, kind: EdgeKind) -> Edge {
+  Edge { from, to, kind }
+}
+
+#[test]
+fn blocks_and_edges() {
+  let prog: Program = assembler::assemble(PROGRAM).unwrap();
+  let image: LoadedImage = loader::parse(&prog.to_obj(), ImageFormat::Obj).unwrap();
+  let mut g: Cfg = analysis::cfg(&image, 0x3000);
+
+  // SUB is only reached through R1
+  assert_eq!(starts(&g), [(0x3000, 3), (0x3003, 2), (0x3005, 1), (0x3006, 1)]);
+  assert_eq!(g.edges, [
+    edge(0x3000, 0x3003, EdgeKind::Fall),
+    edge(0x3003, 0x3005, EdgeKind::Fall),
+    edge(0x3003, 0x3006, EdgeKind::Branch),
+    edge(0x3005, 0x3003, EdgeKind::Jump),
+  ]);
+  assert_eq!(g.block(0x3004).map(Block::last), Some(0x3004));
+  assert_eq!(g.predecessors(0x3003).count(), 2);
+
+  let mut m: Machine = Machine::builder().io(Box::new(NullConsole)).build();
+  m.load_image_bytes(&prog.to_obj()).unwrap();
+  assert_eq!(g.refine(&mut m, 1000).reason, StopReason::Halt);
+  assert_eq!(starts(&g), [(0x3000, 2), (0x3002, 1), (0x3003, 2), (0x3005, 1), (0x3006, 1), (0x3007, 2)]);
+  let out: Vec<Edge> = g.successors(0x3000).copied().collect();
+  assert_eq!(out, [edge(0x3000, 0x3002, EdgeKind::Fall), edge(0x3000, 0x3007, EdgeKind::Indirect)]);
+  // RET isn't followed
+  assert_eq!(g.successors(0x3007).count(), 0);
+
+  let dot: String = g.to_dot(&prog.symbols);
+  assert!(dot.starts_with("digraph cfg {\n"), "{}", dot);
+  assert!(dot.contains("  b3007 [label=\"SUB (x3007)\\l  ADD R2, R2, #1\\l  RET\\l\"];\n"), "{}", dot);
+  assert!(dot.contains("  entry -> b3000;\n"), "{}", dot);
+  assert!(dot.contains("  b3000 -> b3007 [label=\"indirect\", style=dotted];\n"), "{}", dot);
+  assert!(dot.contains("  b3005 -> b3003 [label=\"jump\"];\n"), "{}", dot);
+}
+
+#[test]
+fn calls_and_limits() {
+  let src: &str = ".ORIG x4000\n  JSR F\n  HALT\nF RET\n  .FILL x1234\n.END";
+  let prog: Program = assembler::assemble(src).unwrap();
+  let image: LoadedImage = loader::parse(&prog.to_obj(), ImageFormat::Obj).unwrap();
+  let mut g: Cfg = analysis::cfg(&image, 0x4000);
+  // the data after RET isn't decoded
+  assert_eq!(starts(&g), [(0x4000, 1), (0x4001, 1), (0x4002, 1)]);
+  assert_eq!(g.edges, [edge(0x4000, 0x4001, EdgeKind::Fall), edge(0x4000, 0x4002, EdgeKind::Call)]);
+
+  let mut m: Machine = Machine::builder().io(Box::new(NullConsole)).build();
+  m.load_image_bytes(&prog.to_obj()).unwrap();
+  m.write_reg(Reg::PC, 0x4000);
+  assert_eq!(g.refine(&mut m, 2).reason, StopReason::StepLimit);
+  assert_eq!(m.read_reg(Reg::PC), 0x4001);
+
+  // an entry outside the image has nothing
+  assert!(analysis::cfg(&image, 0x5000).blocks.is_empty());
+}
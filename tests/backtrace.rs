@@ -0,0 +1,124 @@
+extern crate lc3;
+
+use lc3::{CallFrame, Reg, StopReason, Violation, ViolationKind, CALLEE_SAVED};
+
+mod common;
+use common::machine;
+
+const NESTED: &str = "\
+.ORIG x3000
+MAIN  LD R6, STACK
+      JSR OUTER
+      HALT
+OUTER ADD R6, R6, #-1
+      STR R7, R6, #0
+      JSR INNER
+      LDR R7, R6, #0
+      ADD R6, R6, #1
+      RET
+INNER ADD R0, R0, #1
+      RET
+STACK .FILL x4000
+.END";
+
+
+fn frame(pc: u16, entry: Option<u16>, name: &str) -> CallFrame {
+  CallFrame { pc, entry, name: Some(name.to_string()) }
+}
+
+#[test]
+fn nested_calls() {
+  let mut m = machine(NESTED);
+  m.enable_history(16);
+  m.break_at("INNER").unwrap();
+  assert_eq!(m.run().reason, StopReason::Breakpoint(0x3009));
+  assert_eq!(m.backtrace(), vec![
+    frame(0x3009, Some(0x3009), "INNER"),
+    frame(0x3005, Some(0x3003), "OUTER"),
+    frame(0x3001, None, "MAIN"),
+  ]);
+
+  // RET from INNER pops it, undoing the RET pushes it again
+  m.remove_breakpoint(0x3009);
+  m.run_for(2);
+  assert_eq!(m.call_depth(), 1);
+  assert!(m.step_back());
+  assert_eq!(m.call_depth(), 2);
+
+  assert_eq!(m.run().reason, StopReason::Halt);
+  assert_eq!(m.call_depth(), 0);
+}
+
+// a JMP that isn't to a return address leaves the stack alone, one to an
+// outer call's return address unwinds everything above it
+const UNWIND: &str = "\
+.ORIG x3000
+      JSR A
+      HALT
+A     LEA R1, B
+      JMP R1
+B     JSR C
+      HALT
+C     LD R7, BACK
+      RET
+BACK  .FILL x3001
+.END";
+
+#[test]
+fn jumps() {
+  let mut m = machine(UNWIND);
+  m.break_at("C").unwrap();
+  assert_eq!(m.run().reason, StopReason::Breakpoint(0x3006));
+  // nothing is labelled at or before the outermost frame
+  assert_eq!(m.backtrace(), vec![
+    frame(0x3006, Some(0x3006), "C"),
+    frame(0x3004, Some(0x3002), "A"),
+    CallFrame { pc: 0x3000, entry: None, name: None },
+  ]);
+  m.run_for(2);
+  assert_eq!(m.call_depth(), 0);
+}
+
+// GOOD saves and restores R1, LEAKY forgets to pop it, CLOBBER changes R2
+const DISCIPLINE: &str = "\
+.ORIG x3000
+      LD R6, STACK
+      JSR GOOD
+      JSR LEAKY
+      ADD R6, R6, #1
+      JSR CLOBBER
+      HALT
+GOOD  ADD R6, R6, #-1
+      STR R1, R6, #0
+      ADD R1, R1, #5
+      LDR R1, R6, #0
+      ADD R6, R6, #1
+      RET
+LEAKY ADD R6, R6, #-1
+      STR R1, R6, #0
+      RET
+CLOBBER
+      ADD R2, R2, #1
+      ADD R0, R0, #1
+      RET
+STACK .FILL x4000
+.END";
+
+#[test]
+fn stack_check() {
+  let mut m = machine(DISCIPLINE);
+  m.enable_stack_check(CALLEE_SAVED);
+  assert_eq!(m.run().reason, StopReason::Halt);
+  assert_eq!(m.violations(), &[
+    Violation { pc: 0x300E, entry: 0x300C, kind: ViolationKind::StackImbalance { expected: 0x4000, found: 0x3FFF } },
+    Violation { pc: 0x3011, entry: 0x300F, kind: ViolationKind::Clobbered { reg: Reg::R2, expected: 0, found: 1 } },
+  ]);
+  assert_eq!(m.violations()[0].to_string(), "x300E: return from x300C left R6 at x3FFF, it was x4000 at the call");
+
+  // R0 is the result and not checked by the convention, it is if asked
+  let mut m = machine(DISCIPLINE);
+  m.enable_stack_check(&[Reg::R0]);
+  m.run();
+  assert_eq!(m.violations().len(), 2);
+  assert_eq!(m.violations()[1].kind, ViolationKind::Clobbered { reg: Reg::R0, expected: 0, found: 1 });
+}
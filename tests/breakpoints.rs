@@ -0,0 +1,166 @@
+extern crate lc3;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use lc3::assembler;
+use lc3::machine::Goal;
+use lc3::{Expr, Machine, NullConsole, Reg, StopReason, SymbolTable};
+
+const COUNT: &str = "\
+.ORIG x3000
+      AND R0, R0, #0
+; count to three
+LOOP  ADD R0, R0, #1
+      ADD R1, R0, #-3
+      BRn LOOP
+DONE
+      HALT
+.END";
+
+fn machine() -> Machine {
+  Machine::builder().io(Box::new(NullConsole)).build()
+}
+
+#[test]
+fn labels_and_lines() {
+  let prog = assembler::assemble(COUNT).unwrap();
+  let mut m = machine();
+  m.load_image_bytes(&prog.to_obj()).unwrap();
+  m.symbols_mut().extend(&prog.symbols);
+  m.add_line_info("src/count.asm", &prog.lines);
+
+  assert_eq!(m.resolve("LOOP"), Some(0x3001));
+  assert_eq!(m.resolve("x3002"), Some(0x3002));
+  assert_eq!(m.resolve("count.asm:5"), Some(0x3002));
+  assert_eq!(m.resolve("src/count.asm:5"), Some(0x3002));
+  // the comment and the lone label move to the next line with code
+  assert_eq!(m.resolve("count.asm:3"), Some(0x3001));
+  assert_eq!(m.resolve("count.asm:7"), Some(0x3004));
+  assert_eq!(m.resolve("count.asm:9"), None);
+  assert_eq!(m.resolve("other.asm:4"), None);
+  assert_eq!(m.resolve("NOWHERE"), None);
+
+  assert_eq!(m.break_at("LOOP"), Some(0x3001));
+  assert_eq!(m.run().reason, StopReason::Breakpoint(0x3001));
+  m.remove_breakpoint(0x3001);
+  assert_eq!(m.break_at("count.asm:7"), Some(0x3004));
+  assert_eq!(m.run().reason, StopReason::Breakpoint(0x3004));
+  assert_eq!(m.read_reg(Reg::R0), 3);
+}
+
+#[test]
+fn load_program_keeps_lines() {
+  let path: PathBuf = env::temp_dir().join(format!("lc3-breakpoints-{}.asm", std::process::id()));
+  fs::write(&path, COUNT).unwrap();
+  let mut m = machine();
+  m.load_program(&path).unwrap();
+  fs::remove_file(&path).unwrap();
+
+  let file: String = path.file_name().unwrap().to_string_lossy().into_owned();
+  assert_eq!(m.resolve(&format!("{}:4", file)), Some(0x3001));
+  assert_eq!(m.resolve("DONE"), Some(0x3004));
+}
+
+#[test]
+fn conditions() {
+  let prog = assembler::assemble(COUNT).unwrap();
+  let mut m = machine();
+  m.load_image_bytes(&prog.to_obj()).unwrap();
+  m.symbols_mut().extend(&prog.symbols);
+
+  // stops on the third pass through the loop only
+  m.write_mem(0x4000, 7);
+  assert_eq!(m.break_at_if("LOOP", "R0 == 2 && MEM[0x4000] != 0"), Ok(0x3001));
+  assert_eq!(m.run().reason, StopReason::Breakpoint(0x3001));
+  assert_eq!(m.read_reg(Reg::R0), 2);
+  assert_eq!(m.run().reason, StopReason::Halt);
+
+  // made unconditional again by add_breakpoint()
+  m.add_breakpoint(0x3001);
+  assert!(m.breakpoint_condition(0x3001).is_none());
+
+  assert!(m.break_at_if("LOOP", "R0 ==").is_err());
+  assert!(m.break_at_if("NOWHERE", "R0").is_err());
+}
+
+#[test]
+fn expressions() {
+  let mut symbols = SymbolTable::new();
+  symbols.insert("DATA", 0x4000);
+  let mut m = machine();
+  m.write_reg(Reg::R0, 0xFFFF);
+  m.write_reg(Reg::R1, 3);
+  m.write_mem(0x4001, 0x1234);
+
+  let eval = |src: &str| -> u16 { Expr::parse(src, &symbols).unwrap().eval(&m) };
+  assert_eq!(eval("MEM[DATA + R1 - 2]"), 0x1234);
+  assert_eq!(eval("x10 + #10 + 0x10 + 10"), 0x34);
+  // signed comparisons, wrapping arithmetic
+  assert_eq!(eval("R0 < R1"), 1);
+  assert_eq!(eval("R0 == -1"), 1);
+  assert_eq!(eval("R0 == #-1 && R1 - #-2 == 5"), 1);
+  assert_eq!(eval("R0 + R1"), 2);
+  assert_eq!(eval("R1 & 1 | 4"), 5);
+  assert_eq!(eval("1 + 1 == 2 && !(R1 >= 4)"), 1);
+  assert_eq!(eval("0 || R1 <= 2"), 0);
+  assert_eq!(eval("PC"), m.read_reg(Reg::PC));
+
+  assert!(Expr::parse("R8 == 1", &symbols).is_err());
+  assert!(Expr::parse("MEM[x4000", &symbols).is_err());
+  assert!(Expr::parse("1 2", &symbols).is_err());
+  assert!(Expr::parse("1 = 2", &symbols).is_err());
+  assert!(Expr::parse("#-32769", &symbols).is_err());
+}
+
+const CALLS: &str = "\
+.ORIG x3000
+      JSR OUTER
+      ADD R0, R0, #1
+      HALT
+OUTER ADD R6, R7, #0
+      JSR INNER
+      ADD R7, R6, #0
+      RET
+INNER ADD R1, R1, #1
+      RET
+.END";
+
+#[test]
+fn step_over_out_and_to() {
+  let prog = assembler::assemble(CALLS).unwrap();
+  let mut m = machine();
+  m.load_image_bytes(&prog.to_obj()).unwrap();
+
+  let run = m.step_over();
+  assert_eq!((run.reason, run.steps), (StopReason::GoalReached, 7));
+  assert_eq!((m.read_reg(Reg::PC), m.read_reg(Reg::R1)), (0x3001, 1));
+  assert_eq!(m.step_over().steps, 1);
+  assert_eq!(m.read_reg(Reg::PC), 0x3002);
+
+  m.load_image_bytes(&prog.to_obj()).unwrap();
+  m.write_reg(Reg::PC, 0x3000);
+  assert_eq!(m.run_to(0x3007).reason, StopReason::GoalReached);
+  assert_eq!(m.call_depth(), 2);
+  assert_eq!(m.step_out().reason, StopReason::GoalReached);
+  assert_eq!((m.read_reg(Reg::PC), m.call_depth()), (0x3005, 1));
+  assert_eq!(m.step_out().reason, StopReason::GoalReached);
+  assert_eq!((m.read_reg(Reg::PC), m.call_depth()), (0x3001, 0));
+  // there's nothing to return from at the outermost frame
+  assert_eq!(m.step_out().reason, StopReason::Halt);
+
+  // breakpoints on the way still stop
+  m.load_image_bytes(&prog.to_obj()).unwrap();
+  m.init();
+  m.add_breakpoint(0x3007);
+  assert_eq!(m.step_over().reason, StopReason::Breakpoint(0x3007));
+  assert_eq!(m.run_to(0x3002).reason, StopReason::GoalReached);
+  assert_eq!(m.read_reg(Reg::R1), 3);
+
+  // the stop closure firing first isn't the goal
+  m.load_image_bytes(&prog.to_obj()).unwrap();
+  m.init();
+  m.remove_breakpoint(0x3007);
+  assert_eq!(m.run_to_goal(Goal::Over, |m| m.read_reg(Reg::PC) == 0x3007).reason, StopReason::Condition);
+}
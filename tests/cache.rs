@@ -0,0 +1,65 @@
+extern crate lc3;
+
+use lc3::{Machine, NullConsole, Reg, Snapshot};
+
+mod common;
+
+fn machine(src: &str, cache: bool) -> Machine {
+  let mut m: Machine = common::machine(src);
+  m.set_decode_cache(cache);
+  m
+}
+
+// rewrites the ADD at PATCH to add 2 instead of 1 after its first run
+const SELF_MODIFYING: &str = "\
+.ORIG x3000
+      ADD R2, R2, #2
+LOOP
+PATCH ADD R1, R1, #1
+      LD R0, NEW
+      ST R0, PATCH
+      ADD R2, R2, #-1
+      BRp LOOP
+      HALT
+NEW   ADD R1, R1, #2
+.END";
+
+#[test]
+fn sees_self_modifying_code() {
+  let mut m = machine(SELF_MODIFYING, true);
+  assert!(m.decode_cache_enabled());
+  m.run();
+  assert_eq!(m.read_reg(Reg::R1), 3);
+}
+
+#[test]
+fn matches_the_uncached_interpreter() {
+  let src: &str = ".ORIG x3000\nLD R1, N\nLOOP ADD R0, R0, R1\nADD R1, R1, #-1\nBRp LOOP\nST R0, N\nHALT\nN .FILL #100\n.END";
+  let mut plain = machine(src, false);
+  let mut cached = machine(src, true);
+  assert_eq!(plain.run(), cached.run());
+  assert!(plain.snapshot() == cached.snapshot());
+  assert_eq!(cached.read_mem(0x3006), 5050);
+}
+
+#[test]
+fn sees_writes_from_outside() {
+  // ADD R1, R1, #1 then HALT
+  let mut m = Machine::builder().io(Box::new(NullConsole)).load(&[0x1261, 0xF025]).build();
+  m.set_decode_cache(true);
+  let start: Snapshot = m.snapshot();
+  m.run();
+  assert_eq!(m.read_reg(Reg::R1), 1);
+
+  m.restore(&start);
+  m.write_mem(0x3000, 0x1265);
+  m.run();
+  assert_eq!(m.read_reg(Reg::R1), 5);
+
+  m.restore(&start);
+  m.run();
+  assert_eq!(m.read_reg(Reg::R1), 1);
+
+  m.set_decode_cache(false);
+  assert!(!m.decode_cache_enabled());
+}
@@ -0,0 +1,59 @@
+extern crate lc3;
+
+use lc3::{Machine, StopReason};
+
+mod common;
+use common::Output;
+
+const PRINTS: &str = "\
+.ORIG x3000
+      LD R0, BANG
+      OUT
+      LEA R0, HELLO
+      PUTS
+      LEA R0, PACKED
+      PUTSP
+      LD R0, BANG
+      STI R0, DDR
+      HALT
+BANG  .FILL x21
+HELLO .STRINGZ \"hello \"
+PACKED .FILL x6F77
+      .FILL x6C72
+      .FILL x0064
+DDR   .FILL xFE06
+.END";
+
+fn machine() -> (Machine, Output) {
+  let out: Output = Output::default();
+  // for the store to DDR
+  let m: Machine = common::load(Machine::builder().io(Box::new(out.clone())).supervisor(true), PRINTS);
+  (m, out)
+}
+
+#[test]
+fn captures_every_way_of_printing() {
+  let (mut m, console) = machine();
+  m.capture_output(false);
+  m.run_for(2);
+  assert_eq!(m.output(), "!");
+  assert_eq!(m.take_output(), "!");
+  assert_eq!(m.output(), "");
+  assert_eq!(m.run().reason, StopReason::Halt);
+  assert!(m.output().starts_with("hello world!"), "{:?}", m.output());
+  assert!(console.text().is_empty());
+
+  let rest: String = m.stop_capture();
+  assert!(rest.starts_with("hello world!"));
+  assert_eq!(m.take_output(), "");
+}
+
+#[test]
+fn tees_to_the_console() {
+  let (mut m, console) = machine();
+  m.capture_output(true);
+  m.run();
+  let out: String = m.take_output();
+  assert!(out.starts_with("!hello world!"), "{:?}", out);
+  assert_eq!(console.text(), out);
+}
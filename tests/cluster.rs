@@ -0,0 +1,77 @@
+extern crate lc3;
+
+use lc3::assembler;
+use lc3::{Cluster, ClusterRun, Machine, MachineError, Mailbox, NullConsole, Reg, Schedule, StopReason};
+
+// the same program on both machines: machine 0 sends 1 to machine 1,
+// which sends back what it got plus one; each ends with the last word
+// it received in R2
+const PING: &str = "\
+.ORIG x3000
+      LDI R1, MBID
+      BRp PONG
+      ADD R0, R1, #1
+      STI R0, MBID
+      STI R0, MBTX
+      JSR RECV
+      HALT
+PONG  JSR RECV
+      AND R0, R0, #0
+      STI R0, MBID
+      ADD R0, R2, #1
+      STI R0, MBTX
+      HALT
+RECV  LDI R0, MBSR
+      BRzp RECV
+      LDI R2, MBDR
+      RET
+MBSR  .FILL xFE18
+MBDR  .FILL xFE1A
+MBID  .FILL xFE1C
+MBTX  .FILL xFE1E
+.END";
+
+fn cluster(schedule: Schedule, src: &str) -> Cluster {
+  let obj: Vec<u8> = assembler::assemble(src).unwrap().to_obj();
+  let mut c = Cluster::new(schedule);
+  for mbx in Mailbox::network(2) {
+    let mut m = Machine::builder().io(Box::new(NullConsole)).supervisor(true).device(Box::new(mbx)).build();
+    m.load_image_bytes(&obj).unwrap();
+    c.add(m);
+  }
+  c
+}
+
+#[test]
+fn lockstep_ping_pong() {
+  let mut c = cluster(Schedule::Lockstep, PING);
+  let run: ClusterRun = c.run_for(1000);
+  assert_eq!(run.stop, None);
+  assert!(c.halted());
+  assert_eq!((c.machine(0).read_reg(Reg::R2), c.machine(1).read_reg(Reg::R2)), (2, 1));
+}
+
+#[test]
+fn round_robin_ping_pong() {
+  let mut c = cluster(Schedule::RoundRobin { quantum: 7 }, PING);
+  let run: ClusterRun = c.run();
+  assert_eq!(run.stop, None);
+  assert_eq!((c.machine(0).read_reg(Reg::R2), c.machine(1).read_reg(Reg::R2)), (2, 1));
+  // one instruction each per round in lockstep takes more rounds
+  let mut lockstep = cluster(Schedule::Lockstep, PING);
+  assert!(lockstep.run().rounds > run.rounds);
+}
+
+#[test]
+fn stops_on_a_fault() {
+  let mut c = cluster(Schedule::Lockstep, PING);
+  c.machine_mut(1).write_mem(0x3000, 0xD000);
+  let run: ClusterRun = c.run_for(1000);
+  assert_eq!(run.stop, Some((1, StopReason::Fault(MachineError::IllegalOpcode { pc: 0x3000, instr: 0xD000 }))));
+  assert_eq!(run.rounds, 1);
+
+  // the limit
+  let mut c = cluster(Schedule::Lockstep, ".ORIG x3000\nL BRnzp L\n.END");
+  assert_eq!(c.run_for(10), ClusterRun { rounds: 10, stop: None });
+  assert!(!c.halted());
+}
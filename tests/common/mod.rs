@@ -0,0 +1,51 @@
+// helpers shared by the integration tests, each using only some of them
+#![allow(dead_code)]
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use lc3::assembler::{self, Program};
+use lc3::{Console, Machine, MachineBuilder, NullConsole};
+
+// console output shared with the test, no input
+#[derive(Clone, Default)]
+pub struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Output {
+  pub fn text(&self) -> String {
+    String::from_utf8_lossy(&self.0.borrow()).into_owned()
+  }
+}
+
+impl Console for Output {
+  fn read_char(&mut self) -> Option<u8> {
+    None
+  }
+
+  fn write_char(&mut self, c: u8) {
+    self.0.borrow_mut().push(c);
+  }
+
+  fn poll_key(&mut self) -> Option<u8> {
+    None
+  }
+}
+
+// a machine builder without a console
+pub fn builder() -> MachineBuilder {
+  Machine::builder().io(Box::new(NullConsole))
+}
+
+// builds the machine and loads src into it, with its symbols
+pub fn load(builder: MachineBuilder, src: &str) -> Machine {
+  let prog: Program = assembler::assemble(src).unwrap();
+  let mut m: Machine = builder.build();
+  m.load_image_bytes(&prog.to_obj()).unwrap();
+  m.symbols_mut().extend(&prog.symbols);
+  m
+}
+
+// src loaded into a machine without a console
+pub fn machine(src: &str) -> Machine {
+  load(builder(), src)
+}
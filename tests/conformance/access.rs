@@ -0,0 +1,81 @@
+use super::*;
+
+#[test]
+fn user_mode_loads_below_user_space() {
+  let mut m = machine(&[0xA000, 0x2FFF]); // LDI R0, #0 through x2FFF
+  m.write_mem(0x2FFF, 0x1234);
+  assert_eq!(m.step(), Err(MachineError::AccessViolation { pc: ORIGIN, addr: 0x2FFF }));
+  assert_eq!(m.read_reg(Reg::R0), 0);
+}
+
+#[test]
+fn user_mode_stores_to_devices() {
+  let mut m = machine(&[0x7040]); // STR R0, R1, #0
+  m.write_reg(Reg::R1, MCR);
+  assert_eq!(m.step(), Err(MachineError::AccessViolation { pc: ORIGIN, addr: MCR }));
+  assert!(!m.halt);
+}
+
+#[test]
+fn user_mode_fetches_below_user_space() {
+  let mut m = machine(&[0xC040]); // JMP R1
+  m.write_reg(Reg::R1, 0x0200);
+  m.step().unwrap();
+  assert_eq!(m.step(), Err(MachineError::AccessViolation { pc: 0x0200, addr: 0x0200 }));
+}
+
+#[test]
+fn user_space_is_reachable() {
+  let mut m = machine(&[0x6040, 0x7041]); // LDR R0, R1, #0; STR R0, R1, #1
+  m.write_reg(Reg::R1, 0xFDFE);
+  m.write_mem(0xFDFE, 0x1234);
+  m.step().unwrap();
+  m.step().unwrap();
+  assert_eq!(m.read_mem(0xFDFF), 0x1234);
+}
+
+#[test]
+fn supervisor_mode_is_unrestricted() {
+  let mut m = supervisor(&[0x6040]); // LDR R0, R1, #0
+  m.write_reg(Reg::R1, 0x2000);
+  m.write_mem(0x2000, 0x1234);
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R0), 0x1234);
+}
+
+#[test]
+fn vectors_through_the_table() {
+  let mut m = machine(&[0x7040]); // STR R0, R1, #0
+  m.write_mem(0x0102, 0x1000); // access violation exception vector
+  m.write_reg(Reg::R0, 0x1234);
+  m.write_reg(Reg::R1, 0x2000);
+  m.write_reg(Reg::R6, 0x4000);
+  m.step().unwrap();
+
+  assert_eq!(m.read_mem(0x2000), 0);
+  assert!(!m.user_mode());
+  assert_eq!(m.read_reg(Reg::PC), 0x1000);
+  assert_eq!(m.read_reg(Reg::R6), SSP - 2);
+  assert_eq!(m.read_mem(SSP - 2), ORIGIN + 1);
+  assert_eq!(m.read_mem(SSP - 1), PSR_USER | ZRO);
+}
+
+#[test]
+fn os_reports_the_violation() {
+  let out = Capture::default();
+  let mut m = Machine::builder().io(Box::new(out.clone())).os(true).load(&[0xA000, 0xFE00]).build(); // LDI R0, KBSR
+  assert_eq!(m.run().reason, StopReason::Halt);
+  assert!(out.output().contains("access control violation"), "{:?}", out.output());
+}
+
+#[test]
+fn device_holes() {
+  let mut m = supervisor(&[0x6040, 0x6040]); // LDR R0, R1, #0, twice
+  m.write_reg(Reg::R1, 0xFE30);
+  m.write_mem(0xFE30, 0x1234);
+  assert_eq!(m.step(), Err(MachineError::AccessViolation { pc: ORIGIN, addr: 0xFE30 }));
+
+  m.set_strictness(Strictness::Permissive);
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R0), 0x1234);
+}
@@ -0,0 +1,63 @@
+use super::*;
+
+#[test]
+fn register_mode() {
+  let mut m = machine(&[0x1042]); // ADD R0, R1, R2
+  m.write_reg(Reg::R1, 3);
+  m.write_reg(Reg::R2, 4);
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R0), 7);
+  assert_eq!(cond(&m), POS);
+}
+
+#[test]
+fn immediate_mode() {
+  let mut m = machine(&[0x1065]); // ADD R0, R1, #5
+  m.write_reg(Reg::R1, 10);
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R0), 15);
+}
+
+#[test]
+fn sets_zero_and_negative() {
+  let mut m = machine(&[0x1060]); // ADD R0, R1, #0
+  m.write_reg(Reg::R1, 0x8000);
+  m.step().unwrap();
+  assert_eq!(cond(&m), NEG);
+
+  let mut m = machine(&[0x1060]);
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R0), 0);
+  assert_eq!(cond(&m), ZRO);
+}
+
+#[test]
+fn advances_pc() {
+  let mut m = machine(&[0x1042]);
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::PC), ORIGIN + 1);
+}
+
+#[test]
+fn wraps_on_overflow() {
+  let mut m = machine(&[0x1042]); // ADD R0, R1, R2
+  m.write_reg(Reg::R1, 0x7FFF);
+  m.write_reg(Reg::R2, 1);
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R0), 0x8000);
+  assert_eq!(cond(&m), NEG);
+
+  let mut m = machine(&[0x1061]); // ADD R0, R1, #1
+  m.write_reg(Reg::R1, 0xFFFF);
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R0), 0);
+  assert_eq!(cond(&m), ZRO);
+}
+
+#[test]
+fn negative_immediate() {
+  let mut m = machine(&[0x107F]); // ADD R0, R1, #-1
+  m.write_reg(Reg::R1, 5);
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R0), 4);
+}
@@ -0,0 +1,45 @@
+use super::*;
+
+#[test]
+fn register_mode() {
+  let mut m = machine(&[0x5042]); // AND R0, R1, R2
+  m.write_reg(Reg::R1, 0xF0F0);
+  m.write_reg(Reg::R2, 0xFF00);
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R0), 0xF000);
+}
+
+#[test]
+fn immediate_mode() {
+  let mut m = machine(&[0x506F]); // AND R0, R1, #15
+  m.write_reg(Reg::R1, 0xFF3C);
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R0), 0x000C);
+}
+
+#[test]
+fn immediate_is_sign_extended() {
+  let mut m = machine(&[0x507E]); // AND R0, R1, #-2
+  m.write_reg(Reg::R1, 0xFFFF);
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R0), 0xFFFE);
+}
+
+#[test]
+fn sets_condition_codes() {
+  let mut m = machine(&[0x5020]); // AND R0, R0, #0
+  m.write_reg(Reg::R0, 0x1234);
+  m.step().unwrap();
+  assert_eq!(cond(&m), ZRO);
+
+  let mut m = machine(&[0x5042]); // AND R0, R1, R2
+  m.write_reg(Reg::R1, 0x8001);
+  m.write_reg(Reg::R2, 0xFFFF);
+  m.step().unwrap();
+  assert_eq!(cond(&m), NEG);
+
+  let mut m = machine(&[0x5067]); // AND R0, R1, #7
+  m.write_reg(Reg::R1, 0x0003);
+  m.step().unwrap();
+  assert_eq!(cond(&m), POS);
+}
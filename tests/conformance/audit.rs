@@ -0,0 +1,41 @@
+use super::*;
+
+use lc3::encode::*;
+
+// every opcode, run with the condition code audit on
+#[test]
+fn interpreter_matches_reference() {
+  let program: [u16; 14] = [
+    add_reg(0, 1, 2),
+    add_imm(1, 1, -1),
+    and_imm(0, 0, 0),
+    and_imm(2, 2, 1),
+    not(1, 1),
+    lea(0, -5),
+    ld(0, 2),
+    ldi(0, 1),
+    ldr(3, 0, 0),
+    br(true, true, true, 1),
+    nop(), // skipped
+    st(0, -2),
+    str(3, 0, 0),
+    halt(),
+  ];
+  let mut m = supervisor(&program);
+  m.write_reg(Reg::R1, 0x8000);
+  m.write_reg(Reg::R2, 0x7FFF);
+  m.set_cc_audit(true);
+  assert_eq!(m.run().reason, StopReason::Halt);
+}
+
+#[test]
+fn corrupted_codes_are_overwritten() {
+  let mut m = machine(&[0x0E00, 0x1020]); // NOP, ADD R0, R0, #0
+  m.set_cc_audit(true);
+  let pc: u16 = ORIGIN + 1;
+  m.step().unwrap();
+  m.write_reg(Reg::COND, NEG);
+  assert_eq!(m.step(), Ok(None));
+  assert_eq!(cond(&m), ZRO);
+  assert_eq!(m.read_reg(Reg::PC), pc + 1);
+}
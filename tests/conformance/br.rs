@@ -0,0 +1,70 @@
+use super::*;
+
+// runs BR with the given nzp bits and condition code, returns whether the
+// branch was taken
+fn taken(nzp: u16, cc: u16) -> bool {
+  let mut m = machine(&[nzp << 9 | 0x2]); // BR #2
+  m.write_reg(Reg::COND, cc);
+  m.step().unwrap();
+  match m.read_reg(Reg::PC) {
+    pc if pc == ORIGIN + 3 => true,
+    pc if pc == ORIGIN + 1 => false,
+    pc => panic!("unexpected PC x{:04X}", pc),
+  }
+}
+
+#[test]
+fn single_flags() {
+  assert!(taken(0b100, NEG));
+  assert!(!taken(0b100, ZRO));
+  assert!(!taken(0b100, POS));
+
+  assert!(!taken(0b010, NEG));
+  assert!(taken(0b010, ZRO));
+  assert!(!taken(0b010, POS));
+
+  assert!(!taken(0b001, NEG));
+  assert!(!taken(0b001, ZRO));
+  assert!(taken(0b001, POS));
+}
+
+#[test]
+fn combined_flags() {
+  assert!(taken(0b110, NEG));
+  assert!(taken(0b110, ZRO));
+  assert!(!taken(0b110, POS));
+
+  assert!(taken(0b011, ZRO));
+  assert!(taken(0b101, POS));
+  assert!(!taken(0b101, ZRO));
+}
+
+#[test]
+fn unconditional() {
+  assert!(taken(0b111, NEG));
+  assert!(taken(0b111, ZRO));
+  assert!(taken(0b111, POS));
+}
+
+#[test]
+fn no_flags_is_nop() {
+  assert!(!taken(0b000, NEG));
+  assert!(!taken(0b000, ZRO));
+  assert!(!taken(0b000, POS));
+}
+
+#[test]
+fn backward() {
+  let mut m = machine(&[0x0FFF]); // BRnzp #-1
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::PC), ORIGIN);
+}
+
+#[test]
+fn wraps_around_memory() {
+  let mut m = supervisor(&[]);
+  m.write_reg(Reg::PC, 0xFFFF);
+  m.write_mem(0xFFFF, 0x0E01); // BRnzp #1
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::PC), 0x0001);
+}
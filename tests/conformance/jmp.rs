@@ -0,0 +1,17 @@
+use super::*;
+
+#[test]
+fn jumps_to_register() {
+  let mut m = machine(&[0xC080]); // JMP R2
+  m.write_reg(Reg::R2, 0x4000);
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::PC), 0x4000);
+}
+
+#[test]
+fn ret() {
+  let mut m = machine(&[0xC1C0]); // RET
+  m.write_reg(Reg::R7, 0x3456);
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::PC), 0x3456);
+}
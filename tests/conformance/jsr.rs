@@ -0,0 +1,34 @@
+use super::*;
+
+#[test]
+fn pc_relative() {
+  let mut m = machine(&[0x4804]); // JSR #4
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R7), ORIGIN + 1);
+  assert_eq!(m.read_reg(Reg::PC), ORIGIN + 5);
+}
+
+#[test]
+fn jsrr_register() {
+  let mut m = machine(&[0x4080]); // JSRR R2
+  m.write_reg(Reg::R2, 0x4000);
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R7), ORIGIN + 1);
+  assert_eq!(m.read_reg(Reg::PC), 0x4000);
+}
+
+#[test]
+fn jsrr_r7() {
+  let mut m = machine(&[0x41C0]); // JSRR R7
+  m.write_reg(Reg::R7, 0x4000);
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R7), ORIGIN + 1);
+  assert_eq!(m.read_reg(Reg::PC), 0x4000);
+}
+
+#[test]
+fn backward() {
+  let mut m = machine(&[0x4FFE]); // JSR #-2
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::PC), ORIGIN - 1);
+}
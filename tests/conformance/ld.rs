@@ -0,0 +1,30 @@
+use super::*;
+
+#[test]
+fn loads_pc_relative() {
+  let mut m = machine(&[0x2002, 0, 0, 0x1234]); // LD R0, #2
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R0), 0x1234);
+  assert_eq!(cond(&m), POS);
+}
+
+#[test]
+fn sets_condition_codes() {
+  let mut m = machine(&[0x2002, 0, 0, 0x8000]);
+  m.step().unwrap();
+  assert_eq!(cond(&m), NEG);
+
+  let mut m = machine(&[0x2002, 0, 0, 0]);
+  m.write_reg(Reg::R0, 7);
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R0), 0);
+  assert_eq!(cond(&m), ZRO);
+}
+
+#[test]
+fn negative_offset() {
+  let mut m = supervisor(&[0x21FE]); // LD R0, #-2
+  m.write_mem(ORIGIN - 1, 0x1234);
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R0), 0x1234);
+}
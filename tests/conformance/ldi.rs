@@ -0,0 +1,10 @@
+use super::*;
+
+#[test]
+fn loads_indirect() {
+  let mut m = machine(&[0xA002, 0, 0, 0x4000]); // LDI R0, #2
+  m.write_mem(0x4000, 0xBEEF);
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R0), 0xBEEF);
+  assert_eq!(cond(&m), NEG);
+}
@@ -0,0 +1,34 @@
+use super::*;
+
+#[test]
+fn loads_base_plus_offset() {
+  let mut m = machine(&[0x6283]); // LDR R1, R2, #3
+  m.write_reg(Reg::R2, 0x4000);
+  m.write_mem(0x4003, 0x1234);
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R1), 0x1234);
+}
+
+#[test]
+fn negative_offset() {
+  let mut m = machine(&[0x62BF]); // LDR R1, R2, #-1
+  m.write_reg(Reg::R2, 0x4000);
+  m.write_mem(0x3FFF, 0x5678);
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R1), 0x5678);
+}
+
+#[test]
+fn sets_condition_codes() {
+  let mut m = machine(&[0x6280]); // LDR R1, R2, #0
+  m.write_reg(Reg::R2, 0x4000);
+  m.write_mem(0x4000, 0x8000);
+  m.step().unwrap();
+  assert_eq!(cond(&m), NEG);
+
+  let mut m = machine(&[0x6280]);
+  m.write_reg(Reg::R1, 5);
+  m.write_reg(Reg::R2, 0x4000);
+  m.step().unwrap();
+  assert_eq!(cond(&m), ZRO);
+}
@@ -0,0 +1,22 @@
+use super::*;
+
+#[test]
+fn computes_address() {
+  let mut m = machine(&[0xE005]); // LEA R0, #5
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R0), ORIGIN + 6);
+}
+
+#[test]
+fn does_not_read_memory() {
+  let mut m = machine(&[0xE005]);
+  m.add_watchpoint(ORIGIN + 6, WatchKind::Access);
+  assert_eq!(m.step(), Ok(None));
+}
+
+#[test]
+fn negative_offset() {
+  let mut m = machine(&[0xE1FE]); // LEA R0, #-2
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R0), ORIGIN - 1);
+}
@@ -0,0 +1,66 @@
+// instruction-level conformance tests, one module per opcode
+extern crate lc3;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use lc3::*;
+
+mod access;
+mod add;
+mod and;
+mod audit;
+mod br;
+mod jmp;
+mod jsr;
+mod ld;
+mod ldi;
+mod ldr;
+mod lea;
+mod not;
+mod res;
+mod rti;
+mod st;
+mod sti;
+mod str;
+mod trap;
+
+pub const ORIGIN: u16 = 0x3000;
+
+// a machine in user mode with `program` loaded at x3000
+pub fn machine(program: &[u16]) -> Machine {
+  Machine::builder().origin(ORIGIN).load(program).build()
+}
+
+// the same in supervisor mode, for programs reaching outside user space
+pub fn supervisor(program: &[u16]) -> Machine {
+  Machine::builder().origin(ORIGIN).load(program).supervisor(true).build()
+}
+
+pub fn cond(m: &Machine) -> u16 {
+  m.read_reg(Reg::COND)
+}
+
+// console output shared with the test, no input
+#[derive(Clone, Default)]
+pub struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Capture {
+  pub fn output(&self) -> String {
+    String::from_utf8_lossy(&self.0.borrow()).into_owned()
+  }
+}
+
+impl Console for Capture {
+  fn read_char(&mut self) -> Option<u8> {
+    None
+  }
+
+  fn write_char(&mut self, c: u8) {
+    self.0.borrow_mut().push(c);
+  }
+
+  fn poll_key(&mut self) -> Option<u8> {
+    None
+  }
+}
@@ -0,0 +1,21 @@
+use super::*;
+
+#[test]
+fn complements_source() {
+  let mut m = machine(&[0x907F]); // NOT R0, R1
+  m.write_reg(Reg::R0, 0x1111);
+  m.write_reg(Reg::R1, 0x00FF);
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R0), 0xFF00);
+  assert_eq!(m.read_reg(Reg::R1), 0x00FF);
+  assert_eq!(cond(&m), NEG);
+}
+
+#[test]
+fn sets_zero() {
+  let mut m = machine(&[0x907F]);
+  m.write_reg(Reg::R1, 0xFFFF);
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R0), 0);
+  assert_eq!(cond(&m), ZRO);
+}
@@ -0,0 +1,77 @@
+use super::*;
+
+use lc3::encode::{lshf, mul_imm, mul_reg, rshfa, rshfl};
+
+#[test]
+fn illegal_opcode() {
+  let mut m = machine(&[0xD123]);
+  assert_eq!(m.step(), Err(MachineError::IllegalOpcode { pc: ORIGIN, instr: 0xD123 }));
+}
+
+#[test]
+fn vectors_through_the_table() {
+  let mut m = machine(&[0xD123]);
+  m.write_mem(0x0101, 0x1000); // illegal opcode exception vector
+  m.write_reg(Reg::R6, 0x4000);
+  m.step().unwrap();
+
+  assert!(!m.user_mode());
+  assert_eq!(m.read_reg(Reg::PC), 0x1000);
+  assert_eq!(m.read_reg(Reg::R6), SSP - 2);
+  assert_eq!(m.read_mem(SSP - 2), ORIGIN + 1);
+  assert_eq!(m.read_mem(SSP - 1), PSR_USER | ZRO);
+}
+
+#[test]
+fn os_reports_the_opcode() {
+  let out = Capture::default();
+  let mut m = Machine::builder().io(Box::new(out.clone())).os(true).load(&[0xD123]).build();
+  assert_eq!(m.run().reason, StopReason::Halt);
+  assert!(out.output().contains("illegal opcode"), "{:?}", out.output());
+}
+
+fn extended(ext: IsaExtensions, program: &[u16]) -> Machine {
+  Machine::builder().origin(ORIGIN).load(program).isa_extensions(ext).build()
+}
+
+#[test]
+fn shift_extension() {
+  let mut m = extended(IsaExtensions::Shift, &[lshf(1, 0, 4), rshfl(2, 0, 4), rshfa(3, 0, 15), 0xD0A0]);
+  m.write_reg(Reg::R0, 0x8421);
+  for _ in 0..3 {
+    m.step().unwrap();
+  }
+  assert_eq!(m.read_reg(Reg::R1), 0x4210);
+  assert_eq!(m.read_reg(Reg::R2), 0x0842);
+  assert_eq!(m.read_reg(Reg::R3), 0xFFFF);
+  assert_eq!(cond(&m), NEG);
+  // kind 10 is left reserved
+  assert_eq!(m.step(), Err(MachineError::IllegalOpcode { pc: ORIGIN + 3, instr: 0xD0A0 }));
+}
+
+#[test]
+fn mul_extension() {
+  let mut m = extended(IsaExtensions::Mul, &[mul_imm(1, 0, -3), mul_reg(2, 0, 0), mul_imm(3, 0, 0)]);
+  m.write_reg(Reg::R0, 300);
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R1), (-900i16) as u16);
+  assert_eq!(cond(&m), NEG);
+  m.step().unwrap();
+  // the low 16 bits of 90000
+  assert_eq!(m.read_reg(Reg::R2), 0x5F90);
+  m.step().unwrap();
+  assert_eq!(cond(&m), ZRO);
+
+  // off by default
+  assert!(machine(&[mul_imm(1, 0, -3)]).step().is_err());
+}
+
+#[test]
+fn permissive_skips_it() {
+  let mut m = machine(&[0xD123]);
+  m.set_strictness(Strictness::Permissive);
+  m.write_mem(0x0101, 0x1000);
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::PC), ORIGIN + 1);
+  assert!(m.user_mode());
+}
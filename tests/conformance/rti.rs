@@ -0,0 +1,48 @@
+use super::*;
+
+#[test]
+fn returns_to_user_mode() {
+  let mut m = machine(&[0x8000]); // RTI
+  m.set_psr(ZRO);
+  m.write_reg(Reg::R6, 0x2FFE);
+  m.write_mem(0x2FFE, 0x3100); // saved PC
+  m.write_mem(0x2FFF, PSR_USER | POS); // saved PSR
+  m.step().unwrap();
+
+  assert_eq!(m.read_reg(Reg::PC), 0x3100);
+  assert_eq!(m.psr(), PSR_USER | POS);
+  assert!(m.user_mode());
+}
+
+#[test]
+fn privilege_violation_in_user_mode() {
+  let mut m = machine(&[0x8000]);
+  m.write_mem(0x0100, 0x1000); // privilege mode exception vector
+  m.write_reg(Reg::R6, 0x4000);
+  m.step().unwrap();
+
+  assert!(!m.user_mode());
+  assert_eq!(m.read_reg(Reg::PC), 0x1000);
+  assert_eq!(m.read_reg(Reg::R6), SSP - 2);
+  assert_eq!(m.read_mem(SSP - 2), ORIGIN + 1);
+  assert_eq!(m.read_mem(SSP - 1), PSR_USER | ZRO);
+}
+
+#[test]
+fn privilege_violation_without_handler() {
+  let mut m = machine(&[0x8000]);
+  assert_eq!(m.step(), Err(MachineError::PrivilegeViolation { pc: ORIGIN }));
+}
+
+#[test]
+fn malformed_saved_cc() {
+  for &(saved, cc) in &[(0, ZRO), (NEG | POS, NEG), (ZRO | POS, ZRO)] {
+    let mut m = machine(&[0x8000]);
+    m.set_psr(ZRO);
+    m.write_reg(Reg::R6, 0x2FFE);
+    m.write_mem(0x2FFE, 0x3100);
+    m.write_mem(0x2FFF, PSR_USER | saved);
+    m.step().unwrap();
+    assert_eq!(cond(&m), cc);
+  }
+}
@@ -0,0 +1,18 @@
+use super::*;
+
+#[test]
+fn stores_pc_relative() {
+  let mut m = machine(&[0x3202]); // ST R1, #2
+  m.write_reg(Reg::R1, 0xBEEF);
+  m.step().unwrap();
+  assert_eq!(m.read_mem(ORIGIN + 3), 0xBEEF);
+}
+
+#[test]
+fn negative_offset() {
+  let mut m = machine(&[0, 0x33FE]); // ST R1, #-2
+  m.write_reg(Reg::PC, ORIGIN + 1);
+  m.write_reg(Reg::R1, 0x1234);
+  m.step().unwrap();
+  assert_eq!(m.read_mem(ORIGIN), 0x1234);
+}
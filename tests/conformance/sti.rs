@@ -0,0 +1,10 @@
+use super::*;
+
+#[test]
+fn stores_indirect() {
+  let mut m = machine(&[0xB202, 0, 0, 0x4000]); // STI R1, #2
+  m.write_reg(Reg::R1, 0xBEEF);
+  m.step().unwrap();
+  assert_eq!(m.read_mem(0x4000), 0xBEEF);
+  assert_eq!(m.read_mem(ORIGIN + 3), 0x4000);
+}
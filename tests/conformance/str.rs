@@ -0,0 +1,19 @@
+use super::*;
+
+#[test]
+fn stores_base_plus_offset() {
+  let mut m = machine(&[0x7283]); // STR R1, R2, #3
+  m.write_reg(Reg::R1, 0xBEEF);
+  m.write_reg(Reg::R2, 0x4000);
+  m.step().unwrap();
+  assert_eq!(m.read_mem(0x4003), 0xBEEF);
+}
+
+#[test]
+fn negative_offset() {
+  let mut m = machine(&[0x72BF]); // STR R1, R2, #-1
+  m.write_reg(Reg::R1, 0x1234);
+  m.write_reg(Reg::R2, 0x4000);
+  m.step().unwrap();
+  assert_eq!(m.read_mem(0x3FFF), 0x1234);
+}
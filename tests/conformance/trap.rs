@@ -0,0 +1,83 @@
+use super::*;
+
+#[test]
+fn halt() {
+  let mut m = machine(&[0xF025]); // HALT
+  assert_eq!(m.step(), Ok(Some(StopReason::Halt)));
+  assert!(m.halt);
+  assert_eq!(m.read_reg(Reg::R7), ORIGIN + 1);
+}
+
+
+#[test]
+fn unknown_vector() {
+  let mut m = machine(&[0xF0FF]); // TRAP xFF
+  assert_eq!(m.step(), Err(MachineError::UnknownTrap { pc: ORIGIN, vector: 0xFF }));
+}
+
+#[test]
+fn unknown_vector_is_an_illegal_opcode() {
+  let mut m = machine(&[0xF0FF]); // TRAP xFF
+  m.write_mem(0x0101, 0x1000); // illegal opcode exception vector
+  m.step().unwrap();
+  assert!(!m.user_mode());
+  assert_eq!(m.read_reg(Reg::PC), 0x1000);
+  assert_eq!(m.read_mem(SSP - 2), ORIGIN + 1);
+}
+
+#[test]
+fn unknown_vector_when_permissive() {
+  let mut m = machine(&[0xF0FF, 0xF025]); // TRAP xFF, HALT
+  m.set_strictness(Strictness::Permissive);
+  assert_eq!(m.run().reason, StopReason::Halt);
+  assert!(m.user_mode());
+}
+
+#[test]
+fn custom_handler() {
+  let mut m = machine(&[0xF030, 0xF025]); // TRAP x30, HALT
+  m.set_trap_handler(0x30, Box::new(|m: &mut Machine| {
+    let r0: u16 = m.read_reg(Reg::R0);
+    m.write_reg(Reg::R0, r0 + 1);
+  }));
+  m.set_trap_handler(0x25, Box::new(|m: &mut Machine| m.halt = true));
+  assert_eq!(m.run(), Run { steps: 2, reason: StopReason::Halt });
+  assert_eq!(m.read_reg(Reg::R0), 1);
+  assert_eq!(m.read_reg(Reg::R7), ORIGIN + 2);
+}
+
+// runs PUTSP on `string` at x4000, natively or through the OS routine
+fn putsp(string: &[u16], os: bool) -> String {
+  let out = Capture::default();
+  let mut m = Machine::builder().origin(ORIGIN).load(&[0xF024]).io(Box::new(out.clone())).os(os).build();
+  for (i, &w) in string.iter().enumerate() {
+    m.write_mem(0x4000 + i as u16, w);
+  }
+  m.write_reg(Reg::R0, 0x4000);
+  while m.read_reg(Reg::PC) != ORIGIN + 1 {
+    m.step().unwrap();
+  }
+  assert_eq!(m.read_reg(Reg::R0), 0x4000);
+  out.output()
+}
+
+#[test]
+fn putsp_full_word_terminator() {
+  for &os in &[false, true] {
+    assert_eq!(putsp(&[0x6968, 0x0000], os), "hi"); // "hi"
+  }
+}
+
+#[test]
+fn putsp_null_in_high_byte() {
+  for &os in &[false, true] {
+    assert_eq!(putsp(&[0x6568, 0x6C6C, 0x006F, 0x2121], os), "hello");
+  }
+}
+
+#[test]
+fn putsp_null_in_low_byte() {
+  for &os in &[false, true] {
+    assert_eq!(putsp(&[0x6968, 0x2100, 0x0000], os), "hi");
+  }
+}
@@ -0,0 +1,53 @@
+extern crate lc3;
+
+use lc3::controller::{Controller, RunState};
+use lc3::encode::*;
+use lc3::{Machine, NullConsole, StopReason, PC};
+
+// counts R0 up forever
+fn spin() -> Machine {
+  Machine::builder().io(Box::new(NullConsole)).load(&[add_imm(0, 0, 1), br(true, true, true, -2)]).build()
+}
+
+#[test]
+fn pause_resume_stop() {
+  let mut ctl = Controller::spawn(spin);
+  ctl.pause();
+  assert_eq!(ctl.state(), RunState::Paused);
+  let snap = ctl.snapshot().unwrap();
+  assert!(snap.reg[PC as usize] == 0x3000 || snap.reg[PC as usize] == 0x3001);
+  let steps: u64 = ctl.steps();
+  assert!(steps > 0);
+  // nothing runs while paused
+  assert_eq!(ctl.steps(), steps);
+  assert_eq!(ctl.snapshot().unwrap().reg[0], snap.reg[0]);
+
+  ctl.resume();
+  while ctl.steps() < steps + 10_000 {}
+  ctl.pause();
+  assert!(ctl.snapshot().unwrap().reg[0] != snap.reg[0]);
+
+  ctl.resume();
+  let last = ctl.stop().unwrap();
+  assert_eq!(ctl.state(), RunState::Finished);
+  assert!(last.reg[0] != snap.reg[0]);
+}
+
+#[test]
+fn stops_on_its_own() {
+  let ctl = Controller::spawn(|| {
+    let mut m = Machine::builder().io(Box::new(NullConsole)).load(&[add_imm(0, 0, 1), nop(), add_imm(0, 0, 1), halt()]).build();
+    m.add_breakpoint(0x3002);
+    m
+  });
+  assert_eq!(ctl.wait(), RunState::Stopped(StopReason::Breakpoint(0x3002)));
+  assert_eq!(ctl.snapshot().unwrap().reg[0], 1);
+  // pausing a stopped machine does nothing
+  ctl.pause();
+  assert_eq!(ctl.state(), RunState::Stopped(StopReason::Breakpoint(0x3002)));
+
+  ctl.resume();
+  assert_eq!(ctl.wait(), RunState::Stopped(StopReason::Halt));
+  assert_eq!(ctl.snapshot().unwrap().reg[0], 2);
+  assert_eq!(ctl.steps(), 4);
+}
@@ -0,0 +1,76 @@
+extern crate lc3;
+
+use lc3::assembler::{self, Program};
+use lc3::harness::TestCase;
+use lc3::{Coverage, Machine, NullConsole};
+
+const SRC: &str = "\
+.ORIG x3000
+      LD R1, N
+LOOP  ADD R1, R1, #-1
+      BRz DONE
+      BRnzp LOOP
+      ST R1, N
+DONE  ST R1, OUTV
+      HALT
+N     .FILL #3
+OUTV  .BLKW 1
+.END";
+
+fn run(src: &str) -> (Program, Coverage) {
+  let prog: Program = assembler::assemble(src).unwrap();
+  let mut m = Machine::builder().io(Box::new(NullConsole)).build();
+  m.load_image_bytes(&prog.to_obj()).unwrap();
+  m.start_coverage();
+  m.run();
+  (prog, m.stop_coverage().unwrap())
+}
+
+#[test]
+fn marks_executed_read_and_written() {
+  let (_, cov) = run(SRC);
+  assert!((0x3000..=0x3003).all(|a| cov.executed(a)));
+  assert!(!cov.executed(0x3004));
+  assert!(cov.executed(0x3005) && cov.executed(0x3006));
+  assert!(cov.read(0x3007) && !cov.written(0x3007));
+  assert!(cov.written(0x3008) && !cov.read(0x3008));
+  assert!(!cov.executed(0x3007));
+  assert_eq!(cov.executed_count(), 6);
+}
+
+#[test]
+fn only_records_while_started() {
+  let mut m = Machine::builder().io(Box::new(NullConsole)).load(&[0x1261, 0xF025]).build();
+  assert!(m.coverage().is_none());
+  m.run();
+  assert!(m.stop_coverage().is_none());
+}
+
+#[test]
+fn listing_marks_unexecuted_lines() {
+  let (prog, cov) = run(SRC);
+  let listing: String = cov.listing(&prog, SRC);
+  let lines: Vec<&str> = listing.lines().collect();
+  assert_eq!(lines[0], "                 | .ORIG x3000");
+  assert_eq!(lines[2], "      x--  x3001 | LOOP  ADD R1, R1, #-1");
+  assert_eq!(lines[5], "##### ---  x3004 |       ST R1, N");
+  assert_eq!(lines[8], "      -r-  x3007 | N     .FILL #3");
+  assert_eq!(lines[9], "      --w  x3008 | OUTV  .BLKW 1");
+  assert_eq!(listing.matches("#####").count(), 1);
+  assert!(listing.ends_with("\n6 of 7 instructions executed\n"));
+  assert_eq!(cov.instructions(&prog, SRC), (6, 7));
+}
+
+#[test]
+fn merges_test_suite_runs() {
+  let src: &str = ".ORIG x3000\nADD R0, R0, #0\nBRp POS\nAND R1, R1, #0\nHALT\nPOS ADD R1, R1, #1\nHALT\n.END";
+  let prog: Program = assembler::assemble(src).unwrap();
+  let zero = TestCase::new("zero").run(&prog);
+  let one = TestCase::new("one").reg(lc3::Reg::R0, 1).run(&prog);
+  assert_eq!(zero.coverage.instructions(&prog, src), (4, 6));
+  assert_eq!(one.coverage.instructions(&prog, src), (4, 6));
+
+  let mut total: Coverage = zero.coverage.clone();
+  total.merge(&one.coverage);
+  assert_eq!(total.instructions(&prog, src), (6, 6));
+}
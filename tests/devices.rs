@@ -0,0 +1,298 @@
+extern crate lc3;
+
+use std::cell::RefCell;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+use lc3::assembler;
+use lc3::{Device, Frame, Framebuffer, InputScript, Machine, NullConsole, Random, Reg, Snapshot, StopReason, Timer, MCR, RNG, TMR_READY};
+
+mod common;
+
+fn machine(src: &str) -> Machine {
+  common::load(common::builder().supervisor(true), src)
+}
+
+// a register at xFE10 remembering what was stored to it, plus one; the
+// log is shared with the test
+struct Latch {
+  val: u16,
+  log: Rc<RefCell<Vec<u16>>>,
+}
+
+impl Device for Latch {
+  fn addr_range(&self) -> RangeInclusive<u16> {
+    0xFE10..=0xFE10
+  }
+
+  fn read(&mut self, _addr: u16) -> u16 {
+    self.val
+  }
+
+  fn write(&mut self, _addr: u16, val: u16) {
+    self.log.borrow_mut().push(val);
+    self.val = val.wrapping_add(1);
+  }
+}
+
+#[test]
+fn loads_and_stores_reach_the_device() {
+  let mut m = machine("\
+.ORIG x3000
+      AND R0, R0, #0
+      ADD R0, R0, #7
+      STI R0, LATCH
+      LDI R1, LATCH
+      HALT
+LATCH .FILL xFE10
+.END");
+  let log: Rc<RefCell<Vec<u16>>> = Rc::new(RefCell::new(Vec::new()));
+  m.add_device(Box::new(Latch { val: 0, log: log.clone() }));
+  assert_eq!(m.run().reason, StopReason::Halt);
+  assert_eq!(*log.borrow(), vec![7]);
+  assert_eq!(m.read_reg(Reg::R1), 8);
+  // memory behind the device is untouched
+  assert_eq!(m.read_mem(0xFE10), 0);
+}
+
+// shadows the machine control register, so HALT's store doesn't stop the
+// machine
+struct StuckMcr;
+
+impl Device for StuckMcr {
+  fn addr_range(&self) -> RangeInclusive<u16> {
+    MCR..=MCR
+  }
+
+  fn read(&mut self, _addr: u16) -> u16 {
+    0x8000
+  }
+
+  fn write(&mut self, _addr: u16, _val: u16) {}
+}
+
+#[test]
+fn devices_take_over_built_in_registers() {
+  let mut m = machine("\
+.ORIG x3000
+      AND R0, R0, #0
+      STI R0, PMCR
+      LDI R1, PMCR
+      HALT
+PMCR  .FILL xFFFE
+.END");
+  m.add_device(Box::new(StuckMcr));
+  assert_eq!(m.run_for(3).reason, StopReason::StepLimit);
+  assert!(!m.halt);
+  assert_eq!(m.read_reg(Reg::R1), 0x8000);
+}
+
+// the keyboard and display registers, answering loads with x0041
+struct Terminal(Rc<RefCell<Vec<u16>>>);
+
+impl Device for Terminal {
+  fn addr_range(&self) -> RangeInclusive<u16> {
+    0xFE00..=0xFE06
+  }
+
+  fn read(&mut self, _addr: u16) -> u16 {
+    0x0041
+  }
+
+  fn write(&mut self, addr: u16, val: u16) {
+    self.0.borrow_mut().extend([addr, val]);
+  }
+}
+
+#[test]
+fn devices_replace_the_keyboard_and_display() {
+  let mut m = machine("\
+.ORIG x3000
+      LDI R0, PKBDR
+      STI R0, PKBSR
+      STI R0, PDDR
+      HALT
+PKBSR .FILL xFE00
+PKBDR .FILL xFE02
+PDDR  .FILL xFE06
+.END");
+  m.set_input_script(&InputScript::new().text("z"));
+  let log: Rc<RefCell<Vec<u16>>> = Rc::new(RefCell::new(Vec::new()));
+  m.add_device(Box::new(Terminal(log.clone())));
+  assert_eq!(m.run_for(3).reason, StopReason::StepLimit);
+  assert_eq!(m.read_reg(Reg::R0), 0x0041);
+  assert_eq!(*log.borrow(), vec![0xFE00, 0x0041, 0xFE06, 0x0041]);
+  // the built-in keyboard was never polled for the key
+  assert!(m.typing());
+}
+
+#[test]
+fn timer_interrupts() {
+  let mut m = machine("\
+.ORIG x3000
+      LD R1, IVAL
+      STI R1, TMRI
+      LD R1, IE
+      STI R1, TMRS
+LOOP  ADD R2, R2, #1
+      BRnzp LOOP
+IVAL  .FILL #5
+IE    .FILL x4000
+TMRS  .FILL xFE08
+TMRI  .FILL xFE0A
+.END");
+  let handler = assembler::assemble(".ORIG x4000\nADD R3, R3, #1\nRTI\n.END").unwrap();
+  m.load_image_bytes(&handler.to_obj()).unwrap();
+  m.write_reg(Reg::PC, 0x3000);
+  m.write_mem(0x0181, 0x4000);
+  m.add_device(Box::new(Timer::default()));
+
+  // counting from the store to the interval register, the timer fires
+  // before instructions 7, 12, 17 and 22, handlers included
+  m.run_for(25);
+  assert_eq!(m.read_reg(Reg::R3), 4);
+  assert_eq!(m.read_reg(Reg::PC) & 0xF000, 0x3000);
+}
+
+#[test]
+fn timer_polling() {
+  // counts loop iterations until the timer is ready
+  let src: &str = "\
+.ORIG x3000
+      LD R1, IVAL
+      STI R1, TMI
+WAIT  ADD R2, R2, #1
+      LDI R0, TMR
+      BRzp WAIT
+      HALT
+IVAL  .FILL #9
+TMR   .FILL xFE08
+TMI   .FILL xFE0A
+.END";
+  let mut m: Machine = common::load(common::builder().supervisor(true).device(Box::new(Timer::default())), src);
+  assert_eq!(m.run().reason, StopReason::Halt);
+  // ready before the LDI of the fourth iteration, reading cleared it
+  assert_eq!(m.read_reg(Reg::R2), 4);
+  assert_eq!(m.read_reg(Reg::R0), TMR_READY);
+}
+
+#[test]
+fn masked_timer_interrupts_stay_pending_once() {
+  let mut m = machine(".ORIG x3000\nLOOP BRnzp LOOP\n.END");
+  let mut timer = Timer::default();
+  timer.write(0xFE0A, 1);
+  timer.write(0xFE08, 0x4000);
+  m.add_device(Box::new(timer));
+  // running at priority 7 keeps the interrupt from being taken
+  m.set_psr(0x0700 | 0x0002);
+  m.run_for(50);
+  assert_eq!(m.read_reg(Reg::PC), 0x3000);
+  assert_eq!(m.snapshot().pending.len(), 1);
+}
+
+#[test]
+#[should_panic(expected = "outside of the I/O page")]
+fn devices_live_in_the_io_page() {
+  let mut m = Machine::builder().io(Box::new(NullConsole)).supervisor(true).build();
+  m.add_device(Box::new(Timer::new(0x4000, 0x81, 4)));
+}
+
+#[test]
+fn framebuffer() {
+  // lights the top left pixel and the one below it
+  let mut m = machine("\
+.ORIG x3000
+      LD R1, VIDEO
+      AND R0, R0, #0
+      ADD R0, R0, #-1
+      STR R0, R1, #0
+      LD R2, ROW
+      ADD R1, R1, R2
+      STR R0, R1, #0
+      HALT
+VIDEO .FILL xC000
+ROW   .FILL #128
+.END");
+  assert_eq!(m.run().reason, StopReason::Halt);
+
+  let frame: Frame = Framebuffer::default().frame(&m);
+  assert_eq!((frame.width, frame.height, frame.pixels.len()), (128, 124, 128 * 124));
+  assert!(frame.lit(0, 0) && frame.lit(0, 1) && !frame.lit(1, 0));
+  assert_eq!(frame.get(0, 1), 0xFFFF);
+  assert_eq!(Framebuffer::default().addr(127, 123), 0xFDFF);
+
+  let small: Frame = Framebuffer::new(0xC000, 3, 1).frame(&m);
+  assert_eq!(small.to_string(), "#..\n");
+  assert_eq!(small.to_pbm(), "P1\n3 1\n1 0 0\n");
+}
+
+#[test]
+fn random_numbers() {
+  // three words from the generator, reseeded before the third
+  let src: &str = "\
+.ORIG x3000
+      LDI R0, PRNG
+      LDI R1, PRNG
+      AND R3, R3, #0
+      STI R3, PRNG
+      LDI R2, PRNG
+      HALT
+PRNG  .FILL xFE0C
+.END";
+  let run = |seed: u16| {
+    let mut m: Machine = common::load(common::builder().supervisor(true).device(Box::new(Random::new(RNG, seed))), src);
+    assert_eq!(m.run().reason, StopReason::Halt);
+    (m.read_reg(Reg::R0), m.read_reg(Reg::R1), m.read_reg(Reg::R2))
+  };
+
+  let (a, b, c) = run(0);
+  assert_ne!(a, b);
+  // seeding with 0 restarts the default sequence
+  assert_eq!(c, a);
+  assert_eq!(run(0), (a, b, c));
+  assert_ne!(run(1).0, a);
+
+  let mut rng = Random::default();
+  assert_eq!(rng.next_word(), a);
+  assert_eq!(rng.next_word(), b);
+}
+
+#[test]
+fn mcr_clock_enable() {
+  // the OS's HALT sequence: clear bit 15, keeping the rest
+  let mut m = machine("\
+.ORIG x3000
+      LD R0, BITS
+      STI R0, PMCR
+      LDI R1, PMCR
+      AND R0, R1, R2
+      STI R0, PMCR
+      LDI R3, PMCR
+      HALT
+BITS  .FILL x8123
+PMCR  .FILL xFFFE
+.END");
+  m.write_reg(Reg::R2, 0x7FFF);
+  assert_eq!(m.run().reason, StopReason::Halt);
+  assert_eq!(m.read_reg(Reg::R1), 0x8123);
+  assert_eq!(m.read_reg(Reg::PC), 0x3005);
+
+  // restarting sets the bit again
+  m.halt = false;
+  assert_eq!(m.step(), Ok(None));
+  assert_eq!(m.read_reg(Reg::R3), 0x8123);
+}
+
+#[test]
+fn step_back_over_halt() {
+  // MCR is written by the program and by the OS's HALT routine, and
+  // stepping back puts it back
+  let src: &str = ".ORIG x3000\nLD R0, BITS\nSTI R0, PMCR\nHALT\nBITS .FILL x8004\nPMCR .FILL xFFFE\n.END";
+  let mut m: Machine = common::load(common::builder().os(true).supervisor(true), src);
+  m.enable_history(1000);
+  let start: Snapshot = m.snapshot();
+  assert_eq!(m.run().reason, StopReason::Halt);
+  assert_eq!(m.snapshot().mem[MCR as usize], 0x0004);
+  while m.step_back() {}
+  assert!(m.snapshot() == start);
+}
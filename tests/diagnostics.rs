@@ -0,0 +1,156 @@
+extern crate lc3;
+
+use lc3::assembler::{self, AsmError, Diagnostic, Lint, Span};
+
+fn errors(src: &str) -> Vec<Diagnostic> {
+  assembler::assemble(src).unwrap_err().diagnostics
+}
+
+fn cols(start: u32, end: u32) -> Option<Span> {
+  Some(Span { start, end })
+}
+
+#[test]
+fn spans_and_suggestions() {
+  // every line's parse errors are reported, but not the next pass's
+  let src: &str = ".ORIG x3000\nLOOP ADDD R1, R1, #1\n  BRp NOWHERE\nX .FIL 5\n  TRAPP x25\n  .STRINGZ \"oops\n.END";
+  let ds: Vec<Diagnostic> = errors(src);
+  let found: Vec<(usize, Option<Span>, &str, Option<&str>)> = ds.iter()
+    .map(|d| (d.line, d.cols, d.msg.as_str(), d.help.as_deref()))
+    .collect();
+  assert_eq!(found, vec![
+    (2, cols(5, 9), "unknown opcode `ADDD`", Some("did you mean `ADD`?")),
+    (4, cols(2, 6), "unknown opcode `.FIL`", Some("did you mean `.FILL`?")),
+    (5, cols(2, 7), "unknown opcode `TRAPP`", Some("did you mean `TRAP`?")),
+    (6, cols(11, 16), "unterminated string", None),
+  ]);
+  assert_eq!(ds[0].text, "LOOP ADDD R1, R1, #1");
+
+  // the first is also the error's
+  let e: AsmError = assembler::assemble(src).unwrap_err();
+  assert_eq!((e.line, e.to_string()), (2, "line 2: unknown opcode `ADDD`".to_string()));
+
+  let ds: Vec<Diagnostic> = errors(".ORIG x3000\nLOOP ADD R1, R1, #99\n  BRp LOPP\n  LD R0, R9\n  JSR\n  NOT R1, R2, R3\nLOOP HALT\n.END");
+  assert_eq!((ds[0].line, ds[0].cols, ds[0].msg.as_str()), (7, cols(0, 4), "duplicate label `LOOP`"));
+  assert_eq!(ds.len(), 1);
+
+  let ds: Vec<Diagnostic> = errors(".ORIG x3000\nLOOP ADD R1, R1, #99\n  BRp LOPP\n  LD R0, R9\n  JSR\n  NOT R1, R2, R3\n.END");
+  let found: Vec<(usize, Option<Span>, &str, Option<&str>)> = ds.iter()
+    .map(|d| (d.line, d.cols, d.msg.as_str(), d.help.as_deref()))
+    .collect();
+  assert_eq!(found, vec![
+    (2, cols(17, 20), "immediate 99 does not fit in 5 bits", None),
+    (3, cols(6, 10), "undefined label `LOPP`", Some("did you mean `LOOP`?")),
+    (4, cols(9, 11), "undefined label `R9`", Some("the registers are R0 to R7")),
+    (5, cols(2, 5), "expected 1 operands, found 0", None),
+    (6, cols(14, 16), "expected 2 operands, found 3", None),
+  ]);
+
+  // labels that aren't ASCII
+  let ds: Vec<Diagnostic> = errors(".ORIG x3000\n  LD R0, é\n  .FILL Rö\n.END");
+  let found: Vec<(usize, &str)> = ds.iter().map(|d| (d.line, d.msg.as_str())).collect();
+  assert_eq!(found, vec![(2, "undefined label `é`"), (3, "undefined label `Rö`")]);
+  assert!(ds.iter().all(|d| d.help.is_none()));
+
+  // strings are ASCII and fit in memory
+  let long: String = format!(".ORIG x0000\n  .STRINGZ \"{}\"\n.END", "a".repeat(0xFFFF));
+  let ds: Vec<Diagnostic> = errors(".ORIG x3000\n  .STRINGZ \"naïve\"\n  .STRINGZ \"😀\"\n.END");
+  let found: Vec<(usize, Option<Span>, &str)> = ds.iter().map(|d| (d.line, d.cols, d.msg.as_str())).collect();
+  assert_eq!(found, vec![
+    (2, cols(11, 18), "non-ASCII character `ï` in string"),
+    (3, cols(11, 14), "non-ASCII character `😀` in string"),
+  ]);
+  assert_eq!(errors(&long)[0].msg, "string does not fit in memory");
+}
+
+#[test]
+fn rendering() {
+  let d: Diagnostic = errors(".ORIG x3000\nX .FIL 5\n.END").remove(0);
+  assert_eq!(d.render("prog.asm"), "\
+prog.asm:2:3: unknown opcode `.FIL`
+    2 | X .FIL 5
+      |   ^^^^ did you mean `.FILL`?
+");
+  assert_eq!(d.to_json().to_string(),
+    r#"{"line":2,"column":3,"endColumn":7,"file":null,"message":"unknown opcode `.FIL`","help":"did you mean `.FILL`?"}"#);
+
+  // in an included file the excerpt is of its line
+  let mut include = |_: Option<&str>, name: &str| Ok((name.to_string(), "HALT\n  ADD R1, R1".to_string()));
+  let e: AsmError = assembler::assemble_with(".ORIG x3000\n.INCLUDE \"lib.asm\"\n.END", Some("prog.asm"), &mut include).unwrap_err();
+  assert_eq!(e.msg, "lib.asm:2: expected 3 operands, found 2");
+  assert_eq!(e.diagnostics[0].render("prog.asm"), "\
+prog.asm:2: lib.asm:2:3: expected 3 operands, found 2
+      |   ADD R1, R1
+      |   ^^^
+");
+}
+
+#[test]
+fn lines_with_errors() {
+  // every line that parses, for editors, next to the errors of the rest
+  let lines = assembler::parse_lines(".ORIG x3000\n\nLOOP ADDD R1, R1, #1\nDONE HALT ; bye\n.END");
+  assert_eq!(lines.len(), 4);
+  assert_eq!(lines[1].as_ref().unwrap_err().msg, "unknown opcode `ADDD`");
+  let done = lines[2].as_ref().unwrap();
+  assert_eq!((done.num, done.label.as_deref(), done.op.as_deref()), (4, Some("DONE"), Some("HALT")));
+  assert_eq!(done.spans.label, cols(0, 4));
+}
+
+fn lints(src: &str) -> Vec<(usize, &'static str, String)> {
+  let mut include = |_: Option<&str>, _: &str| Err(String::new());
+  assembler::lint(src, None, &mut include).unwrap().into_iter()
+    .map(|l| (l.diagnostic.line, l.kind.name(), l.diagnostic.msg))
+    .collect()
+}
+
+#[test]
+fn lints_of_programs() {
+  let src: &str = "\
+.ORIG x3000
+  LD R1, TEN
+  LD R0, CHAR
+  JSR PRINT
+  ADD R1, R1, R0
+  BR SKIP
+  ADD R2, R2, #1
+SKIP BRz TEN
+  ADD R3, R3, #1
+TEN .FILL #10
+CHAR .FILL x41
+PRINT ADD R1, R0, #0
+  OUT
+  ST R2, SAVE
+  AND R2, R2, #0
+  LD R2, SAVE
+  RET
+SAVE .BLKW 1
+.END";
+  assert_eq!(lints(src), vec![
+    (1, "missing-halt", "no HALT is reachable from the start of the program".to_string()),
+    (4, "clobbered-register", "`PRINT` overwrites R1, which is set before this call and read after it".to_string()),
+    (7, "unreachable", "unreachable instruction".to_string()),
+    (8, "branch-to-data", "branch to `TEN`, which is data".to_string()),
+    (10, "fall-into-data", "execution goes on into data".to_string()),
+  ]);
+
+  // arguments, saved registers and labelled code after a branch are fine
+  let clean: &str = "\
+.ORIG x3000
+  LD R0, N
+  JSR DOUBLE
+  ADD R1, R0, #0
+  BRz DONE
+  BR NEXT
+NEXT OUT
+DONE HALT
+N .FILL #2
+DOUBLE ADD R0, R0, R0
+  RET
+.END";
+  assert_eq!(lints(clean), vec![]);
+
+  let mut include = |_: Option<&str>, _: &str| Err(String::new());
+  let lint: Lint = assembler::lint(".ORIG x3000\nBR X\nX .FILL 1\n.END", None, &mut include).unwrap().remove(1);
+  assert_eq!(lint.render("p.asm"), "p.asm:2:4: warning[branch-to-data]: branch to `X`, which is data\n    2 | BR X\n      |    ^\n");
+  assert_eq!(lint.to_json().get("lint").and_then(|l| l.as_str()), Some("branch-to-data"));
+}
@@ -0,0 +1,120 @@
+// differential tests: random user-mode programs run on the Machine and on
+// the independent interpreter in oracle.rs, comparing state after every
+// instruction
+extern crate lc3;
+
+mod oracle;
+
+use lc3::{Machine, MachineError, NullConsole, Reg};
+
+use oracle::Oracle;
+
+const CASES: u64 = 1000;
+const PROGRAM: usize = 48;
+const STEPS: usize = 200;
+
+// opcodes the oracle implements: everything but RTI, the reserved opcode
+// and TRAP
+const OPCODES: [u16; 13] = [0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0x9, 0xA, 0xB, 0xC, 0xE];
+
+const GPRS: [Reg; 8] = [Reg::R0, Reg::R1, Reg::R2, Reg::R3, Reg::R4, Reg::R5, Reg::R6, Reg::R7];
+
+// xorshift64*, reproducible from the case number
+struct Rng(u64);
+
+impl Rng {
+  fn new(seed: u64) -> Rng {
+    Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+  }
+
+  fn next(&mut self) -> u64 {
+    self.0 ^= self.0 >> 12;
+    self.0 ^= self.0 << 25;
+    self.0 ^= self.0 >> 27;
+    self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+  }
+
+  fn below(&mut self, n: u64) -> u64 {
+    self.next() % n
+  }
+
+  fn word(&mut self) -> u16 {
+    self.next() as u16
+  }
+}
+
+fn instruction(rng: &mut Rng) -> u16 {
+  let op: u16 = OPCODES[rng.below(OPCODES.len() as u64) as usize];
+  let mut word: u16 = op << 12 | (rng.word() & 0x0FFF);
+  // keep most PC-relative loads, stores and branches near the program so
+  // they read and write code and each other's data
+  if matches!(op, 0x0 | 0x2 | 0x3 | 0xA | 0xB | 0xE) && rng.below(4) != 0 {
+    word = (word & !0x1FF) | (rng.word() & 0x3F);
+    if rng.below(2) == 0 {
+      word |= 0x1C0; // small negative offset
+    }
+  }
+  word
+}
+
+// a register value: small, a pointer into the program, or anything
+fn value(rng: &mut Rng) -> u16 {
+  match rng.below(3) {
+    0 => rng.word() & 0xF,
+    1 => 0x3000 + (rng.word() & 0x3F),
+    _ => rng.word(),
+  }
+}
+
+fn compare(case: u64, step: usize, m: &Machine, o: &Oracle) {
+  for (i, &r) in GPRS.iter().enumerate() {
+    assert_eq!(m.read_reg(r), o.reg[i], "case {} step {}: R{}", case, step, i);
+  }
+  assert_eq!(m.read_reg(Reg::PC), o.pc, "case {} step {}: PC", case, step);
+  assert_eq!(m.read_reg(Reg::COND), o.cc, "case {} step {}: CC", case, step);
+}
+
+fn run_case(case: u64) {
+  let mut rng = Rng::new(case);
+  let program: Vec<u16> = (0..PROGRAM).map(|_| instruction(&mut rng)).collect();
+
+  let mut m = Machine::builder().load(&program).io(Box::new(NullConsole)).build();
+  m.set_cc_audit(true);
+  let mut o = Oracle::new();
+  o.mem[0x3000..0x3000 + PROGRAM].copy_from_slice(&program);
+  for (i, &r) in GPRS.iter().enumerate() {
+    let v: u16 = value(&mut rng);
+    m.write_reg(r, v);
+    o.reg[i] = v;
+  }
+
+  compare(case, 0, &m, &o);
+  for step in 1..=STEPS {
+    // stop before anything the oracle can't model
+    let op: u16 = o.mem[o.pc as usize] >> 12;
+    if matches!(op, 0x8 | 0xD | 0xF) || !oracle::accessible(o.pc) {
+      break;
+    }
+    // where the oracle refuses an access the machine faults on it
+    if let Err(addr) = o.step() {
+      assert_eq!(m.step(), Err(MachineError::AccessViolation { pc: o.pc, addr }), "case {} step {}", case, step);
+      break;
+    }
+    match m.step() {
+      Ok(None) => {},
+      r => panic!("case {} step {}: machine stopped with {:?}", case, step, r),
+    }
+    compare(case, step, &m, &o);
+  }
+
+  for addr in 0..oracle::DEVICES {
+    assert_eq!(m.read_mem(addr), o.mem[addr as usize], "case {}: memory at {:#06x}", case, addr);
+  }
+}
+
+#[test]
+fn random_programs() {
+  for case in 0..CASES {
+    run_case(case);
+  }
+}
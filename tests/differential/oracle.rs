@@ -0,0 +1,101 @@
+// a second, deliberately plain LC-3 interpreter for user-mode code. It
+// shares nothing with the crate: decoding, sign extension and condition
+// codes are all redone here from the ISA description.
+
+pub const N: u16 = 0b100;
+pub const Z: u16 = 0b010;
+pub const P: u16 = 0b001;
+
+// start of the memory-mapped device registers, which the oracle doesn't
+// model
+pub const DEVICES: u16 = 0xFE00;
+
+// user-mode code may only touch USER..DEVICES, anything else is an access
+// violation
+pub const USER: u16 = 0x3000;
+
+pub fn accessible(addr: u16) -> bool {
+  (USER..DEVICES).contains(&addr)
+}
+
+pub struct Oracle {
+  pub reg: [u16; 8],
+  pub pc: u16,
+  pub cc: u16,
+  pub mem: Vec<u16>,
+}
+
+fn sext(word: u16, bits: u32) -> u16 {
+  let shift: u32 = 16 - bits;
+  (((word << shift) as i16) >> shift) as u16
+}
+
+impl Oracle {
+  pub fn new() -> Oracle {
+    Oracle { reg: [0; 8], pc: 0x3000, cc: Z, mem: vec![0; 1 << 16] }
+  }
+
+  fn set(&mut self, r: usize, val: u16) {
+    self.reg[r] = val;
+    self.cc = if val == 0 { Z } else if val & 0x8000 != 0 { N } else { P };
+  }
+
+  fn load(&self, addr: u16) -> Result<u16, u16> {
+    if !accessible(addr) { Err(addr) } else { Ok(self.mem[addr as usize]) }
+  }
+
+  fn store(&mut self, addr: u16, val: u16) -> Result<(), u16> {
+    if !accessible(addr) {
+      return Err(addr);
+    }
+    self.mem[addr as usize] = val;
+    Ok(())
+  }
+
+  // executes one instruction. Err(addr) when it would touch addr outside
+  // user space, before any state changes; TRAP, RTI and the reserved opcode
+  // aren't supported and panic.
+  pub fn step(&mut self) -> Result<(), u16> {
+    let ir: u16 = self.mem[self.pc as usize];
+    let npc: u16 = self.pc.wrapping_add(1);
+    let dr: usize = ((ir >> 9) & 7) as usize;
+    let sr1: usize = ((ir >> 6) & 7) as usize;
+    let second: u16 = if ir & 0x20 != 0 { sext(ir, 5) } else { self.reg[(ir & 7) as usize] };
+    let pc9: u16 = npc.wrapping_add(sext(ir, 9));
+    let base6: u16 = self.reg[sr1].wrapping_add(sext(ir, 6));
+
+    match ir >> 12 {
+      0b0001 => { let v: u16 = self.reg[sr1].wrapping_add(second); self.set(dr, v); self.pc = npc; },
+      0b0101 => { let v: u16 = self.reg[sr1] & second; self.set(dr, v); self.pc = npc; },
+      0b1001 => { let v: u16 = !self.reg[sr1]; self.set(dr, v); self.pc = npc; },
+      0b0000 => {
+        let taken: bool = (ir >> 9) & self.cc != 0;
+        self.pc = if taken { pc9 } else { npc };
+      },
+      0b1100 => self.pc = self.reg[sr1],
+      0b0100 => {
+        let target: u16 = if ir & 0x800 != 0 { npc.wrapping_add(sext(ir, 11)) } else { self.reg[sr1] };
+        self.reg[7] = npc;
+        self.pc = target;
+      },
+      0b0010 => { let v: u16 = self.load(pc9)?; self.set(dr, v); self.pc = npc; },
+      0b1010 => {
+        let ptr: u16 = self.load(pc9)?;
+        let v: u16 = self.load(ptr)?;
+        self.set(dr, v);
+        self.pc = npc;
+      },
+      0b0110 => { let v: u16 = self.load(base6)?; self.set(dr, v); self.pc = npc; },
+      0b1110 => { self.set(dr, pc9); self.pc = npc; },
+      0b0011 => { self.store(pc9, self.reg[dr])?; self.pc = npc; },
+      0b1011 => {
+        let ptr: u16 = self.load(pc9)?;
+        self.store(ptr, self.reg[dr])?;
+        self.pc = npc;
+      },
+      0b0111 => { self.store(base6, self.reg[dr])?; self.pc = npc; },
+      op => panic!("oracle doesn't implement opcode {:#06b}", op),
+    }
+    Ok(())
+  }
+}
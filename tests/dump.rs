@@ -0,0 +1,100 @@
+extern crate lc3;
+
+use lc3::assembler;
+use lc3::{ImageFormat, Machine, NullConsole, Reg, Region, RegionKind};
+
+#[test]
+fn dump_state() {
+  let prog = assembler::assemble(".ORIG x3000\nLD R0, N\nHALT\nN .FILL #-2\n.END").unwrap();
+  let mut m = Machine::builder().io(Box::new(NullConsole)).build();
+  m.load_image_bytes(&prog.to_obj()).unwrap();
+  m.symbols_mut().insert("N", 0x3002);
+  m.write_reg(Reg::R5, 0x8000);
+  m.step().unwrap();
+
+  assert_eq!(m.dump_state(), m.to_string());
+  assert_eq!(m.dump_state(), "\
+R0 xFFFE     -2  65534    R4 x0000      0      0
+R1 x0000      0      0    R5 x8000 -32768  32768
+R2 x0000      0      0    R6 x0000      0      0
+R3 x0000      0      0    R7 x0000      0      0
+PC x3001  PSR x8004  user  priority 0  CC N
+USP x0000  SSP x3000
+
+  x2FFD  x0000  NOP
+  x2FFE  x0000  NOP
+  x2FFF  x0000  NOP
+  x3000  x2001  LD R0, N
+> x3001  xF025  HALT
+  x3002  xFFFE  N: TRAP xFE
+  x3003  x0000  NOP
+  x3004  x0000  NOP
+  x3005  x0000  NOP
+  x3006  x0000  NOP
+");
+}
+
+#[test]
+fn dump_image() {
+  let prog = assembler::assemble(".ORIG x3000\nLD R0, N\nST R0, M\nHALT\nN .FILL x1234\nM .BLKW 1\n.END").unwrap();
+  let mut m = Machine::builder().io(Box::new(NullConsole)).build();
+  m.load_image_bytes(&prog.to_obj()).unwrap();
+  m.run();
+
+  assert_eq!(m.dump_image(0x3003..0x3005), [0x30, 0x03, 0x12, 0x34, 0x12, 0x34]);
+  assert_eq!(m.dump_image(0x3004..=0x3004), [0x30, 0x04, 0x12, 0x34]);
+  assert_eq!(m.dump_image(0xFFFF..).len(), 4);
+  assert_eq!(m.dump_image(..).len(), 2 + 2 * 0x10000);
+  assert_eq!(m.dump_image_as(0x3004..0x3005, ImageFormat::RawLittleEndian { origin: 0 }), [0x34, 0x12]);
+
+  // loads back as it was
+  let mut other = Machine::builder().io(Box::new(NullConsole)).build();
+  other.load_image_bytes(&m.dump_image(0x3000..0x3005)).unwrap();
+  for addr in 0x3000..0x3005 {
+    assert_eq!(other.read_mem(addr), m.read_mem(addr));
+  }
+}
+
+#[test]
+fn regions_and_views() {
+  let prog = assembler::assemble(".ORIG x3000\nLEA R0, MSG\nHALT\nN .FILL #-5\n.FILL x41\nMSG .STRINGZ \"Hi\\n\"\n.END").unwrap();
+  let mut m = Machine::builder().io(Box::new(NullConsole)).build();
+  m.load_image_bytes(&prog.to_obj()).unwrap();
+  m.symbols_mut().extend(&prog.symbols);
+  m.annotate(0x3000..0x3002, RegionKind::Code, "main");
+  m.annotate(0x3002.., RegionKind::Data, "vars");
+  m.add_region(Region::parse("x4000..x4002:stack").unwrap());
+  m.write_reg(Reg::R6, 0x4001);
+  assert_eq!(m.region_at(0x3001).map(|r| r.name.as_str()), Some("main"));
+  assert_eq!(m.region_at(0x2FFF), None);
+  assert_eq!(m.regions()[2].to_string(), "(stack) x4000..x4001");
+
+  // the same name replaces a region, later ones win where they overlap
+  m.annotate(0x3002..0x3008, RegionKind::Data, "vars");
+  m.annotate(0x3003..0x3004, RegionKind::Device, "");
+  assert_eq!(m.regions().len(), 4);
+  assert_eq!(m.region_at(0x3003).unwrap().kind, RegionKind::Device);
+  assert!(m.remove_region("main"));
+  assert!(!m.remove_region("main"));
+  m.annotate(0x3000..0x3002, RegionKind::Code, "main");
+
+  assert_eq!(m.as_i16_slice(0x3002..=0x3003), vec![-5, 0x41]);
+  assert_eq!(m.read_string(0x3004), "Hi\n");
+  assert_eq!(m.dump_text(0x3000..0x3009), "\
+; main (code) x3000..x3001
+x3000  xE003  LEA R0, MSG
+x3001  xF025  HALT
+; vars (data) x3002..x3007
+x3002  N: xFFFB     -5
+; (device) x3003..x3003
+x3003  x0041
+; vars (data) x3002..x3007
+x3004  MSG: \"Hi\\n\"
+x3008  x0000  NOP
+");
+  assert_eq!(m.dump_text(0x4000..0x4002), "; (stack) x4000..x4001\nx4000  x0000\nx4001  x0000  <- R6\n");
+
+  for bad in &["x4000:stack", "x4000..x3000:code", "x4000..:heap", "..x10:data"] {
+    assert!(Region::parse(bad).is_err(), "{}", bad);
+  }
+}
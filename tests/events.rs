@@ -0,0 +1,107 @@
+extern crate lc3;
+
+use std::sync::mpsc;
+
+use lc3::{Event, MachineError, StopReason};
+
+mod common;
+use common::machine;
+
+const HI: &str = "\
+.ORIG x3000
+      LD R0, H
+      OUT
+      HALT
+H     .FILL x68
+.END";
+
+
+#[test]
+fn iterates_until_halt() {
+  let mut m = machine(HI);
+  let events: Vec<Event> = m.events().collect();
+  assert_eq!(events, [
+    Event::Fetched { pc: 0x3000, instr: 0x2002 },
+    Event::Fetched { pc: 0x3001, instr: 0xF021 },
+    Event::TrapEntered(0x21),
+    Event::OutputChar(b'h'),
+    Event::Fetched { pc: 0x3002, instr: 0xF025 },
+    Event::TrapEntered(0x25),
+    Event::OutputChar(b'\n'),
+    Event::OutputChar(b'H'),
+    Event::OutputChar(b'A'),
+    Event::OutputChar(b'L'),
+    Event::OutputChar(b'T'),
+    Event::OutputChar(b'\n'),
+    Event::Halted,
+  ]);
+}
+
+#[test]
+fn ends_at_a_fault_or_breakpoint() {
+  let mut m = machine(".ORIG x3000\nADD R0, R0, #1\n.FILL xD000\n.END");
+  m.add_breakpoint(0x3001);
+  assert_eq!(m.events().count(), 1);
+  let events: Vec<Event> = m.events().collect();
+  assert_eq!(events.last(), Some(&Event::Fault(MachineError::IllegalOpcode { pc: 0x3001, instr: 0xD000 })));
+}
+
+#[test]
+fn interrupts_and_exceptions() {
+  let mut m = machine(".ORIG x3000\nRTI\n.END");
+  m.write_mem(0x0100, 0x4000);
+  m.write_mem(0x4000, 0xF025);
+  let events: Vec<Event> = m.events().filter(|e| !matches!(e, Event::Fetched { .. } | Event::OutputChar(_))).collect();
+  assert_eq!(events, [Event::Interrupt(0x00), Event::TrapEntered(0x25), Event::Halted]);
+}
+
+#[test]
+fn sends_to_a_channel() {
+  let mut m = machine(HI);
+  let (tx, rx) = mpsc::channel();
+  m.send_events(tx);
+  assert_eq!(m.run_for(1).reason, StopReason::StepLimit);
+  assert_eq!(rx.try_iter().collect::<Vec<Event>>(), [Event::Fetched { pc: 0x3000, instr: 0x2002 }]);
+
+  // events() takes over while it is iterated, then hands back
+  assert_eq!(m.events().take(1).count(), 1);
+  assert_eq!(rx.try_iter().count(), 0);
+  assert_eq!(m.run().reason, StopReason::Halt);
+  assert_eq!(rx.try_iter().last(), Some(Event::Halted));
+
+  m.stop_events();
+  m.halt = false;
+  m.run_for(1);
+  assert!(rx.try_recv().is_err());
+}
+
+#[test]
+fn watch_expressions() {
+  let mut m = machine("\
+.ORIG x3000
+      LD R6, STACK
+      ADD R3, R3, #5
+      STR R3, R6, #1
+      ADD R3, R3, #0
+      HALT
+STACK .FILL x4000
+.END");
+  assert_eq!(m.add_watch("R3"), Ok(1));
+  assert_eq!(m.add_watch("MEM[SP + 1]"), Ok(2));
+  assert_eq!(m.add_watch(" MEM[x4000..x4002] "), Ok(3));
+  assert!(m.add_watch("MEM[x4000..]").is_err());
+  assert_eq!(m.watches()[2].to_string(), "MEM[x4000..x4002] = x0000 x0000");
+
+  let events: Vec<Event> = m.events().filter(|e| matches!(e, Event::WatchChanged { .. })).collect();
+  assert_eq!(events, [
+    // MEM[SP + 1] moves with R6, to x4001 which is already 0
+    Event::WatchChanged { id: 1, index: 0, value: 5 },
+    Event::WatchChanged { id: 2, index: 0, value: 5 },
+    Event::WatchChanged { id: 3, index: 1, value: 5 },
+  ]);
+  assert_eq!(m.watches().iter().map(|w| w.value.clone()).collect::<Vec<Vec<u16>>>(), [vec![5], vec![5], vec![0, 5]]);
+
+  assert!(m.remove_watch(2));
+  assert!(!m.remove_watch(2));
+  assert_eq!(m.add_watch("PC"), Ok(4));
+}
@@ -0,0 +1,98 @@
+extern crate lc3;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use lc3::assembler;
+use lc3::{Machine, NullConsole, Reg, StopReason};
+
+// copies INF to OUTF a byte at a time, leaving the byte count in R4 and the
+// handles in R5 and R6
+const COPY: &str = "\
+.ORIG x3000
+      LEA R0, INF
+      AND R1, R1, #0
+      TRAP x30
+      ADD R5, R0, #0
+      BRn DONE
+      LEA R0, OUTF
+      ADD R1, R1, #1
+      TRAP x30
+      ADD R6, R0, #0
+      BRn DONE
+      AND R4, R4, #0
+LOOP  ADD R0, R5, #0
+      TRAP x32
+      ADD R1, R0, #0
+      BRn CLOSE
+      ADD R0, R6, #0
+      TRAP x33
+      ADD R4, R4, #1
+      BRnzp LOOP
+CLOSE ADD R0, R5, #0
+      TRAP x31
+      ADD R0, R6, #0
+      TRAP x31
+DONE  HALT
+INF   .STRINGZ \"{in}\"
+OUTF  .STRINGZ \"{out}\"
+.END";
+
+fn dir(name: &str) -> PathBuf {
+  let dir: PathBuf = env::temp_dir().join(format!("lc3-files-{}-{}", name, std::process::id()));
+  let _ = fs::remove_dir_all(&dir);
+  fs::create_dir_all(&dir).unwrap();
+  dir
+}
+
+fn copy(input: &Path, output: &Path, allow: Option<&Path>) -> Machine {
+  let src: String = COPY.replace("{in}", input.to_str().unwrap()).replace("{out}", output.to_str().unwrap());
+  let prog = assembler::assemble(&src).unwrap();
+  let mut m = Machine::builder().io(Box::new(NullConsole)).build();
+  m.load_image_bytes(&prog.to_obj()).unwrap();
+  if let Some(dir) = allow {
+    m.allow_path(dir).unwrap();
+  }
+  assert_eq!(m.run_for(10_000).reason, StopReason::Halt);
+  m
+}
+
+#[test]
+fn copies_a_file() {
+  let dir: PathBuf = dir("copy");
+  fs::write(dir.join("in.txt"), "hello\n").unwrap();
+
+  let m = copy(&dir.join("in.txt"), &dir.join("out.txt"), Some(dir.as_path()));
+  assert_eq!(m.read_reg(Reg::R4), 6);
+  assert_eq!((m.read_reg(Reg::R5), m.read_reg(Reg::R6)), (0, 1));
+  // closing succeeded
+  assert_eq!(m.read_reg(Reg::R0), 0);
+  assert_eq!(fs::read_to_string(dir.join("out.txt")).unwrap(), "hello\n");
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn stays_in_the_sandbox() {
+  let dir: PathBuf = dir("sandbox");
+  let inside: PathBuf = dir.join("inside");
+  fs::create_dir_all(&inside).unwrap();
+  fs::write(dir.join("secret.txt"), "x").unwrap();
+  fs::write(inside.join("in.txt"), "x").unwrap();
+
+  // nothing is allowed by default
+  let m = copy(&inside.join("in.txt"), &inside.join("out.txt"), None);
+  assert_eq!(m.read_reg(Reg::R5), 0xFFFF);
+
+  // reading outside, directly or through ..
+  let m = copy(&dir.join("secret.txt"), &inside.join("out.txt"), Some(inside.as_path()));
+  assert_eq!(m.read_reg(Reg::R5), 0xFFFF);
+  let m = copy(&inside.join("../secret.txt"), &inside.join("out.txt"), Some(inside.as_path()));
+  assert_eq!(m.read_reg(Reg::R5), 0xFFFF);
+
+  // writing outside
+  let m = copy(&inside.join("in.txt"), &inside.join("../out.txt"), Some(inside.as_path()));
+  assert_eq!((m.read_reg(Reg::R5), m.read_reg(Reg::R6)), (0, 0xFFFF));
+  assert!(!dir.join("out.txt").exists());
+  fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,41 @@
+// the fuzz entry points over a fixed pseudo-random corpus, so they run
+// with the rest of the tests; see fuzz/ for the real fuzzing setup
+extern crate lc3;
+
+use lc3::fuzz;
+
+// xorshift64, fixed seed
+fn corpus(n: usize, len: usize) -> Vec<Vec<u8>> {
+  let mut x: u64 = 0x2545_F491_4F6C_DD1D;
+  (0..n).map(|i| {
+    (0..(i % len) + 1).map(|_| {
+      x ^= x << 13;
+      x ^= x >> 7;
+      x ^= x << 17;
+      x as u8
+    }).collect()
+  }).collect()
+}
+
+#[test]
+fn decode() {
+  let all: Vec<u8> = (0..=0xFFFFu16).flat_map(|w| w.to_be_bytes()).collect();
+  fuzz::fuzz_decode(&all);
+  fuzz::fuzz_decode(&[0xFF]);
+}
+
+#[test]
+fn assemble() {
+  for input in corpus(200, 64) {
+    fuzz::fuzz_assemble(&input);
+  }
+  fuzz::fuzz_assemble(b".ORIG xFFFF\n.BLKW 2\n.END");
+  fuzz::fuzz_assemble(b".ORIG x3000\nA .STRINGZ \"\\");
+}
+
+#[test]
+fn step() {
+  for input in corpus(500, 128) {
+    fuzz::fuzz_step(&input);
+  }
+}
@@ -0,0 +1,198 @@
+extern crate lc3;
+
+use std::time::Duration;
+
+use lc3::assembler::{self, Program};
+use lc3::harness::{self, Failure, Regex, TestCase};
+use lc3::{MachineError, Reg, StopReason};
+
+// echoes one key in upper case, and stores the sum of R1 and R2 at SUM
+const SRC: &str = "\
+.ORIG x3000
+      ADD R3, R1, R2
+      ST R3, SUM
+      GETC
+      ADD R0, R0, #-16
+      ADD R0, R0, #-16
+      OUT
+      HALT
+SUM   .FILL #0
+.END";
+
+fn program() -> Program {
+  assembler::assemble(SRC).unwrap()
+}
+
+#[test]
+fn passing_case() {
+  let report = TestCase::new("sum and echo")
+    .reg(Reg::R1, 2).reg(Reg::R2, 3)
+    .input("a")
+    .expect_reg(Reg::R3, 5)
+    .expect_mem(0x3007, 5)
+    .expect_output("A\nHALT\n")
+    .run(&program());
+  assert!(report.passed(), "{}", report);
+  assert_eq!(report.steps, 7);
+  assert_eq!(report.to_string(), "PASS sum and echo\n");
+}
+
+#[test]
+fn failing_case() {
+  let report = TestCase::new("wrong")
+    .reg(Reg::R1, 2)
+    .input("b")
+    .expect_reg(Reg::R3, 5)
+    .expect_mem(0x3007, 5)
+    .expect_output("A")
+    .run(&program());
+  assert_eq!(report.failures, vec![
+    Failure::Register { reg: Reg::R3, expected: 5, found: 2 },
+    Failure::Memory { addr: 0x3007, expected: 5, found: 2 },
+    Failure::Output { expected: "A".to_string(), found: "B\nHALT\n".to_string() },
+  ]);
+  assert_eq!(report.to_string(), "\
+FAIL wrong
+  R3 is x0002, expected x0005
+  x3007 is x0002, expected x0005
+  output \"B\\nHALT\\n\", expected \"A\"
+    - A
+    + B
+    + HALT
+    + \n");
+  assert_eq!(report.to_json().get("passed").and_then(|p| p.as_bool()), Some(false));
+}
+
+#[test]
+fn output_patterns() {
+  let run = |case: TestCase| case.reg(Reg::R1, 2).reg(Reg::R2, 3).input("a").run(&program());
+  assert!(run(TestCase::new("regex").expect_output_regex("[A-Z]\\n(HALT|STOP)\\n")).passed());
+  let report = run(TestCase::new("regex").expect_output_regex("^B$\\n.*\\n"));
+  assert_eq!(report.failures, vec![
+    Failure::OutputRegex { pattern: "^B$\\n.*\\n".to_string(), found: "A\nHALT\n".to_string() },
+  ]);
+  assert_eq!(report.failures[0].to_string(), "output \"A\\nHALT\\n\" doesn't match /^B$\\n.*\\n/");
+  assert_eq!(report.failures[0].diff(), None);
+  let report = run(TestCase::new("regex").expect_output_regex("(A"));
+  assert_eq!(report.failures[0].to_string(), "invalid regex /(A/: unclosed `(`");
+
+  let sums: Program = assembler::assemble(".ORIG x3000\nLEA R0, S\nPUTS\nHALT\nS .STRINGZ \"sum: 41.96\\nok {5}\"\n.END").unwrap();
+  let case = |template: &str| TestCase::new("template").expect_output_template(template).run(&sums);
+  assert!(case("sum: {42+-0.05}\nok {{{}}}\nHALT\n").passed());
+  assert!(case("sum: {}\nok {{{5}}}\nHALT\n").passed());
+  let report = case("sum: {42+-0.01}\nok {{{}}}\nHALT\n");
+  assert_eq!(report.failures[0].diff().unwrap(), "\
+- sum: {42+-0.01}
++ sum: 41.96
+  ok {5}
+  HALT
+  \n");
+
+  let spaced: &str = "  sum:   41.96  \n\n\tok {5}\nHALT";
+  assert!(!case(spaced).passed());
+  let report = TestCase::new("spaced").expect_output(spaced).ignore_whitespace(true).run(&sums);
+  assert!(report.passed(), "{}", report);
+  let report = TestCase::new("spaced").expect_output_template("sum: {}\nok  {{{6}}}").ignore_whitespace(true).run(&sums);
+  assert_eq!(report.failures, vec![
+    Failure::OutputTemplate { template: "sum: {}\nok {{{6}}}".to_string(), found: "sum: 41.96\nok {5}\nHALT".to_string() },
+  ]);
+}
+
+#[test]
+fn regex_scales() {
+  // output from a program printing until the step limit
+  let long: String = format!("{}HALT{}", "x".repeat(250_000), "y\n".repeat(100_000));
+  assert!(Regex::parse("x*HALT.*").unwrap().matches("HALT and more"));
+  assert!(Regex::parse("x*HALT(.|\\n)*").unwrap().matches(&long));
+  assert!(!Regex::parse("x*HALT.*").unwrap().matches(&long));
+
+  // nested repeats that backtracking takes time exponential in
+  let nested: Regex = Regex::parse("(a*)*b").unwrap();
+  assert!(!nested.matches(&"a".repeat(5_000)));
+  assert!(nested.matches(&format!("{}b", "a".repeat(5_000))));
+  assert!(Regex::parse("(a|aa)+").unwrap().matches(&"a".repeat(5_000)));
+  assert!(Regex::parse("(x?){3}x{3}").unwrap().matches("xxx"));
+  assert!(!Regex::parse("(x?){3}x{3}").unwrap().matches("xx"));
+  assert_eq!(Regex::parse("a{60000}{60000}"), Err("pattern too large".to_string()));
+}
+
+#[test]
+fn step_limit_and_faults() {
+  let spin: Program = assembler::assemble(".ORIG x3000\nL BRnzp L\n.END").unwrap();
+  let report = TestCase::new("spin").max_steps(10).run(&spin);
+  assert_eq!(report.failures, vec![Failure::NoHalt(StopReason::StepLimit)]);
+  let report = TestCase::new("spin").max_steps(u64::MAX).time_limit(Duration::from_millis(20)).run(&spin);
+  assert_eq!(report.failures, vec![Failure::NoHalt(StopReason::LimitExceeded)]);
+  assert_eq!(report.failures[0].to_string(), "did not halt within the time limit");
+  assert!(TestCase::new("spin").max_steps(10).expect_halt(false).run(&spin).passed());
+  let report = TestCase::new("spin").max_steps(u64::MAX).detect_loops(true).run(&spin);
+  assert_eq!(report.failures[0].to_string(), "stuck in a loop that makes no progress");
+  assert_eq!(report.steps, 1);
+
+  let bad: Program = assembler::assemble(".ORIG x3000\n.FILL xD000\n.END").unwrap();
+  let report = TestCase::new("bad").run(&bad);
+  assert_eq!(report.failures, vec![
+    Failure::NoHalt(StopReason::Fault(MachineError::IllegalOpcode { pc: 0x3000, instr: 0xD000 })),
+  ]);
+}
+
+#[test]
+fn through_the_os() {
+  let report = TestCase::new("os").os(true).input("c").expect_output("C\nHALT\n").run(&program());
+  assert!(report.passed(), "{}", report);
+}
+
+#[test]
+fn parses_specs() {
+  let spec: &str = r##"
+# defaults for every case
+max_steps = 1_000
+
+[[test]]
+name = "sum and echo"
+regs = { R1 = 2, r2 = 0x3 }
+input = 'a'
+expect_regs = { R3 = 5 }
+expect_mem = { x3007 = 5 }   # SUM
+expect_output = "A\nHALT\n"
+
+[[test]]
+name = "patterns"
+ignore_whitespace = true
+expect_output_regex = '[A-Z]\sHALT'
+
+[[test]]
+regs = { R1 = -1 }
+mem = { "#12295" = 9 }
+os = true
+time_limit_ms = 500
+expect_halt = false
+"##;
+  let cases: Vec<TestCase> = harness::parse_spec(spec).unwrap();
+  assert_eq!(cases.len(), 3);
+  assert_eq!(cases[0], TestCase::new("sum and echo")
+    .max_steps(1000)
+    .reg(Reg::R1, 2).reg(Reg::R2, 3)
+    .input("a")
+    .expect_reg(Reg::R3, 5)
+    .expect_mem(0x3007, 5)
+    .expect_output("A\nHALT\n"));
+  assert_eq!(cases[1], TestCase::new("patterns").max_steps(1000).ignore_whitespace(true).expect_output_regex("[A-Z]\\sHALT"));
+  assert_eq!(cases[2], TestCase::new("test 3").max_steps(1000).reg(Reg::R1, 0xFFFF).mem(0x3007, 9).os(true)
+    .time_limit(Duration::from_millis(500)).expect_halt(false));
+  assert!(cases[0].run(&program()).passed());
+  assert!(cases[1].clone().reg(Reg::R1, 1).input("q").run(&program()).passed());
+
+  for (src, err) in &[
+    ("[[case]]", "line 1: expected `[[test]]`"),
+    ("[[test]]\nname = 3", "line 2: invalid value for `name`"),
+    ("colour = \"red\"", "line 1: unknown key `colour`"),
+    ("regs = { R8 = 1 }", "line 1: unknown register `R8`"),
+    ("mem = { x3000 = 0x10000 }", "line 1: `x3000` doesn't fit in 16 bits"),
+    ("name = \"open", "line 1: unterminated string"),
+    ("os = true false", "line 1: unexpected `f`"),
+    ("expect_output_regex = 'a{2,1}'", "line 1: invalid regex: invalid repeat `{2,1}`"),
+  ] {
+    assert_eq!(harness::parse_spec(src).unwrap_err().0, *err);
+  }
+}
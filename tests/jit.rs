@@ -0,0 +1,138 @@
+extern crate lc3;
+
+use lc3::{Machine, MachineError, Reg, Run, Snapshot, StopReason};
+
+mod common;
+
+const FIBONACCI: &str = "\
+.ORIG x3000
+       LD R6, STACK
+       LD R0, N
+       JSR FIB
+       ST R0, RESULT
+       HALT
+FIB    ADD R6, R6, #-3
+       STR R7, R6, #0
+       STR R1, R6, #1
+       STR R2, R6, #2
+       ADD R1, R0, #-2
+       BRn BASE
+       ADD R1, R0, #0
+       ADD R0, R1, #-1
+       JSR FIB
+       ADD R2, R0, #0
+       ADD R0, R1, #-2
+       JSR FIB
+       ADD R0, R0, R2
+BASE   LDR R7, R6, #0
+       LDR R1, R6, #1
+       LDR R2, R6, #2
+       ADD R6, R6, #3
+       RET
+N      .FILL #12
+STACK  .FILL x6000
+RESULT .BLKW 1
+.END";
+
+// rewrites the ADD at PATCH, in the middle of a block, as it runs
+const SELF_MODIFYING: &str = "\
+.ORIG x3000
+      ADD R2, R2, #3
+LOOP  LD R0, NEW
+      ST R0, PATCH
+PATCH ADD R1, R1, #1
+      ADD R1, R1, #1
+      ADD R2, R2, #-1
+      BRp LOOP
+      HALT
+NEW   ADD R1, R1, #4
+.END";
+
+const STRINGS: &str = "\
+.ORIG x3000
+      LEA R0, TEXT
+      PUTS
+      LD R0, BANG
+      OUT
+      HALT
+TEXT  .STRINGZ \"hello\"
+BANG  .FILL x21
+.END";
+
+fn machine(src: &str, os: bool, jit: bool) -> Machine {
+  let mut m: Machine = common::load(common::builder().os(os), src);
+  m.write_reg(Reg::PC, 0x3000);
+  m.set_jit(jit);
+  m.capture_output(false);
+  m
+}
+
+// runs src both ways and checks they end up in the same state
+fn compare<F: Fn(&mut Machine) -> Run>(src: &str, os: bool, run: F) -> Machine {
+  let mut plain: Machine = machine(src, os, false);
+  let mut jit: Machine = machine(src, os, true);
+  assert!(jit.jit_enabled());
+  assert_eq!(run(&mut plain), run(&mut jit));
+  assert!(plain.snapshot() == jit.snapshot());
+  assert_eq!(plain.stats(), jit.stats());
+  assert_eq!(plain.instructions(), jit.instructions());
+  assert_eq!(plain.output(), jit.output());
+  jit
+}
+
+#[test]
+fn matches_the_interpreter() {
+  let m: Machine = compare(FIBONACCI, false, |m| m.run());
+  assert_eq!(m.read_mem(0x3019), 144);
+  compare(STRINGS, false, |m| m.run());
+  compare(STRINGS, true, |m| m.run());
+}
+
+#[test]
+fn stops_at_the_same_step() {
+  for n in [1, 7, 100, 1234] {
+    compare(FIBONACCI, false, |m| m.run_for(n));
+  }
+  compare(FIBONACCI, false, |m| m.run_until(|m| m.read_reg(Reg::R6) < 0x5FF0));
+}
+
+#[test]
+fn sees_self_modifying_code() {
+  let m: Machine = compare(SELF_MODIFYING, false, |m| m.run());
+  assert_eq!(m.read_reg(Reg::R1), 15);
+}
+
+#[test]
+fn falls_back_for_breakpoints_and_trap_catching() {
+  compare(FIBONACCI, false, |m| {
+    m.add_breakpoint(0x300A);
+    m.run()
+  });
+  let mut m: Machine = machine(STRINGS, false, true);
+  m.catch_traps(true);
+  assert_eq!(m.run().reason, StopReason::Trap(0x22));
+
+  let mut m: Machine = machine(FIBONACCI, false, true);
+  let start: Snapshot = m.snapshot();
+  m.add_breakpoint(0x300A);
+  assert_eq!(m.run().reason, StopReason::Breakpoint(0x300A));
+  m.restore(&start);
+  m.remove_breakpoint(0x300A);
+  assert_eq!(m.run().reason, StopReason::Halt);
+}
+
+#[test]
+fn stops_at_the_io_page() {
+  // straight-line user code running from xFDFF into xFE00 faults there
+  let src: &str = ".ORIG xFDFD\nADD R1, R1, #1\nADD R1, R1, #1\nADD R1, R1, #1\n.END";
+  for os in [false, true] {
+    let m: Machine = compare(src, os, |m| {
+      m.write_reg(Reg::PC, 0xFDFD);
+      m.run_for(10)
+    });
+    assert_eq!(m.read_reg(Reg::R1), 3);
+  }
+  let mut m: Machine = machine(src, false, true);
+  m.write_reg(Reg::PC, 0xFDFD);
+  assert_eq!(m.run_for(10), Run { steps: 4, reason: StopReason::Fault(MachineError::AccessViolation { pc: 0xFE00, addr: 0xFE00 }) });
+}
@@ -0,0 +1,32 @@
+extern crate lc3;
+
+use lc3::json::Json;
+
+#[test]
+fn parses_and_prints() {
+  let src: &str = r#"{"seq": 3, "command": "setBreakpoints", "arguments": {"lines": [1, -2.5e1], "ok": true, "none": null}}"#;
+  let v: Json = Json::parse(src).unwrap();
+  assert_eq!(v.get("seq").and_then(Json::as_i64), Some(3));
+  assert_eq!(v.get("command").and_then(Json::as_str), Some("setBreakpoints"));
+  let args: &Json = v.get("arguments").unwrap();
+  assert_eq!(args.get("lines").and_then(Json::as_array).map(|l| l.len()), Some(2));
+  assert_eq!(args.get("ok").and_then(Json::as_bool), Some(true));
+  assert_eq!(args.get("none"), Some(&Json::Null));
+
+  assert_eq!(v.to_string(), r#"{"seq":3,"command":"setBreakpoints","arguments":{"lines":[1,-25],"ok":true,"none":null}}"#);
+}
+
+#[test]
+fn escapes_strings() {
+  let v: Json = Json::from("a \"b\"\n\\ \u{1}");
+  assert_eq!(v.to_string(), r#""a \"b\"\n\\ \u0001""#);
+  assert_eq!(Json::parse(&v.to_string()).unwrap(), v);
+  assert_eq!(Json::parse(r#""A\t""#).unwrap(), Json::from("A\t"));
+}
+
+#[test]
+fn rejects_malformed() {
+  for src in &["", "{", "[1,]", "{\"a\" 1}", "tru", "\"open", "1 2", "{1: 2}"] {
+    assert!(Json::parse(src).is_err(), "{}", src);
+  }
+}
@@ -0,0 +1,91 @@
+extern crate lc3;
+
+use lc3::{Lc3bMachine, MachineError, Reg, StopReason, ZRO};
+
+fn lea(dr: u16, off: i16) -> u16 { 0xE000 | dr << 9 | (off as u16 & 0x1FF) }
+fn ldb(dr: u16, base: u16, off: i16) -> u16 { 0x2000 | dr << 9 | base << 6 | (off as u16 & 0x3F) }
+fn stb(sr: u16, base: u16, off: i16) -> u16 { 0x3000 | sr << 9 | base << 6 | (off as u16 & 0x3F) }
+fn ldw(dr: u16, base: u16, off: i16) -> u16 { 0x6000 | dr << 9 | base << 6 | (off as u16 & 0x3F) }
+fn xor_imm(dr: u16, sr: u16, imm: i16) -> u16 { 0x9000 | dr << 9 | sr << 6 | 1 << 5 | (imm as u16 & 0x1F) }
+fn shf(dr: u16, sr: u16, kind: u16, amount: u16) -> u16 { 0xD000 | dr << 9 | sr << 6 | kind << 4 | amount }
+const HALT: u16 = 0xF025;
+
+#[test]
+fn bytes_xor_and_shifts() {
+  let mut m = Lc3bMachine::default();
+  m.load(0x3000, &[
+    lea(0, 0x20),
+    ldb(1, 0, 0),
+    ldb(2, 0, 1),
+    stb(2, 0, 2),
+    ldw(3, 0, 0),
+    xor_imm(4, 3, -1),
+    shf(5, 3, 3, 4),
+    shf(6, 3, 1, 4),
+    shf(0, 3, 0, 4),
+    HALT,
+  ]);
+  m.write_word(0x3042, 0x80F1);
+
+  // LEA scales its offset and leaves the condition codes alone
+  m.step().unwrap();
+  assert_eq!(m.read_reg(Reg::R0), 0x3042);
+  assert_eq!(m.read_reg(Reg::COND), ZRO);
+
+  assert_eq!(m.run().reason, StopReason::Halt);
+  // little-endian, bytes sign extended
+  assert_eq!(m.read_reg(Reg::R1), 0xFFF1);
+  assert_eq!(m.read_reg(Reg::R2), 0xFF80);
+  assert_eq!(m.read_byte(0x3044), 0x80);
+  assert_eq!(m.read_byte(0x3045), 0x00);
+  assert_eq!(m.read_reg(Reg::R3), 0x80F1);
+  assert_eq!(m.read_reg(Reg::R4), 0x7F0E);
+  assert_eq!(m.read_reg(Reg::R5), 0xF80F);
+  assert_eq!(m.read_reg(Reg::R6), 0x080F);
+  assert_eq!(m.read_reg(Reg::R0), 0x0F10);
+}
+
+#[test]
+fn scaled_branches_and_trap_table() {
+  let mut m = Lc3bMachine::default();
+  m.load(0x3000, &[
+    0x103F, // ADD R0, R0, #-1
+    0x0801, // BRn #1
+    HALT,
+    0x4801, // JSR #1
+    HALT,
+    0xF020, // TRAP x20, through the table to the HALT above
+  ]);
+  m.write_word(0x0040, 0x3008);
+  let run = m.run();
+  assert_eq!((run.steps, run.reason), (5, StopReason::Halt));
+  assert_eq!(m.read_reg(Reg::PC), 0x300A);
+}
+
+#[test]
+fn faults() {
+  let mut m = Lc3bMachine::default();
+  m.load(0x3000, &[ldw(0, 1, 0)]);
+  m.write_reg(Reg::R1, 0x3101);
+  assert_eq!(m.step(), Err(MachineError::Unaligned { pc: 0x3000, addr: 0x3101 }));
+
+  // LDI's old opcode is unused
+  let mut m = Lc3bMachine::default();
+  m.load(0x3000, &[0xA000]);
+  assert_eq!(m.step(), Err(MachineError::IllegalOpcode { pc: 0x3000, instr: 0xA000 }));
+
+  let mut m = Lc3bMachine::default();
+  m.write_reg(Reg::PC, 0x3001);
+  assert_eq!(m.step(), Err(MachineError::Unaligned { pc: 0x3001, addr: 0x3001 }));
+}
+
+#[test]
+fn loads_obj_at_byte_addresses() {
+  let mut m = Lc3bMachine::default();
+  m.load_image_bytes(&[0x30, 0x02, 0x12, 0x34, 0xF0, 0x25]).unwrap();
+  assert_eq!(m.read_byte(0x3002), 0x34);
+  assert_eq!(m.read_byte(0x3003), 0x12);
+  assert_eq!(m.read_word(0x3004), HALT);
+  assert_eq!(m.read_reg(Reg::PC), 0x3002);
+  assert!(m.load_image_bytes(&[0x30, 0x01, 0x12, 0x34]).is_err());
+}
@@ -0,0 +1,85 @@
+extern crate lc3;
+
+use std::time::{Duration, Instant};
+
+use lc3::assembler;
+use lc3::{Machine, NullConsole, Reg, Run, StopReason};
+
+// BRnzp #-1, forever
+fn spin() -> Machine {
+  Machine::builder().io(Box::new(NullConsole)).load(&[0x0FFF]).build()
+}
+
+#[test]
+fn instruction_limit() {
+  let mut m = spin();
+  m.run_for(5);
+  m.set_limits(Some(100), None);
+  assert_eq!(m.run(), Run { steps: 100, reason: StopReason::LimitExceeded });
+
+  // counted again from each call, and lifted by None
+  m.set_limits(Some(10), None);
+  assert_eq!(m.run_for(1000), Run { steps: 10, reason: StopReason::LimitExceeded });
+  m.set_limits(None, None);
+  assert_eq!(m.run_for(1000).reason, StopReason::StepLimit);
+}
+
+#[test]
+fn wall_time_limit() {
+  let mut m = spin();
+  let start: Instant = Instant::now();
+  m.set_limits(None, Some(Duration::from_millis(50)));
+  assert_eq!(m.run().reason, StopReason::LimitExceeded);
+  assert!(start.elapsed() >= Duration::from_millis(50));
+}
+
+#[test]
+fn the_first_limit_wins() {
+  let mut m = spin();
+  m.set_limits(Some(1000), Some(Duration::from_secs(60)));
+  assert_eq!(m.run(), Run { steps: 1000, reason: StopReason::LimitExceeded });
+}
+
+#[cfg(feature = "jit")]
+#[test]
+fn limits_the_jit() {
+  let mut m = spin();
+  m.set_jit(true);
+  m.set_limits(Some(100), None);
+  assert_eq!(m.run(), Run { steps: 100, reason: StopReason::LimitExceeded });
+}
+
+// in supervisor mode, to poll KBSR
+fn program(src: &str) -> Machine {
+  let words: Vec<u16> = assembler::assemble(src).unwrap().words;
+  let mut m = Machine::builder().io(Box::new(NullConsole)).supervisor(true).load(&words).build();
+  m.set_progress_check(true);
+  m
+}
+
+#[test]
+fn no_progress() {
+  let mut m = spin();
+  m.set_progress_check(true);
+  assert_eq!(m.run(), Run { steps: 1, reason: StopReason::NoProgress });
+
+  // the counter is never updated
+  let mut m = program(".ORIG x3000\nAND R0, R0, #0\nL ADD R1, R0, #-1\nBRn L\nHALT\n.END");
+  assert_eq!(m.run().reason, StopReason::NoProgress);
+  assert_eq!(m.read_reg(Reg::PC), 0x3001);
+
+  // counting all the way round takes a while, but repeats
+  let mut m = program(".ORIG x3000\nL ADD R0, R0, #1\nBR L\n.END");
+  let run: Run = m.run_for(1 << 20);
+  assert_eq!(run.reason, StopReason::NoProgress);
+  assert!(run.steps > 1 << 17);
+
+  // one that gets somewhere halts, stores of new values and polling the
+  // keyboard are progress
+  let mut m = program(".ORIG x3000\nLD R0, N\nL ADD R0, R0, #-1\nBRp L\nHALT\nN .FILL 100\n.END");
+  assert_eq!(m.run().reason, StopReason::Halt);
+  let mut m = program(".ORIG x3000\nL ADD R0, R0, #1\nST R0, V\nAND R0, R0, #0\nBR L\nV .FILL 0\n.END");
+  assert_eq!(m.run_for(1000).reason, StopReason::NoProgress);
+  let mut m = program(".ORIG x3000\nL LDI R0, K\nBRzp L\nHALT\nK .FILL xFE00\n.END");
+  assert_eq!(m.run_for(1000).reason, StopReason::StepLimit);
+}
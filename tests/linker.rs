@@ -0,0 +1,110 @@
+extern crate lc3;
+
+use lc3::assembler::Include;
+use lc3::linker::{self, LinkError, Linked, Module};
+use lc3::loader::{LoadedImage, Segment};
+use lc3::harness::{Report, TestCase};
+use lc3::SymbolTable;
+
+fn link(modules: &[Module]) -> Result<Linked, LinkError> {
+  let mut include = |_: Option<&str>, name: &str| Err(format!("no `{}`", name));
+  let include: &mut Include = &mut include;
+  linker::link(modules, include)
+}
+
+const MAIN: &str = "\
+.ORIG x3000
+  LD R0, COUNT
+  JSR PRINT
+  OUT
+  HALT
+COUNT .FILL #3
+.END";
+
+const PRINT: &str = "\
+.ORIG x3010
+PRINT LD R1, BASE
+  ADD R0, R0, R1
+  RET
+BASE .FILL x30
+.END";
+
+#[test]
+fn sources() {
+  let modules: &[Module] = &[
+    Module::Source { name: "main.asm", src: MAIN },
+    Module::Source { name: "print.asm", src: PRINT },
+  ];
+  let linked: Linked = link(modules).unwrap();
+  assert_eq!(linked.image.entry, Some(0x3000));
+  assert_eq!(linked.image.segments.len(), 2);
+  assert_eq!(linked.symbols.lookup("PRINT"), Some(0x3010));
+  assert_eq!(linked.symbols.lookup("COUNT"), Some(0x3004));
+  // JSR PRINT from x3001
+  assert_eq!(linked.image.segments[0].words[1], 0x480E);
+
+  let (origin, words): (u16, Vec<u16>) = linked.flatten();
+  assert_eq!(origin, 0x3000);
+  assert_eq!(words.len(), 0x14);
+  assert_eq!(words[5..0x10], [0; 11]);
+
+  let report: Report = TestCase::new("linked").run_obj(&linked.to_obj());
+  assert!(report.passed() && report.output.starts_with('3'), "{}", report.output);
+
+  // modules placed back to back merge into one segment
+  let next: &str = ".ORIG x3005\nNEXT .FILL COUNT\n.END";
+  let linked: Linked = link(&[Module::Source { name: "next.asm", src: next }, modules[0], modules[1]]).unwrap();
+  assert_eq!(linked.image.segments.len(), 2);
+  assert_eq!((linked.image.segments[0].origin, linked.image.segments[0].words.len()), (0x3000, 6));
+  assert_eq!(linked.image.segments[0].words[5], 0x3004);
+  assert_eq!(linked.image.entry, Some(0x3005));
+}
+
+#[test]
+fn images() {
+  let print: LoadedImage = LoadedImage {
+    segments: vec![Segment { origin: 0x3010, words: vec![0xF021, 0xC1C0] }],
+    entry: None,
+  };
+  let mut symbols: SymbolTable = SymbolTable::new();
+  symbols.insert("PRINT", 0x3010);
+  let linked: Linked = link(&[
+    Module::Source { name: "main.asm", src: MAIN },
+    Module::Image { name: "print.obj", image: &print, symbols: &symbols },
+  ]).unwrap();
+  assert_eq!(linked.image.segments[1], print.segments[0]);
+  assert_eq!(linked.image.segments[0].words[1], 0x480E);
+
+  // an image first gives its entry point
+  let start: LoadedImage = LoadedImage { entry: Some(0x3011), ..print.clone() };
+  let linked: Linked = link(&[Module::Image { name: "print.obj", image: &start, symbols: &symbols }]).unwrap();
+  assert_eq!(linked.image.entry, Some(0x3011));
+}
+
+#[test]
+fn errors() {
+  let print: Module = Module::Source { name: "print.asm", src: PRINT };
+  let overlapping: &str = ".ORIG x3003\nHALT\nHALT\nHALT\n.END";
+  let e: LinkError = link(&[
+    Module::Source { name: "main.asm", src: MAIN },
+    Module::Source { name: "other.asm", src: overlapping },
+    print,
+  ]).unwrap_err();
+  assert_eq!(e.to_string(), "main.asm and other.asm overlap at x3003-x3004");
+  let one: &str = ".ORIG x3004\nHALT\n.END";
+  let e: LinkError = link(&[Module::Source { name: "main.asm", src: MAIN }, Module::Source { name: "one.asm", src: one }, print]).unwrap_err();
+  assert_eq!(e.to_string(), "main.asm and one.asm overlap at x3004");
+
+  let twice: &str = ".ORIG x4000\nCOUNT .FILL #4\n.END";
+  let e: LinkError = link(&[Module::Source { name: "main.asm", src: MAIN }, Module::Source { name: "twice.asm", src: twice }, print]).unwrap_err();
+  assert_eq!(e.to_string(), "label `COUNT` is defined in both main.asm and twice.asm");
+
+  // a label no module defines
+  match link(&[Module::Source { name: "main.asm", src: MAIN }]).unwrap_err() {
+    LinkError::Asm { module, error } => {
+      assert_eq!(module, "main.asm");
+      assert_eq!((error.line, error.msg.as_str()), (3, "undefined label `PRINT`"));
+    },
+    e => panic!("{:?}", e),
+  }
+}
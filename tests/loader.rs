@@ -0,0 +1,105 @@
+// image formats other than the assembler's own .obj
+extern crate lc3;
+
+use lc3::loader::{self, ImageFormat, LoadedImage, Segment};
+use lc3::{FormatError, Machine, Reg};
+
+// builds a record with its length and checksum filled in
+fn record(addr: u16, kind: u8, data: &[u8]) -> String {
+  let mut bytes: Vec<u8> = vec![data.len() as u8, (addr >> 8) as u8, addr as u8, kind];
+  bytes.extend_from_slice(data);
+  let sum: u8 = bytes.iter().fold(0u8, |s, &b| s.wrapping_add(b));
+  bytes.push(sum.wrapping_neg());
+  let hex: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+  format!(":{}\n", hex)
+}
+
+#[test]
+fn raw_big_endian() {
+  let mut m = Machine::builder().build();
+  m.load_bytes(&[0x12, 0x34, 0xF0, 0x25], ImageFormat::RawBigEndian { origin: 0x4000 }).unwrap();
+  assert_eq!(m.read_mem(0x4000), 0x1234);
+  assert_eq!(m.read_mem(0x4001), 0xF025);
+  // without an entry point the program starts at its origin
+  assert_eq!(m.read_reg(Reg::PC), 0x4000);
+}
+
+#[test]
+fn raw_little_endian() {
+  let mut m = Machine::builder().build();
+  m.load_bytes(&[0x34, 0x12, 0x25, 0xF0], ImageFormat::RawLittleEndian { origin: 0x4000 }).unwrap();
+  assert_eq!(m.read_mem(0x4000), 0x1234);
+  assert_eq!(m.read_mem(0x4001), 0xF025);
+}
+
+#[test]
+fn raw_rejects_bad_sizes() {
+  assert!(loader::parse(&[0x12, 0x34, 0x56], ImageFormat::RawBigEndian { origin: 0x3000 }).is_err());
+  assert_eq!(loader::parse(&[0; 4], ImageFormat::RawLittleEndian { origin: 0xFFFF }),
+    Err(FormatError("image does not fit in memory".to_string())));
+}
+
+#[test]
+fn intel_hex() {
+  // x3000: ADD R0, R0, #1; HALT, then a word at x4000 and an entry point
+  let src: String = [
+    record(0x6000, 0x00, &[0x10, 0x21, 0xF0, 0x25]),
+    record(0x8000, 0x00, &[0xAB, 0xCD]),
+    record(0, 0x05, &[0x00, 0x00, 0x60, 0x00]),
+    record(0, 0x01, &[]),
+  ].concat();
+
+  assert_eq!(loader::parse(src.as_bytes(), ImageFormat::IntelHex), Ok(LoadedImage {
+    segments: vec![
+      Segment { origin: 0x3000, words: vec![0x1021, 0xF025] },
+      Segment { origin: 0x4000, words: vec![0xABCD] },
+    ],
+    entry: Some(0x3000),
+  }));
+
+  let mut m = Machine::builder().origin(0x5000).build();
+  m.load_bytes(src.as_bytes(), ImageFormat::IntelHex).unwrap();
+  assert_eq!(m.read_mem(0x3001), 0xF025);
+  assert_eq!(m.read_reg(Reg::PC), 0x3000);
+}
+
+#[test]
+fn intel_hex_extended_and_split_words() {
+  // x8000 is byte x10000, past the 16-bit record address; its two bytes
+  // arrive in separate records
+  let src: String = [
+    record(0, 0x04, &[0x00, 0x01]),
+    record(0x0000, 0x00, &[0x12]),
+    record(0x0001, 0x00, &[0x34]),
+    record(0, 0x01, &[]),
+  ].concat();
+  let image: LoadedImage = loader::parse(src.as_bytes(), ImageFormat::IntelHex).unwrap();
+  assert_eq!(image.segments, vec![Segment { origin: 0x8000, words: vec![0x1234] }]);
+  assert_eq!(image.entry, None);
+}
+
+#[test]
+fn intel_hex_errors() {
+  let eof: String = record(0, 0x01, &[]);
+  let parse = |src: &str| loader::parse(src.as_bytes(), ImageFormat::IntelHex);
+
+  assert!(parse(":0100000012EE\n:00000001FF\n").unwrap_err().0.contains("checksum"));
+  assert!(parse(&record(0, 0x00, &[0x12])).unwrap_err().0.contains("end of file"));
+  assert!(parse(&format!("{}{}", eof, eof)).is_err());
+  assert!(parse(&format!("0100000012ED\n{}", eof)).is_err());
+  assert!(parse(&format!("{}{}", record(0, 0x07, &[]), eof)).is_err());
+}
+
+#[test]
+fn write_round_trips() {
+  let words: Vec<u16> = (0..40).map(|i| 0x1000 + i).collect();
+  for format in [ImageFormat::Obj, ImageFormat::RawBigEndian { origin: 0x7FF0 }, ImageFormat::RawLittleEndian { origin: 0x7FF0 }, ImageFormat::IntelHex] {
+    let image: LoadedImage = loader::parse(&loader::write(0x7FF0, &words, format), format).unwrap();
+    assert_eq!(image, LoadedImage { segments: vec![Segment { origin: 0x7FF0, words: words.clone() }], entry: None }, "{:?}", format);
+  }
+
+  // records stop at the 64K byte boundary, x8000 in words
+  let hex: String = String::from_utf8(loader::write(0x7FFC, &[1, 2, 3, 4, 5], ImageFormat::IntelHex)).unwrap();
+  assert_eq!(hex, record(0xFFF8, 0x00, &[0, 1, 0, 2, 0, 3, 0, 4])
+    + &record(0, 0x04, &[0, 1]) + &record(0, 0x00, &[0, 5]) + &record(0, 0x01, &[]));
+}
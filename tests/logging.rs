@@ -0,0 +1,62 @@
+extern crate lc3;
+extern crate log;
+
+use std::sync::Mutex;
+
+use lc3::{LogTarget, Machine, NullConsole};
+use log::{LevelFilter, Log, Metadata, Record};
+
+// target and level of everything logged
+static RECORDS: Mutex<Vec<(String, log::Level)>> = Mutex::new(Vec::new());
+
+struct Capture;
+
+impl Log for Capture {
+  fn enabled(&self, _metadata: &Metadata) -> bool {
+    true
+  }
+
+  fn log(&self, record: &Record) {
+    RECORDS.lock().unwrap().push((record.target().to_string(), record.level()));
+  }
+
+  fn flush(&self) {}
+}
+
+fn logged() -> Vec<(String, log::Level)> {
+  RECORDS.lock().unwrap().drain(..).collect()
+}
+
+fn targets(records: &[(String, log::Level)]) -> Vec<&str> {
+  let mut t: Vec<&str> = records.iter().map(|r| r.0.as_str()).collect();
+  t.dedup();
+  t
+}
+
+// one test, as the logger is shared by the whole binary
+#[test]
+fn per_subsystem_filters() {
+  log::set_logger(&Capture).unwrap();
+  log::set_max_level(LevelFilter::Trace);
+
+  // ADD, then an illegal opcode
+  let mut m = Machine::builder().io(Box::new(NullConsole)).load(&[0x1021, 0xD000]).build();
+  for &target in LogTarget::ALL.iter() {
+    assert_eq!(m.log_filter(target), LevelFilter::Trace);
+  }
+  m.step().unwrap();
+  assert_eq!(targets(&logged()), ["lc3::exec"]);
+
+  m.set_log_filter(LogTarget::Exec, LevelFilter::Warn);
+  assert!(m.step().is_err());
+  assert_eq!(logged(), [("lc3::exec".to_string(), log::Level::Warn)]);
+
+  m.set_log_filter(LogTarget::Exec, LevelFilter::Off);
+  m.set_log_filter(LogTarget::Mem, LevelFilter::Off);
+  m.load_image_bytes(&[0x30, 0x00, 0x10, 0x21]).unwrap();
+  m.step().unwrap();
+  assert_eq!(logged(), []);
+
+  assert_eq!(LogTarget::from_name("irq"), Some(LogTarget::Irq));
+  assert_eq!(LogTarget::from_name("lc3::irq"), None);
+}
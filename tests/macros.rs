@@ -0,0 +1,147 @@
+extern crate lc3;
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use lc3::assembler::{self, AsmError, Program};
+
+// assembles src with includes read from files, keyed by name
+fn assemble(src: &str, files: &[(&str, &str)]) -> Result<Program, AsmError> {
+  let files: HashMap<&str, &str> = files.iter().cloned().collect();
+  let mut include = |_: Option<&str>, name: &str| match files.get(name) {
+    Some(src) => Ok((name.to_string(), src.to_string())),
+    None => Err("not found".to_string()),
+  };
+  assembler::assemble_with(src, Some("main.asm"), &mut include)
+}
+
+fn msg(src: &str, files: &[(&str, &str)]) -> (usize, String) {
+  let e: AsmError = assemble(src, files).unwrap_err();
+  (e.line, e.msg)
+}
+
+const MACROS: &str = "\
+.MACRO PUSH reg
+  ADD R6, R6, #-1
+  STR \\reg, R6, #0
+.ENDM
+.MACRO WAIT  ; spins on the display status
+POLL\\@ LDI R3, DSR\\@
+  BRzp POLL\\@
+  BR DONE\\@
+DSR\\@ .FILL xFE04
+DONE\\@
+.ENDM
+.MACRO SAY msg
+  LEA R0, S\\@
+  PUTS
+  BR E\\@
+S\\@ .STRINGZ \\msg
+E\\@
+.ENDM
+.ORIG x3000
+START PUSH R1
+  WAIT
+  wait
+  SAY \"a b, c\\n\"
+  HALT
+.END";
+
+#[test]
+fn macros() {
+  let prog: Program = assembler::assemble(MACROS).unwrap();
+  let plain: Program = assembler::assemble("\
+.ORIG x3000
+START ADD R6, R6, #-1
+  STR R1, R6, #0
+A LDI R3, B
+  BRzp A
+  BR C
+B .FILL xFE04
+C LDI R3, E
+  BRzp C
+  BR F
+E .FILL xFE04
+F LEA R0, S
+  PUTS
+  BR T
+S .STRINGZ \"a b, c\\n\"
+T HALT
+.END").unwrap();
+  assert_eq!(prog.words, plain.words);
+  assert_eq!(prog.symbols.lookup("START"), Some(0x3000));
+
+  // the words of an expansion are listed against the line invoking it
+  assert_eq!(prog.lines[0], assembler::LineEntry { line: 20, addr: 0x3000, len: 2 });
+  assert_eq!(prog.lines[1], assembler::LineEntry { line: 21, addr: 0x3002, len: 4 });
+  assert!(prog.listing(MACROS).contains("(3000) 1DBF  0001110110111111 (  20) START PUSH R1\n  (3001) 7380"));
+
+  assert_eq!(msg(".ORIG x3000\nPUSH R1\n.END", &[]), (2, "unknown opcode `PUSH`".to_string()));
+  let defs: &str = ".MACRO TWO a b\nADD \\a, \\a, \\b\n.ENDM\n";
+  assert_eq!(msg(&format!("{}.ORIG x3000\nTWO R1\n.END", defs), &[]).1, "macro `TWO` expects 2 arguments, found 1");
+  assert_eq!(msg(".MACRO BAD\nADD \\c, R0, R0\n.ENDM\n.ORIG x3000\nBAD\n.END", &[]), (5, "unknown macro parameter `\\c`".to_string()));
+  assert_eq!(msg(".MACRO LOOP\nLOOP\n.ENDM\n.ORIG x3000\nLOOP\n.END", &[]).1, "macro `LOOP` expands too deeply");
+  assert_eq!(msg(".ORIG x3000\n.MACRO OPEN\nHALT\n.END", &[]), (2, "macro `OPEN` has no .ENDM".to_string()));
+  assert_eq!(msg(".MACRO ADD\n.ENDM", &[]).1, "invalid macro name `ADD`");
+  assert_eq!(msg(&format!("{}{}", defs, defs), &[]), (4, "macro `TWO` is already defined".to_string()));
+}
+
+#[test]
+fn includes() {
+  let lib: &str = "\
+; prints the digit in R0
+PRINT_DIGIT
+  ST R0, SAVE
+  LD R1, ZERO
+  ADD R0, R0, R1
+  OUT
+  LD R0, SAVE
+  RET
+ZERO .FILL x30
+SAVE .BLKW 1";
+  let src: &str = ".ORIG x3000\nAND R0, R0, #0\nJSR PRINT_DIGIT\nHALT\n.INCLUDE \"print.asm\"\n.END";
+  let prog: Program = assemble(src, &[("print.asm", lib)]).unwrap();
+  assert_eq!(prog.symbols.lookup("PRINT_DIGIT"), Some(0x3003));
+  assert_eq!(prog.words.len(), 3 + 8);
+  assert_eq!(prog.lines.last(), Some(&assembler::LineEntry { line: 5, addr: 0x3003, len: 8 }));
+
+  // errors in included files say where
+  let broken: &[(&str, &str)] = &[("print.asm", "PRINT_DIGIT RET\nADD R1, R1")];
+  assert_eq!(msg(src, broken), (5, "print.asm:2: expected 3 operands, found 2".to_string()));
+  let nested: &[(&str, &str)] = &[("print.asm", ".INCLUDE \"half.asm\""), ("half.asm", "PRINT_DIGIT\n  JMP PRINT_DIGIT")];
+  assert_eq!(msg(src, nested), (5, "print.asm:1: half.asm:2: operand 1 must be a register".to_string()));
+  assert_eq!(msg(src, &[]).1, "cannot include `print.asm`: not found");
+  assert_eq!(msg(src, &[("print.asm", ".ORIG x4000")]).1, "print.asm:1: included files cannot have .ORIG or .END");
+  assert_eq!(assembler::assemble(src).unwrap_err().msg, "cannot include `print.asm`: there are no files to include from");
+
+  // macros defined in one file can be used after it's included
+  let defs: &[(&str, &str)] = &[("defs.asm", ".MACRO CLEAR r\nAND \\r, \\r, #0\n.ENDM")];
+  let prog: Program = assemble(".ORIG x3000\n.INCLUDE \"defs.asm\"\nCLEAR R2\n.END", defs).unwrap();
+  assert_eq!(prog.words, vec![0x54A0]);
+
+  let cycle: &[(&str, &str)] = &[("a.asm", ".INCLUDE \"b.asm\""), ("b.asm", "HALT\n.INCLUDE \"a.asm\"")];
+  assert_eq!(msg(".ORIG x3000\n.INCLUDE \"a.asm\"\n.END", cycle), (2, "a.asm:1: b.asm:2: `a.asm` includes itself".to_string()));
+  assert_eq!(msg(".ORIG x3000\n.INCLUDE \"main.asm\"\n.END", &[("main.asm", "")]).1, "`main.asm` includes itself");
+}
+
+#[test]
+fn include_files() {
+  let dir: PathBuf = env::temp_dir().join(format!("lc3-macros-{}", std::process::id()));
+  let _ = fs::remove_dir_all(&dir);
+  fs::create_dir_all(dir.join("lib")).unwrap();
+  fs::write(dir.join("lib/io.asm"), ".INCLUDE \"digits.asm\"\nNEWLINE .FILL x0A").unwrap();
+  fs::write(dir.join("lib/digits.asm"), "ZERO .FILL x30").unwrap();
+
+  // paths are relative to the file that includes them
+  let src: &str = ".ORIG x3000\nLD R0, ZERO\nHALT\n.INCLUDE \"lib/io.asm\"\n.END";
+  let prog: Program = assembler::assemble_file(src, &dir.join("main.asm")).unwrap();
+  assert_eq!(prog.words[2..], [0x30, 0x0A]);
+
+  // the same file by another path is still a cycle
+  fs::write(dir.join("lib/digits.asm"), ".INCLUDE \"../lib/io.asm\"").unwrap();
+  let e: AsmError = assembler::assemble_file(src, &dir.join("main.asm")).unwrap_err();
+  assert_eq!(e.msg, "lib/io.asm:1: digits.asm:1: `../lib/io.asm` includes itself");
+  fs::remove_dir_all(&dir).unwrap();
+}
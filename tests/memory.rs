@@ -0,0 +1,104 @@
+extern crate lc3;
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::thread;
+
+use lc3::encode::*;
+use lc3::{FlatMemory, Machine, Memory, NullConsole, Reg, SparseMemory, StopReason};
+
+// R0 = 5 stored at x3010, then halt
+fn prog() -> Vec<u16> {
+  vec![and_imm(0, 0, 0), add_imm(0, 0, 5), st(0, 0x0D), halt()]
+}
+
+#[test]
+fn sparse_backend() {
+  let mut m = Machine::builder().backend(Box::new(SparseMemory::new())).io(Box::new(NullConsole)).load(&prog()).build();
+  assert_eq!(m.run().reason, StopReason::Halt);
+  assert_eq!(m.read_reg(Reg::R0), 5);
+  assert_eq!(m.read_mem(0x3010), 5);
+  assert_eq!(m.read_mem(0x8000), 0);
+
+  let mut sparse = SparseMemory::new();
+  sparse.write(0x4000, 1);
+  sparse.write(0x4001, 2);
+  sparse.write(0x4000, 0);
+  assert_eq!(sparse.len(), 1);
+  assert_eq!(sparse.read(0x4001), 2);
+}
+
+#[test]
+fn small_flat_backend() {
+  let mut mem = FlatMemory::with_size(0x100);
+  mem.write(0xFF, 7);
+  mem.write(0x100, 7);
+  assert_eq!((mem.read(0xFF), mem.read(0x100)), (7, 0));
+}
+
+// counts the words read through it
+struct Counting {
+  inner: SparseMemory,
+  reads: Rc<Cell<u32>>,
+}
+
+impl Memory for Counting {
+  fn read(&self, addr: u16) -> u16 {
+    self.reads.set(self.reads.get() + 1);
+    self.inner.read(addr)
+  }
+
+  fn write(&mut self, addr: u16, val: u16) {
+    self.inner.write(addr, val);
+  }
+}
+
+#[test]
+fn custom_backend() {
+  let reads: Rc<Cell<u32>> = Rc::new(Cell::new(0));
+  let mem = Counting { inner: SparseMemory::new(), reads: reads.clone() };
+  let mut m = Machine::with_backend(Box::new(mem), Box::new(NullConsole));
+  m.init();
+  for (i, &w) in prog().iter().enumerate() {
+    m.write_mem(0x3000 + i as u16, w);
+  }
+  assert_eq!(m.run().reason, StopReason::Halt);
+  assert_eq!(m.read_mem(0x3010), 5);
+  // at least the four instruction fetches
+  assert!(reads.get() >= 4);
+}
+
+#[test]
+fn small_stack() {
+  let t = thread::Builder::new().stack_size(32 * 1024).spawn(|| {
+    let mut m = Machine::builder().io(Box::new(NullConsole)).load(&prog()).build();
+    m.run();
+    m.read_mem(0x3010)
+  });
+  assert_eq!(t.unwrap().join().unwrap(), 5);
+}
+
+#[test]
+fn boxed_slice() {
+  let mut words: Vec<u16> = vec![0; 0x3010];
+  words[0x3000..0x3004].copy_from_slice(&prog());
+  let mem = FlatMemory::from_boxed_slice(words.into_boxed_slice());
+  let mut m = Machine::with_backend(Box::new(mem), Box::new(NullConsole));
+  m.init();
+  assert_eq!(m.run().reason, StopReason::Halt);
+  assert_eq!(m.read_reg(Reg::R0), 5);
+  // x3010 is past the end of the buffer
+  assert_eq!(m.read_mem(0x3010), 0);
+
+  let mem = FlatMemory::from_boxed_slice(vec![1, 2, 3].into_boxed_slice());
+  assert_eq!(mem.read(2), 3);
+  let mut m = Machine::from_boxed_slice(vec![0; 0x10000].into_boxed_slice(), Box::new(NullConsole));
+  m.write_mem(0xFFFF, 9);
+  assert_eq!(m.read_mem(0xFFFF), 9);
+
+  // the buffer isn't copied
+  let buf: Box<[u16]> = vec![4; 8].into_boxed_slice();
+  let ptr: *const u16 = buf.as_ptr();
+  let back: Box<[u16]> = FlatMemory::from_boxed_slice(buf).into_boxed_slice();
+  assert_eq!(back.as_ptr(), ptr);
+}
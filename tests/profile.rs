@@ -0,0 +1,59 @@
+extern crate lc3;
+
+use lc3::assembler::{self, Program};
+use lc3::{Machine, NullConsole, Profile, Subroutine};
+
+const SRC: &str = "\
+.ORIG x3000
+MAIN  AND R0, R0, #0
+      JSR TWICE
+      JSR TWICE
+      HALT
+TWICE ADD R6, R7, #0
+      JSR INC
+      JSR INC
+      ADD R7, R6, #0
+      RET
+INC   ADD R0, R0, #1
+      RET
+.END";
+
+fn run(src: &str) -> (Program, Profile) {
+  let prog: Program = assembler::assemble(src).unwrap();
+  let mut m = Machine::builder().io(Box::new(NullConsole)).build();
+  m.load_image_bytes(&prog.to_obj()).unwrap();
+  m.start_profile();
+  m.run();
+  (prog, m.stop_profile().unwrap())
+}
+
+fn sub(entry: Option<u16>, name: &str, calls: u64, own: u64, total: u64) -> Subroutine {
+  Subroutine { entry, name: name.to_string(), calls, own, total }
+}
+
+#[test]
+fn counts_by_address() {
+  let (_, p) = run(SRC);
+  assert_eq!(p.total(), 22);
+  assert_eq!(p.count(0x3000), 1);
+  assert_eq!(p.count(0x3004), 2);
+  assert_eq!(p.count(0x3009), 4);
+  assert_eq!(p.count(0x300B), 0);
+  assert_eq!(p.hottest(3), [(0x3009, 4), (0x300A, 4), (0x3004, 2)]);
+}
+
+#[test]
+fn counts_by_subroutine() {
+  let (prog, p) = run(SRC);
+  assert_eq!(p.subroutines(&prog.symbols), [
+    sub(None, "MAIN", 0, 4, 22),
+    sub(Some(0x3004), "TWICE", 2, 10, 18),
+    sub(Some(0x3009), "INC", 4, 8, 8),
+  ]);
+  assert_eq!(p.folded(&prog.symbols), "MAIN 4\nMAIN;TWICE 10\nMAIN;TWICE;INC 8\n");
+  let report: String = p.report(&prog.symbols);
+  assert!(report.lines().nth(2).is_some_and(|l| l.ends_with("  TWICE") && l.contains("81.8%")), "{}", report);
+
+  // without symbols, addresses
+  assert!(p.folded(&Default::default()).starts_with("x3000 4\nx3000;x3004 10\n"));
+}
@@ -0,0 +1,132 @@
+// ALU, sign extension and address arithmetic against i16 reference
+// semantics. The operand spaces are small enough to cover exhaustively or
+// by dense sampling, so these run without a property testing framework.
+extern crate lc3;
+
+use lc3::encode::*;
+use lc3::{sign_extend, Machine, NullConsole, Reg, NEG, POS, ZRO};
+
+// operands for two-input checks: every corner case, plus a sample
+const CORNERS: [u16; 10] = [0, 1, 2, 0x7FFE, 0x7FFF, 0x8000, 0x8001, 0xFFFE, 0xFFFF, 0x5555];
+
+fn operands() -> Vec<u16> {
+  let mut v: Vec<u16> = CORNERS.to_vec();
+  let mut x: u32 = 0x1234_5678;
+  for _ in 0..246 {
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    v.push(x as u16);
+  }
+  v
+}
+
+fn reference_cc(v: i16) -> u16 {
+  if v < 0 { NEG } else if v == 0 { ZRO } else { POS }
+}
+
+// executes `instr` at `pc` with R1 and R2 set, returning R0 and CC
+fn exec(m: &mut Machine, pc: u16, instr: u16, r1: u16, r2: u16) -> (u16, u16) {
+  m.write_mem(pc, instr);
+  m.write_reg(Reg::PC, pc);
+  m.write_reg(Reg::R1, r1);
+  m.write_reg(Reg::R2, r2);
+  m.step().unwrap();
+  (m.read_reg(Reg::R0), m.read_reg(Reg::COND))
+}
+
+// in supervisor mode, so any address can be reached
+fn machine() -> Machine {
+  Machine::builder().io(Box::new(NullConsole)).supervisor(true).build()
+}
+
+#[test]
+fn sign_extend_matches_i16() {
+  for bits in 1..=16usize {
+    for n in 0..(1u32 << bits) {
+      let n: u16 = n as u16;
+      let shift: u32 = 16 - bits as u32;
+      let reference: i16 = ((n << shift) as i16) >> shift;
+      assert_eq!(sign_extend(n, bits) as i16, reference, "{:#x} in {} bits", n, bits);
+    }
+  }
+}
+
+#[test]
+fn add_register() {
+  let mut m = machine();
+  for &a in &operands() {
+    for &b in &operands() {
+      let sum: i16 = (a as i16).wrapping_add(b as i16);
+      assert_eq!(exec(&mut m, 0x3000, add_reg(0, 1, 2), a, b), (sum as u16, reference_cc(sum)), "{:#x} + {:#x}", a, b);
+    }
+  }
+}
+
+#[test]
+fn add_immediate() {
+  let mut m = machine();
+  for a in 0..=0xFFFFu16 {
+    for &imm in &[-16i16, -1, 0, 1, 15] {
+      let sum: i16 = (a as i16).wrapping_add(imm);
+      assert_eq!(exec(&mut m, 0x3000, add_imm(0, 1, imm), a, 0), (sum as u16, reference_cc(sum)), "{:#x} + #{}", a, imm);
+    }
+  }
+}
+
+#[test]
+fn and_register_and_immediate() {
+  let mut m = machine();
+  for &a in &operands() {
+    for &b in &operands() {
+      let and: i16 = (a as i16) & (b as i16);
+      assert_eq!(exec(&mut m, 0x3000, and_reg(0, 1, 2), a, b), (and as u16, reference_cc(and)));
+    }
+    for imm in -16..=15i16 {
+      let and: i16 = (a as i16) & imm;
+      assert_eq!(exec(&mut m, 0x3000, and_imm(0, 1, imm), a, 0), (and as u16, reference_cc(and)));
+    }
+  }
+}
+
+#[test]
+fn not() {
+  let mut m = machine();
+  for a in 0..=0xFFFFu16 {
+    let not: i16 = !(a as i16);
+    assert_eq!(exec(&mut m, 0x3000, lc3::encode::not(0, 1), a, 0), (not as u16, reference_cc(not)));
+  }
+}
+
+#[test]
+fn pc_relative_addresses() {
+  let mut m = machine();
+  let pcs: [u16; 6] = [0x0000, 0x00FF, 0x3000, 0x7FFF, 0xFE00 - 1, 0xFFFF];
+  for &pc in &pcs {
+    for offset in -256..=255i16 {
+      let target: u16 = (pc as i16).wrapping_add(1).wrapping_add(offset) as u16;
+      assert_eq!(exec(&mut m, pc, lea(0, offset), 0, 0).0, target, "LEA #{} at {:#x}", offset, pc);
+    }
+    for offset in -1024..=1023i16 {
+      exec(&mut m, pc, jsr(offset), 0, 0);
+      assert_eq!(m.read_reg(Reg::PC), (pc as i16).wrapping_add(1).wrapping_add(offset) as u16);
+    }
+  }
+}
+
+#[test]
+fn base_offset_addresses() {
+  let mut m = machine();
+  for &base in &operands() {
+    for offset in -32..=31i16 {
+      let addr: u16 = (base as i16).wrapping_add(offset) as u16;
+      if addr >= 0xFE00 {
+        continue; // device registers
+      }
+      m.write_mem(addr, addr ^ 0xA5A5);
+      let instr: u16 = ldr(0, 1, offset);
+      let pc: u16 = if addr == 0x3000 { 0x4000 } else { 0x3000 };
+      assert_eq!(exec(&mut m, pc, instr, base, 0).0, addr ^ 0xA5A5, "LDR R1={:#x} #{}", base, offset);
+    }
+  }
+}
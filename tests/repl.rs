@@ -0,0 +1,70 @@
+extern crate lc3;
+
+use lc3::repl::Repl;
+use lc3::{Machine, NullConsole, Reg};
+
+fn setup() -> (Machine, Repl) {
+  let m = Machine::builder().io(Box::new(NullConsole)).build();
+  let repl: Repl = Repl::new(&m);
+  (m, repl)
+}
+
+#[test]
+fn executes_each_instruction() {
+  let (mut m, mut repl) = setup();
+  let out: String = repl.eval(&mut m, "ADD R1, R1, #5");
+  assert_eq!(out, "x3000: x1265  ADD R1, R1, #5\nR1 x0000 -> x0005\nCC Z -> P\n");
+  assert_eq!(m.read_reg(Reg::R1), 5);
+  assert_eq!(m.read_mem(0x3000), 0x1265);
+  assert_eq!(repl.next_addr(), 0x3001);
+
+  let out: String = repl.eval(&mut m, "ADD R1, R1, #-5");
+  assert_eq!(out, "x3001: x127B  ADD R1, R1, #-5\nR1 x0005 -> x0000\nCC P -> Z\n");
+}
+
+#[test]
+fn remembers_labels_and_stores_data() {
+  let (mut m, mut repl) = setup();
+  assert_eq!(repl.eval(&mut m, "N .FILL #7"), "x3000: x0007\n");
+  assert_eq!(m.symbols().lookup("N"), Some(0x3000));
+  assert!(repl.eval(&mut m, "LD R2, N").contains("R2 x0000 -> x0007"));
+  assert!(repl.eval(&mut m, "ST R2, N2").starts_with("error: "));
+  assert_eq!(repl.next_addr(), 0x3002);
+
+  repl.eval(&mut m, ".ORIG x4000");
+  assert_eq!(repl.next_addr(), 0x4000);
+  assert_eq!(repl.eval(&mut m, "ADD R2, R2, #1"), "x4000: x14A1  ADD R2, R2, #1\nR2 x0007 -> x0008\n");
+  assert_eq!(repl.next_addr(), 0x4001);
+}
+
+#[test]
+fn reports_memory_writes() {
+  let (mut m, mut repl) = setup();
+  repl.eval(&mut m, "ADD R0, R0, #9");
+  let out: String = repl.eval(&mut m, "ST R0, #4");
+  assert!(out.ends_with("x3006: x0000 -> x0009\n"));
+}
+
+#[test]
+fn runs_calls_to_completion() {
+  let (mut m, mut repl) = setup();
+  // ADD R2, R2, #3 ; RET
+  m.write_mem(0x3100, 0x14A3);
+  m.write_mem(0x3101, 0xC1C0);
+  m.symbols_mut().insert("SUB", 0x3100);
+
+  let out: String = repl.eval(&mut m, "JSR SUB");
+  assert!(out.contains("R2 x0000 -> x0003"));
+  assert!(!out.contains("PC x"));
+  assert_eq!(m.read_reg(Reg::PC), 0x3001);
+}
+
+#[test]
+fn reports_halt_and_errors() {
+  let (mut m, mut repl) = setup();
+  assert!(repl.eval(&mut m, "HALT").ends_with("machine halted\n"));
+  assert!(repl.eval(&mut m, "ADD R1, R1, #1").contains("R1 x0000 -> x0001"));
+  assert_eq!(repl.eval(&mut m, "FROB R1"), "error: unknown opcode `FROB`\n");
+  assert_eq!(repl.eval(&mut m, ":frob"), "unknown command `:frob`, type `:help` for commands\n");
+  assert_eq!(repl.next_addr(), 0x3002);
+}
@@ -0,0 +1,184 @@
+// decoder/encoder/assembler agreement over the whole instruction space
+extern crate lc3;
+
+use lc3::assembler;
+use lc3::encode::*;
+use lc3::instr::{decode, encode, Instruction};
+
+#[test]
+fn encode_inverts_decode() {
+  for w in 0..=0xFFFFu16 {
+    let instr: Instruction = decode(w);
+    assert_eq!(decode(encode(instr)), instr, "word {:#06x}", w);
+  }
+}
+
+#[test]
+fn canonical_words_round_trip() {
+  let words: [u16; 12] = [
+    0x1042, // ADD R0, R1, R2
+    0x127F, // ADD R1, R1, #-1
+    0x5020, // AND R0, R0, #0
+    0x0FFD, // BRnzp #-3
+    0xC1C0, // RET
+    0x4FFF, // JSR #-1
+    0x4080, // JSRR R2
+    0x6E7F, // LDR R7, R1, #-1
+    0x927F, // NOT R1, R1
+    0x8000, // RTI
+    0xF025, // HALT
+    0xD123, // reserved
+  ];
+  for &w in &words {
+    assert_eq!(encode(decode(w)), w, "word {:#06x}", w);
+  }
+}
+
+// the disassembler's text for every instruction assembles back to it
+#[test]
+fn disassembly_reassembles() {
+  for w in 0..=0xFFFFu16 {
+    let instr: Instruction = decode(w);
+    if let Instruction::Br { n: false, z: false, p: false, .. } = instr {
+      continue; // shown as NOP, which is not an assembler mnemonic
+    }
+
+    let src: String = format!(".ORIG x3000\n{}\n.END\n", instr);
+    let prog = assembler::assemble(&src).unwrap_or_else(|e| panic!("`{}`: {}", instr, e));
+    assert_eq!(prog.words, vec![encode(instr)], "`{}`", instr);
+  }
+}
+
+#[test]
+fn image_matches_assembler() {
+  let src: &str = "\
+    .ORIG x3000
+    LEA R0, MSG
+    PUTS
+    AND R1, R1, #0
+LOOP ADD R1, R1, #1
+    BRn LOOP
+    HALT
+MSG .STRINGZ \"hi\"
+    .END";
+  let asm = assembler::assemble(src).unwrap();
+
+  let image = Image::new(0x3000).word(lea(0, 5)).word(puts()).word(and_imm(1, 1, 0));
+  let top: u16 = image.here();
+  let image = image.label("LOOP").word(add_imm(1, 1, 1));
+  let back: i16 = image.offset_to(top);
+  let image = image.word(br(true, false, false, back)).word(halt()).label("MSG").stringz("hi");
+  let built = image.build();
+
+  assert_eq!(built.origin, asm.origin);
+  assert_eq!(built.words, asm.words);
+  assert_eq!(built.symbols.lookup("LOOP"), asm.symbols.lookup("LOOP"));
+  assert_eq!(built.symbols.lookup("MSG"), asm.symbols.lookup("MSG"));
+}
+
+#[test]
+fn sym_file_reads_back() {
+  let prog = assembler::assemble(".ORIG x3000\nLOOP BRnzp LOOP\nDATA .FILL #1\n.END").unwrap();
+  let sym: String = prog.symbols.to_sym();
+  assert!(sym.contains("//\tLOOP              3000\n"));
+  assert_eq!(lc3::SymbolTable::parse(&sym).unwrap(), prog.symbols);
+}
+
+#[test]
+fn listing_shows_every_word() {
+  let src: &str = ".ORIG x3000\n; comment\nMSG .STRINGZ \"a\"\nHALT\n.END";
+  let prog = assembler::assemble(src).unwrap();
+  let lst: Vec<String> = prog.listing(src).lines().map(|l| l.to_string()).collect();
+  assert_eq!(lst, vec![
+    format!("{:32}(   1) .ORIG x3000", ""),
+    format!("{:32}(   2) ; comment", ""),
+    "  (3000) 0061  0000000001100001 (   3) MSG .STRINGZ \"a\"".to_string(),
+    "  (3001) 0000  0000000000000000".to_string(),
+    "  (3002) F025  1111000000100101 (   4) HALT".to_string(),
+    format!("{:32}(   5) .END", ""),
+  ]);
+}
+
+#[test]
+fn constant_expressions() {
+  let src: &str = "\
+.ORIG x3000 + x100
+START LD R0, TABLE+1
+  ADD R1, R1, #10*3/2
+  AND R2, R2, x3 | x4
+  TRAP x20 + 5
+  .FILL 'A' | x80
+  .FILL ' '
+  .FILL ';'   ; still a comment
+  .FILL END - START
+  .FILL (1 << 4) - 1
+  .BLKW 2, '\\n'
+TABLE .FILL ~0
+END .FILL END-TABLE
+.END";
+  let prog = assembler::assemble(src).unwrap();
+  assert_eq!(prog.origin, 0x3100);
+  assert_eq!(prog.words, vec![
+    0x200B, 0x126F, 0x54A7, 0xF025, 0x00C1, 0x0020, 0x003B, 0x000C, 0x000F, 0x000A, 0x000A, 0xFFFF, 0x0001,
+  ]);
+
+  let e = assembler::assemble(".ORIG x3000\n.FILL MISSING+1\n.FILL 1/0\n.FILL x8000 * 2\n.END").unwrap_err();
+  let msgs: Vec<&str> = e.diagnostics.iter().map(|d| d.msg.as_str()).collect();
+  assert_eq!(msgs, vec!["division by zero", "value 65536 does not fit in 16 bits"]);
+  // values past 64 bits don't wrap
+  let src: &str = ".ORIG x3000\n.FILL 1<<64\n.FILL 1<<70\n.FILL (1<<62)*4\n.FILL 1>>-1\n.FILL 3<<63\n.FILL -((-1<<62)*2)\n.END";
+  let e = assembler::assemble(src).unwrap_err();
+  let msgs: Vec<&str> = e.diagnostics.iter().map(|d| d.msg.as_str()).collect();
+  assert_eq!(msgs, vec![
+    "shift by 64 is not in 0 to 63",
+    "shift by 70 is not in 0 to 63",
+    "value does not fit in 64 bits",
+    "shift by -1 is not in 0 to 63",
+    "value does not fit in 64 bits",
+    "value does not fit in 64 bits",
+  ]);
+  let e = assembler::assemble(".ORIG x3000\nSTART .FILL START*(1<<62)\n.END").unwrap_err();
+  assert_eq!(e.msg, "value does not fit in 64 bits");
+  let prog = assembler::assemble(".ORIG x3000\n.FILL (1<<62)>>47\n.FILL -1<<15\n.FILL x7FFF*2-xFFFE\n.END").unwrap();
+  assert_eq!(prog.words, vec![0x8000, 0x8000, 0x0000]);
+
+  let e = assembler::assemble(".ORIG x3000\n.FILL MISSING+1\n.END").unwrap_err();
+  assert_eq!(e.msg, "undefined label `MISSING`");
+}
+
+#[test]
+fn formatting() {
+  let src: &str = "\
+; counts down
+.orig x3000
+loop add r1,r1,#-1 ; again
+   BRp loop
+  ; done
+  HALT
+MSG .STRINGZ \"a; b\"   ;; kept
+.MACRO CLEAR r
+  AND \\r, \\r, #0
+.ENDM
+.END
+";
+  let style: assembler::Style = assembler::Style::default();
+  let formatted: String = assembler::format_source(src, &style);
+  assert_eq!(formatted, "\
+; counts down
+     .ORIG x3000
+loop ADD R1, R1, #-1 ; again
+     BRP loop
+     ; done
+     HALT
+MSG  .STRINGZ \"a; b\" ;; kept
+.MACRO CLEAR r
+  AND \\r, \\r, #0
+.ENDM
+     .END
+");
+  assert_eq!(assembler::format_source(&formatted, &style), formatted);
+  assert_eq!(assembler::assemble(&formatted).unwrap().words, assembler::assemble(src).unwrap().words);
+
+  let custom = assembler::Style { op_column: Some(8), comment_column: Some(28), case: assembler::Case::Lower };
+  assert_eq!(assembler::format_source("LOOP ADD R1, R1, #-1 ; again\n", &custom), "LOOP    add r1, r1, #-1     ; again\n");
+}
@@ -0,0 +1,123 @@
+extern crate lc3;
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+use lc3::{KeySource, Machine, Reg, Run, StopReason};
+
+mod common;
+use common::Output;
+
+// counts its wakeups
+struct Counter(AtomicUsize);
+
+impl Wake for Counter {
+  fn wake(self: Arc<Self>) {
+    self.0.fetch_add(1, Ordering::SeqCst);
+  }
+}
+
+// keys the test hands over one at a time, pending in between
+#[derive(Clone, Default)]
+struct Keys(Rc<RefCell<(VecDeque<u8>, Option<Waker>)>>);
+
+impl Keys {
+  fn send(&self, c: u8) {
+    let mut k = self.0.borrow_mut();
+    k.0.push_back(c);
+    if let Some(w) = k.1.take() {
+      w.wake();
+    }
+  }
+}
+
+impl KeySource for Keys {
+  fn poll_key(&mut self, cx: &mut Context<'_>) -> Poll<Option<u8>> {
+    let mut k = self.0.borrow_mut();
+    match k.0.pop_front() {
+      Some(c) => Poll::Ready(Some(c)),
+      None => {
+        k.1 = Some(cx.waker().clone());
+        Poll::Pending
+      },
+    }
+  }
+}
+
+const ECHO: &str = ".ORIG x3000\nGETC\nOUT\nGETC\nOUT\nHALT\n.END";
+
+fn machine(os: bool, out: &Output) -> Machine {
+  common::load(Machine::builder().io(Box::new(out.clone())).os(os), ECHO)
+}
+
+fn echo(os: bool) {
+  let out: Output = Output::default();
+  let mut m = machine(os, &out);
+  let keys: Keys = Keys::default();
+  let wakes: Arc<Counter> = Arc::new(Counter(AtomicUsize::new(0)));
+  let waker: Waker = Waker::from(wakes.clone());
+  let mut cx = Context::from_waker(&waker);
+
+  let mut fut = m.run_async(keys.clone());
+  let mut poll = |cx: &mut Context| Pin::new(&mut fut).poll(cx);
+  assert!(poll(&mut cx).is_pending());
+  assert_eq!(wakes.0.load(Ordering::SeqCst), 0);
+
+  keys.send(b'a');
+  assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+  assert!(poll(&mut cx).is_pending());
+  assert_eq!(out.text(), "a");
+
+  keys.send(b'b');
+  let run: Run = match poll(&mut cx) {
+    Poll::Ready(run) => run,
+    Poll::Pending => panic!("still pending"),
+  };
+  assert_eq!(run.reason, StopReason::Halt);
+  assert!(out.text().starts_with("ab"));
+}
+
+#[test]
+fn waits_for_keys_in_getc() {
+  echo(false);
+}
+
+#[test]
+fn waits_for_keys_polling_kbsr() {
+  echo(true);
+}
+
+#[test]
+fn yields_on_long_runs() {
+  let mut m = Machine::builder().io(Box::new(Output::default())).load(&[0x0FFF]).build();
+  let wakes: Arc<Counter> = Arc::new(Counter(AtomicUsize::new(0)));
+  let waker: Waker = Waker::from(wakes.clone());
+  let mut cx = Context::from_waker(&waker);
+
+  let mut fut = m.run_async(VecDeque::new());
+  assert!(Pin::new(&mut fut).poll(&mut cx).is_pending());
+  // woke itself to be polled again
+  assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+  drop(fut);
+  assert_eq!(m.read_reg(Reg::PC), 0x3000);
+}
+
+#[test]
+fn ended_input_reads_zero() {
+  let out: Output = Output::default();
+  let mut m = machine(false, &out);
+  let waker: Waker = Waker::from(Arc::new(Counter(AtomicUsize::new(0))));
+  let mut cx = Context::from_waker(&waker);
+  let keys: VecDeque<u8> = b"z".iter().cloned().collect();
+  let mut fut = m.run_async(keys);
+  assert!(Pin::new(&mut fut).poll(&mut cx).is_ready());
+  drop(fut);
+  assert_eq!(m.read_reg(Reg::R0), 0);
+  assert!(out.text().starts_with("z\0"));
+}
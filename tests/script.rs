@@ -0,0 +1,78 @@
+extern crate lc3;
+
+use lc3::script::When;
+use lc3::{InputScript, Machine, Reg, StopReason};
+
+mod common;
+
+fn machine(src: &str, script: &InputScript) -> Machine {
+  // for the device registers
+  let mut m: Machine = common::load(common::builder().supervisor(true), src);
+  m.set_input_script(script);
+  m.capture_output(false);
+  m
+}
+
+// echoes keys until q
+const ECHO: &str = "\
+.ORIG x3000
+LOOP  GETC
+      LD R1, NEG_Q
+      ADD R1, R0, R1
+      BRz DONE
+      OUT
+      BRnzp LOOP
+DONE  HALT
+NEG_Q .FILL #-113
+.END";
+
+// counts polls of KBSR until a key is ready into R1
+const POLL: &str = "\
+.ORIG x3000
+      AND R1, R1, #0
+POLL  ADD R1, R1, #1
+      LDI R2, KBSR
+      BRzp POLL
+      LDI R0, KBDR
+      BRnzp POLL
+KBSR  .FILL xFE00
+KBDR  .FILL xFE02
+.END";
+
+#[test]
+fn parse_and_display() {
+  let src: &str = "# a comment\n\"alice\\n\"\n\nwait 500 \"a b\\t\\\"\"\nat 10000 \"\\x03\\\\\"\n";
+  let script: InputScript = InputScript::parse(src).unwrap();
+  assert_eq!(script, InputScript::new()
+    .text("alice\n")
+    .then(When::After(500), "a b\t\"")
+    .then(When::At(10000), "\u{3}\\"));
+  assert_eq!(script.to_string(), "\"alice\\n\"\nwait 500 \"a b\\t\\\"\"\nat 10000 \"\\x03\\\\\"\n");
+  assert_eq!(InputScript::parse(&script.to_string()), Ok(script));
+
+  for bad in ["alice", "wait \"a\"", "wait x \"a\"", "at 5", "\"a\\q\"", "\"\\x4\"", "\"a\"b\""] {
+    assert!(InputScript::parse(bad).is_err(), "{}", bad);
+  }
+}
+
+#[test]
+fn getc_reads_the_script() {
+  let mut m: Machine = machine(ECHO, &InputScript::new().text("hi").then(When::After(1000), "!q"));
+  assert_eq!(m.run().reason, StopReason::Halt);
+  assert!(m.output().starts_with("hi!"));
+  assert!(!m.typing());
+}
+
+#[test]
+fn polling_sees_keys_when_due() {
+  let script: InputScript = InputScript::new().then(When::At(300), "a").then(When::After(600), "b");
+  let mut m: Machine = machine(POLL, &script);
+  // a key is seen on the first poll from instruction 300
+  m.run_until(|m| m.read_reg(Reg::R0) == 'a' as u16);
+  assert_eq!(m.read_reg(Reg::R1), 100);
+  assert!(m.typing());
+  m.write_reg(Reg::R1, 0);
+  m.run_until(|m| m.read_reg(Reg::R0) == 'b' as u16);
+  assert_eq!(m.read_reg(Reg::R1), 200);
+  assert!(!m.typing());
+}
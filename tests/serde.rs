@@ -0,0 +1,255 @@
+#[macro_use]
+extern crate serde;
+extern crate lc3;
+
+use std::fmt;
+
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{self, Deserialize, Deserializer, IntoDeserializer, Visitor};
+use serde::ser::{self, Impossible, Serialize, SerializeSeq, SerializeStruct, SerializeTuple, Serializer};
+
+use lc3::assembler;
+use lc3::{Machine, NullConsole, Random, Reg, Snapshot, StopReason, RNG};
+
+// a tree of values, just what the machine serializes to, standing in for
+// a real format
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+  Uint(u64),
+  Bool(bool),
+  Seq(Vec<Value>),
+  Struct(Vec<(String, Value)>),
+}
+
+#[derive(Debug)]
+struct Error(String);
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(&self.0)
+  }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+  fn custom<T: fmt::Display>(msg: T) -> Error {
+    Error(msg.to_string())
+  }
+}
+
+impl de::Error for Error {
+  fn custom<T: fmt::Display>(msg: T) -> Error {
+    Error(msg.to_string())
+  }
+}
+
+fn unsupported<T>() -> Result<T, Error> {
+  Err(Error("unsupported".to_string()))
+}
+
+struct ToValue;
+
+struct SeqBuilder(Vec<Value>);
+
+struct StructBuilder(Vec<(String, Value)>);
+
+impl SerializeSeq for SeqBuilder {
+  type Ok = Value;
+  type Error = Error;
+
+  fn serialize_element<T: ?Sized + Serialize>(&mut self, v: &T) -> Result<(), Error> {
+    self.0.push(v.serialize(ToValue)?);
+    Ok(())
+  }
+
+  fn end(self) -> Result<Value, Error> {
+    Ok(Value::Seq(self.0))
+  }
+}
+
+impl SerializeTuple for SeqBuilder {
+  type Ok = Value;
+  type Error = Error;
+
+  fn serialize_element<T: ?Sized + Serialize>(&mut self, v: &T) -> Result<(), Error> {
+    SerializeSeq::serialize_element(self, v)
+  }
+
+  fn end(self) -> Result<Value, Error> {
+    SerializeSeq::end(self)
+  }
+}
+
+impl SerializeStruct for StructBuilder {
+  type Ok = Value;
+  type Error = Error;
+
+  fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, v: &T) -> Result<(), Error> {
+    self.0.push((key.to_string(), v.serialize(ToValue)?));
+    Ok(())
+  }
+
+  fn end(self) -> Result<Value, Error> {
+    Ok(Value::Struct(self.0))
+  }
+}
+
+impl Serializer for ToValue {
+  type Ok = Value;
+  type Error = Error;
+  type SerializeSeq = SeqBuilder;
+  type SerializeTuple = SeqBuilder;
+  type SerializeTupleStruct = Impossible<Value, Error>;
+  type SerializeTupleVariant = Impossible<Value, Error>;
+  type SerializeMap = Impossible<Value, Error>;
+  type SerializeStruct = StructBuilder;
+  type SerializeStructVariant = Impossible<Value, Error>;
+
+  fn serialize_bool(self, v: bool) -> Result<Value, Error> { Ok(Value::Bool(v)) }
+  fn serialize_u8(self, v: u8) -> Result<Value, Error> { Ok(Value::Uint(v as u64)) }
+  fn serialize_u16(self, v: u16) -> Result<Value, Error> { Ok(Value::Uint(v as u64)) }
+  fn serialize_u32(self, v: u32) -> Result<Value, Error> { Ok(Value::Uint(v as u64)) }
+  fn serialize_u64(self, v: u64) -> Result<Value, Error> { Ok(Value::Uint(v)) }
+  fn serialize_i8(self, _v: i8) -> Result<Value, Error> { unsupported() }
+  fn serialize_i16(self, _v: i16) -> Result<Value, Error> { unsupported() }
+  fn serialize_i32(self, _v: i32) -> Result<Value, Error> { unsupported() }
+  fn serialize_i64(self, _v: i64) -> Result<Value, Error> { unsupported() }
+  fn serialize_f32(self, _v: f32) -> Result<Value, Error> { unsupported() }
+  fn serialize_f64(self, _v: f64) -> Result<Value, Error> { unsupported() }
+  fn serialize_char(self, _v: char) -> Result<Value, Error> { unsupported() }
+  fn serialize_str(self, _v: &str) -> Result<Value, Error> { unsupported() }
+  fn serialize_bytes(self, _v: &[u8]) -> Result<Value, Error> { unsupported() }
+  fn serialize_none(self) -> Result<Value, Error> { unsupported() }
+  fn serialize_some<T: ?Sized + Serialize>(self, _v: &T) -> Result<Value, Error> { unsupported() }
+  fn serialize_unit(self) -> Result<Value, Error> { unsupported() }
+  fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, Error> { unsupported() }
+
+  fn serialize_unit_variant(self, _name: &'static str, _index: u32, _variant: &'static str) -> Result<Value, Error> {
+    unsupported()
+  }
+
+  fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _name: &'static str, _v: &T) -> Result<Value, Error> {
+    unsupported()
+  }
+
+  fn serialize_newtype_variant<T: ?Sized + Serialize>(self, _name: &'static str, _index: u32, _variant: &'static str, _v: &T)
+    -> Result<Value, Error> {
+    unsupported()
+  }
+
+  fn serialize_seq(self, len: Option<usize>) -> Result<SeqBuilder, Error> {
+    Ok(SeqBuilder(Vec::with_capacity(len.unwrap_or(0))))
+  }
+
+  fn serialize_tuple(self, len: usize) -> Result<SeqBuilder, Error> {
+    Ok(SeqBuilder(Vec::with_capacity(len)))
+  }
+
+  fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Impossible<Value, Error>, Error> {
+    unsupported()
+  }
+
+  fn serialize_tuple_variant(self, _name: &'static str, _index: u32, _variant: &'static str, _len: usize)
+    -> Result<Impossible<Value, Error>, Error> {
+    unsupported()
+  }
+
+  fn serialize_map(self, _len: Option<usize>) -> Result<Impossible<Value, Error>, Error> {
+    unsupported()
+  }
+
+  fn serialize_struct(self, _name: &'static str, len: usize) -> Result<StructBuilder, Error> {
+    Ok(StructBuilder(Vec::with_capacity(len)))
+  }
+
+  fn serialize_struct_variant(self, _name: &'static str, _index: u32, _variant: &'static str, _len: usize)
+    -> Result<Impossible<Value, Error>, Error> {
+    unsupported()
+  }
+}
+
+impl<'de> Deserializer<'de> for Value {
+  type Error = Error;
+
+  fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    match self {
+      Value::Uint(v) => visitor.visit_u64(v),
+      Value::Bool(v) => visitor.visit_bool(v),
+      Value::Seq(v) => visitor.visit_seq(SeqDeserializer::new(v.into_iter())),
+      Value::Struct(v) => visitor.visit_map(MapDeserializer::new(v.into_iter())),
+    }
+  }
+
+  forward_to_deserialize_any! {
+    bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+    bytes byte_buf option unit unit_struct newtype_struct seq tuple
+    tuple_struct map struct enum identifier ignored_any
+  }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+  type Deserializer = Value;
+
+  fn into_deserializer(self) -> Value {
+    self
+  }
+}
+
+const RANDOM: &str = ".ORIG x3000\nLDI R0, P\nHALT\nP .FILL xFE0C\n.END";
+
+fn machine(seed: u16) -> Machine {
+  let obj: Vec<u8> = assembler::assemble(RANDOM).unwrap().to_obj();
+  let mut m = Machine::builder().io(Box::new(NullConsole)).supervisor(true).device(Box::new(Random::new(RNG, seed))).build();
+  m.load_image_bytes(&obj).unwrap();
+  m
+}
+
+fn field<'a>(v: &'a mut Value, key: &str) -> &'a mut Value {
+  match v {
+    Value::Struct(fields) => &mut fields.iter_mut().find(|f| f.0 == key).unwrap().1,
+    _ => panic!("not a struct"),
+  }
+}
+
+#[test]
+fn machine_round_trip() {
+  let mut m = machine(1);
+  let saved: Value = m.serialize(ToValue).unwrap();
+  assert_eq!(field(&mut saved.clone(), "version"), &Value::Uint(2));
+  assert_eq!(m.run().reason, StopReason::Halt);
+
+  // resumed elsewhere, the RNG picks up from the saved seed
+  let mut other = machine(2);
+  other.restore(&Snapshot::deserialize(saved).unwrap());
+  assert_eq!(other.run().reason, StopReason::Halt);
+  assert_eq!(other.read_reg(Reg::R0), m.read_reg(Reg::R0));
+  assert!(other.snapshot() == m.snapshot());
+}
+
+#[test]
+fn versions() {
+  let m = machine(1);
+  let saved: Value = m.snapshot().serialize(ToValue).unwrap();
+
+  // a version 1 session has no devices
+  let mut old: Value = saved.clone();
+  *field(&mut old, "version") = Value::Uint(1);
+  if let Value::Struct(fields) = &mut old {
+    fields.retain(|f| f.0 != "devices");
+  }
+  assert!(Snapshot::deserialize(old).unwrap().devices.is_empty());
+
+  // fields from a later version are skipped, a later version is refused
+  let mut newer: Value = saved.clone();
+  if let Value::Struct(fields) = &mut newer {
+    fields.push(("breakpoints".to_string(), Value::Seq(vec![Value::Uint(0x3000)])));
+  }
+  assert!(Snapshot::deserialize(newer.clone()).unwrap() == m.snapshot());
+  *field(&mut newer, "version") = Value::Uint(3);
+  assert_eq!(Snapshot::deserialize(newer).err().unwrap().0, "unsupported snapshot version 3");
+
+  let mut short: Value = saved;
+  *field(&mut short, "mem") = Value::Seq(vec![Value::Uint(0)]);
+  assert!(Snapshot::deserialize(short).is_err());
+}
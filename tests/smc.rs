@@ -0,0 +1,59 @@
+extern crate lc3;
+
+use lc3::{CodeWrite, Machine, MachineError, Reg, SmcCheck, StopReason};
+
+mod common;
+
+// patches the ADD at LOOP into ADD R0, R0, #2 after its first run, and
+// writes to DATA, which never runs
+const PATCH: &str = "\
+.ORIG x3000
+      AND R0, R0, #0
+LOOP  ADD R0, R0, #1
+      ADD R1, R0, #-3
+      BRzp DONE
+      LD R2, NEW
+      ST R2, LOOP
+      ST R2, DATA
+      BR LOOP
+DONE  HALT
+NEW   ADD R0, R0, #2
+DATA  .FILL 0
+.END";
+
+fn machine(check: SmcCheck) -> Machine {
+  let mut m: Machine = common::machine(PATCH);
+  m.set_smc_check(check);
+  m
+}
+
+#[test]
+fn reports_stores_to_executed_code() {
+  let mut m = machine(SmcCheck::Report);
+  assert_eq!(m.run().reason, StopReason::Halt);
+  assert_eq!(m.read_reg(Reg::R0), 3);
+  // the second pass stores to LOOP again, reported once
+  assert_eq!(m.code_writes(), &[CodeWrite { pc: 0x3005, addr: 0x3001 }]);
+
+  // DATA is code once protected, LOOP isn't flagged once allowed
+  let mut m = machine(SmcCheck::Report);
+  m.protect_code(0x3000..=0x300A);
+  m.allow_code_writes(0x3001..0x3002);
+  m.run();
+  assert_eq!(m.code_writes(), &[CodeWrite { pc: 0x3006, addr: 0x300A }]);
+
+  let mut m = machine(SmcCheck::Off);
+  m.protect_code(..);
+  m.run();
+  assert!(m.code_writes().is_empty());
+}
+
+#[test]
+fn faults() {
+  let mut m = machine(SmcCheck::Fault);
+  let e: MachineError = MachineError::CodeWrite { pc: 0x3005, addr: 0x3001 };
+  assert_eq!(m.run().reason, StopReason::Fault(e));
+  assert_eq!(e.to_string(), "store to code at 0x3001 by 0x3005");
+  // the store is done before the fault
+  assert_eq!(m.read_mem(0x3001), m.read_mem(0x3009));
+}
@@ -0,0 +1,165 @@
+extern crate lc3;
+
+use lc3::assembler;
+use lc3::encode::*;
+use lc3::script::When;
+use lc3::{InputScript, Machine, NullConsole, Random, Reg, Snapshot, StateDiff, Timeline, RNG};
+
+#[test]
+fn diff_lists_changes() {
+  let program: [u16; 3] = [add_imm(0, 0, 5), st(0, 1), halt()];
+  let mut m = Machine::builder().load(&program).io(Box::new(NullConsole)).build();
+  let before: Snapshot = m.snapshot();
+  m.run();
+  let after: Snapshot = m.snapshot();
+
+  let diff: StateDiff = before.diff(&after);
+  assert_eq!(diff.regs, vec![
+    ("R0", 0, 5),
+    ("R7", 0, 0x3003),
+    ("PC", 0x3000, 0x3003),
+    ("CC", 0b010, 0b001),
+  ]);
+  assert_eq!(diff.mem, vec![(0x3003, 0, 5)]);
+  assert_eq!(diff.halt, Some((false, true)));
+  assert_eq!(diff.to_string(), "\
+R0: x0000 -> x0005
+R7: x0000 -> x3003
+PC: x3000 -> x3003
+CC: x0002 -> x0001
+x3003: x0000 -> x0005
+halt: false -> true
+");
+
+  assert!(after.diff(&after).is_empty());
+  assert_eq!(after.diff(&before).mem, vec![(0x3003, 5, 0)]);
+}
+
+#[test]
+fn diff_survives_serialization() {
+  let mut m = Machine::builder().io(Box::new(NullConsole)).build();
+  let before: Snapshot = m.snapshot();
+  m.write_mem(0xFFFF, 1);
+  let after: Snapshot = Snapshot::from_bytes(&m.snapshot().to_bytes()).unwrap();
+  assert_eq!(before.diff(&after).mem, vec![(0xFFFF, 0, 1)]);
+}
+
+#[test]
+fn keeps_device_state() {
+  let src: &str = ".ORIG x3000\nLDI R0, P\nHALT\nP .FILL xFE0C\n.END";
+  let machine = |seed: u16| {
+    let mut m = Machine::builder().io(Box::new(NullConsole)).supervisor(true).device(Box::new(Random::new(RNG, seed))).build();
+    m.load_image_bytes(&assembler::assemble(src).unwrap().to_obj()).unwrap();
+    m
+  };
+
+  let mut m = machine(1);
+  let snap: Snapshot = Snapshot::from_bytes(&m.snapshot().to_bytes()).unwrap();
+  m.run();
+  let first: u16 = m.read_reg(Reg::R0);
+
+  let mut fresh = machine(2);
+  fresh.run();
+  assert_ne!(fresh.read_reg(Reg::R0), first);
+
+  // a different seed, put back where the first machine started
+  let mut other = machine(2);
+  other.restore(&snap);
+  other.run();
+  assert_eq!(other.read_reg(Reg::R0), first);
+}
+
+#[test]
+fn reads_version_1() {
+  let m = Machine::builder().io(Box::new(NullConsole)).build();
+  let mut bytes: Vec<u8> = m.snapshot().to_bytes();
+  // no device count, which is zero here
+  bytes.truncate(bytes.len() - 2);
+  bytes[4..6].copy_from_slice(&1u16.to_be_bytes());
+  assert!(Snapshot::from_bytes(&bytes).unwrap() == m.snapshot());
+
+  bytes[4..6].copy_from_slice(&3u16.to_be_bytes());
+  assert!(Snapshot::from_bytes(&bytes).is_err());
+}
+
+#[test]
+fn timeline_state_at() {
+  // sums keys polled from KBSR until one is zero, then one read by GETC
+  let src: &str = "\
+.ORIG x3000
+      AND R2, R2, #0
+POLL  LDI R1, KBSR
+      BRzp POLL
+      LDI R0, KBDR
+      BRz LAST
+      ADD R2, R2, R0
+      ST R2, SUM
+      BRnzp POLL
+LAST  GETC
+      ADD R2, R2, R0
+      HALT
+KBSR  .FILL xFE00
+KBDR  .FILL xFE02
+SUM   .FILL #0
+.END";
+  let make = || {
+    let mut m = Machine::builder().io(Box::new(NullConsole)).supervisor(true).build();
+    m.load_image_bytes(&assembler::assemble(src).unwrap().to_obj()).unwrap();
+    m
+  };
+  let script: InputScript = InputScript::new()
+    .then(When::After(5), "a").then(When::After(13), "bc").then(When::After(4), "\0").text("d");
+
+  let mut reference: Machine = make();
+  reference.set_input_script(&script);
+  let mut states: Vec<Snapshot> = vec![reference.snapshot()];
+  while !reference.snapshot().halt {
+    reference.run_for(1);
+    states.push(reference.snapshot());
+  }
+
+  let mut t: Timeline = Timeline::new(make, 7);
+  t.machine_mut().set_input_script(&script);
+  t.run();
+  assert_eq!(t.len() as usize, states.len() - 1);
+  assert_eq!(t.machine().read_reg(Reg::R2), [b'a', b'b', b'c', b'd'].iter().map(|&c| c as u16).sum::<u16>());
+  for (n, state) in states.iter().enumerate().rev() {
+    assert!(t.state_at(n as u64) == *state, "state after {} instructions", n);
+  }
+  assert!(t.state_at(u64::MAX) == *states.last().unwrap());
+}
+
+#[test]
+fn timeline_through_exceptions() {
+  // the fetch from x0200 in user mode vectors to the OS's ACV handler, a
+  // step that isn't an instruction
+  let src: &str = ".ORIG x3000\nLD R1, TO\nADD R2, R2, #1\nJMP R1\nTO .FILL x0200\n.END";
+  let make = || {
+    let mut m = Machine::builder().io(Box::new(NullConsole)).os(true).build();
+    m.load_image_bytes(&assembler::assemble(src).unwrap().to_obj()).unwrap();
+    m
+  };
+
+  // the state once each instruction count was reached, and any steps
+  // after it
+  let mut reference: Machine = make();
+  let mut states: Vec<Snapshot> = vec![reference.snapshot()];
+  while !reference.snapshot().halt {
+    reference.run_for(1);
+    let n: usize = reference.instructions() as usize;
+    states.truncate(n);
+    states.push(reference.snapshot());
+  }
+
+  let mut t: Timeline = Timeline::new(make, 5);
+  assert_eq!(t.run_for(4).steps, 4);
+  assert_eq!(t.len(), 3);
+  assert!(t.state_at(t.len()) == t.machine().snapshot());
+  assert!(t.state_at(2) == states[2]);
+
+  t.run();
+  assert_eq!(t.len() as usize, states.len() - 1);
+  for (n, state) in states.iter().enumerate() {
+    assert!(t.state_at(n as u64) == *state, "state after {} instructions", n);
+  }
+}
@@ -0,0 +1,100 @@
+// structured execution traces
+extern crate lc3;
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use lc3::encode::*;
+use lc3::json::Json;
+use lc3::trace::{self, Hit, Query, TraceRecord};
+use lc3::{Machine, TraceFormat, TraceSink};
+
+#[derive(Clone, Default)]
+struct Shared(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Shared {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.0.borrow_mut().extend_from_slice(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+fn traced(format: TraceFormat, program: &[u16]) -> Vec<u8> {
+  let out = Shared::default();
+  let mut m = Machine::builder().load(program).io(Box::new(lc3::NullConsole)).build();
+  m.enable_trace(TraceSink::new(format, Box::new(out.clone())));
+  m.run();
+  m.disable_trace();
+  let bytes: Vec<u8> = out.0.borrow().clone();
+  bytes
+}
+
+#[test]
+fn json_lines() {
+  let out: Vec<u8> = traced(TraceFormat::JsonLines, &[add_imm(0, 0, -1), br(true, false, false, 1), nop(), halt()]);
+  let lines: Vec<Json> = String::from_utf8(out).unwrap().lines().map(|l| Json::parse(l).unwrap()).collect();
+  assert_eq!(lines.len(), 3);
+
+  assert_eq!(lines[0].to_string(), r#"{"step":1,"pc":12288,"instr":4159,"asm":"ADD R0, R0, #-1","regs":{"R0":65535,"CC":4}}"#);
+  // taken branch, the PC jumps over the NOP
+  assert_eq!(lines[1].get("regs").unwrap().to_string(), r#"{"PC":12291}"#);
+  assert_eq!(lines[2].get("asm").and_then(Json::as_str), Some("HALT"));
+  assert_eq!(lines[2].get("step").and_then(Json::as_i64), Some(3));
+}
+
+#[test]
+fn binary() {
+  let out: Vec<u8> = traced(TraceFormat::Binary, &[and_imm(1, 1, 0), halt()]);
+  let words: Vec<u16> = out[4..].chunks(2).map(|w| u16::from_le_bytes([w[0], w[1]])).collect();
+  assert_eq!(&out[..4], b"LC3T");
+  // the AND leaves R1 at 0 and CC at Z as the machine starts out, so it
+  // records no changes; HALT sets R7
+  assert_eq!(words, vec![
+    0x3000, 0x5260, 0,
+    0x3001, 0xF025, 1 << 7, 0x3002,
+  ]);
+}
+
+// R0 counts down from 3, stored to x3007 each time round
+fn looped() -> Vec<u16> {
+  vec![add_imm(0, 0, 3), st(0, 5), add_imm(0, 0, -1), br(false, false, true, -3), halt(), nop(), nop(), nop()]
+}
+
+#[test]
+fn compact() {
+  let out: Vec<u8> = traced(TraceFormat::Compact, &looped());
+  assert_eq!(&out[..4], b"LC3C");
+  assert!(out.len() < traced(TraceFormat::Binary, &looped()).len());
+
+  let records: Vec<TraceRecord> = trace::read(&out).unwrap().collect::<Result<_, _>>().unwrap();
+  let json: Vec<Json> = String::from_utf8(traced(TraceFormat::JsonLines, &looped())).unwrap().lines().map(|l| Json::parse(l).unwrap()).collect();
+  assert_eq!(records.len(), json.len());
+  for (r, j) in records.iter().zip(&json) {
+    assert_eq!(Some(r.step as i64), j.get("step").and_then(Json::as_i64));
+    assert_eq!(Some(r.pc as i64), j.get("pc").and_then(Json::as_i64));
+    assert_eq!(Some(r.instr as i64), j.get("instr").and_then(Json::as_i64));
+  }
+  assert_eq!(records[1].writes, vec![(0x3007, 3)]);
+  assert_eq!(records[2].regs[0], 2);
+  assert_eq!(records.last().unwrap().regs[7], 0x3005);
+
+  let stores = |q: Query| trace::query(&out, q).unwrap().iter().map(|h| (h.step, h.value)).collect::<Vec<(u64, Option<u16>)>>();
+  assert_eq!(stores(Query::Writes(0x3007)), vec![(2, Some(3)), (5, Some(2)), (8, Some(1))]);
+  assert_eq!(trace::query(&out, Query::LastWrite(0x3007)).unwrap(), vec![Hit { step: 8, pc: 0x3001, instr: st(0, 5), value: Some(1) }]);
+  assert_eq!(stores(Query::Executions(0x3003)), vec![(4, None), (7, None), (10, None)]);
+  assert_eq!(stores(Query::LastWrite(0x3006)), vec![]);
+
+  // LC3T reads too, without the stores
+  let bin: Vec<u8> = traced(TraceFormat::Binary, &looped());
+  assert_eq!(trace::query(&bin, Query::Executions(0x3003)).unwrap().len(), 3);
+  assert!(trace::query(&bin, Query::Writes(0x3007)).unwrap().is_empty());
+
+  assert!(trace::read(b"LC3X").is_err());
+  let cut: Vec<Result<TraceRecord, _>> = trace::read(&out[..out.len() - 1]).unwrap().collect();
+  assert!(cut.last().unwrap().is_err());
+}
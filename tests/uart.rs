@@ -0,0 +1,71 @@
+extern crate lc3;
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+use lc3::{Machine, Reg, StopReason, Uart, URSR};
+
+mod common;
+
+fn machine(src: &str, uart: Uart) -> Machine {
+  common::load(common::builder().supervisor(true).device(Box::new(uart)), src)
+}
+
+const SEND: &str = "\
+.ORIG x3000
+      LD R0, CHAR
+      STI R0, UTDR
+      ADD R0, R0, #1
+      STI R0, UTDR
+      HALT
+CHAR  .FILL x61
+UTDR  .FILL xFE16
+.END";
+
+// waits for two bytes, into R1 and R2
+const RECV: &str = "\
+.ORIG x3000
+W1    LDI R0, URSR
+      BRzp W1
+      LDI R1, URDR
+W2    LDI R0, URSR
+      BRzp W2
+      LDI R2, URDR
+      HALT
+URSR  .FILL xFE10
+URDR  .FILL xFE12
+.END";
+
+#[test]
+fn two_machines() {
+  let listener: TcpListener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let addr = listener.local_addr().unwrap();
+  let sender = thread::spawn(move || {
+    let mut m = machine(SEND, Uart::connect(addr).unwrap());
+    m.run().reason
+  });
+
+  let mut m = machine(RECV, Uart::accept(&listener).unwrap());
+  assert_eq!(m.run_for(100_000_000).reason, StopReason::Halt);
+  assert_eq!(sender.join().unwrap(), StopReason::Halt);
+  assert_eq!((m.read_reg(Reg::R1), m.read_reg(Reg::R2)), (0x61, 0x62));
+}
+
+#[test]
+fn external_peer() {
+  let listener: TcpListener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let mut peer: TcpStream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+  let (stream, _) = listener.accept().unwrap();
+
+  peer.write_all(b"xy").unwrap();
+  let mut m = machine(RECV, Uart::new(URSR, stream.try_clone().unwrap()).unwrap());
+  assert_eq!(m.run_for(100_000_000).reason, StopReason::Halt);
+  assert_eq!((m.read_reg(Reg::R1), m.read_reg(Reg::R2)), (0x78, 0x79));
+
+  let mut m = machine(SEND, Uart::new(URSR, stream).unwrap());
+  assert_eq!(m.run().reason, StopReason::Halt);
+  let mut buf: [u8; 2] = [0; 2];
+  peer.read_exact(&mut buf).unwrap();
+  assert_eq!(&buf, b"ab");
+}
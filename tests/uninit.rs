@@ -0,0 +1,86 @@
+extern crate lc3;
+
+use lc3::assembler;
+use lc3::{Machine, MachineError, NullConsole, Reg, StopReason, Uninit, UninitCheck, UninitRead};
+
+mod common;
+
+const READS: &str = "\
+.ORIG x3000
+      LD R1, PTR
+      LDR R0, R1, #0
+      LDR R0, R1, #0
+      STR R0, R1, #1
+      LDR R0, R1, #1
+      ADD R2, R3, #0
+      ADD R2, R3, #0
+      HALT
+PTR   .FILL x4000
+.END";
+
+fn machine(src: &str, check: UninitCheck, os: bool) -> Machine {
+  common::load(common::builder().os(os).uninit_check(check), src)
+}
+
+#[test]
+fn reports_each_once() {
+  let mut m = machine(READS, UninitCheck::Report, false);
+  assert_eq!(m.run().reason, StopReason::Halt);
+  // x4001 was stored to before it was read
+  assert_eq!(m.uninit_reads(), &[
+    UninitRead { pc: 0x3001, read: Uninit::Mem(0x4000) },
+    UninitRead { pc: 0x3005, read: Uninit::Reg(Reg::R3) },
+  ]);
+
+  // registers set from the host count as written
+  let mut m = machine(READS, UninitCheck::Report, false);
+  m.write_reg(Reg::R3, 1);
+  m.write_mem(0x4000, 2);
+  m.run();
+  assert!(m.uninit_reads().is_empty());
+}
+
+#[test]
+fn faults() {
+  let mut m = machine(READS, UninitCheck::Fault, false);
+  let e: MachineError = MachineError::Uninitialized { pc: 0x3001, read: Uninit::Mem(0x4000) };
+  assert_eq!(m.run().reason, StopReason::Fault(e));
+  assert_eq!(e.to_string(), "read of uninitialized x4000 at 0x3001");
+
+  // running off the end of the program fetches unwritten memory
+  let mut m = machine(".ORIG x3000\nAND R0, R0, #0\n.END", UninitCheck::Fault, false);
+  assert_eq!(m.run().reason, StopReason::Fault(MachineError::Uninitialized { pc: 0x3001, read: Uninit::Mem(0x3001) }));
+  assert_eq!(m.read_reg(Reg::PC), 0x3001);
+}
+
+#[test]
+fn os_routines_are_clean() {
+  // PUTS saves registers the program never set
+  let src = ".ORIG x3000\nLEA R0, MSG\nPUTS\nHALT\nMSG .STRINGZ \"hi\"\n.END";
+  let mut m = machine(src, UninitCheck::Report, true);
+  assert_eq!(m.run().reason, StopReason::Halt);
+  assert_eq!(m.uninit_reads(), &[]);
+  assert_eq!(m.uninit_check(), UninitCheck::Report);
+}
+
+#[test]
+fn randomized_state() {
+  let build = |seed: u16| Machine::builder().io(Box::new(NullConsole)).os(true).randomize(seed).uninit_check(UninitCheck::Report).build();
+  let (a, b, c) = (build(7), build(7), build(8));
+  let words = |m: &Machine| (0x4000..0x4100).map(|addr| m.read_mem(addr)).collect::<Vec<u16>>();
+  assert_eq!(words(&a), words(&b));
+  assert_ne!(words(&a), words(&c));
+  assert!(words(&a).iter().any(|&w| w != 0));
+  assert_eq!(a.read_reg(Reg::R3), b.read_reg(Reg::R3));
+
+  // the OS and the program are loaded over it, and reading what's left is
+  // still reading garbage
+  let mut m = build(7);
+  m.load_image_bytes(&assembler::assemble(READS).unwrap().to_obj()).unwrap();
+  assert_eq!(m.run().reason, StopReason::Halt);
+  assert_eq!(m.read_reg(Reg::R2), b.read_reg(Reg::R3));
+  assert_eq!(m.uninit_reads(), &[
+    UninitRead { pc: 0x3001, read: Uninit::Mem(0x4000) },
+    UninitRead { pc: 0x3005, read: Uninit::Reg(Reg::R3) },
+  ]);
+}